@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+
+/// Embeds every file in `stdlib/` into beach, so that an install doesn't need
+/// a copy of the standard library beside it. See `src/stdlib.rs`.
+fn main() {
+    let root = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR"));
+    let stdlib = root.join("stdlib");
+    println!("cargo:rerun-if-changed={}", stdlib.display());
+    let mut files = vec![];
+    find_files(&stdlib, &mut files);
+    files.sort();
+    let mut output = String::from("/// Every file in `stdlib/`, by its path in `stdlib/`, and its contents.\n");
+    output.push_str("const EMBEDDED_FILES: &[(&str, &str)] = &[\n");
+    for file in files {
+        // Cargo only reruns this when a file's modification time changes, so
+        // every file is watched individually along with the folders.
+        println!("cargo:rerun-if-changed={}", file.display());
+        let name: Vec<String> = file.strip_prefix(&stdlib).unwrap()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        output.push_str(&format!("    ({:?}, include_str!({:?})),\n", name.join("/"), file.display().to_string()));
+    }
+    output.push_str("];\n");
+    let out = PathBuf::from(std::env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+    std::fs::write(out.join("stdlib.rs"), output).expect("unable to write the embedded standard library");
+}
+
+/// Adds every `.beach` file in `folder` and its subfolders to `files`.
+fn find_files(folder: &Path, files: &mut Vec<PathBuf>) {
+    let entries = std::fs::read_dir(folder).expect("unable to read the standard library");
+    for entry in entries {
+        let path = entry.expect("unable to read the standard library").path();
+        if path.is_dir() {
+            println!("cargo:rerun-if-changed={}", path.display());
+            find_files(&path, files);
+        }
+        else if path.extension().is_some_and(|extension| extension == "beach") {
+            files.push(path);
+        }
+    }
+}
@@ -1,3 +0,0 @@
-rm -d -r "$HOME/Library/Application Support/beach"
-mkdir -p "$HOME/Library/Application Support/beach/stdlib"
-cp -R ../stdlib "$HOME/Library/Application Support/beach"
@@ -1,6 +1,13 @@
-use std::env::args;
+use std::{env::args, path::{Path, PathBuf}, time::Instant};
 
-use crate::{parser::beach::ast::user_token_format::{keywords::Keyword, Symbol}, utils::install_directory};
+use crate::{
+    generator::generic::{Architecture, AssemblyGenerator, MachineCode, Priority},
+    modules::ModuleGraph,
+    package::{self, Manifest, ProjectKind},
+    parser::{aarch64::emulator::emulate, beach::{Executable, interpret, optimise}},
+    platform::{generic::Platform, get_all_platforms},
+    utils::{install_directory, stdlib_directory}
+};
 
 pub fn main() {
     println!("beach 🏖️  v{}", env!("CARGO_PKG_VERSION"));
@@ -13,11 +20,19 @@ pub fn main() {
     // execution cycle.
     let first_arg = args.next();
     if let Some(first_arg) = first_arg {
-        match first_arg.as_str() {
+        let result = match first_arg.as_str() {
             "build" => build(&mut args),
+            "run" => run(&mut args),
             "help" => help(&mut args),
             "info" => info(&mut args),
-            _ => todo!()
+            "new" => new(&mut args),
+            unknown => Err(anyhow::Error::msg(format!(
+                "Unknown command. (`{unknown}`) Try `beach help` for a list of commands."
+            )))
+        };
+        if let Err(e) = result {
+            println!("❌ {e}");
+            std::process::exit(1);
         }
     }
     else {
@@ -25,22 +40,69 @@ pub fn main() {
     }
 }
 
-fn help(args: &mut std::env::Args) {
-    // TODO: read args for more specific help
+/// Where [install_directory] looks for application data, as described in
+/// `beach help info`.
+#[cfg(target_os = "macos")]
+const DATA_DIRECTORY: &str = "`~/Library/Application Support`";
+#[cfg(target_os = "windows")]
+const DATA_DIRECTORY: &str = "`%APPDATA%`";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const DATA_DIRECTORY: &str = "`$XDG_DATA_HOME` (or `~/.local/share`)";
+
+fn help(args: &mut std::env::Args) -> Result<(), anyhow::Error> {
     match args.next().as_deref() {
         Some("build") => {
             println!("\
 beach build [ARGS]
+Arguments override the [build] settings in `beach.toml`.
 Avalable arguments:
-input [FILE]
+input [FILE] - a `.beach` file, or a `.ir` file written by `ir`
 output folder [FOLDER]
 output name [NAME]
+target [TARGET] - only builds for TARGET, can be given more than once
+optimisation [0-{}] - 0 doesn't optimise, 1 removes unused code and data,
+    2 also computes constants while compiling, {} repeats until nothing changes
+size - chooses smaller instructions over faster ones
 debug
-assembly");
+assembly - also writes the assembly to `intermediates/`
+ir - also writes the intermediate representation to `intermediates/`
+--stdlib [FOLDER] - uses the standard library in FOLDER, unless `BEACH_HOME`
+    is set", package::MAX_OPTIMISATION, package::MAX_OPTIMISATION);
+        }
+        Some("run") => {
+            println!("\
+beach run [--interpret | --emulate] [ARGS]
+Runs your program as if it were on the target given with `target`, or
+`linux_aarch64` if there isn't one, using that target's standard library.
+Programs can write to stdout and stderr, and exit. One of these is required,
+as programs can't be run natively yet:
+--interpret - runs the program without compiling it
+--emulate - compiles the program and runs it on an AArch64 emulator
+Any of the arguments to `beach build` can also be given.");
+        }
+        Some("info") => {
+            println!("\
+beach info [ARGS]
+Shows where beach is installed, and where its standard library is. These are
+found in `BEACH_HOME` if it's set, then the folder given with `--stdlib` (for
+the standard library), then the `beach` folder in {}, then
+beside the beach executable, or in `../share/beach` from its folder. After
+those, debug builds use the standard library in the repository they were built
+from, and the one built into beach is used last.
+Avalable arguments:
+--stdlib [FOLDER] - as given to `beach build`", DATA_DIRECTORY);
+        }
+        Some("new") => {
+            println!("\
+beach new [NAME] [ARGS]
+Creates a project called NAME in a new folder of the same name.
+Avalable arguments:
+--lib - creates a library project instead of an executable");
         }
-        Some(_) => {
-            println!("Unknown argument. Try `beach help` for a list of options.");
-            todo!();
+        Some(unknown) => {
+            return Err(anyhow::Error::msg(format!(
+                "Unknown command. (`{unknown}`) Try `beach help` for a list of options."
+            )));
         }
         None => {
             println!("\
@@ -49,293 +111,722 @@ beach build - builds your program for many platforms
 beach run - builds and runs your program on this platform
 beach check - checks if your program is valid
 beach update - updates the compiler, if possible
+beach new - creates a new project
 beach info - gives information about the current installation
 beach help - provides this help menu
 beach help [COMMAND] - provides more detailed help");
         }
     }
+    Ok(())
 }
 
-fn build(args: &mut std::env::Args) {
-    // TODO: no unwrap!
-    let mut input_file = std::env::current_dir().unwrap();
-    input_file.push("main.beach");
-    // Indicates if the build process should output the generated assembly for
-    // each given platform.
-    let mut output_assembly = false;
-    // Indicates if debug symbols should be included in the final executables.
-    let mut enable_debug_symbols = false;
-    while let Some(arg) = args.next() {
-        match arg.as_ref() {
-            "input" => {
-                // TODO: no unwrap!
-                input_file = std::env::current_dir().unwrap();
-                input_file.push(args.next().expect("Expected a filepath following `input`."));
-            }
-            "output" => {
-                todo!();
-            }
-            "debug" => {
-                enable_debug_symbols = true;
-                todo!("not used");
-            }
-            "assembly" => {
-                output_assembly = true;
-                todo!("not used");
+/// The arguments given to `beach build`. Each one overrides the same setting
+/// in the project's `beach.toml`.
+#[derive(Debug, Default, PartialEq)]
+struct BuildArguments {
+    input: Option<PathBuf>,
+    output_folder: Option<PathBuf>,
+    output_name: Option<String>,
+    targets: Vec<String>,
+    optimisation: Option<u8>,
+    size: bool,
+    debug: bool,
+    assembly: bool,
+    ir: bool,
+    stdlib: Option<PathBuf>,
+}
+
+impl BuildArguments {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<BuildArguments, anyhow::Error> {
+        let mut arguments = BuildArguments::default();
+        while let Some(arg) = args.next() {
+            let mut value = |what: &str| args.next().ok_or(anyhow::Error::msg(format!(
+                "Expected {what} following `{arg}`. Try `beach help build` for more info."
+            )));
+            match arg.as_str() {
+                "input" => arguments.input = Some(PathBuf::from(value("a file")?)),
+                "output" => {
+                    match value("`folder` or `name`")?.as_str() {
+                        "folder" => arguments.output_folder = Some(PathBuf::from(value("a folder")?)),
+                        "name" => arguments.output_name = Some(value("a name")?),
+                        other => {
+                            return Err(anyhow::Error::msg(format!(
+                                "Expected `folder` or `name` following `output`, not `{other}`."
+                            )));
+                        }
+                    }
+                }
+                "target" => arguments.targets.push(value("a target, such as `linux_aarch64`,")?),
+                "optimisation" => arguments.optimisation = Some(package::optimisation_level(&value("a level")?)?),
+                "size" => arguments.size = true,
+                "debug" => arguments.debug = true,
+                "assembly" => arguments.assembly = true,
+                "ir" => arguments.ir = true,
+                "--stdlib" => arguments.stdlib = Some(PathBuf::from(value("a folder")?)),
+                inv => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Invalid argument. (`{inv}`) Try `beach help build` for a list of valid arguments."
+                    )));
+                }
             }
-            inv => {
-                println!("Invalid argument. (`{inv}`) Try `beach help build` for a list of valid arguments.");
-                std::process::exit(0);
+        }
+        Ok(arguments)
+    }
+}
+
+/// Everything `beach build` needs to know, taken from its arguments, then the
+/// project's `beach.toml`, then beach's defaults.
+struct BuildOptions {
+    input: PathBuf,
+    output_folder: PathBuf,
+    output_name: String,
+    /// Empty to build for every platform that supports the project.
+    targets: Vec<String>,
+    optimisation: u8,
+    priority: Priority,
+    debug: bool,
+    assembly: bool,
+    ir: bool,
+    /// The folder with `core.beach` and `std/`.
+    stdlib: PathBuf,
+}
+
+impl BuildOptions {
+    fn new(arguments: BuildArguments, manifest: Option<&Manifest>, project_folder: &Path) -> BuildOptions {
+        let settings = manifest.map(|manifest| manifest.build.clone()).unwrap_or_default();
+        let input = project_folder.join(
+            arguments.input
+                .or(manifest.map(|manifest| manifest.entry.clone()))
+                .unwrap_or(PathBuf::from("main.beach"))
+        );
+        let output_folder = project_folder.join(
+            arguments.output_folder
+                .or(settings.output)
+                .unwrap_or(PathBuf::from("build"))
+        );
+        let output_name = arguments.output_name
+            .or(settings.name)
+            .or(manifest.map(|manifest| manifest.name.clone()))
+            .unwrap_or(input.file_stem().unwrap_or_default().to_string_lossy().to_string());
+        let targets = if arguments.targets.is_empty() { settings.targets } else { arguments.targets };
+        BuildOptions {
+            input,
+            output_folder,
+            output_name,
+            targets,
+            optimisation: arguments.optimisation.or(settings.optimisation).unwrap_or(0),
+            priority: if arguments.size || settings.size.unwrap_or(false) { Priority::Size } else { Priority::Performance },
+            debug: arguments.debug || settings.debug.unwrap_or(false),
+            assembly: arguments.assembly || settings.assembly.unwrap_or(false),
+            ir: arguments.ir || settings.ir.unwrap_or(false),
+            stdlib: stdlib_directory(arguments.stdlib.map(|stdlib| project_folder.join(stdlib)).as_deref()).0,
+        }
+    }
+}
+
+/// The program a project builds, as read from its input file.
+pub(crate) enum Input {
+    /// Beach source, and every module it needs.
+    Source(ModuleGraph),
+    /// IR as written by `beach build ir`, which is already linked with its
+    /// standard library.
+    Ir(Executable),
+}
+
+impl Input {
+    /// The linked program, with the standard library (in `std`) for
+    /// `platform` if it's source.
+    fn executable<G: AssemblyGenerator>(self, platform: &Platform<G>, std: &Path) -> Result<Executable, anyhow::Error> {
+        match self {
+            Input::Source(mut graph) => {
+                let features: Vec<&str> = platform.features.iter().map(|(feature, _)| *feature).collect();
+                graph.include_stdlib(std, platform.technical_id, &features)
+                    .map_err(|reason| anyhow::Error::msg(format!("Unable to run as {}: {reason}", platform.technical_id)))?;
+                Executable::from_ast(graph.link()?)
             }
+            Input::Ir(executable) => Ok(executable)
         }
     }
+}
+
+/// Finds the project in the current folder, checks `arguments` against it,
+/// and parses the main file and every file it needs.
+fn load_project(arguments: BuildArguments) -> Result<(BuildOptions, Option<Manifest>, Input), anyhow::Error> {
+    let project_folder = std::env::current_dir()
+        .map_err(|e| anyhow::Error::msg(format!("Unable to access the current folder: {e}")))?;
+    let manifest = Manifest::find(&project_folder)?;
+    let options = BuildOptions::new(arguments, manifest.as_ref(), &project_folder);
+
     // TOCTOU ok here: We handle all error conditions gracefully. We're only
     // really checking to *improve* error messages, not *provide* them.
-    let file_exists = input_file.try_exists();
-    if let Err(_e) = file_exists {
-        // TODO: check input filename for error
-        println!("Unable to find or access `main.beach`. Check directory permissions and try again.");
-        std::process::exit(0);
+    let input_name = options.input.file_name().unwrap_or_default().to_string_lossy().to_string();
+    match options.input.try_exists() {
+        Err(_) => {
+            return Err(anyhow::Error::msg(format!(
+                "Unable to find or access `{input_name}`. Check directory permissions and try again."
+            )));
+        }
+        Ok(false) => {
+            return Err(anyhow::Error::msg(format!(
+                "`{input_name}` not found. Check your directory and try again."
+            )));
+        }
+        Ok(true) => {}
     }
-    if let Ok(false) = file_exists {
-        // TODO: check input filename for error
-        println!("`main.beach` not found. Check your directory and try again.");
-        std::process::exit(0);
+    let platforms = get_all_platforms();
+    for target in &options.targets {
+        if !platforms.iter().any(|platform| platform.technical_id == target) {
+            let valid: Vec<&str> = platforms.iter().map(|platform| platform.technical_id).collect();
+            return Err(anyhow::Error::msg(format!(
+                "Unknown target `{target}`. Valid targets are: {}",
+                valid.join(", ")
+            )));
+        }
+    }
+    // IR is already linked, so it doesn't need libraries.
+    if options.input.extension().is_some_and(|extension| extension == "ir") {
+        println!("👓 Parsing IR...");
+        let text = std::fs::read_to_string(&options.input)
+            .map_err(|e| anyhow::Error::msg(format!("Unable to read `{input_name}`: {e}")))?;
+        let executable = text.parse()
+            .map_err(|e| anyhow::Error::msg(format!("`{input_name}` isn't valid IR: {e}")))?;
+        return Ok((options, manifest, Input::Ir(executable)));
     }
+    // Find every library the project uses, checking them against the
+    // lockfile
+    let libraries = match &manifest {
+        Some(manifest) if !manifest.dependencies.is_empty() => {
+            println!("📚 Finding libraries...");
+            let registry = package::registry_folder(&project_folder, manifest);
+            package::resolve(&project_folder, manifest, &registry)?
+        }
+        _ => vec![]
+    };
 
-    if let Ok(data) = std::fs::read_to_string(input_file.clone()) {
-        // Import std:core into file directly
-        let mut dir = install_directory();
-        dir.push("stdlib");
-        dir.push("core.beach");
-        let std_core = std::fs::read_to_string(dir).unwrap();
-        // Reassign file with std imported
-        let data = std_core + &data;
-
-        // Parse file to ast
-        println!("👓 Parsing main file...");
-        let parsed_data = crate::parser::parse_string_file(data);
-
-        /*
-         * Find and parse subfiles
-         * =======================
-         * This section steps through every current unprocessed file to find all
-         * imports and refrences. If an import hasn't yet been calculated
-         * elsewhere, it's added as another file to process. This repeats until
-         * there are no remaining unprocessed files.
-         * 
-         * This process does not verify correct syntax or logic and may
-         * erroniously import files in the case of incomplete or incorrect input
-         * code. Treat its output gently and start with the main project file.
-         */
-        
-        // Main file's name (main.beach, alternate_main_file_name.beach, etc.)
-        let input_file_ending = input_file.file_name().unwrap().to_str().unwrap().to_string();
-        // A list of all files that have not yet been processed for looking for
-        // imports. They are stored in the relative path format (pathname
-        // relative to project root, host platform style dividers)
-        let mut potential_subfiles_in = vec![input_file_ending.clone()];
-        // Files that have been aquired from disk. This vector contains both
-        // parsed and unparsed files.
-        // Vec<(relative path format, ast)>
-        let mut current_files = vec![(input_file_ending.clone(), parsed_data)];
-        // A list of all files that have been aquired into `current_files`. This
-        // vector contains both parsed and unparsed files as Strings with the
-        // relative path format.
-        let mut file_names = vec![input_file_ending.clone()];
-
-        // As long as we have at least one file left to check, we should keep going!
-        while !potential_subfiles_in.is_empty() {
-            // Why use a manual index variable for the `'outer` for loop?
-            // Because we remove elements from it mid-iteration! Not really the
-            // best idea... but I'm not super sure how I want to do this yet so
-            // this gets the job done.
-            let mut idx = 0;
-            'outer: for loc in &potential_subfiles_in.clone() {
-                println!("👓 Parsing files ({}/{})...", potential_subfiles_in.len(), current_files.len());
-                for (locof, syntax) in &current_files.clone() {
-                    if locof == loc {
-                        /* 
-                         * Okay, we have two loops, `'outer` and what we'll call
-                         * `'inner` for explanation's sake.
-                         * 
-                         * 'outer goes over every file we still need to look at.
-                         * 'inner goes over every file we've aquired the ast for.
-                         * 
-                         * This *should* be more than zero files unless we've
-                         * parsed every file in `potential_subfiles`.
-                         * 
-                         * If 'outer and 'inner are matching the same file, we
-                         * handle the file we've found. Once we've handled that
-                         * file, we break out of 'outer to avoid the funkiness
-                         * of iterating over a clone of a list we just modified
-                         * the original of.
-                         * 
-                         * This restarts us at the top of the while loop.
-                         */
-                        let mut symbols = syntax.symbols.iter();
-                        while let Some(symbol) = symbols.next() {
-                            if Symbol::Keyword(Keyword::Kfile) == *symbol {
-                                // Local imports using `file`
-                                let target = symbols.next();
-                                if let Some(Symbol::Label(lbl)) = target {
-                                    let glob = lbl.ends_with(":*");
-                                    let mut working_pathized = lbl.clone();
-                                    if glob {
-                                        working_pathized = working_pathized.trim_end_matches(":*").to_string();
-                                        todo!();
-                                    }
-                                    #[cfg(target_family = "unix")]
-                                    { working_pathized = working_pathized.replace(":", "/") }
-                                    #[cfg(not(target_family = "unix"))]
-                                    { working_pathized = working_pathized.replace(":", "\\") }
-
-                                    let mut file_path = input_file.clone();
-                                    file_path.pop();
-                                    file_path = file_path.join(locof);
-                                    file_path.pop();
-                                    file_path = file_path.join(working_pathized);
-                                    let file_string = file_path.clone().into_os_string().to_string_lossy().to_string();
-                                    current_files.push(
-                                        (file_string.clone(),
-                                        crate::parser::parse_string_file(std::fs::read_to_string(file_path.clone()).unwrap()))
-                                    );
-                                    if !file_names.contains(&file_string) {
-                                        potential_subfiles_in.push(file_string.clone());
-                                        file_names.push(file_string.clone());
-                                    }
-                                }
-                                else {
-                                    println!("Failed parsing.");
-                                    std::process::exit(0);
-                                }
-                            }
-                            else if Symbol::Keyword(Keyword::Kinclude) == *symbol {
-                                // Standard library imports using `include`
-                                let target = symbols.next();
-                                let mut next_target = symbols.next();
-                                let mut total_label = String::new();
-                                if let Some(Symbol::Label(lbl)) = target {
-                                    total_label += lbl;
-                                }
-                                else {
-                                    // TODO: handle this case
-                                    todo!();
-                                }
-                                // TODO: handle invalid symbols and premature end
-                                while next_target != Some(&Symbol::PhraseEnd) && next_target.is_some() {
-                                    if next_target == Some(&Symbol::Is) {
-                                        #[cfg(target_family = "unix")]
-                                        { total_label += "/"; }
-                                        #[cfg(not(target_family = "unix"))]
-                                        { total_label += "\\"; }
-                                    }
-                                    else if let Some(Symbol::Label(lbl)) = next_target {
-                                        total_label += lbl;
-                                    }
-                                    next_target = symbols.next();
-                                }
-
-                                let mut file_path = install_directory();
-                                file_path.push("stdlib");
-                                file_path.push("std");
-                                file_path = file_path.join(&total_label);
-                                file_path.set_extension("beach");
-                                if !file_path.try_exists().is_ok_and(|v| v) {
-                                    file_path.pop();
-                                    file_path.set_extension("beach");
-                                }
-                                // TODO: verify that the path one level up actually exists
-                                
-                                let file_string = file_path.clone().into_os_string().to_string_lossy().to_string();
-                                //println!("in {}", locof);
-                                //println!("current file tokens: {:#?}", current_files[0].1);
-                                //println!("looking for all: {:?}", potential_subfiles_in);
-                                //println!("have the following: {:?}", file_names);
-                                //println!("label: {}", total_label);
-                                //println!("Searching for: {:?}", file_path);
-                                current_files.push(
-                                    (file_string.clone(),
-                                    crate::parser::parse_string_file(std::fs::read_to_string(file_path.clone()).unwrap()))
-                                );
-                                if !file_names.contains(&file_string) {
-                                    potential_subfiles_in.push(file_string.clone());
-                                    file_names.push(file_string.clone());
-                                }
-                            }
-                            else if let Symbol::Compiler(val) = symbol {
-                                // Internal compiler imports using `!!core`
-                                let mut iter = val.split(" ");
-                                if iter.next() == Some("core") {
-                                    let path = iter.next().unwrap();
-                                    #[cfg(not(target_family = "unix"))]
-                                    let path = path.replace("/", "\\");
-                                    let mut dirpath = install_directory();
-                                    dirpath.push("stdlib");
-                                    dirpath.push("core");
-                                    dirpath.push(format!("{}.beach", path));
-                                    let file_string = dirpath.to_str().unwrap().to_string();
-                                    current_files.push(
-                                        (file_string.clone(),
-                                        crate::parser::parse_string_file(std::fs::read_to_string(dirpath.clone()).unwrap()))
-                                    );
-                                    if !file_names.contains(&file_string) {
-                                        potential_subfiles_in.push(file_string.clone());
-                                        file_names.push(file_string.clone());
-                                    }
-                                }
-                            }
-                        }
-                        // cleanse the list!
-                        potential_subfiles_in.remove(idx);
-                        break 'outer;
-                    }
-                }
-                idx += 1;
-            }
-            if idx.checked_sub(1) == Some(potential_subfiles_in.len()) {
-                panic!("Couldn't find appropriate location!");
-            }
-            //println!("All filenames: {:?}", file_names);
-            //println!("Looking for files: {:?}", potential_subfiles_in);
-            //println!("Parsed files: {:?}", current_files);
+    // Parse the main file, and every file it needs
+    println!("👓 Parsing files...");
+    let graph = ModuleGraph::load(&options.input, &options.stdlib.join("core.beach"), &libraries)?;
+    println!("👓 Parsed {} files.", graph.modules.len());
+    Ok((options, manifest, Input::Source(graph)))
+}
+
+fn build(args: &mut std::env::Args) -> Result<(), anyhow::Error> {
+    let build_start = Instant::now();
+    let (options, manifest, input) = load_project(BuildArguments::parse(args)?)?;
+
+    // Libraries are only built as part of the projects that use them.
+    if let Some(manifest) = manifest.as_ref().filter(|manifest| manifest.kind == ProjectKind::Library) {
+        let Input::Source(graph) = &input
+        else {
+            return Err(anyhow::Error::msg("Libraries are built from beach source, not IR."));
+        };
+        graph.check_main_blocks(true)?;
+        println!(
+            "☑️ Checked library {} v{} in {:.2?}",
+            manifest.name,
+            manifest.version,
+            build_start.elapsed()
+        );
+        return Ok(());
+    }
+
+    println!("🎛️ Calculating valid targets...");
+    // Every `include` (or deprecated `system`) is a requirement that each
+    // platform needs to satisfy, either with a feature of its own or with
+    // an implementation in the standard library. IR already has its
+    // standard library, so it's built for every target.
+    let std_path = options.stdlib.join("std");
+    let mut valid_targets = vec![];
+    for platform in get_all_platforms() {
+        let selected = options.targets.is_empty() || options.targets.iter().any(|target| target == platform.technical_id);
+        if !selected {
+            continue;
         }
-        println!("DEBUG / All filenames: {:#?}", file_names);
-        println!("📖 Generating intermediates...");
-        todo!();
-        println!("🎛️ Calculating valid targets...");
-        todo!();
-        /*
-        for target in valid_targets {
-            println!("🔨 Compiling for {}", target.name);
-            todo!();
-            if output_assembly {
-                todo!();
+        let graph = match &input {
+            Input::Source(graph) => graph,
+            Input::Ir(executable) => {
+                valid_targets.push((platform, Input::Ir(executable.clone())));
+                continue;
             }
-            for packager in target.packagers {
-                println!("📦 Packaging as {}...", packager.name);
-                todo!();
+        };
+        let features: Vec<&str> = platform.features.iter().map(|(feature, _)| *feature).collect();
+        let mut target_graph = graph.clone();
+        match target_graph.include_stdlib(&std_path, platform.technical_id, &features) {
+            Ok(()) => valid_targets.push((platform, Input::Source(target_graph))),
+            // Targets that were asked for by name have to be built.
+            Err(reason) if !options.targets.is_empty() => {
+                return Err(anyhow::Error::msg(format!("Unable to build for {}: {reason}", platform.technical_id)));
             }
+            Err(reason) => println!("⛔ {}: {reason}", platform.technical_id)
+        }
+    }
+    if valid_targets.is_empty() {
+        return Err(anyhow::Error::msg("No platforms support everything this program needs."));
+    }
+
+    println!("📖 Generating intermediates...");
+    let target_count = valid_targets.len();
+    for (platform, target_input) in valid_targets {
+        println!("🔨 Compiling for {}...", platform.friendly_name);
+        let compiled = match target_input {
+            Input::Source(target_graph) => compile_target(&platform, &target_graph, options.optimisation, options.priority, options.debug),
+            Input::Ir(executable) => compile_executable(&platform, executable, options.optimisation, options.priority, options.debug),
+        };
+        let (ir, assembly, code) = compiled
+            .map_err(|e| anyhow::Error::msg(format!("Failed compiling for {}:\n{e}", platform.technical_id)))?;
+        // Spec B.9: intermediates go in `build/intermediates/`.
+        let mut intermediates = options.output_folder.clone();
+        intermediates.push("intermediates");
+        intermediates.push(platform.technical_id);
+        if options.assembly {
+            std::fs::create_dir_all(&intermediates)?;
+            std::fs::write(intermediates.join(format!("{}.s", options.output_name)), assembly)?;
+        }
+        if options.ir {
+            std::fs::create_dir_all(&intermediates)?;
+            std::fs::write(intermediates.join(format!("{}.ir", options.output_name)), ir)?;
+        }
+        // Spec B.8: binaries go in `build/binaries/`.
+        let mut target_folder = options.output_folder.clone();
+        target_folder.push("binaries");
+        target_folder.push(platform.technical_id);
+        for packager in &platform.packagers {
+            println!("📦 Packaging as {}...", packager.name());
+            packager.package(&code, platform.architecture(), options.debug)
+                .and_then(|executable| write_executable(&target_folder, &options.output_name, &executable))
+                .map_err(|e| anyhow::Error::msg(format!("Failed packaging for {}: {e}", platform.technical_id)))?;
         }
-        println!(
-            "☑️ Built for {} targets in {}",
-            valid_targets.len(),
-            elapsed_build_time
-        );
-        */
     }
+    println!(
+        "☑️ Built for {} targets in {:.2?}",
+        target_count,
+        build_start.elapsed()
+    );
+    Ok(())
+}
+
+/// Compiles every module in `graph` into IR, assembly, and then machine code,
+/// for `platform`, optimised at `optimisation` and favouring `priority`. With
+/// `debug`, the machine code keeps a line table. The IR is returned as text,
+/// as it was after optimising.
+pub(crate) fn compile_target<G: AssemblyGenerator>(
+    platform: &Platform<G>,
+    graph: &ModuleGraph,
+    optimisation: u8,
+    priority: Priority,
+    debug: bool
+) -> Result<(String, String, MachineCode), anyhow::Error> {
+    compile_executable(platform, Executable::from_ast(graph.link()?)?, optimisation, priority, debug)
+}
+
+/// Compiles `executable`, such as IR read back from text, as
+/// [compile_target] does.
+pub(crate) fn compile_executable<G: AssemblyGenerator>(
+    platform: &Platform<G>,
+    mut executable: Executable,
+    optimisation: u8,
+    priority: Priority,
+    debug: bool
+) -> Result<(String, String, MachineCode), anyhow::Error> {
+    optimise::<G>(&mut executable, optimisation)?;
+    let ir = executable.to_string();
+    let assembly = platform.generate_assembly(executable, priority, debug)?;
+    let code = G::assemble(&assembly)?;
+    Ok((ir, assembly, code))
+}
+
+/// How `beach run` runs a program, as no target runs natively yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunMode {
+    /// Runs the IR with [interpret].
+    Interpret,
+    /// Compiles for the target and runs the machine code with
+    /// [crate::parser::aarch64::emulator::emulate].
+    Emulate,
+}
+
+fn run(args: &mut std::env::Args) -> Result<(), anyhow::Error> {
+    let mut mode = None;
+    let build_args: Vec<String> = args
+        .filter(|arg| match arg.as_str() {
+            "--interpret" => {
+                mode = Some(RunMode::Interpret);
+                false
+            }
+            "--emulate" => {
+                mode = Some(RunMode::Emulate);
+                false
+            }
+            _ => true
+        })
+        .collect();
+    let arguments = BuildArguments::parse(build_args.into_iter())?;
+    let Some(mode) = mode
     else {
-        println!("`main.beach` is not valid UTF-8 or otherwise could not be read.");
+        return Err(anyhow::Error::msg(
+            "Programs can't be run natively yet. Try `beach run --interpret` or `beach run --emulate`."
+        ));
+    };
+    let (options, manifest, input) = load_project(arguments)?;
+    if manifest.as_ref().is_some_and(|manifest| manifest.kind == ProjectKind::Library) {
+        return Err(anyhow::Error::msg("Libraries can't be run, only built."));
+    }
+    // Programs are run as if they were running on a target, using its
+    // standard library and system calls.
+    let target = match options.targets.as_slice() {
+        [] => "linux_aarch64",
+        [target] => target.as_str(),
+        _ => return Err(anyhow::Error::msg("Only one target can be run at a time."))
+    };
+    let platform = get_all_platforms().into_iter()
+        .find(|platform| platform.technical_id == target)
+        .expect("targets were checked while loading the project");
+    println!("🏃 Running as {}...", platform.technical_id);
+    let (stdout, stderr) = (&mut std::io::stdout(), &mut std::io::stderr());
+    let code = match mode {
+        RunMode::Interpret => interpret_target(&platform, input, &options.stdlib.join("std"), options.optimisation, stdout, stderr)?,
+        RunMode::Emulate => emulate_target(&platform, input, &options.stdlib.join("std"), &options, stdout, stderr)?,
+    };
+    if code != 0 {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+/// Interprets the program in `input` with the standard library (in `std`) and
+/// system calls of `platform`, after optimising it at `optimisation`. Returns
+/// the program's exit code.
+pub(crate) fn interpret_target<G: AssemblyGenerator>(
+    platform: &Platform<G>,
+    input: Input,
+    std: &Path,
+    optimisation: u8,
+    stdout: &mut dyn std::io::Write,
+    stderr: &mut dyn std::io::Write
+) -> Result<i32, anyhow::Error> {
+    let mut executable = input.executable(platform, std)?;
+    optimise::<G>(&mut executable, optimisation)?;
+    interpret(&executable, &platform.syscall_convention, stdout, stderr)
+}
+
+/// Compiles the program in `input` for `platform`, with the standard library
+/// in `std`, and runs it on the AArch64 emulator. Returns the program's exit
+/// code.
+fn emulate_target<G: AssemblyGenerator>(
+    platform: &Platform<G>,
+    input: Input,
+    std: &Path,
+    options: &BuildOptions,
+    stdout: &mut dyn std::io::Write,
+    stderr: &mut dyn std::io::Write
+) -> Result<i32, anyhow::Error> {
+    if platform.architecture() != Architecture::AArch64 {
+        return Err(anyhow::Error::msg(format!("{} can't be emulated.", platform.technical_id)));
     }
+    let executable = input.executable(platform, std)?;
+    let (_, _, code) = compile_executable(platform, executable, options.optimisation, options.priority, options.debug)?;
+    emulate(&code, &platform.syscall_convention, stdout, stderr)
 }
 
-fn info(_args: &mut std::env::Args) {
+/// Writes a packaged executable to `folder/name`, creating `folder` if needed.
+fn write_executable(folder: &Path, name: &str, executable: &[u8]) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(folder)?;
+    let path = folder.join(name);
+    std::fs::write(&path, executable)?;
+    // Executables need permission to be run on Unix-likes.
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+fn new(args: &mut std::env::Args) -> Result<(), anyhow::Error> {
+    let mut name = None;
+    let mut kind = ProjectKind::Executable;
+    for arg in args {
+        match arg.as_str() {
+            "--lib" => kind = ProjectKind::Library,
+            _ if name.is_none() => name = Some(arg),
+            inv => {
+                return Err(anyhow::Error::msg(format!(
+                    "Invalid argument. (`{inv}`) Try `beach help new` for a list of valid arguments."
+                )));
+            }
+        }
+    }
+    let name = name.ok_or(anyhow::Error::msg("Expected a name for the project. Try `beach help new` for more info."))?;
+    let folder = std::env::current_dir()?.join(&name);
+    package::scaffold(&folder, &name, kind)
+        .map_err(|e| anyhow::Error::msg(format!("Unable to create the project: {e}")))?;
+    println!("🏝️ Created `{name}` at {}", folder.display());
+    Ok(())
+}
+
+fn info(args: &mut std::env::Args) -> Result<(), anyhow::Error> {
+    let mut stdlib = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stdlib" => stdlib = Some(PathBuf::from(args.next().ok_or(anyhow::Error::msg(
+                "Expected a folder following `--stdlib`."
+            ))?)),
+            inv => {
+                return Err(anyhow::Error::msg(format!(
+                    "Invalid argument. (`{inv}`) Try `beach help info` for a list of valid arguments."
+                )));
+            }
+        }
+    }
     // TODO: auto generate this date on build
     println!("🕰️ Approximate build date: April 2025");
     // TODO: error handling
     println!("🔍Executable located at {}", std::env::current_exe().unwrap().display());
+    let (install, source) = install_directory();
+    println!("🏠 Installed at {} ({source})", install.display());
+    let (stdlib, source) = stdlib_directory(stdlib.as_deref());
+    println!("📚 Standard library at {} ({source})", stdlib.display());
     // I understand *this* repository was not created in 2019, but some code in
     // this repository is migrated from other locations datestamped as far back
     // as 2019, thus grandfathering in the date.
     println!("©️ Created and (c) Jaiden Bernard 2019-2025.");
+    Ok(())
+}
+
+/// The standard library in this repository.
+#[cfg(test)]
+fn test_stdlib() -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("stdlib").join("std");
+}
+
+/// Loads `source` as the `main.beach` of a project in a temporary folder
+/// named after `name`. The folder is left for the test to remove, as
+/// compiling reads the source again for debug info.
+#[cfg(test)]
+fn test_project(name: &str, source: &str) -> (PathBuf, ModuleGraph) {
+    let folder = std::env::temp_dir().join(format!("beach_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("main.beach"), source).unwrap();
+    let core = test_stdlib().parent().unwrap().join("core.beach");
+    let graph = ModuleGraph::load(&folder.join("main.beach"), &core, &[]).unwrap();
+    return (folder, graph);
+}
+
+/// The target tests compile for when any one will do.
+#[cfg(test)]
+fn test_platform() -> Platform<impl AssemblyGenerator> {
+    return get_all_platforms().into_iter()
+        .find(|platform| platform.technical_id == "linux_aarch64")
+        .unwrap();
+}
+
+#[test]
+fn test_build_target() {
+    let std = test_stdlib();
+    let (folder, graph) = test_project("test_build_target", "\
+include io:stdout;
+print => stdout;
+main {
+    print(\"Hello world!\");
+    return;
+}");
+    assert_eq!(
+        graph.clone().include_stdlib(&std, "windows_x86_64", &[]),
+        Err(String::from("no implementation of io:stdout"))
+    );
+    let platform = test_platform();
+    let mut target_graph = graph.clone();
+    target_graph.include_stdlib(&std, platform.technical_id, &[]).unwrap();
+    let (ir, assembly, code) = compile_target(&platform, &target_graph, package::MAX_OPTIMISATION, Priority::Size, true).unwrap();
+    std::fs::remove_dir_all(&folder).unwrap();
+    // The IR reads back as it was written.
+    assert_eq!(ir.parse::<Executable>().unwrap().to_string(), ir);
+    assert!(ir.contains("data compiler_ir_const_data_allocation_0 12 = \"Hello world!\"\n"));
+    // Code is commented with the line it came from.
+    assert!(assembly.contains("// main.beach:3: main {\n"));
+    assert!(assembly.contains("// main.beach:4: print(\"Hello world!\");\n.loc 1 4\nadr x0, compiler_ir_const_data_allocation_0\n"));
+    // Arguments are set without leaking stack space.
+    assert!(!assembly.contains("str x0, [sp, #-16]!"));
+    // ...and debug builds map it back to that line.
+    assert!(code.files[0].ends_with("main.beach"));
+    assert!(code.lines.iter().any(|entry| entry.file == 0 && entry.line == 4));
+    let executable = crate::packager::Packager::Elf.package(&code, platform.architecture(), true).unwrap();
+    assert_eq!(&executable[..4], b"\x7fELF");
+    assert!(executable.windows(11).any(|name| name == b".debug_line"));
+    // Data keeps its label in the symbol table.
+    assert!(executable.windows(35).any(|name| name == b"compiler_ir_const_data_allocation_0"));
+}
+
+#[test]
+fn test_interpret_target() {
+    let (folder, graph) = test_project("test_interpret_target", "\
+include io:stdout;
+include process:exit;
+main {
+    stdout(\"Hello world!\");
+    exit(7);
+}");
+    std::fs::remove_dir_all(&folder).unwrap();
+    // The standard library's system calls are the same on every target.
+    for platform in get_all_platforms() {
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let code = interpret_target(&platform, Input::Source(graph.clone()), &test_stdlib(), 0, &mut stdout, &mut stderr).unwrap();
+        assert_eq!((code, stdout.as_slice(), stderr.as_slice()), (7, &b"Hello world!"[..], &b""[..]));
+    }
+}
+
+#[test]
+fn test_register_sized_arguments() {
+    let (folder, graph) = test_project("test_register_sized_arguments", "\
+var quit = |code: usize| -> never {
+    unsafe {
+        !!syscall(93, (code));
+    }
+};
+main {
+    quit(3);
+}");
+    std::fs::remove_dir_all(&folder).unwrap();
+    let platform = test_platform();
+    // A `usize` is passed by value, not as a pointer to it.
+    for optimisation in 0..=package::MAX_OPTIMISATION {
+        let (_, _, code) = compile_target(&platform, &graph, optimisation, Priority::Performance, false).unwrap();
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let emulated = emulate(&code, &platform.syscall_convention, &mut stdout, &mut stderr).unwrap();
+        let interpreted = interpret_target(&platform, Input::Source(graph.clone()), &test_stdlib(), optimisation, &mut stdout, &mut stderr).unwrap();
+        assert_eq!((emulated, interpreted), (3, 3));
+    }
+}
+
+#[test]
+fn test_priority() {
+    let (folder, graph) = test_project("test_priority", "\
+var quit = |code: usize| -> never {
+    unsafe {
+        !!syscall(93, (code));
+    }
+};
+main {
+    quit(18364758544493064720);
+}");
+    std::fs::remove_dir_all(&folder).unwrap();
+    let platform = test_platform();
+    // Optimising for size loads the constant from memory instead of building
+    // it, which is smaller but runs the same.
+    let (_, fast_assembly, fast) = compile_target(&platform, &graph, 2, Priority::Performance, false).unwrap();
+    let (_, small_assembly, small) = compile_target(&platform, &graph, 2, Priority::Size, false).unwrap();
+    assert!(fast_assembly.contains("movk x0, #65244, lsl #48\n"));
+    assert!(small_assembly.contains("ldr x0, =0xFEDCBA9876543210\n"));
+    // The instructions shrink by more than the eight bytes the pool takes,
+    // though the pool's alignment can pad that back out.
+    let code_size = |code: &MachineCode| code.symbols.iter().map(|symbol| symbol.size).sum::<usize>();
+    assert_eq!(code_size(&small) + 8 + 4, code_size(&fast));
+    for code in [fast, small] {
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        assert_eq!(emulate(&code, &platform.syscall_convention, &mut stdout, &mut stderr).unwrap(), 0x10);
+    }
+}
+
+#[test]
+fn test_compile_ir() {
+    let platform = test_platform();
+    let source = |argument: &str| format!("\
+function pick {{
+    %0 = argument 0
+    goto_if_zero zero, %0
+    syscall 0x5d (0x07)
+    unreachable
+    label zero
+    syscall 0x5d (0x03)
+    unreachable
+}}
+main {{
+    set_argument 0 = {argument}
+    call pick
+}}
+");
+    // Hand-written IR compiles and runs like IR made from source.
+    for (argument, expected) in [("0x00", 3), ("0x01", 7)] {
+        let executable: Executable = source(argument).parse().unwrap();
+        for optimisation in 0..=package::MAX_OPTIMISATION {
+            let (_, _, code) = compile_executable(&platform, executable.clone(), optimisation, Priority::Size, false).unwrap();
+            let (mut stdout, mut stderr) = (vec![], vec![]);
+            let emulated = emulate(&code, &platform.syscall_convention, &mut stdout, &mut stderr).unwrap();
+            let interpreted = interpret_target(&platform, Input::Ir(executable.clone()), Path::new(""), optimisation, &mut stdout, &mut stderr).unwrap();
+            assert_eq!((emulated, interpreted), (expected, expected), "{argument} at optimisation {optimisation}");
+        }
+    }
+}
+
+#[test]
+fn test_hello_world() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let core = test_stdlib().parent().unwrap().join("core.beach");
+    let graph = ModuleGraph::load(&root.join("tests").join("hello_world.beach"), &core, &[]).unwrap();
+    // Every target runs the same on the emulator, at every optimisation
+    // level.
+    for platform in get_all_platforms() {
+        let mut target_graph = graph.clone();
+        target_graph.include_stdlib(&test_stdlib(), platform.technical_id, &[]).unwrap();
+        for optimisation in 0..=package::MAX_OPTIMISATION {
+            let (_, _, code) = compile_target(&platform, &target_graph, optimisation, Priority::Performance, false).unwrap();
+            let (mut stdout, mut stderr) = (vec![], vec![]);
+            let exit_code = emulate(&code, &platform.syscall_convention, &mut stdout, &mut stderr).unwrap();
+            assert_eq!(
+                (exit_code, stdout.as_slice(), stderr.as_slice()),
+                (0, &b"Hello world!"[..], &b""[..]),
+                "{} at optimisation {optimisation}", platform.technical_id
+            );
+        }
+    }
+}
+
+#[test]
+fn test_build_arguments() {
+    let parse = |args: &str| BuildArguments::parse(args.split_whitespace().map(String::from));
+    let arguments = parse("output name hello target linux_aarch64 optimisation 2 assembly ir --stdlib lib").unwrap();
+    assert!(arguments.assembly && arguments.ir);
+    assert_eq!(arguments.stdlib, Some(PathBuf::from("lib")));
+    assert_eq!(arguments.output_name.as_deref(), Some("hello"));
+    assert_eq!(arguments.targets, ["linux_aarch64"]);
+    assert_eq!(arguments.optimisation, Some(2));
+    assert_eq!(
+        parse("output file x").unwrap_err().to_string(),
+        "Expected `folder` or `name` following `output`, not `file`."
+    );
+    assert_eq!(
+        parse("input").unwrap_err().to_string(),
+        "Expected a file following `input`. Try `beach help build` for more info."
+    );
+    assert!(parse("optimisation 9").is_err());
+    assert!(parse("fast").is_err());
+
+    // Arguments override `beach.toml`, which overrides the defaults.
+    let manifest = Manifest::parse("\
+[project]
+name = \"hello\"
+version = \"0.1.0\"
+
+[build]
+output = \"out\"
+targets = [\"macos_aarch64\"]
+optimisation = 1
+size = true
+debug = true
+").unwrap();
+    let folder = Path::new("/project");
+    let options = BuildOptions::new(parse("optimisation 3").unwrap(), Some(&manifest), folder);
+    assert_eq!(options.input, folder.join("main.beach"));
+    assert_eq!(options.output_folder, folder.join("out"));
+    assert_eq!(options.output_name, "hello");
+    assert_eq!(options.targets, ["macos_aarch64"]);
+    assert_eq!(options.optimisation, 3);
+    assert_eq!(options.priority, Priority::Size);
+    assert!(options.debug && !options.assembly);
+    let options = BuildOptions::new(parse("input app.beach target linux_aarch64").unwrap(), None, folder);
+    assert_eq!(options.output_folder, folder.join("build"));
+    assert_eq!(options.output_name, "app");
+    assert_eq!(options.targets, ["linux_aarch64"]);
+    assert_eq!(options.optimisation, 0);
+    assert_eq!(options.priority, Priority::Performance);
 }
@@ -10,41 +10,29 @@ impl AssemblyGenerator for AArch64AssemblyGenerator {
     fn label(label: String) -> String {
         format!("{label}:\n")
     }
-    fn goto(label: HardwareData) -> String {
+    fn goto(label: HardwareData) -> Result<String, anyhow::Error> {
         match label {
             HardwareData::Label(label_name) => {
-                let mut output = String::new();
-                output += &format!("mov x9, [{label_name}]\n");
-                output += "br x9\n";
-                return output;
+                return Ok(format!("b {label_name}\n"));
             }
             HardwareData::ImmediateRegister(register_name) => {
-                return format!("br {register_name}\n");
+                return Ok(format!("br {register_name}\n"));
             }
-            _ => todo!()
+            unsupported => Err(unsupported_data("go to", &unsupported))
         }
     }
-    fn call(label: HardwareData) -> String {
-        match label {
-            HardwareData::Label(label_name) => {
-                let mut output = String::new();
-                output += "stp x29, x30, [sp, #-16]!\n";
-                output += "mov x29, sp\n";
-                output += &format!("adr x9, {label_name}\n");
-                output += "blr x9\n";
-                output += "ldp x29, x30, [sp], #16\n";
-                return output;
-            }
-            HardwareData::ImmediateRegister(register_name) => {
-                let mut output = String::new();
-                output += "stp x29, x30, [sp, #-16]!\n";
-                output += "mov x29, sp\n";
-                output += &format!("blr {register_name}\n");
-                output += "ldp x29, x30, [sp], #16\n";
-                return output;
-            }
-            _ => todo!()
-        }
+    fn call(label: HardwareData) -> Result<String, anyhow::Error> {
+        let branch = match label {
+            HardwareData::Label(label_name) => format!("bl {label_name}\n"),
+            HardwareData::ImmediateRegister(register_name) => format!("blr {register_name}\n"),
+            unsupported => return Err(unsupported_data("call", &unsupported))
+        };
+        let mut output = String::new();
+        output += "stp x29, x30, [sp, #-16]!\n";
+        output += "mov x29, sp\n";
+        output += &branch;
+        output += "ldp x29, x30, [sp], #16\n";
+        return Ok(output);
     }
     fn endcall() -> String {
         String::from("ret\n")
@@ -64,62 +52,287 @@ impl AssemblyGenerator for AArch64AssemblyGenerator {
         return output;
     }
 
-    fn push(data: HardwareData) -> String {
+    fn push(data: HardwareData) -> Result<String, anyhow::Error> {
         match data {
             HardwareData::ImmediateRegister(reg) => {
                 // TODO: this is inefficent (uses 16 bytes for 8 byte registers)
-                return format!("str {reg}, [sp, #-16]!\n");
+                return Ok(format!("str {reg}, [sp, #-16]!\n"));
             }
-            _ => todo!()
+            unsupported => Err(unsupported_data("push", &unsupported))
         }
     }
 
-    fn pop(amount: usize, location: HardwareData) -> String {
-        todo!()
+    fn pop(amount: usize, location: HardwareData) -> Result<String, anyhow::Error> {
+        match location {
+            HardwareData::ImmediateRegister(reg) if amount <= 8 => {
+                // matches the 16 byte slots used by `push`
+                return Ok(format!("ldr {reg}, [sp], #16\n"));
+            }
+            unsupported => Err(anyhow::Error::msg(format!("AArch64 can't pop {amount} bytes into {unsupported:?}.")))
+        }
     }
 
-    fn add(value: HardwareData, to: HardwareData) -> String {
-        todo!()
+    fn add(value: HardwareData, to: HardwareData) -> Result<String, anyhow::Error> {
+        match (value, to) {
+            (HardwareData::ImmediateRegister(value), HardwareData::ImmediateRegister(to)) => {
+                return Ok(format!("add {to}, {to}, {value}\n"));
+            }
+            (HardwareData::Immediate(value), HardwareData::ImmediateRegister(to)) if small_immediate(&value).is_some() => {
+                return Ok(format!("add {to}, {to}, #{}\n", small_immediate(&value).unwrap()));
+            }
+            (HardwareData::Immediate(value), HardwareData::ImmediateRegister(to)) => {
+                let mut output = Self::set(HardwareData::ImmediateRegister(String::from(SCRATCH_REGISTER)), HardwareData::Immediate(value))?;
+                output += &format!("add {to}, {to}, {SCRATCH_REGISTER}\n");
+                return Ok(output);
+            }
+            (value, to) => Err(anyhow::Error::msg(format!("AArch64 can't add {value:?} to {to:?}.")))
+        }
+    }
+    fn subtract(value: HardwareData, from: HardwareData) -> Result<String, anyhow::Error> {
+        match (value, from) {
+            (HardwareData::ImmediateRegister(value), HardwareData::ImmediateRegister(from)) => {
+                return Ok(format!("sub {from}, {from}, {value}\n"));
+            }
+            (HardwareData::Immediate(value), HardwareData::ImmediateRegister(from)) if small_immediate(&value).is_some() => {
+                return Ok(format!("sub {from}, {from}, #{}\n", small_immediate(&value).unwrap()));
+            }
+            (HardwareData::Immediate(value), HardwareData::ImmediateRegister(from)) => {
+                let mut output = Self::set(HardwareData::ImmediateRegister(String::from(SCRATCH_REGISTER)), HardwareData::Immediate(value))?;
+                output += &format!("sub {from}, {from}, {SCRATCH_REGISTER}\n");
+                return Ok(output);
+            }
+            (value, from) => Err(anyhow::Error::msg(format!("AArch64 can't subtract {value:?} from {from:?}.")))
+        }
+    }
+    fn multiply(value: HardwareData, to: HardwareData) -> Result<String, anyhow::Error> {
+        match (value, to) {
+            (HardwareData::ImmediateRegister(value), HardwareData::ImmediateRegister(to)) => {
+                return Ok(format!("mul {to}, {to}, {value}\n"));
+            }
+            // `mul` only takes registers.
+            (HardwareData::Immediate(value), HardwareData::ImmediateRegister(to)) => {
+                let mut output = Self::set(HardwareData::ImmediateRegister(String::from(SCRATCH_REGISTER)), HardwareData::Immediate(value))?;
+                output += &format!("mul {to}, {to}, {SCRATCH_REGISTER}\n");
+                return Ok(output);
+            }
+            (value, to) => Err(anyhow::Error::msg(format!("AArch64 can't multiply {to:?} by {value:?}.")))
+        }
     }
 
-    fn set(location: HardwareData, value: HardwareData) -> String {
-        match location {
-            HardwareData::ImmediateRegister(reg) => {
-                match value {
-                    HardwareData::ImmediateRegister(reg2) => {
-                        return format!("mov {reg}, {reg2}\n");
-                    }
-                    HardwareData::Label(label) => {
-                        return format!("adr {reg}, {label}\n");
+    fn set(location: HardwareData, value: HardwareData) -> Result<String, anyhow::Error> {
+        match (location, value) {
+            (HardwareData::ImmediateRegister(reg), HardwareData::ImmediateRegister(reg2)) => {
+                return Ok(format!("mov {reg}, {reg2}\n"));
+            }
+            (HardwareData::ImmediateRegister(reg), HardwareData::Label(label)) => {
+                return Ok(format!("adr {reg}, {label}\n"));
+            }
+            (HardwareData::ImmediateRegister(reg), HardwareData::StackSlot(offset)) => {
+                return Ok(format!("ldr {reg}, [sp, #{offset}]\n"));
+            }
+            (HardwareData::ImmediateRegister(reg), HardwareData::Immediate(imm)) if imm.len() <= 8 => {
+                if imm.len() == 1 {
+                    return Ok(format!("movz {reg}, #{}\n", imm[0]));
+                }
+                // Build the value 16 bits at a time, skipping any zeroed parts
+                // after the first.
+                let mut output = String::new();
+                for (index, value) in immediate_parts(&imm).into_iter().enumerate() {
+                    if index == 0 {
+                        output += &format!("movz {reg}, #{value}\n");
                     }
-                    HardwareData::Immediate(imm) => {
-                        if imm.len() == 1 {
-                            return format!("movz {reg}, #{}\n", imm[0]);
-                        }
-                        todo!();
+                    else if value != 0 {
+                        output += &format!("movk {reg}, #{value}, lsl #{}\n", index * 16);
                     }
-                    unfinished => todo!("val: {:?}", unfinished)
                 }
+                return Ok(output);
+            }
+            (HardwareData::StackSlot(offset), HardwareData::ImmediateRegister(reg)) => {
+                return Ok(format!("str {reg}, [sp, #{offset}]\n"));
+            }
+            // Stack slots are only set from registers, so anything else goes
+            // through the scratch register.
+            (HardwareData::StackSlot(offset), value) => {
+                let mut output = Self::set(HardwareData::ImmediateRegister(String::from(SCRATCH_REGISTER)), value)?;
+                output += &format!("str {SCRATCH_REGISTER}, [sp, #{offset}]\n");
+                return Ok(output);
             }
-            _ => todo!()
+            (location, value) => Err(anyhow::Error::msg(format!("AArch64 can't set {location:?} to {value:?}.")))
         }
     }
+    fn enter_frame(saved: &[String], size: usize) -> String {
+        let mut output = String::new();
+        let total = frame_size(saved, size);
+        if total == 0 {
+            return output;
+        }
+        // The stack pointer has to stay 16 byte aligned.
+        output += &format!("sub sp, sp, #{total}\n");
+        for (index, reg) in saved.iter().enumerate() {
+            output += &format!("str {reg}, [sp, #{}]\n", size + index * 8);
+        }
+        return output;
+    }
+    fn exit_frame(saved: &[String], size: usize) -> String {
+        let mut output = String::new();
+        let total = frame_size(saved, size);
+        if total == 0 {
+            return output;
+        }
+        for (index, reg) in saved.iter().enumerate() {
+            output += &format!("ldr {reg}, [sp, #{}]\n", size + index * 8);
+        }
+        output += &format!("add sp, sp, #{total}\n");
+        return output;
+    }
+    
+    fn assemble(assembly: &str) -> Result<MachineCode, anyhow::Error> {
+        crate::parser::aarch64::assembly::assemble(assembly)
+    }
+
+    fn trap() -> String {
+        String::from("brk #1\n")
+    }
+    fn comment(text: &str) -> String {
+        format!("// {text}\n")
+    }
+    fn symbol_kind(label: &str, kind: SymbolKind) -> String {
+        match kind {
+            SymbolKind::Function => format!(".type {label}, %function\n"),
+            SymbolKind::Data => format!(".type {label}, %object\n"),
+            SymbolKind::Label => String::new(),
+        }
+    }
+    fn source_file(index: usize, path: &str) -> String {
+        format!(".file {index} \"{}\"\n", path.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+    fn source_location(file: usize, line: usize) -> String {
+        format!(".loc {file} {line}\n")
+    }
     
     fn new() -> Self {
         Self {}
     }
 
+    const ARCHITECTURE: Architecture = Architecture::AArch64;
+
     // ARM conventions and C, doesn't work with C++ which passes func addr in x0
     const ARGUMENT_REGISTERS: &'static [&'static str] = &[
         "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"
     ];
-    const EXTENSIONS: Vec<Extension> = vec![
-        // TODO: extensions
+    const RESULT_REGISTERS: &'static [&'static str] = &[
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"
+    ];
+    // x16 and x17 are left alone, as MacOS passes system call numbers in x16
+    // and x17 is the scratch register, and x18 is reserved by some platforms.
+    const CALLER_SAVED_REGISTERS: &'static [&'static str] = &[
+        "x9", "x10", "x11", "x12", "x13", "x14", "x15"
     ];
-    const EXTENSION_PERFORMANCE_ORDER: Vec<Extension> = vec![
-        // TODO: ext perf order
+    const CALLEE_SAVED_REGISTERS: &'static [&'static str] = &[
+        "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28"
     ];
-    const EXTENSION_SIZE_ORDER: Vec<Extension> = vec![
-        // TODO: ext size order
+    const EXTENSIONS: &'static [Extension] = &[
+        Extension::AddStore(add_store),
+        Extension::GotoIfZero(goto_if_zero),
+        Extension::LoadImmediate(load_literal),
     ];
+    // `add_store` and `goto_if_zero` are a single instruction, replacing two,
+    // so they're faster and smaller. `load_literal` is smaller than building
+    // the value, but reads memory, so it's slower.
+    const EXTENSION_PERFORMANCE_ORDER: &'static [Extension] = &[
+        Extension::AddStore(add_store),
+        Extension::GotoIfZero(goto_if_zero),
+    ];
+    const EXTENSION_SIZE_ORDER: &'static [Extension] = &[
+        Extension::AddStore(add_store),
+        Extension::GotoIfZero(goto_if_zero),
+        Extension::LoadImmediate(load_literal),
+    ];
+}
+
+/// Holds values for a single instruction that can't take them as an
+/// immediate. The register allocator never uses it.
+const SCRATCH_REGISTER: &str = "x17";
+
+/// The bytes a stack frame takes up, with `size` bytes of spilled values below
+/// the saved registers.
+fn frame_size(saved: &[String], size: usize) -> usize {
+    (size + saved.len() * 8).next_multiple_of(16)
+}
+
+/// An error for `data` that `action` can't be done with.
+fn unsupported_data(action: &str, data: &HardwareData) -> anyhow::Error {
+    anyhow::Error::msg(format!("AArch64 can't {action} {data:?}."))
+}
+
+/// The 16 bit parts of an immediate of up to 8 bytes, lowest first, as
+/// `movz` and `movk` set them.
+fn immediate_parts(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks(2)
+        .map(|part| part[0] as u16 | (*part.get(1).unwrap_or(&0) as u16) << 8)
+        .collect()
+}
+
+/// The value of `bytes` if it fits in the 12 bits of an `add` or `sub`
+/// immediate.
+fn small_immediate(bytes: &[u8]) -> Option<u16> {
+    if bytes.len() > 2 && bytes[2..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let value = *bytes.first().unwrap_or(&0) as u16 | (*bytes.get(1).unwrap_or(&0) as u16) << 8;
+    if value < 4096 {
+        return Some(value);
+    }
+    return None;
+}
+
+/// `add` takes both values and the destination at once, instead of moving
+/// one value into the destination first.
+fn add_store(a: HardwareData, b: HardwareData, destination: HardwareData) -> Option<String> {
+    match (a, b, destination) {
+        (HardwareData::ImmediateRegister(a), HardwareData::ImmediateRegister(b), HardwareData::ImmediateRegister(destination)) => {
+            return Some(format!("add {destination}, {a}, {b}\n"));
+        }
+        // Either value can be the immediate, as the order doesn't matter.
+        (HardwareData::ImmediateRegister(register), HardwareData::Immediate(value), HardwareData::ImmediateRegister(destination))
+        | (HardwareData::Immediate(value), HardwareData::ImmediateRegister(register), HardwareData::ImmediateRegister(destination)) => {
+            return Some(format!("add {destination}, {register}, #{}\n", small_immediate(&value)?));
+        }
+        _ => None
+    }
+}
+
+/// `cbz` tests a register and branches without touching the flags.
+fn goto_if_zero(label: HardwareData, value: HardwareData) -> Option<String> {
+    match (label, value) {
+        (HardwareData::Label(label), HardwareData::ImmediateRegister(register)) => {
+            return Some(format!("cbz {register}, {label}\n"));
+        }
+        (HardwareData::Label(label), HardwareData::StackSlot(offset)) => {
+            return Some(format!("ldr {SCRATCH_REGISTER}, [sp, #{offset}]\ncbz {SCRATCH_REGISTER}, {label}\n"));
+        }
+        // Constants always or never branch.
+        (HardwareData::Label(label), HardwareData::Immediate(value)) if value.iter().all(|byte| *byte == 0) => {
+            return Some(format!("b {label}\n"));
+        }
+        (HardwareData::Label(_), HardwareData::Immediate(value)) if value.len() <= 8 => Some(String::new()),
+        _ => None
+    }
+}
+
+/// `ldr` from the literal pool the assembler adds after the code. It takes
+/// four bytes, and eight for the value, where building a value that needs all
+/// four 16 bit parts takes sixteen.
+fn load_literal(location: HardwareData, value: HardwareData) -> Option<String> {
+    match (location, value) {
+        (HardwareData::ImmediateRegister(register), HardwareData::Immediate(value)) if value.len() <= 8 => {
+            if immediate_parts(&value).iter().skip(1).filter(|part| **part != 0).count() < 3 {
+                return None;
+            }
+            let mut bytes = [0; 8];
+            bytes[..value.len()].copy_from_slice(&value);
+            return Some(format!("ldr {register}, =0x{:X}\n", u64::from_le_bytes(bytes)));
+        }
+        _ => None
+    }
 }
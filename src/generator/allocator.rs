@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+
+use super::generic::AssemblyGenerator;
+use crate::parser::beach::{CodeSegment, GeneratableTask, ImmediateOrRefrence};
+
+/// The tasks a virtual register is needed for.
+#[derive(Debug)]
+struct Interval {
+    register: usize,
+    /// The first task the register is needed for.
+    start: usize,
+    /// The last task the register is needed for.
+    end: usize,
+    /// Whether a call happens while the register is needed, so it has to be
+    /// kept somewhere calls don't overwrite.
+    crosses_call: bool,
+}
+
+/// Where a virtual register ended up.
+#[derive(Debug, Clone, PartialEq)]
+enum Location {
+    Register(&'static str),
+    /// The index of an 8 byte slot in the stack frame.
+    Spilled(usize),
+}
+
+/// Gives every virtual register in `segment` one of `G`'s registers, with
+/// linear scan allocation. Values needed across a call go in callee saved
+/// registers, and everything else prefers caller saved ones, which don't need
+/// to be saved. Values are only spilled to the stack when every suitable
+/// register is taken.
+///
+/// Functions (`is_function`) save the callee saved registers they use, and
+/// restore them before returning. The main block exits without returning, so
+/// it doesn't.
+///
+/// A value is needed wherever a path through the segment leads from it being
+/// set to it being used, which can include tasks written after its last use
+/// when a branch goes back to before it.
+pub fn allocate<G: AssemblyGenerator>(segment: &mut CodeSegment, is_function: bool) {
+    let intervals = intervals(segment);
+    if intervals.is_empty() {
+        return;
+    }
+    let locations = scan::<G>(&intervals);
+
+    // Callee saved registers are saved in the order `G` lists them.
+    let mut saved: Vec<String> = G::CALLEE_SAVED_REGISTERS.iter()
+        .filter(|register| locations.values().any(|location| location == &Location::Register(register)))
+        .map(|register| register.to_string())
+        .collect();
+    if !is_function {
+        saved.clear();
+    }
+    let slots = locations.values()
+        .filter(|location| matches!(location, Location::Spilled(_)))
+        .count();
+    let size = slots * G::REGISTER_WIDTH as usize;
+
+    for task in &mut segment.tasks {
+        for operand in task.operands_mut() {
+            if let ImmediateOrRefrence::Virtual(register) = operand {
+                *operand = match locations[register] {
+                    Location::Register(name) => ImmediateOrRefrence::Register(name.to_string()),
+                    Location::Spilled(slot) => ImmediateOrRefrence::Stack(slot * G::REGISTER_WIDTH as usize),
+                };
+            }
+        }
+    }
+    if saved.is_empty() && size == 0 {
+        return;
+    }
+    // The frame starts after the first source line, so debuggers stop after
+    // it's set up.
+    let start = segment.tasks.iter()
+        .position(|task| !matches!(task, GeneratableTask::SourceLine { .. }))
+        .unwrap_or(segment.tasks.len());
+    let mut tasks = segment.tasks.drain(..start).collect::<Vec<_>>();
+    tasks.push(GeneratableTask::EnterFrame { saved: saved.clone(), size });
+    for task in segment.tasks.drain(..) {
+        if is_function && matches!(task, GeneratableTask::EndCall) {
+            tasks.push(GeneratableTask::ExitFrame { saved: saved.clone(), size });
+        }
+        tasks.push(task);
+    }
+    segment.tasks = tasks;
+}
+
+/// Finds the interval of every virtual register in `segment`, ordered by where
+/// they start. Each interval covers every task its register is live at.
+fn intervals(segment: &mut CodeSegment) -> Vec<Interval> {
+    let successors = segment.successors();
+    let mut uses: Vec<HashSet<usize>> = vec![];
+    let mut definitions = vec![];
+    for task in &mut segment.tasks {
+        uses.push(task.uses_mut().into_iter()
+            .filter_map(|operand| match operand {
+                ImmediateOrRefrence::Virtual(register) => Some(*register),
+                _ => None
+            })
+            .collect());
+        definitions.push(task.definition());
+    }
+    // A register is live going into a task if the task uses it, or if it's
+    // live after the task and the task doesn't set it. Branches going back
+    // mean this has to be repeated until nothing changes.
+    let length = segment.tasks.len();
+    let mut live_in: Vec<HashSet<usize>> = vec![HashSet::new(); length];
+    let mut live_out: Vec<HashSet<usize>> = vec![HashSet::new(); length];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..length).rev() {
+            let out: HashSet<usize> = successors[index].iter()
+                .flat_map(|successor| live_in[*successor].iter().copied())
+                .collect();
+            let mut live: HashSet<usize> = out.iter()
+                .copied()
+                .filter(|register| definitions[index] != Some(*register))
+                .collect();
+            live.extend(uses[index].iter().copied());
+            if live != live_in[index] || out != live_out[index] {
+                live_in[index] = live;
+                live_out[index] = out;
+                changed = true;
+            }
+        }
+    }
+
+    let mut intervals: Vec<Interval> = vec![];
+    for (index, task) in segment.tasks.iter_mut().enumerate() {
+        let mut needed: Vec<usize> = task.operands_mut().into_iter()
+            .filter_map(|operand| match operand {
+                ImmediateOrRefrence::Virtual(register) => Some(*register),
+                _ => None
+            })
+            .collect();
+        needed.extend(live_in[index].iter().chain(&live_out[index]));
+        for register in needed {
+            match intervals.iter_mut().find(|interval| interval.register == register) {
+                Some(interval) => {
+                    interval.start = interval.start.min(index);
+                    interval.end = interval.end.max(index);
+                }
+                None => intervals.push(Interval {
+                    register,
+                    start: index,
+                    end: index,
+                    crosses_call: false
+                })
+            }
+        }
+    }
+    // Values still needed after a call have to survive it.
+    for (index, task) in segment.tasks.iter().enumerate() {
+        if let GeneratableTask::Call(_) = task {
+            for interval in &mut intervals {
+                if live_out[index].contains(&interval.register) {
+                    interval.crosses_call = true;
+                }
+            }
+        }
+    }
+    intervals.sort_by_key(|interval| (interval.start, interval.register));
+    intervals
+}
+
+/// Assigns each interval a register or stack slot.
+fn scan<G: AssemblyGenerator>(intervals: &[Interval]) -> HashMap<usize, Location> {
+    let mut locations = HashMap::new();
+    let mut free_caller_saved = G::CALLER_SAVED_REGISTERS.to_vec();
+    let mut free_callee_saved = G::CALLEE_SAVED_REGISTERS.to_vec();
+    // Intervals in a register, and that register.
+    let mut active: Vec<(&Interval, &'static str)> = vec![];
+    let mut slots = 0;
+    for interval in intervals {
+        // Registers of intervals that have ended are free again.
+        active.retain(|(other, register)| {
+            if other.end >= interval.start {
+                return true;
+            }
+            if G::CALLEE_SAVED_REGISTERS.contains(register) {
+                free_callee_saved.push(register);
+            }
+            else {
+                free_caller_saved.push(register);
+            }
+            false
+        });
+        free_caller_saved.sort_by_key(|register| G::CALLER_SAVED_REGISTERS.iter().position(|known| known == register));
+        free_callee_saved.sort_by_key(|register| G::CALLEE_SAVED_REGISTERS.iter().position(|known| known == register));
+
+        let free = if !interval.crosses_call && !free_caller_saved.is_empty() {
+            Some(free_caller_saved.remove(0))
+        }
+        else if !free_callee_saved.is_empty() {
+            Some(free_callee_saved.remove(0))
+        }
+        else {
+            None
+        };
+        if let Some(register) = free {
+            locations.insert(interval.register, Location::Register(register));
+            active.push((interval, register));
+            continue;
+        }
+        // Every suitable register is taken, so whichever value is needed for
+        // the longest is spilled.
+        let candidate = active.iter()
+            .enumerate()
+            .filter(|(_, (_, register))| !interval.crosses_call || G::CALLEE_SAVED_REGISTERS.contains(register))
+            .max_by_key(|(_, (other, _))| other.end)
+            .map(|(index, _)| index);
+        match candidate {
+            Some(index) if active[index].0.end > interval.end => {
+                let (spilled, register) = active.remove(index);
+                locations.insert(spilled.register, Location::Spilled(slots));
+                locations.insert(interval.register, Location::Register(register));
+                active.push((interval, register));
+            }
+            _ => {
+                locations.insert(interval.register, Location::Spilled(slots));
+            }
+        }
+        slots += 1;
+    }
+    locations
+}
+
+#[test]
+fn test_allocate() {
+    use crate::generator::aarch64::AArch64AssemblyGenerator;
+    let read = |argument_number| GeneratableTask::ReadArgument {
+        argument_number,
+        destination: ImmediateOrRefrence::Virtual(argument_number)
+    };
+    let pass = |argument_number| GeneratableTask::SetCallArgument {
+        argument_number,
+        argument_value: ImmediateOrRefrence::Virtual(argument_number)
+    };
+    let locations = |segment: &CodeSegment| -> Vec<String> {
+        segment.tasks.iter()
+            .filter_map(|task| match task {
+                GeneratableTask::SetCallArgument { argument_value, .. } => Some(format!("{argument_value:?}")),
+                _ => None
+            })
+            .collect()
+    };
+
+    // Only the value needed after the call has to be kept in a callee saved
+    // register.
+    let mut segment = CodeSegment::new("function");
+    segment.tasks = vec![read(0), read(1), pass(1), GeneratableTask::Call(String::from("other")), pass(0), GeneratableTask::EndCall];
+    allocate::<AArch64AssemblyGenerator>(&mut segment, true);
+    assert_eq!(locations(&segment), ["Register(\"x9\")", "Register(\"x19\")"]);
+    let saved = vec![String::from("x19")];
+    assert!(matches!(&segment.tasks[0], GeneratableTask::EnterFrame { saved: s, size: 0 } if s == &saved));
+    assert!(matches!(&segment.tasks[6], GeneratableTask::ExitFrame { saved: s, size: 0 } if s == &saved));
+
+    // With more values needed across a call than there are callee saved
+    // registers, the one needed for longest is spilled.
+    let mut segment = CodeSegment::new("function");
+    segment.tasks = (0..11).map(read).collect();
+    segment.tasks.push(GeneratableTask::Call(String::from("other")));
+    segment.tasks.extend((0..11).map(pass));
+    allocate::<AArch64AssemblyGenerator>(&mut segment, true);
+    let placed = locations(&segment);
+    assert_eq!(placed[9], "Register(\"x28\")");
+    assert_eq!(placed[10], "Stack(0)");
+    assert!(matches!(&segment.tasks[0], GeneratableTask::EnterFrame { saved, size: 8 } if saved.len() == 10));
+
+    // Values that aren't needed across calls don't need a frame.
+    let mut segment = CodeSegment::new("function");
+    segment.tasks = vec![read(0), pass(0), GeneratableTask::EndCall];
+    allocate::<AArch64AssemblyGenerator>(&mut segment, true);
+    assert_eq!(locations(&segment), ["Register(\"x9\")"]);
+    assert_eq!(segment.tasks.len(), 3);
+
+    // Values needed again after going back to an earlier label keep their
+    // register until then.
+    let mut segment: crate::parser::beach::Executable = "
+        main {
+            %0 = 0x00
+            label top
+            goto_if_zero first, %0
+            syscall 0x5d (%0)
+            unreachable
+            label first
+            %1 = 0x09
+            goto top
+        }
+    ".parse().unwrap();
+    allocate::<AArch64AssemblyGenerator>(&mut segment.entry_point, false);
+    let registers: Vec<&GeneratableTask> = segment.entry_point.tasks.iter()
+        .filter(|task| matches!(task, GeneratableTask::Copy { .. }))
+        .collect();
+    assert!(matches!(
+        registers[..],
+        [GeneratableTask::Copy { destination: a, .. }, GeneratableTask::Copy { destination: b, .. }] if a != b
+    ));
+}
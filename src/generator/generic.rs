@@ -1,4 +1,7 @@
 /// Represents a type capable of generating assembly for a platform from abstract concepts.
+///
+/// Methods that take [HardwareData] fail for combinations the platform can't
+/// make assembly for.
 pub trait AssemblyGenerator {
     /// The width of pointers on this platform, in bytes.
     const POINTER_WIDTH: u8;
@@ -7,46 +10,130 @@ pub trait AssemblyGenerator {
     /// The minimum width of instructions on this platform, in bytes.
     const INSTRUCTION_WIDTH: u8;
     /// Assembly that sets the executing location to `label`.
-    fn goto(label: HardwareData) -> String;
+    fn goto(label: HardwareData) -> Result<String, anyhow::Error>;
     /// Assembly that calls a method located at `label`.
-    fn call(label: HardwareData) -> String;
+    fn call(label: HardwareData) -> Result<String, anyhow::Error>;
     /// Assembly that exits a method
     fn endcall() -> String;
     /// Assembly that stores `bytes` at a location `label`.
     fn data(label: String, bytes: &[u8]) -> String;
     /// Adds `value` to `to`, storing the result in `to`.
-    fn add(value: HardwareData, to: HardwareData) -> String;
+    fn add(value: HardwareData, to: HardwareData) -> Result<String, anyhow::Error>;
+    /// Subtracts `value` from `from`, storing the result in `from`.
+    fn subtract(value: HardwareData, from: HardwareData) -> Result<String, anyhow::Error>;
+    /// Multiplies `to` by `value`, storing the result in `to`.
+    fn multiply(value: HardwareData, to: HardwareData) -> Result<String, anyhow::Error>;
     /// Sets `location` equal to `value`.
-    fn set(location: HardwareData, value: HardwareData) -> String;
+    fn set(location: HardwareData, value: HardwareData) -> Result<String, anyhow::Error>;
     /// Assembly that defines a label named `label`.
     fn label(label: String) -> String;
     /// Assembly that stores `data` on a generic stack.
-    fn push(data: HardwareData) -> String;
+    fn push(data: HardwareData) -> Result<String, anyhow::Error>;
     /// Assembly that retrieves `amount` bytes from a generic stack, storing
     /// them in `location`.
-    fn pop(amount: usize, location: HardwareData) -> String;
+    fn pop(amount: usize, location: HardwareData) -> Result<String, anyhow::Error>;
+    /// Assembly that starts a function's stack frame, saving `saved` and
+    /// reserving `size` bytes below them. Values in the frame are found from
+    /// the stack pointer, as [HardwareData::StackSlot]s.
+    fn enter_frame(saved: &[String], size: usize) -> String;
+    /// Assembly that restores `saved` and frees a frame started by
+    /// [AssemblyGenerator::enter_frame].
+    fn exit_frame(saved: &[String], size: usize) -> String;
+    /// Assembly that stops the program immediately, for places it should
+    /// never reach.
+    fn trap() -> String;
+    /// A line of assembly that does nothing, but leaves `text` for whoever
+    /// reads it.
+    fn comment(text: &str) -> String;
+    /// Assembly that marks `label` as a function or data, for the symbol
+    /// table.
+    fn symbol_kind(label: &str, kind: SymbolKind) -> String;
+    /// Assembly that numbers the source file `path` as `index`, for use by
+    /// [AssemblyGenerator::source_location]. Indices count up from one.
+    fn source_file(index: usize, path: &str) -> String;
+    /// Assembly that marks the following instructions as coming from `line`
+    /// of the source file numbered `file`, for debug info.
+    fn source_location(file: usize, line: usize) -> String;
+    /// Turns assembly made by this generator into machine code.
+    fn assemble(assembly: &str) -> Result<MachineCode, anyhow::Error>;
     /// Creates a fresh generator.
     fn new() -> Self;
+    /// The processor architecture this generator targets.
+    const ARCHITECTURE: Architecture;
     /// Register names used for passing arguments to functions, in order.
     const ARGUMENT_REGISTERS: &'static [&'static str];
-    const EXTENSIONS: Vec<Extension>;
-    const EXTENSION_PERFORMANCE_ORDER: Vec<Extension>;
-    const EXTENSION_SIZE_ORDER: Vec<Extension>;
+    /// Register names used for returning results from functions and system
+    /// calls, in order.
+    const RESULT_REGISTERS: &'static [&'static str];
+    /// Registers the register allocator can use that calls may overwrite, so
+    /// they only hold values that aren't needed after a call.
+    const CALLER_SAVED_REGISTERS: &'static [&'static str];
+    /// Registers the register allocator can use that calls leave as they
+    /// were. Functions have to save them before using them.
+    const CALLEE_SAVED_REGISTERS: &'static [&'static str];
+    /// Every [Extension] this generator has.
+    const EXTENSIONS: &'static [Extension];
+    /// The [Extension]s worth using when optimising for speed, from most to
+    /// least preferred. Any that are slower than the generic assembly they
+    /// replace are left out.
+    const EXTENSION_PERFORMANCE_ORDER: &'static [Extension];
+    /// The [Extension]s worth using when optimising for size, from most to
+    /// least preferred. Any that are larger than the generic assembly they
+    /// replace are left out.
+    const EXTENSION_SIZE_ORDER: &'static [Extension];
 }
 
+/// Assembly for something that would otherwise take several generic
+/// instructions, such as a fused instruction. Each returns `None` when it
+/// can't handle the data it's given, so the generic assembly is used instead.
 pub enum Extension {
     /// pushes [0] to a generic stack
-    StackPush(Box<dyn Fn(HardwareData) -> String>),
+    StackPush(fn(HardwareData) -> Option<String>),
     /// pops [0] from a generic stack
-    StackPop(Box<dyn Fn(HardwareData) -> String>),
+    StackPop(fn(HardwareData) -> Option<String>),
     /// goes to [0] if [1] equals zero
-    GotoIfZero(Box<dyn Fn(HardwareData, HardwareData) -> String>),
+    GotoIfZero(fn(HardwareData, HardwareData) -> Option<String>),
     /// adds [0] to [1], storing the result in [2]
-    AddStore(Box<dyn Fn(HardwareData, HardwareData, HardwareData) -> String>),
+    AddStore(fn(HardwareData, HardwareData, HardwareData) -> Option<String>),
+    /// sets [0] to the immediate [1]
+    LoadImmediate(fn(HardwareData, HardwareData) -> Option<String>),
+}
+
+impl Extension {
+    /// The name [crate::parser::beach::GeneratableTask::RequiredExtension]
+    /// uses for this extension.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StackPush(_) => "stack_push",
+            Self::StackPop(_) => "stack_pop",
+            Self::GotoIfZero(_) => "goto_if_zero",
+            Self::AddStore(_) => "add_store",
+            Self::LoadImmediate(_) => "load_immediate",
+        }
+    }
+}
+
+/// What instruction selection favours, which decides the [Extension]s it
+/// uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    #[default]
+    Performance,
+    Size,
+}
+
+impl Priority {
+    /// `G`'s extensions worth using for this priority, most preferred first.
+    pub fn extensions<G: AssemblyGenerator>(self) -> &'static [Extension] {
+        match self {
+            Self::Performance => G::EXTENSION_PERFORMANCE_ORDER,
+            Self::Size => G::EXTENSION_SIZE_ORDER,
+        }
+    }
 }
 
 /// Represents a real, tangible piece of data.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum HardwareData {
     // A valid hardware register for the platform that contains some data.
     ImmediateRegister(String),
@@ -54,6 +141,65 @@ pub enum HardwareData {
     RefrenceRegister(String),
     // A valid label that points to some data.
     Label(String),
+    // A slot in the current stack frame, as a byte offset from the stack
+    // pointer.
+    StackSlot(usize),
     // Raw immediate data.
     Immediate(Vec<u8>)
 }
+
+/// Processor architectures beach can generate code for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    AArch64,
+}
+
+/// Assembled machine code, ready to be packaged into an executable.
+#[derive(Debug)]
+pub struct MachineCode {
+    /// Instructions and data, in the order they should be loaded.
+    pub bytes: Vec<u8>,
+    /// Offset into `bytes` that execution starts at.
+    pub entry_point: usize,
+    /// Every label, in the order they're defined.
+    pub symbols: Vec<Symbol>,
+    /// The source files named by `lines`, in order.
+    pub files: Vec<String>,
+    /// Where the code from each source line starts, in order of offset.
+    pub lines: Vec<LineEntry>,
+}
+
+/// A label in [MachineCode], kept in the executable's symbol table so tools
+/// such as `nm` and debuggers can name each part of the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// Offset into [MachineCode::bytes].
+    pub offset: usize,
+    /// How many bytes follow the label before the next one, not counting any
+    /// alignment padding.
+    pub size: usize,
+    pub kind: SymbolKind,
+    /// Whether the label was made visible outside the file with `.global`.
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Data,
+    /// A label that wasn't given a kind, such as a jump target.
+    Label,
+}
+
+/// A row of a line table, marking that the instructions from `offset` on come
+/// from a line of a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEntry {
+    /// Offset into [MachineCode::bytes].
+    pub offset: usize,
+    /// Index into [MachineCode::files].
+    pub file: usize,
+    /// Counts up from one.
+    pub line: usize,
+}
@@ -1,2 +1,3 @@
+pub mod allocator;
 pub mod generic;
 pub mod aarch64;
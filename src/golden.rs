@@ -0,0 +1,259 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cli::{Input, compile_executable, compile_target, interpret_target},
+    generator::generic::Priority,
+    modules::ModuleGraph,
+    package::MAX_OPTIMISATION,
+    parser::aarch64::emulator::emulate,
+    platform::get_all_platforms
+};
+
+/// The environment variable that rewrites golden files instead of comparing
+/// against them.
+const BLESS_VARIABLE: &str = "BEACH_BLESS";
+/// The target whose standard library is used when writing IR golden files.
+const IR_TARGET: &str = "linux_aarch64";
+
+/// The golden files kept beside a test program, by extension.
+///
+/// A program that compiles has `.stdout` (what it writes to stdout), `.exit`
+/// (its exit code) and `.ir` (its IR before optimising, for [IR_TARGET]). A
+/// program that doesn't compile only has `.diagnostics`, the errors it was
+/// given. Paths in these files are relative to the repository.
+///
+/// Programs written in IR, which are `.ir` files without a `.beach` file
+/// beside them, don't have an `.ir` golden file, as that's the program.
+const GOLDEN_EXTENSIONS: [&str; 4] = ["stdout", "exit", "ir", "diagnostics"];
+
+/// What happened when a test program was compiled and run.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Ran {
+        stdout: String,
+        exit: i32,
+        /// [None] for programs written in IR.
+        ir: Option<String>,
+    },
+    Failed {
+        diagnostics: String,
+    },
+}
+
+impl Outcome {
+    /// The contents of every golden file for this outcome, by extension.
+    /// Files that shouldn't exist are [None], and a program written in IR
+    /// doesn't list its `.ir` file.
+    fn golden_files(&self) -> Vec<(&'static str, Option<String>)> {
+        match self {
+            Outcome::Ran { stdout, exit, ir } => {
+                let mut files = vec![
+                    ("stdout", Some(stdout.clone())),
+                    ("exit", Some(format!("{exit}\n"))),
+                    ("diagnostics", None),
+                ];
+                if let Some(ir) = ir {
+                    files.push(("ir", Some(ir.clone())));
+                }
+                files
+            }
+            Outcome::Failed { diagnostics } => vec![
+                ("stdout", None),
+                ("exit", None),
+                ("ir", None),
+                ("diagnostics", Some(diagnostics.clone())),
+            ],
+        }
+    }
+}
+
+/// Finds every `.beach` file under `folder`, and every `.ir` file that isn't
+/// the golden file of one, sorted so that failures are reported in the same
+/// order every time.
+fn discover(folder: &Path) -> Vec<PathBuf> {
+    let mut programs = vec![];
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in std::fs::read_dir(&folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                folders.push(path);
+            }
+            else {
+                let is_program = match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("beach") => true,
+                    Some("ir") => !path.with_extension("beach").exists(),
+                    _ => false
+                };
+                if is_program {
+                    programs.push(path);
+                }
+            }
+        }
+    }
+    programs.sort();
+    return programs;
+}
+
+/// Removes the repository's path from `text`, so golden files don't depend on
+/// where the repository was checked out.
+fn relative(text: &str, root: &Path) -> String {
+    let root = root.canonicalize().unwrap_or(root.to_path_buf());
+    return text.replace(&format!("{}/", root.display()), "");
+}
+
+/// Compiles and runs `program`, with `core` as its prelude and the standard
+/// library in `std`. Programs are run on the interpreter and on the emulator,
+/// for every target and optimisation level, and these must all agree. So must
+/// the IR for [IR_TARGET], read back from text and compiled again.
+fn run_program(program: &Path, core: &Path, std: &Path, root: &Path) -> Result<Outcome, String> {
+    let graph = match ModuleGraph::load(program, core, &[]) {
+        Ok(graph) => graph,
+        Err(error) => return Ok(Outcome::Failed { diagnostics: relative(&format!("{error}\n"), root) })
+    };
+    let mut expected: Option<(String, i32)> = None;
+    let mut ir = String::new();
+    let mut ir_platform = None;
+    for platform in get_all_platforms() {
+        let mut target_graph = graph.clone();
+        let features: Vec<&str> = platform.features.iter().map(|(feature, _)| *feature).collect();
+        if let Err(error) = target_graph.include_stdlib(std, platform.technical_id, &features) {
+            return Ok(Outcome::Failed { diagnostics: relative(&format!("{error}\n"), root) });
+        }
+        for optimisation in 0..=MAX_OPTIMISATION {
+            let runner = format!("{} at optimisation {optimisation}", platform.technical_id);
+            let (compiled_ir, _, code) = match compile_target(&platform, &target_graph, optimisation, Priority::Performance, false) {
+                Ok(compiled) => compiled,
+                Err(error) => return Ok(Outcome::Failed { diagnostics: relative(&format!("{error}\n"), root) })
+            };
+            if optimisation == 0 && platform.technical_id == IR_TARGET {
+                ir = compiled_ir;
+            }
+            let (mut stdout, mut stderr) = (vec![], vec![]);
+            let interpreted = interpret_target(&platform, Input::Source(graph.clone()), std, optimisation, &mut stdout, &mut stderr)
+                .map_err(|error| format!("{runner}, interpreted: {error}"))?;
+            let interpreted = (String::from_utf8_lossy(&stdout).into_owned(), interpreted);
+            let (mut stdout, mut stderr) = (vec![], vec![]);
+            let emulated = emulate(&code, &platform.syscall_convention, &mut stdout, &mut stderr)
+                .map_err(|error| format!("{runner}, emulated: {error}"))?;
+            let emulated = (String::from_utf8_lossy(&stdout).into_owned(), emulated);
+            if interpreted != emulated {
+                return Err(format!("{runner}: interpreted as {interpreted:?}, but emulated as {emulated:?}"));
+            }
+            match &expected {
+                Some(expected) if *expected != emulated => {
+                    return Err(format!("{runner}: ran as {emulated:?}, but other runs gave {expected:?}"));
+                }
+                _ => expected = Some(emulated)
+            }
+        }
+        if platform.technical_id == IR_TARGET {
+            ir_platform = Some(platform);
+        }
+    }
+    let (stdout, exit) = expected.expect("there is at least one target");
+    let platform = ir_platform.expect("the IR target is a platform");
+    let executable: crate::parser::beach::Executable = ir.parse()
+        .map_err(|error| format!("the IR doesn't read back: {error}"))?;
+    for optimisation in 0..=MAX_OPTIMISATION {
+        let runner = format!("{IR_TARGET} at optimisation {optimisation}, from IR");
+        let (_, _, code) = compile_executable(&platform, executable.clone(), optimisation, Priority::Performance, false)
+            .map_err(|error| format!("{runner}: {error}"))?;
+        let (mut output, mut errors) = (vec![], vec![]);
+        let emulated = emulate(&code, &platform.syscall_convention, &mut output, &mut errors)
+            .map_err(|error| format!("{runner}, emulated: {error}"))?;
+        let emulated = (String::from_utf8_lossy(&output).into_owned(), emulated);
+        if emulated != (stdout.clone(), exit) {
+            return Err(format!("{runner}: ran as {emulated:?}, but the source gave {:?}", (stdout, exit)));
+        }
+    }
+    return Ok(Outcome::Ran { stdout, exit, ir: Some(relative(&ir, root)) });
+}
+
+/// Runs `program`, written in IR for [IR_TARGET], on the interpreter and on
+/// the emulator, which must agree. Hand-written IR might not be in SSA form,
+/// so it isn't optimised.
+fn run_ir_program(program: &Path) -> Result<Outcome, String> {
+    let text = std::fs::read_to_string(program).map_err(|error| error.to_string())?;
+    let executable: crate::parser::beach::Executable = match text.parse() {
+        Ok(executable) => executable,
+        Err(error) => return Ok(Outcome::Failed { diagnostics: format!("{error}\n") })
+    };
+    let platform = get_all_platforms().into_iter()
+        .find(|platform| platform.technical_id == IR_TARGET)
+        .expect("the IR target is a platform");
+    let (_, _, code) = match compile_executable(&platform, executable.clone(), 0, Priority::Performance, false) {
+        Ok(compiled) => compiled,
+        Err(error) => return Ok(Outcome::Failed { diagnostics: format!("{error}\n") })
+    };
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let interpreted = interpret_target(&platform, Input::Ir(executable), Path::new(""), 0, &mut stdout, &mut stderr)
+        .map_err(|error| format!("interpreted: {error}"))?;
+    let interpreted = (String::from_utf8_lossy(&stdout).into_owned(), interpreted);
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let emulated = emulate(&code, &platform.syscall_convention, &mut stdout, &mut stderr)
+        .map_err(|error| format!("emulated: {error}"))?;
+    let emulated = (String::from_utf8_lossy(&stdout).into_owned(), emulated);
+    if interpreted != emulated {
+        return Err(format!("interpreted as {interpreted:?}, but emulated as {emulated:?}"));
+    }
+    let (stdout, exit) = emulated;
+    return Ok(Outcome::Ran { stdout, exit, ir: None });
+}
+
+/// Compiles and runs every program in `tests/`, and compares what happened
+/// against the golden files beside it. Set `BEACH_BLESS=1` to write the golden
+/// files from what happened instead.
+#[test]
+fn test_golden_files() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let bless = std::env::var_os(BLESS_VARIABLE).is_some_and(|value| !value.is_empty() && value != "0");
+    let mut failures = vec![];
+    for program in discover(&root.join("tests")) {
+        let name = relative(&program.display().to_string(), &root);
+        let outcome = if program.extension().is_some_and(|extension| extension == "ir") {
+            run_ir_program(&program)
+        }
+        else {
+            run_program(&program, &root.join("stdlib").join("core.beach"), &root.join("stdlib").join("std"), &root)
+        };
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(reason) => {
+                failures.push(format!("{name}: {reason}"));
+                continue;
+            }
+        };
+        if bless {
+            for (extension, contents) in outcome.golden_files() {
+                let path = program.with_extension(extension);
+                match contents {
+                    Some(contents) => std::fs::write(&path, contents).unwrap(),
+                    None if path.exists() => std::fs::remove_file(&path).unwrap(),
+                    None => {}
+                }
+            }
+            continue;
+        }
+        if GOLDEN_EXTENSIONS.iter().all(|extension| !program.with_extension(extension).exists()) {
+            failures.push(format!("{name}: has no golden files"));
+            continue;
+        }
+        for (extension, contents) in outcome.golden_files() {
+            let golden = std::fs::read_to_string(program.with_extension(extension)).ok();
+            if golden != contents {
+                failures.push(format!(
+                    "{name}: .{extension} differs\n--- expected\n{}\n--- found\n{}",
+                    golden.as_deref().unwrap_or("(no file)"),
+                    contents.as_deref().unwrap_or("(no file)")
+                ));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} golden file test(s) failed. If the changes are expected, run `{BLESS_VARIABLE}=1 cargo test golden`.\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}
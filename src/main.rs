@@ -4,7 +4,12 @@ mod utils;
 mod generator;
 mod cli;
 mod platform;
+mod stdlib;
+mod modules;
 mod ir;
+mod package;
+#[cfg(test)]
+mod golden;
 
 fn main() {
     cli::main();
@@ -0,0 +1,476 @@
+mod scope;
+
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use crate::package::{Library, Requirement};
+use crate::parser::beach::ast::{user_token_format::Annotation, Definition, Program};
+
+/// Every file that makes up a project, each parsed into its own [Program], and
+/// the imports between them.
+///
+/// Files are found through `file` imports, starting from the project's main
+/// file, `library` imports of the project's libraries, and `!!core` imports,
+/// starting from the core prelude (`core.beach`) that every project file
+/// implicitly imports. Standard library `include`s are left for each target to
+/// resolve, as they can point to different files (or none) on every platform,
+/// and are added by [ModuleGraph::include_stdlib]. Each file is only read and
+/// parsed once, no matter how many times it's imported.
+#[derive(Clone)]
+pub struct ModuleGraph {
+    /// The main file is always first.
+    pub modules: Vec<Module>,
+    /// The index of `core.beach`.
+    prelude: usize,
+}
+
+/// A single file in a [ModuleGraph].
+#[derive(Clone)]
+pub struct Module {
+    /// The namespace this module's definitions live in. For project files this
+    /// is their path relative to the project folder, (`["utils", "math"]` for
+    /// `utils/math.beach`) empty for the main file. Core files are under
+    /// `core`, and library files are under the library's name.
+    pub namespace: Vec<String>,
+    /// If this module is part of the core library. Core definitions make up
+    /// the prelude, so they keep their plain names and every other module can
+    /// use them.
+    pub is_core: bool,
+    /// The library this module is part of, if it isn't part of the project.
+    pub library: Option<String>,
+    pub path: PathBuf,
+    pub program: Program,
+    /// Indices of the modules this one imports.
+    pub imports: Vec<usize>,
+}
+
+/// A single import from a module's program.
+enum Import {
+    /// `file utils:math;`
+    Project(String),
+    /// `!!core types;`
+    Core(String),
+    /// `library geometry "1.2";`, with an empty version if none is given.
+    Library(String, String),
+}
+
+impl ModuleGraph {
+    /// Finds and parses every file needed by the project with the main file
+    /// `main`. `prelude` is the core library's `core.beach`, and files it
+    /// imports with `!!core` are found in the `core` folder beside it.
+    /// `libraries` are the project's resolved libraries (see
+    /// [crate::package::resolve]) that can be imported with `library`.
+    pub fn load(main: &Path, prelude: &Path, libraries: &[Library]) -> Result<ModuleGraph, anyhow::Error> {
+        let project_folder = main.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut graph = ModuleGraph { modules: vec![], prelude: 0 };
+        // Maps every file we've read to its module.
+        let mut known: HashMap<PathBuf, usize> = HashMap::new();
+        graph.add(main, vec![], None, false, &mut known)?;
+        graph.prelude = graph.add(prelude, vec![String::from("core")], None, true, &mut known)?;
+        let core_folder = graph.modules[graph.prelude].path.with_extension("");
+
+        // Modules are appended as they're found, so this visits every one.
+        let mut index = 0;
+        while index < graph.modules.len() {
+            let is_core = graph.modules[index].is_core;
+            let mut imports = vec![];
+            if !is_core {
+                imports.push(graph.prelude);
+            }
+            // Project files are named from the folder of the project (or
+            // library) they're in.
+            let library = graph.modules[index].library.clone();
+            let (root_folder, root_namespace) = match &library {
+                Some(name) => {
+                    let library = libraries.iter().find(|library| &library.name == name).expect("libraries are only loaded once resolved");
+                    (library.folder.clone(), vec![name.clone()])
+                }
+                None => (project_folder.clone(), vec![])
+            };
+            for (import, location) in Self::imports_of(&graph.modules[index].program) {
+                let importer = graph.modules[index].path.clone();
+                let error = |e: anyhow::Error| anyhow::Error::msg(format!(
+                    "{}:{location}: {e}", importer.display()
+                ));
+                let mut files = vec![];
+                match &import {
+                    Import::Core(label) => {
+                        let mut namespace = vec![String::from("core")];
+                        namespace.extend(label.split('/').map(String::from));
+                        files.push((core_folder.join(format!("{label}.beach")), namespace, None));
+                    }
+                    Import::Project(label) => {
+                        let importer_folder = graph.modules[index].path.parent().unwrap_or(Path::new("")).to_path_buf();
+                        for file in Self::project_files(&importer_folder, label).map_err(error)? {
+                            let mut namespace = root_namespace.clone();
+                            namespace.extend(Self::namespace_of(&root_folder, &file));
+                            files.push((file, namespace, library.clone()));
+                        }
+                    }
+                    Import::Library(name, version) => {
+                        let found = Self::find_library(libraries, name, version).map_err(error)?;
+                        files.push((found.entry.clone(), vec![name.clone()], Some(name.clone())));
+                    }
+                }
+                for (file, namespace, library) in files {
+                    let is_core = file.starts_with(&core_folder);
+                    let imported = graph.add(&file, namespace, library, is_core, &mut known).map_err(error)?;
+                    if !imports.contains(&imported) {
+                        imports.push(imported);
+                    }
+                }
+            }
+            graph.modules[index].imports = imports;
+            index += 1;
+        }
+        if let Some(cycle) = graph.find_cycle() {
+            let chain: Vec<String> = cycle.iter()
+                .map(|index| graph.modules[*index].path.display().to_string())
+                .collect();
+            return Err(anyhow::Error::msg(format!("Files import each other in a cycle: {}", chain.join(" -> "))));
+        }
+        graph.check_main_blocks(false)?;
+        Ok(graph)
+    }
+    /// Checks that no library has a `main` block. (spec D.1.6) If
+    /// `is_library`, the project itself is a library too.
+    pub fn check_main_blocks(&self, is_library: bool) -> Result<(), anyhow::Error> {
+        for module in &self.modules {
+            if module.is_core || (module.library.is_none() && !is_library) {
+                continue;
+            }
+            if let Some(location) = module.program.main {
+                return Err(anyhow::Error::msg(format!(
+                    "{}:{location}: Libraries can't have a `main` block.",
+                    module.path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+    /// Adds the standard library files implementing every module's
+    /// `include`s on the platform with the technical id `platform_id`, along
+    /// with anything they include in turn. Includes naming one of the
+    /// platform's own `features` don't need the standard library. If the
+    /// platform can't support every include, the reason is returned instead.
+    pub fn include_stdlib(&mut self, std: &Path, platform_id: &str, features: &[&str]) -> Result<(), String> {
+        let mut known: HashMap<PathBuf, usize> = self.modules.iter()
+            .enumerate()
+            .map(|(index, module)| (module.path.clone(), index))
+            .collect();
+        let mut index = 0;
+        while index < self.modules.len() {
+            let includes: Vec<String> = self.modules[index].program.definitions.iter()
+                .filter_map(|definition| match definition {
+                    Definition::System { label } if !features.contains(&label.as_str()) => Some(label.clone()),
+                    _ => None
+                })
+                .collect();
+            for include in includes {
+                let path: Vec<String> = include.split(':').map(String::from).collect();
+                let file = crate::stdlib::resolve_include(std, &path, platform_id)
+                    .ok_or(format!("no implementation of {include}"))?;
+                // Platform specific files (`io/macos_aarch64.beach`) share a
+                // namespace with the generic ones (`io.beach`).
+                let mut namespace: Vec<String> = file.strip_prefix(std).unwrap_or(&file)
+                    .with_extension("")
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                if namespace.last().map(String::as_str) == Some(platform_id) {
+                    namespace.pop();
+                }
+                let imported = self.add(&file, namespace, None, false, &mut known).map_err(|e| e.to_string())?;
+                if self.modules[imported].imports.is_empty() {
+                    self.modules[imported].imports.push(self.prelude);
+                }
+                if !self.modules[index].imports.contains(&imported) {
+                    self.modules[index].imports.push(imported);
+                }
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+    /// Every module's program combined into one, with each name replaced by
+    /// its full path. The main file's program comes first so that its `main`
+    /// block stays the entry point.
+    pub fn link(&self) -> Result<Program, anyhow::Error> {
+        let programs = scope::qualify(self)?;
+        let mut programs = programs.into_iter();
+        let mut program = programs.next().expect("the main file is always loaded");
+        for other in programs {
+            program = program + other;
+        }
+        Ok(program)
+    }
+    /// Reads and parses `file` as a new module, unless it already is one.
+    /// `is_core` files are part of the core library, which is read with
+    /// [Program::from_core_lst]. Returns the module's index.
+    fn add(&mut self, file: &Path, namespace: Vec<String>, library: Option<String>, is_core: bool, known: &mut HashMap<PathBuf, usize>) -> Result<usize, anyhow::Error> {
+        let path = crate::stdlib::canonicalize(file)
+            .ok_or_else(|| anyhow::Error::msg(format!("Cannot find the file `{}`.", file.display())))?;
+        if let Some(index) = known.get(&path) {
+            return Ok(*index);
+        }
+        let source = crate::stdlib::read_file(&path)
+            .ok_or_else(|| anyhow::Error::msg(format!("`{}` is not valid UTF-8 or otherwise could not be read.", path.display())))?;
+        let mut program = crate::parser::parse_string_file(source)
+            .and_then(|syntax| match is_core {
+                true => Program::from_core_lst(syntax),
+                false => Program::from_lst(syntax, None)
+            })
+            .map_err(|e| anyhow::Error::msg(format!("{}: {e}", path.display())))?;
+        // Remember where everything came from, for source comments and debug
+        // info.
+        program.path = Some(path.clone());
+        for definition in &mut program.definitions {
+            match definition {
+                Definition::Function(function) => function.file = Some(path.clone()),
+                Definition::Type { methods, file, .. } | Definition::Enum { methods, file, .. } => {
+                    *file = Some(path.clone());
+                    for method in methods {
+                        method.file = Some(path.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        for diagnostic in &mut program.unloaded {
+            diagnostic.file = Some(path.clone());
+        }
+        self.modules.push(Module { namespace, is_core, library, path: path.clone(), program, imports: vec![] });
+        known.insert(path, self.modules.len() - 1);
+        Ok(self.modules.len() - 1)
+    }
+    /// The `file`, `library` and `!!core` imports of `program`.
+    fn imports_of(program: &Program) -> Vec<(Import, Annotation)> {
+        program.definitions.iter().filter_map(|definition| match definition {
+            Definition::File { label, location } => Some((Import::Project(label.clone()), *location)),
+            Definition::Core { label, location } => Some((Import::Core(label.clone()), *location)),
+            Definition::Library { name, version, location } => Some((Import::Library(name.clone(), version.clone()), *location)),
+            _ => None
+        }).collect()
+    }
+    /// The library imported by `library <name> "<version>";`. The version is
+    /// optional, but if it's given the resolved library has to meet it.
+    fn find_library<'a>(libraries: &'a [Library], name: &str, version: &str) -> Result<&'a Library, anyhow::Error> {
+        let library = libraries.iter().find(|library| library.name == name).ok_or(anyhow::Error::msg(format!(
+            "`{name}` isn't a library this project depends on. Add it to [dependencies] in `{}`.",
+            crate::package::MANIFEST_NAME
+        )))?;
+        if !version.is_empty() && !Requirement::parse(version)?.matches(&library.version) {
+            return Err(anyhow::Error::msg(format!(
+                "`{name}` {version} was asked for, but version {} is the one in use.",
+                library.version
+            )));
+        }
+        Ok(library)
+    }
+    /// The files imported by `file <label>;` from a file in `folder`. Globs
+    /// (`file shapes:*;`) import every `.beach` file directly in a folder, in
+    /// name order.
+    fn project_files(folder: &Path, label: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let mut path = folder.to_path_buf();
+        let parts: Vec<&str> = label.split(':').collect();
+        let (last, parents) = parts.split_last().expect("`file` paths are never empty");
+        path.extend(parents);
+        if *last != "*" {
+            path.push(format!("{last}.beach"));
+            return Ok(vec![path]);
+        }
+        // The folder may be inside the standard library built into beach.
+        let mut files: Vec<PathBuf> = crate::stdlib::files_in(&path)
+            .ok_or(anyhow::Error::msg(format!("Cannot find the folder `{}` for `file {label};`.", path.display())))?
+            .into_iter()
+            .filter(|file| file.extension().is_some_and(|extension| extension == "beach"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+    /// The namespace of a project file, from its path relative to the project
+    /// folder.
+    fn namespace_of(project_folder: &Path, file: &Path) -> Vec<String> {
+        let file = file.with_extension("");
+        let project_folder = project_folder.canonicalize().unwrap_or(project_folder.to_path_buf());
+        let file = file.parent()
+            .and_then(|parent| parent.canonicalize().ok())
+            .map(|parent| parent.join(file.file_name().unwrap_or_default()))
+            .unwrap_or(file);
+        let relative = file.strip_prefix(&project_folder).unwrap_or(&file);
+        relative.components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                _ => None
+            })
+            .collect()
+    }
+    /// Looks for modules that import themselves through other modules, and
+    /// returns the first such chain found, starting and ending at the same
+    /// module.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        // 0: unvisited, 1: on the current path, 2: finished
+        let mut states = vec![0u8; self.modules.len()];
+        let mut chain = vec![];
+        for start in 0..self.modules.len() {
+            if states[start] != 0 {
+                continue;
+            }
+            if let Some(cycle) = self.visit(start, &mut states, &mut chain) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+    fn visit(&self, index: usize, states: &mut [u8], chain: &mut Vec<usize>) -> Option<Vec<usize>> {
+        states[index] = 1;
+        chain.push(index);
+        for import in &self.modules[index].imports {
+            match states[*import] {
+                0 => {
+                    if let Some(cycle) = self.visit(*import, states, chain) {
+                        return Some(cycle);
+                    }
+                }
+                1 => {
+                    let start = chain.iter().position(|module| module == import).unwrap();
+                    let mut cycle = chain[start..].to_vec();
+                    cycle.push(*import);
+                    return Some(cycle);
+                }
+                _ => {}
+            }
+        }
+        chain.pop();
+        states[index] = 2;
+        None
+    }
+}
+
+#[test]
+fn test_module_graph() {
+    let folder = std::env::temp_dir().join(format!("beach_test_module_graph_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(folder.join("shapes")).unwrap();
+    std::fs::create_dir_all(folder.join("core")).unwrap();
+    std::fs::write(folder.join("core.beach"), "!!core types;").unwrap();
+    std::fs::write(folder.join("core").join("types.beach"), "type string {}").unwrap();
+    std::fs::write(folder.join("main.beach"), "file shapes:*;\nfile shapes:circle;\nmain {}").unwrap();
+    std::fs::write(folder.join("shapes").join("circle.beach"), "file square;").unwrap();
+    std::fs::write(folder.join("shapes").join("square.beach"), "").unwrap();
+
+    let graph = ModuleGraph::load(&folder.join("main.beach"), &folder.join("core.beach"), &[]).unwrap();
+    let namespaces: Vec<String> = graph.modules.iter().map(|module| module.namespace.join("~")).collect();
+    // Each file is only loaded once, even though `circle` is imported twice.
+    assert_eq!(namespaces, ["", "core", "shapes~circle", "shapes~square", "core~types"]);
+    assert_eq!(graph.modules[0].imports, [1, 2, 3]);
+    assert_eq!(graph.modules[2].imports, [1, 3]);
+    assert_eq!(graph.modules[1].imports, [4]);
+
+    std::fs::write(folder.join("shapes").join("square.beach"), "file circle;").unwrap();
+    let error = ModuleGraph::load(&folder.join("main.beach"), &folder.join("core.beach"), &[]).map(|_| ()).unwrap_err();
+    let circle = folder.join("shapes").join("circle.beach").canonicalize().unwrap();
+    let square = folder.join("shapes").join("square.beach").canonicalize().unwrap();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "Files import each other in a cycle: {} -> {} -> {}",
+            circle.display(), square.display(), circle.display()
+        )
+    );
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn test_prelude() {
+    let folder = std::env::temp_dir().join(format!("beach_test_prelude_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("main.beach"), "\n\nmain {\n    return;\n}").unwrap();
+    // The core library built into beach is its own set of modules, and
+    // doesn't shift the lines of the files importing it.
+    let prelude = Path::new(crate::stdlib::EMBEDDED_FOLDER).join("core.beach");
+    let graph = ModuleGraph::load(&folder.join("main.beach"), &prelude, &[]).unwrap();
+    std::fs::remove_dir_all(&folder).unwrap();
+    let namespaces: Vec<String> = graph.modules.iter().map(|module| module.namespace.join("~")).collect();
+    assert_eq!(namespaces, ["", "core", "core~types"]);
+    assert_eq!(graph.modules[2].path, Path::new(crate::stdlib::EMBEDDED_FOLDER).join("core").join("types.beach"));
+    assert!(!graph.modules[0].is_core && graph.modules[1].is_core && graph.modules[2].is_core);
+    assert_eq!(graph.modules[0].imports, [1]);
+    assert_eq!(graph.modules[0].program.main.map(|location| location.line), Some(3));
+    // Core definitions keep their plain names.
+    let program = graph.link().unwrap();
+    assert!(program.definitions.iter().any(|definition| definition.name().is_some_and(|name| name == "string")));
+    // Members that can't be read yet are left out, and say so.
+    let unloaded: Vec<String> = graph.modules[2].program.unloaded.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert!(unloaded.iter().any(|diagnostic| diagnostic.ends_with("types.beach:57:16: `is_success` was left out because it couldn't be read: 58:9: Unexpected symbol Label(\"self\") following `match`.")));
+    assert!(unloaded.iter().any(|diagnostic| diagnostic.contains("`printable` was left out")));
+    assert!(program.definitions.iter().any(|definition| matches!(
+        definition,
+        Definition::Enum { name, cases, implementations, .. }
+            if name == "result" && cases.len() == 2 && implementations.is_empty()
+    )));
+    // Globs list the files built into beach too.
+    let embedded = Path::new(crate::stdlib::EMBEDDED_FOLDER);
+    assert_eq!(ModuleGraph::project_files(embedded, "core:*").unwrap(), [embedded.join("core").join("types.beach")]);
+    assert!(ModuleGraph::project_files(embedded, "missing:*").is_err());
+
+    // Project files aren't left incomplete, so their methods have to be read.
+    let folder = std::env::temp_dir().join(format!("beach_test_prelude_unreadable_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("main.beach"), "type point {\n    var x: u8;\n    var get_x = |&self| -> u8 {\n        return self.x self.x;\n    }\n}\nmain {\n    return;\n}").unwrap();
+    let error = ModuleGraph::load(&folder.join("main.beach"), &prelude, &[]).map(|_| ()).unwrap_err();
+    std::fs::remove_dir_all(&folder).unwrap();
+    assert!(error.to_string().ends_with("4:23: Expected `;` following the value returned."), "{error}");
+}
+
+#[test]
+fn test_libraries() {
+    use crate::package::{self, Manifest, ProjectKind};
+    let folder = std::env::temp_dir().join(format!("beach_test_libraries_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    let geometry = folder.join("vendor").join("geometry");
+    package::scaffold(&geometry, "geometry", ProjectKind::Library).unwrap();
+    std::fs::write(geometry.join("lib.beach"), "file shapes:circle;\npublic var pi = 3;").unwrap();
+    std::fs::create_dir_all(geometry.join("shapes")).unwrap();
+    std::fs::write(geometry.join("shapes").join("circle.beach"), "var radius = 1;").unwrap();
+    std::fs::write(folder.join("core.beach"), "").unwrap();
+    std::fs::write(folder.join(package::MANIFEST_NAME), "\
+[project]
+name = \"project\"
+version = \"0.1.0\"
+
+[dependencies]
+geometry = { path = \"vendor/geometry\" }
+").unwrap();
+    let manifest = Manifest::find(&folder).unwrap().unwrap();
+    let libraries = package::resolve(&folder, &manifest, &folder.join("registry")).unwrap();
+    let load = || ModuleGraph::load(&folder.join("main.beach"), &folder.join("core.beach"), &libraries);
+
+    std::fs::write(folder.join("main.beach"), "library geometry \"0.1\";\nmain {}").unwrap();
+    let graph = load().unwrap();
+    let namespaces: Vec<String> = graph.modules.iter().map(|module| module.namespace.join("~")).collect();
+    // Library files are named from the library's folder.
+    assert_eq!(namespaces, ["", "core", "geometry", "geometry~shapes~circle"]);
+    assert_eq!(graph.modules[3].library.as_deref(), Some("geometry"));
+    graph.link().unwrap();
+
+    std::fs::write(folder.join("main.beach"), "library geometry \"2\";").unwrap();
+    assert!(load().map(|_| ()).unwrap_err().to_string().ends_with(
+        "1:1: `geometry` 2 was asked for, but version 0.1.0 is the one in use."
+    ));
+    std::fs::write(folder.join("main.beach"), "library shapes;").unwrap();
+    assert!(load().map(|_| ()).unwrap_err().to_string().ends_with(
+        "`shapes` isn't a library this project depends on. Add it to [dependencies] in `beach.toml`."
+    ));
+
+    // Spec D.1.6: libraries can't have a `main` block.
+    std::fs::write(folder.join("main.beach"), "library geometry;").unwrap();
+    std::fs::write(geometry.join("shapes").join("circle.beach"), "main {}").unwrap();
+    assert_eq!(
+        load().map(|_| ()).unwrap_err().to_string(),
+        format!(
+            "{}:1:1: Libraries can't have a `main` block.",
+            geometry.join("shapes").join("circle.beach").canonicalize().unwrap().display()
+        )
+    );
+    std::fs::remove_dir_all(&folder).unwrap();
+}
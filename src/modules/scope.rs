@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+
+use crate::parser::beach::ast::{
+    Definition, Evaluatable, Function, GenericParameter, Implementation, Program, Task, TypeIdentity, Value
+};
+
+use super::ModuleGraph;
+
+/// The names defined by a single module.
+struct Names {
+    /// Maps each name, as written inside the module, to its full path. Names
+    /// inside `namespace` blocks include the block's name. (`shapes~area`)
+    own: HashMap<String, String>,
+    /// The names other modules can use.
+    public: Vec<String>,
+}
+
+/// Returns every module's program with its definitions renamed to their full
+/// paths, (the core library's keep their plain names) and every name they refer to replaced by the full path of what it
+/// refers to. Names that can't be found are left alone for type checking to
+/// report.
+///
+/// From inside a module, a name can refer to:
+/// - a definition in the same module, starting from the innermost `namespace`
+///   block and working outwards.
+/// - a `public` definition or exported alias (`=>!`) in a module it imports,
+///   or in the prelude, unless the module has disabled that name.
+/// - a `public` definition by its full path, as in `shapes~circle~area`.
+///
+/// Defining a name that an import already provides, or using a name provided
+/// by more than one import, is an error.
+pub fn qualify(graph: &ModuleGraph) -> Result<Vec<Program>, anyhow::Error> {
+    let mut names = vec![];
+    for module in &graph.modules {
+        // The main file's definitions are named after the file, so they
+        // can't clash with the prelude's.
+        let prefix = match module.namespace.is_empty() {
+            true => module.path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+            false => module.namespace.join("~")
+        };
+        let mut own = HashMap::new();
+        for definition in &module.program.definitions {
+            let Some(name) = definition.name() else { continue };
+            let full = if module.is_core {
+                name.clone()
+            }
+            else {
+                format!("{prefix}~{name}")
+            };
+            if own.insert(name.clone(), full).is_some() {
+                return Err(anyhow::Error::msg(format!(
+                    "{}: `{name}` is defined more than once.", module.path.display()
+                )));
+            }
+        }
+        // Exported aliases can be used by importers without being `public`.
+        let mut public = module.program.public.clone();
+        for definition in &module.program.definitions {
+            match definition {
+                Definition::Alias { from, export: true, .. } if !public.contains(from) => public.push(from.clone()),
+                _ => {}
+            }
+        }
+        names.push(Names { own, public });
+    }
+    // Every full path is only defined once.
+    let mut defined_by: HashMap<&String, usize> = HashMap::new();
+    for (index, module_names) in names.iter().enumerate() {
+        for full in module_names.own.values() {
+            if let Some(other) = defined_by.insert(full, index) {
+                return Err(anyhow::Error::msg(format!(
+                    "`{full}` is defined by both {} and {}.",
+                    graph.modules[other].path.display(), graph.modules[index].path.display()
+                )));
+            }
+        }
+    }
+
+    let mut programs = vec![];
+    for (index, module) in graph.modules.iter().enumerate() {
+        let resolver = Resolver { graph, names: &names, module: index, block: vec![], locals: vec![] };
+        let visible = resolver.visible();
+        for name in &module.program.disabled {
+            if !visible.iter().any(|other| names[*other].public.contains(name)) {
+                return Err(anyhow::Error::msg(format!(
+                    "{}: `{name}` can't be disabled, as nothing imported provides it.",
+                    module.path.display()
+                )));
+            }
+        }
+        for name in names[index].own.keys() {
+            if let Some(provider) = resolver.imported(name).first() {
+                return Err(anyhow::Error::msg(format!(
+                    "{}: `{name}` is already defined by {}.",
+                    module.path.display(), graph.modules[*provider].path.display()
+                )));
+            }
+        }
+        let mut program = module.program.clone();
+        for definition in &mut program.definitions {
+            resolver.definition(definition)?;
+        }
+        resolver.tasks(&mut program.main_tasks)?;
+        resolver.tasks(&mut program.global_tasks)?;
+        for public in &mut program.public {
+            *public = names[index].own[public.as_str()].clone();
+        }
+        programs.push(program);
+    }
+    Ok(programs)
+}
+
+/// Looks up names used inside one module.
+struct Resolver<'a> {
+    graph: &'a ModuleGraph,
+    names: &'a [Names],
+    module: usize,
+    /// The `namespace` blocks around the definition being resolved, outermost
+    /// first.
+    block: Vec<String>,
+    /// Names local to the function being resolved, such as its arguments.
+    locals: Vec<String>,
+}
+
+impl <'a>Resolver<'a> {
+    /// The modules whose public definitions can be used without a path.
+    fn visible(&self) -> Vec<usize> {
+        let module = &self.graph.modules[self.module];
+        let mut visible = module.imports.clone();
+        if !module.is_core {
+            for (index, other) in self.graph.modules.iter().enumerate() {
+                if other.is_core && !visible.contains(&index) {
+                    visible.push(index);
+                }
+            }
+        }
+        visible.retain(|index| *index != self.module);
+        visible
+    }
+    /// The visible modules that publicly define `name`, unless it's been
+    /// disabled.
+    fn imported(&self, name: &str) -> Vec<usize> {
+        if self.graph.modules[self.module].program.disabled.iter().any(|disabled| disabled == name) {
+            return vec![];
+        }
+        self.visible().into_iter()
+            .filter(|index| self.names[*index].public.iter().any(|public| public == name))
+            .collect()
+    }
+    /// Finds the full path of whatever `name` refers to.
+    fn lookup(&self, name: &str) -> Result<Option<String>, anyhow::Error> {
+        let names = &self.names[self.module];
+        // Definitions in this module, from the innermost namespace block out
+        for depth in (0..=self.block.len()).rev() {
+            let mut candidate = self.block[..depth].to_vec();
+            candidate.push(name.to_string());
+            if let Some(full) = names.own.get(&candidate.join("~")) {
+                return Ok(Some(full.clone()));
+            }
+        }
+        // Public definitions of imports
+        let providers = self.imported(name);
+        match providers.as_slice() {
+            [] => {}
+            [provider] => return Ok(Some(self.names[*provider].own[name].clone())),
+            _ => {
+                let options: Vec<String> = providers.iter()
+                    .map(|provider| format!(
+                        "`{}` ({})",
+                        self.names[*provider].own[name],
+                        self.graph.modules[*provider].path.display()
+                    ))
+                    .collect();
+                return Err(anyhow::Error::msg(format!(
+                    "{}: `{name}` could refer to {}. Use its full path to pick one.",
+                    self.graph.modules[self.module].path.display(),
+                    options.join(" or ")
+                )));
+            }
+        }
+        // Full paths to public definitions of imports
+        for provider in self.visible() {
+            let namespace = self.graph.modules[provider].namespace.join("~");
+            let Some(rest) = name.strip_prefix(&format!("{namespace}~")) else { continue };
+            if self.names[provider].public.iter().any(|public| public == rest) {
+                return Ok(Some(self.names[provider].own[rest].clone()));
+            }
+        }
+        Ok(None)
+    }
+    /// Replaces `name` with the full path of what it refers to, if found.
+    fn name(&self, name: &mut String) -> Result<(), anyhow::Error> {
+        if let Some(full) = self.lookup(name)? {
+            *name = full;
+        }
+        Ok(())
+    }
+    fn definition(&self, definition: &mut Definition) -> Result<(), anyhow::Error> {
+        // Names inside a definition are looked up from the namespace block
+        // it's in.
+        let mut resolver = Resolver { block: vec![], locals: vec![], ..*self };
+        if let Some(name) = definition.name() {
+            resolver.block = name.split('~').map(String::from).collect();
+            resolver.block.pop();
+        }
+        match definition {
+            Definition::Alias { to, .. } => resolver.name(to)?,
+            Definition::GlobalConstant { value, .. } => resolver.value(value)?,
+            Definition::Function(function) => resolver.function(function)?,
+            Definition::Type { generics, data, methods, implementations, .. } => {
+                for (_, field_type, _) in data {
+                    resolver.type_identity(field_type)?;
+                }
+                resolver.members(generics, methods, implementations)?;
+            }
+            Definition::Enum { generics, cases, methods, implementations, .. } => {
+                for case_type in cases.iter_mut().filter_map(|(_, case_type, _)| case_type.as_mut()) {
+                    resolver.type_identity(case_type)?;
+                }
+                resolver.members(generics, methods, implementations)?;
+            }
+            Definition::Trait { requires, methods, .. } => {
+                for required in requires {
+                    resolver.name(required)?;
+                }
+                for method in methods {
+                    resolver.function(method)?;
+                }
+            }
+            Definition::System { .. } | Definition::File { .. } |
+            Definition::Core { .. } | Definition::Library { .. } => {}
+        }
+        if let Some(name) = definition.name_mut() {
+            *name = self.names[self.module].own[name.as_str()].clone();
+        }
+        Ok(())
+    }
+    /// Resolves the parts shared by types and enums.
+    fn members(
+        &self,
+        generics: &mut [GenericParameter],
+        methods: &mut [Function],
+        implementations: &mut [Implementation]
+    ) -> Result<(), anyhow::Error> {
+        for generic in generics {
+            for constraint in &mut generic.constraints {
+                self.name(constraint)?;
+            }
+        }
+        for method in methods {
+            self.function(method)?;
+        }
+        for implementation in implementations {
+            self.name(&mut implementation.trait_name)?;
+            for requirement in &mut implementation.requirements {
+                for constraint in &mut requirement.constraints {
+                    self.name(constraint)?;
+                }
+            }
+            for method in &mut implementation.methods {
+                self.function(method)?;
+            }
+        }
+        Ok(())
+    }
+    fn function(&self, function: &mut Function) -> Result<(), anyhow::Error> {
+        let mut locals: Vec<String> = function.arguments.iter().map(|argument| argument.name.clone()).collect();
+        locals.extend(function.generics.iter().map(|generic| generic.name.clone()));
+        let resolver = Resolver { block: self.block.clone(), locals, ..*self };
+        for generic in &mut function.generics {
+            for constraint in &mut generic.constraints {
+                resolver.name(constraint)?;
+            }
+        }
+        for argument in &mut function.arguments {
+            resolver.type_identity(&mut argument.arg_type)?;
+        }
+        resolver.type_identity(&mut function.returns)?;
+        resolver.tasks(&mut function.code)
+    }
+    fn type_identity(&self, identity: &mut TypeIdentity) -> Result<(), anyhow::Error> {
+        match identity {
+            TypeIdentity::Trait(name) | TypeIdentity::Enum(name) | TypeIdentity::Structured(name) => self.name(name),
+            TypeIdentity::Instance { name, arguments } => {
+                self.name(name)?;
+                for argument in arguments {
+                    self.type_identity(argument)?;
+                }
+                Ok(())
+            }
+            TypeIdentity::Closure { arguments, returns } => {
+                for argument in arguments {
+                    self.type_identity(argument)?;
+                }
+                self.type_identity(returns)
+            }
+            TypeIdentity::Primitive(_) | TypeIdentity::Generic(_) => Ok(())
+        }
+    }
+    fn tasks(&self, tasks: &mut [Task]) -> Result<(), anyhow::Error> {
+        // Labels set in the block are local to it from then on.
+        let mut scope = Resolver { block: self.block.clone(), locals: self.locals.clone(), ..*self };
+        for task in tasks {
+            match task {
+                Task::Set { label, type_, value, .. } => {
+                    if let Some(type_) = type_ {
+                        scope.name(type_)?;
+                    }
+                    scope.value(value)?;
+                    scope.locals.push(label.clone());
+                }
+                Task::Call { function_identifier, generics, arguments, .. } => {
+                    scope.label(function_identifier)?;
+                    for generic in generics {
+                        scope.type_identity(generic)?;
+                    }
+                    for argument in arguments {
+                        scope.value(argument)?;
+                    }
+                }
+                Task::Evaluate { label, task } => {
+                    match task {
+                        Evaluatable::Call { label, generics, arguments, .. } => {
+                            scope.label(label)?;
+                            for generic in generics {
+                                scope.type_identity(generic)?;
+                            }
+                            for argument in arguments {
+                                scope.value(argument)?;
+                            }
+                        }
+                        Evaluatable::Math { a, b, .. } => {
+                            scope.value(a)?;
+                            scope.value(b)?;
+                        }
+                        Evaluatable::Value { value } => scope.value(value)?,
+                    }
+                    scope.locals.push(label.clone());
+                }
+                Task::Syscall { number, arguments, result, .. } => {
+                    scope.value(number)?;
+                    for argument in arguments {
+                        scope.value(argument)?;
+                    }
+                    scope.locals.extend(result.clone());
+                }
+                Task::Return { value, .. } => scope.value(value)?,
+                Task::If { a, b, tasks, .. } => {
+                    scope.value(a)?;
+                    scope.value(b)?;
+                    scope.tasks(tasks)?;
+                }
+                Task::FreeEvaluated { .. } | Task::ExitBlock | Task::Unreachable => {}
+            }
+        }
+        Ok(())
+    }
+    fn value(&self, value: &mut Value) -> Result<(), anyhow::Error> {
+        let Value::Label(label) = value else { return Ok(()) };
+        self.label(label)
+    }
+    /// Replaces the name at the start of `label` with the full path of what
+    /// it refers to, unless it's local.
+    fn label(&self, label: &mut String) -> Result<(), anyhow::Error> {
+        // Only the start of `output.pointer` is a name.
+        let (start, fields) = match label.split_once('.') {
+            Some((start, fields)) => (start.to_string(), format!(".{fields}")),
+            None => (label.clone(), String::new())
+        };
+        if self.locals.contains(&start) || start.starts_with("compiler_") {
+            return Ok(());
+        }
+        if let Some(full) = self.lookup(&start)? {
+            *label = full + &fields;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_qualify() {
+    let folder = std::env::temp_dir().join(format!("beach_test_qualify_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("core.beach"), "public type string {}").unwrap();
+    std::fs::write(folder.join("main.beach"), "\
+file circle;
+file square;
+main {
+    area(1);
+    square~measure~perimeter(2);
+}").unwrap();
+    std::fs::write(folder.join("circle.beach"), "\
+public var area = |radius: u8| -> nothing {
+    helper(radius);
+    return;
+};
+var helper = |radius: u8| -> nothing {
+    return;
+};").unwrap();
+    std::fs::write(folder.join("square.beach"), "\
+namespace measure {
+    public var perimeter = |side: u8| -> nothing {
+        sides(side);
+        return;
+    };
+    var sides = |side: u8| -> nothing {
+        return;
+    };
+}
+var helper = |name: string| -> nothing {
+    return;
+};").unwrap();
+    let load = || ModuleGraph::load(&folder.join("main.beach"), &folder.join("core.beach"), &[]).unwrap();
+
+    let programs = qualify(&load()).unwrap();
+    let calls: Vec<&String> = programs.iter()
+        .flat_map(|program| program.main_tasks.iter().chain(program.definitions.iter().flat_map(|definition| match definition {
+            Definition::Function(function) => function.code.iter(),
+            _ => [].iter()
+        })))
+        .filter_map(|task| match task {
+            Task::Call { function_identifier, .. } => Some(function_identifier),
+            _ => None
+        })
+        .collect();
+    // Both files define a `helper`, but each only sees their own.
+    assert_eq!(calls, ["circle~area", "square~measure~perimeter", "circle~helper", "square~measure~sides"]);
+    let Definition::Function(helper) = &programs[3].definitions[2] else { panic!() };
+    assert_eq!(helper.name, "square~helper");
+    assert_eq!(helper.arguments[0].arg_type, TypeIdentity::Structured(String::from("string")));
+
+    // Private definitions can't be used from other files.
+    std::fs::write(folder.join("main.beach"), "file circle;\nmain { helper(1); }").unwrap();
+    let programs = qualify(&load()).unwrap();
+    let Task::Call { function_identifier, .. } = &programs[0].main_tasks[0] else { panic!() };
+    assert_eq!(function_identifier, "helper");
+
+    // Two imports providing the same name need a full path to tell apart.
+    std::fs::write(folder.join("square.beach"), "public var area = |side: u8| -> nothing { return; };").unwrap();
+    std::fs::write(folder.join("main.beach"), "file circle;\nfile square;\nmain { area(1); }").unwrap();
+    let error = qualify(&load()).map(|_| ()).unwrap_err().to_string();
+    let file = |name: &str| folder.join(name).canonicalize().unwrap().display().to_string();
+    assert!(error.ends_with(&format!(
+        "`area` could refer to `circle~area` ({}) or `square~area` ({}). Use its full path to pick one.",
+        file("circle.beach"), file("square.beach")
+    )));
+
+    // and defining something an import already provides isn't allowed either.
+    std::fs::write(folder.join("main.beach"), "file circle;\nvar area = |radius: u8| -> nothing { return; };").unwrap();
+    let error = qualify(&load()).map(|_| ()).unwrap_err().to_string();
+    assert!(error.ends_with(&format!("`area` is already defined by {}.", file("circle.beach"))));
+    std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn test_exported_aliases() {
+    let folder = std::env::temp_dir().join(format!("beach_test_exported_aliases_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("core.beach"), "public type maybe {}\nbyte =>! u8;").unwrap();
+    std::fs::write(folder.join("shapes.beach"), "\
+public var area = |radius: byte| -> nothing {
+    return;
+};
+circle_area =>! area;
+square_area => area;").unwrap();
+    std::fs::write(folder.join("main.beach"), "\
+file shapes;
+disable maybe;
+type maybe {}
+main {
+    circle_area(1);
+    square_area(2);
+}").unwrap();
+    let load = || ModuleGraph::load(&folder.join("main.beach"), &folder.join("core.beach"), &[]).unwrap();
+
+    let programs = qualify(&load()).unwrap();
+    let calls: Vec<&String> = programs[0].main_tasks.iter()
+        .filter_map(|task| match task {
+            Task::Call { function_identifier, .. } => Some(function_identifier),
+            _ => None
+        })
+        .collect();
+    // Only the exported alias can be used by `main.beach`.
+    assert_eq!(calls, ["shapes~circle_area", "square_area"]);
+    let Definition::Function(area) = &programs[2].definitions[0] else { panic!() };
+    assert_eq!(area.arguments[0].arg_type, TypeIdentity::Structured(String::from("byte")));
+    // Calls through aliases from other files go to the function they alias.
+    std::fs::write(folder.join("main.beach"), "file shapes;\nmain { circle_area(1); }").unwrap();
+    assert!(crate::parser::beach::Executable::from_ast(load().link().unwrap()).is_ok());
+
+    std::fs::write(folder.join("main.beach"), "disable area;").unwrap();
+    let error = qualify(&load()).map(|_| ()).unwrap_err().to_string();
+    assert!(error.ends_with("`area` can't be disabled, as nothing imported provides it."));
+    std::fs::remove_dir_all(&folder).unwrap();
+}
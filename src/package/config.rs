@@ -0,0 +1,226 @@
+//! Reads the small subset of TOML used by `beach.toml` and `beach.lock`:
+//! `[section]` headers and `key = value` lines, where values are strings,
+//! integers, booleans, arrays or inline tables. Comments start with `#`.
+
+/// A value on the right of a `key = value` line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    /// `{ key = value, ... }`
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// What kind of value this is, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+}
+
+/// A single `key = value` line, along with its section and line number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub section: String,
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+/// Reads every entry in `source`. Errors start with the line they're on.
+pub fn parse(source: &str) -> Result<Vec<Entry>, anyhow::Error> {
+    let mut entries = vec![];
+    let mut section = String::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut reader = Reader { chars: line.chars().collect(), location: 0 };
+        reader.skip_whitespace();
+        match reader.peek() {
+            None | Some('#') => continue,
+            Some('[') => {
+                reader.next();
+                section = reader.key();
+                reader.skip_whitespace();
+                if reader.next() != Some(']') || section.is_empty() {
+                    return Err(anyhow::Error::msg(format!(
+                        "{line_number}: Expected a section name such as `[project]`."
+                    )));
+                }
+            }
+            Some(_) => {
+                let key = reader.key();
+                reader.skip_whitespace();
+                if key.is_empty() || reader.next() != Some('=') {
+                    return Err(anyhow::Error::msg(format!(
+                        "{line_number}: Expected a line such as `key = \"value\"`."
+                    )));
+                }
+                let value = reader.value().map_err(|e| anyhow::Error::msg(format!("{line_number}: {e}")))?;
+                entries.push(Entry { section: section.clone(), key, value, line: line_number });
+            }
+        }
+        reader.skip_whitespace();
+        if !matches!(reader.peek(), None | Some('#')) {
+            return Err(anyhow::Error::msg(format!(
+                "{line_number}: Unexpected `{}` at the end of the line.",
+                reader.chars[reader.location..].iter().collect::<String>()
+            )));
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes `text` as a string value, escaping it where needed.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\t' => quoted += "\\t",
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+struct Reader {
+    chars: Vec<char>,
+    location: usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.location).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let character = self.peek();
+        self.location += 1;
+        character
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.location += 1;
+        }
+    }
+    /// Reads a bare key, such as `name` or `opt-level`.
+    fn key(&mut self) -> String {
+        let mut key = String::new();
+        while let Some(character) = self.peek() {
+            if !(character.is_alphanumeric() || character == '_' || character == '-') {
+                break;
+            }
+            key.push(character);
+            self.location += 1;
+        }
+        key
+    }
+    fn value(&mut self) -> Result<Value, anyhow::Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => {
+                self.next();
+                let mut text = String::new();
+                loop {
+                    match self.next() {
+                        Some('"') => return Ok(Value::String(text)),
+                        Some('\\') => match self.next() {
+                            Some('"') => text.push('"'),
+                            Some('\\') => text.push('\\'),
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            _ => return Err(anyhow::Error::msg("Unknown escape in a string.")),
+                        },
+                        Some(character) => text.push(character),
+                        None => return Err(anyhow::Error::msg("Expected a `\"` to end the string.")),
+                    }
+                }
+            }
+            Some('[') => {
+                self.next();
+                let mut values = vec![];
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.next();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(anyhow::Error::msg("Expected `,` or `]` in an array.")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.next();
+                let mut entries = vec![];
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some('}') && entries.is_empty() {
+                        self.next();
+                        return Ok(Value::Table(entries));
+                    }
+                    let key = self.key();
+                    self.skip_whitespace();
+                    if key.is_empty() || self.next() != Some('=') {
+                        return Err(anyhow::Error::msg("Expected `key = value` in a table."));
+                    }
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Value::Table(entries)),
+                        _ => return Err(anyhow::Error::msg("Expected `,` or `}` in a table.")),
+                    }
+                }
+            }
+            _ => {
+                let word = self.key();
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => word.parse().map(Value::Integer).map_err(|_| anyhow::Error::msg(
+                        "Expected a value, such as \"text\", 3, true, [...] or { ... }."
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_config() {
+    let entries = parse("\
+# a comment
+top = 1
+[project]
+name = \"say \\\"hi\\\"\" # trailing comment
+list = [\"a\", \"b\",]
+[dependencies]
+shapes = { path = \"../shapes\", optional = false }
+").unwrap();
+    assert_eq!(entries[0], Entry { section: String::new(), key: String::from("top"), value: Value::Integer(1), line: 2 });
+    assert_eq!(entries[1].value, Value::String(String::from("say \"hi\"")));
+    assert_eq!(entries[2].value, Value::Array(vec![Value::String(String::from("a")), Value::String(String::from("b"))]));
+    assert_eq!(entries[3].section, "dependencies");
+    assert_eq!(entries[3].value, Value::Table(vec![
+        (String::from("path"), Value::String(String::from("../shapes"))),
+        (String::from("optional"), Value::Boolean(false)),
+    ]));
+    assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    assert_eq!(parse("name = \"open").unwrap_err().to_string(), "1: Expected a `\"` to end the string.");
+    assert_eq!(parse("[project]\nname \"x\"").unwrap_err().to_string(), "2: Expected a line such as `key = \"value\"`.");
+}
@@ -0,0 +1,292 @@
+use std::path::{Path, PathBuf};
+
+use super::config::{self, Value};
+use super::{Requirement, Version};
+
+/// The name of the manifest file in a project folder.
+pub const MANIFEST_NAME: &str = "beach.toml";
+
+/// A project's `beach.toml`, which names the project, lists the libraries it
+/// uses and configures how it's built. Settings live here rather than in code
+/// with `system`, as recommended by spec B.3.
+///
+/// ```toml
+/// [project]
+/// name = "shapes"
+/// version = "0.1.0"
+/// kind = "library"
+///
+/// [build]
+/// output = "out"
+/// targets = ["linux_aarch64"]
+/// optimisation = 2
+///
+/// [dependencies]
+/// geometry = "1.2"
+/// vendored = { path = "vendor/vendored" }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+    pub kind: ProjectKind,
+    /// The project's root file, relative to the project folder. `main.beach`
+    /// for executables and `lib.beach` for libraries, unless it's set.
+    pub entry: PathBuf,
+    /// A folder to find libraries in, instead of the usual registry,
+    /// relative to the project folder.
+    pub registry: Option<PathBuf>,
+    pub build: BuildSettings,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// The `[build]` section of a manifest. Anything not set is left to the
+/// command line, or beach's defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BuildSettings {
+    /// The folder builds are written to, relative to the project folder.
+    pub output: Option<PathBuf>,
+    /// The name of the built executables.
+    pub name: Option<String>,
+    /// The technical ids of the platforms to build for. Empty builds for
+    /// every platform that supports the project.
+    pub targets: Vec<String>,
+    pub optimisation: Option<u8>,
+    /// Whether to choose smaller instructions over faster ones.
+    pub size: Option<bool>,
+    pub debug: Option<bool>,
+    pub assembly: Option<bool>,
+    pub ir: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectKind {
+    Executable,
+    /// Library projects can be imported by other projects, but can't have a
+    /// `main` block. (spec A.7, D.1.6)
+    Library,
+}
+
+/// A library a project depends on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub requirement: Requirement,
+    /// Set for vendored libraries, which are found at this path (relative to
+    /// the depending project's folder) instead of in the registry.
+    pub path: Option<PathBuf>,
+}
+
+impl Manifest {
+    /// A manifest for a new project with no dependencies.
+    pub fn new(name: &str, kind: ProjectKind) -> Manifest {
+        Manifest {
+            name: String::from(name),
+            version: Version { major: 0, minor: 1, patch: 0 },
+            kind,
+            entry: Self::default_entry(kind),
+            registry: None,
+            build: BuildSettings::default(),
+            dependencies: vec![],
+        }
+    }
+    /// Reads the manifest in `folder`, if there is one.
+    pub fn find(folder: &Path) -> Result<Option<Manifest>, anyhow::Error> {
+        let path = folder.join(MANIFEST_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let source = std::fs::read_to_string(&path)
+            .map_err(|_| anyhow::Error::msg(format!("`{}` is not valid UTF-8 or otherwise could not be read.", path.display())))?;
+        Self::parse(&source)
+            .map(Some)
+            .map_err(|e| anyhow::Error::msg(format!("{}:{e}", path.display())))
+    }
+    /// Reads a manifest from the text of a `beach.toml`. Errors start with the
+    /// line they're on.
+    pub fn parse(source: &str) -> Result<Manifest, anyhow::Error> {
+        let mut name = None;
+        let mut version = None;
+        let mut kind = ProjectKind::Executable;
+        let mut entry = None;
+        let mut registry = None;
+        let mut build = BuildSettings::default();
+        let mut dependencies = vec![];
+        for item in config::parse(source)? {
+            let line = item.line;
+            match (item.section.as_str(), item.key.as_str()) {
+                ("project", "name") => name = Some(Self::string(&item.value, "name", line)?),
+                ("project", "version") => {
+                    let text = Self::string(&item.value, "version", line)?;
+                    version = Some(Version::parse(&text).map_err(|e| anyhow::Error::msg(format!("{line}: {e}")))?);
+                }
+                ("project", "kind") => {
+                    kind = match Self::string(&item.value, "kind", line)?.as_str() {
+                        "executable" => ProjectKind::Executable,
+                        "library" => ProjectKind::Library,
+                        other => {
+                            return Err(anyhow::Error::msg(format!(
+                                "{line}: `{other}` isn't a kind of project. Use \"executable\" or \"library\"."
+                            )));
+                        }
+                    };
+                }
+                ("project", "entry") => entry = Some(PathBuf::from(Self::string(&item.value, "entry", line)?)),
+                ("project", "registry") => registry = Some(PathBuf::from(Self::string(&item.value, "registry", line)?)),
+                ("build", "output") => build.output = Some(PathBuf::from(Self::string(&item.value, "output", line)?)),
+                ("build", "name") => build.name = Some(Self::string(&item.value, "name", line)?),
+                ("build", "targets") => {
+                    let Value::Array(targets) = &item.value else {
+                        return Err(anyhow::Error::msg(format!(
+                            "{line}: `targets` should be an array, not {}.",
+                            item.value.kind()
+                        )));
+                    };
+                    for target in targets {
+                        build.targets.push(Self::string(target, "targets", line)?);
+                    }
+                }
+                ("build", "optimisation") => {
+                    build.optimisation = match item.value {
+                        Value::Integer(level) => Some(super::optimisation_level(&level.to_string()).map_err(|e| anyhow::Error::msg(format!("{line}: {e}")))?),
+                        other => {
+                            return Err(anyhow::Error::msg(format!(
+                                "{line}: `optimisation` should be an integer, not {}.",
+                                other.kind()
+                            )));
+                        }
+                    };
+                }
+                ("build", "size") => build.size = Some(Self::boolean(&item.value, "size", line)?),
+                ("build", "debug") => build.debug = Some(Self::boolean(&item.value, "debug", line)?),
+                ("build", "assembly") => build.assembly = Some(Self::boolean(&item.value, "assembly", line)?),
+                ("build", "ir") => build.ir = Some(Self::boolean(&item.value, "ir", line)?),
+                ("dependencies", library) => {
+                    dependencies.push(Self::dependency(library, &item.value, line)?);
+                }
+                (section, key) => {
+                    return Err(anyhow::Error::msg(format!("{line}: Unknown setting `{key}` in [{section}].")));
+                }
+            }
+        }
+        let name = name.ok_or(anyhow::Error::msg("The project needs a `name` in [project]."))?;
+        let version = version.ok_or(anyhow::Error::msg("The project needs a `version` in [project]."))?;
+        Ok(Manifest {
+            name,
+            version,
+            kind,
+            entry: entry.unwrap_or(Self::default_entry(kind)),
+            registry,
+            build,
+            dependencies,
+        })
+    }
+    fn default_entry(kind: ProjectKind) -> PathBuf {
+        match kind {
+            ProjectKind::Executable => PathBuf::from("main.beach"),
+            ProjectKind::Library => PathBuf::from("lib.beach"),
+        }
+    }
+    fn string(value: &Value, key: &str, line: usize) -> Result<String, anyhow::Error> {
+        match value {
+            Value::String(text) => Ok(text.clone()),
+            other => Err(anyhow::Error::msg(format!("{line}: `{key}` should be a string, not {}.", other.kind()))),
+        }
+    }
+    fn boolean(value: &Value, key: &str, line: usize) -> Result<bool, anyhow::Error> {
+        match value {
+            Value::Boolean(boolean) => Ok(*boolean),
+            other => Err(anyhow::Error::msg(format!("{line}: `{key}` should be true or false, not {}.", other.kind()))),
+        }
+    }
+    /// Reads `name = "1.2"` or `name = { version = "1.2", path = "..." }`.
+    fn dependency(name: &str, value: &Value, line: usize) -> Result<Dependency, anyhow::Error> {
+        let requirement = |text: &str| Requirement::parse(text).map_err(|e| anyhow::Error::msg(format!("{line}: {e}")));
+        let mut dependency = Dependency { name: String::from(name), requirement: Requirement::Any, path: None };
+        match value {
+            Value::String(text) => dependency.requirement = requirement(text)?,
+            Value::Table(entries) => {
+                for (key, value) in entries {
+                    match key.as_str() {
+                        "version" => dependency.requirement = requirement(&Self::string(value, key, line)?)?,
+                        "path" => dependency.path = Some(PathBuf::from(Self::string(value, key, line)?)),
+                        other => {
+                            return Err(anyhow::Error::msg(format!("{line}: Unknown setting `{other}` for library `{name}`.")));
+                        }
+                    }
+                }
+                if entries.is_empty() {
+                    return Err(anyhow::Error::msg(format!("{line}: Library `{name}` needs a `version` or a `path`.")));
+                }
+            }
+            other => {
+                return Err(anyhow::Error::msg(format!(
+                    "{line}: Library `{name}` should be a version string or a table, not {}.",
+                    other.kind()
+                )));
+            }
+        }
+        Ok(dependency)
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[project]")?;
+        writeln!(f, "name = {}", config::quote(&self.name))?;
+        writeln!(f, "version = \"{}\"", self.version)?;
+        let kind = match self.kind {
+            ProjectKind::Executable => "executable",
+            ProjectKind::Library => "library",
+        };
+        writeln!(f, "kind = \"{kind}\"")?;
+        if self.entry != Self::default_entry(self.kind) {
+            writeln!(f, "entry = {}", config::quote(&self.entry.to_string_lossy()))?;
+        }
+        if let Some(registry) = &self.registry {
+            writeln!(f, "registry = {}", config::quote(&registry.to_string_lossy()))?;
+        }
+        if self.build != BuildSettings::default() {
+            writeln!(f)?;
+            writeln!(f, "[build]")?;
+            if let Some(output) = &self.build.output {
+                writeln!(f, "output = {}", config::quote(&output.to_string_lossy()))?;
+            }
+            if let Some(name) = &self.build.name {
+                writeln!(f, "name = {}", config::quote(name))?;
+            }
+            if !self.build.targets.is_empty() {
+                let targets: Vec<String> = self.build.targets.iter().map(|target| config::quote(target)).collect();
+                writeln!(f, "targets = [{}]", targets.join(", "))?;
+            }
+            if let Some(optimisation) = self.build.optimisation {
+                writeln!(f, "optimisation = {optimisation}")?;
+            }
+            if let Some(size) = self.build.size {
+                writeln!(f, "size = {size}")?;
+            }
+            if let Some(debug) = self.build.debug {
+                writeln!(f, "debug = {debug}")?;
+            }
+            if let Some(assembly) = self.build.assembly {
+                writeln!(f, "assembly = {assembly}")?;
+            }
+            if let Some(ir) = self.build.ir {
+                writeln!(f, "ir = {ir}")?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "[dependencies]")?;
+        for dependency in &self.dependencies {
+            match &dependency.path {
+                Some(path) => writeln!(
+                    f,
+                    "{} = {{ version = \"{}\", path = {} }}",
+                    dependency.name, dependency.requirement, config::quote(&path.to_string_lossy())
+                )?,
+                None => writeln!(f, "{} = \"{}\"", dependency.name, dependency.requirement)?,
+            }
+        }
+        Ok(())
+    }
+}
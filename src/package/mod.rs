@@ -0,0 +1,160 @@
+mod config;
+mod manifest;
+mod registry;
+
+use std::path::Path;
+
+pub use manifest::{Dependency, Manifest, ProjectKind, MANIFEST_NAME};
+pub use registry::{registry_folder, resolve, Library};
+
+/// A library's version, as `major.minor.patch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// Reads a full version, such as `1.2.0`.
+    pub fn parse(text: &str) -> Result<Version, anyhow::Error> {
+        let parts = Self::parts(text)?;
+        if parts.len() != 3 {
+            return Err(anyhow::Error::msg(format!(
+                "`{text}` isn't a version. Versions have three parts, such as `1.2.0`."
+            )));
+        }
+        Ok(Version { major: parts[0], minor: parts[1], patch: parts[2] })
+    }
+    /// Reads up to three numbers seperated by `.`.
+    fn parts(text: &str) -> Result<Vec<u64>, anyhow::Error> {
+        let parts: Result<Vec<u64>, _> = text.split('.').map(str::parse).collect();
+        match parts {
+            Ok(parts) if parts.len() <= 3 => Ok(parts),
+            _ => Err(anyhow::Error::msg(format!("`{text}` isn't a version, such as `1.2.0`.")))
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The versions of a library that a project accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    /// Any version at all. (`*`)
+    Any,
+    /// Only this exact version. (`=1.2.0`)
+    Exact(Version),
+    /// This version, or any later one that should be compatible with it. For
+    /// `1.2` this is anything from `1.2.0` up to (but not including) `2.0.0`.
+    /// Before `1.0.0`, the minor version is treated as the major one.
+    Compatible(Version),
+}
+
+impl Requirement {
+    pub fn parse(text: &str) -> Result<Requirement, anyhow::Error> {
+        let text = text.trim();
+        if text == "*" {
+            return Ok(Requirement::Any);
+        }
+        if let Some(exact) = text.strip_prefix('=') {
+            return Ok(Requirement::Exact(Version::parse(exact.trim())?));
+        }
+        // Missing parts are zero, so `1.2` is `1.2.0`.
+        let mut parts = Version::parts(text)?;
+        parts.resize(3, 0);
+        Ok(Requirement::Compatible(Version { major: parts[0], minor: parts[1], patch: parts[2] }))
+    }
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            Requirement::Any => true,
+            Requirement::Exact(exact) => exact == version,
+            Requirement::Compatible(minimum) => {
+                if version < minimum {
+                    return false;
+                }
+                if minimum.major == 0 {
+                    return version.major == 0 && version.minor == minimum.minor;
+                }
+                version.major == minimum.major
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::Any => write!(f, "*"),
+            Requirement::Exact(version) => write!(f, "={version}"),
+            Requirement::Compatible(version) => write!(f, "{version}"),
+        }
+    }
+}
+
+/// The highest optimisation level.
+pub const MAX_OPTIMISATION: u8 = 3;
+
+/// Reads an optimisation level, from `0` (none) to [MAX_OPTIMISATION].
+pub fn optimisation_level(text: &str) -> Result<u8, anyhow::Error> {
+    match text.parse() {
+        Ok(level) if level <= MAX_OPTIMISATION => Ok(level),
+        _ => Err(anyhow::Error::msg(format!(
+            "`{text}` isn't an optimisation level. Use a number from 0 to {MAX_OPTIMISATION}."
+        )))
+    }
+}
+
+/// Creates a new project called `name` in `folder`, which must not exist yet.
+/// Executables start with a `main.beach`, and libraries with a `lib.beach`.
+pub fn scaffold(folder: &Path, name: &str, kind: ProjectKind) -> Result<(), anyhow::Error> {
+    if folder.exists() {
+        return Err(anyhow::Error::msg(format!("`{}` already exists.", folder.display())));
+    }
+    std::fs::create_dir_all(folder)?;
+    let manifest = Manifest::new(name, kind);
+    std::fs::write(folder.join(MANIFEST_NAME), manifest.to_string())?;
+    let code = match kind {
+        ProjectKind::Executable => String::from("\
+include io:stdout;
+print => stdout;
+
+main {
+    print(\"Hello world!\");
+    return;
+}
+"),
+        // Spec D.1.6: libraries can't have a `main` block.
+        ProjectKind::Library => format!("\
+// Definitions marked `public` can be used by projects that import this
+// library with `library {name};`.
+public var greeting = \"Hello from {name}!\";
+"),
+    };
+    std::fs::write(folder.join(&manifest.entry), code)?;
+    Ok(())
+}
+
+#[test]
+fn test_requirements() {
+    let version = |text| Version::parse(text).unwrap();
+    let requirement = |text| Requirement::parse(text).unwrap();
+    assert!(version("1.2.0") < version("1.10.0"));
+    assert!(Version::parse("1.2").is_err());
+    assert!(Requirement::parse("1.x").is_err());
+
+    assert!(requirement("1.2").matches(&version("1.2.0")));
+    assert!(requirement("1.2").matches(&version("1.9.3")));
+    assert!(!requirement("1.2").matches(&version("1.1.9")));
+    assert!(!requirement("1.2").matches(&version("2.0.0")));
+    assert!(requirement("0.3").matches(&version("0.3.7")));
+    assert!(!requirement("0.3").matches(&version("0.4.0")));
+    assert!(requirement("=1.2.0").matches(&version("1.2.0")));
+    assert!(!requirement("=1.2.0").matches(&version("1.2.1")));
+    assert!(requirement("*").matches(&version("0.0.1")));
+    assert_eq!(requirement("1").to_string(), "1.0.0");
+}
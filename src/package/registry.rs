@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+
+use super::config::{self, Value};
+use super::{Dependency, Manifest, ProjectKind, Version};
+
+/// The name of the lockfile in a project folder.
+pub const LOCKFILE_NAME: &str = "beach.lock";
+
+/// A library that's been found, and whose version has been checked, ready to
+/// be imported with `library <name>;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+    pub name: String,
+    pub version: Version,
+    /// The library's project folder.
+    pub folder: PathBuf,
+    /// The library's root file, which its namespace is named after.
+    pub entry: PathBuf,
+}
+
+/// The versions picked for every library a project uses, directly or through
+/// other libraries. Stored in `beach.lock` so that builds keep using the same
+/// versions until the lockfile is changed.
+///
+/// ```toml
+/// [libraries]
+/// geometry = "1.2.0"
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Lockfile {
+    pub versions: Vec<(String, Version)>,
+}
+
+impl Lockfile {
+    /// Reads the lockfile in `folder`, or an empty one if there isn't one.
+    pub fn read(folder: &Path) -> Result<Lockfile, anyhow::Error> {
+        let path = folder.join(LOCKFILE_NAME);
+        if !path.exists() {
+            return Ok(Lockfile::default());
+        }
+        let source = std::fs::read_to_string(&path)
+            .map_err(|_| anyhow::Error::msg(format!("`{}` is not valid UTF-8 or otherwise could not be read.", path.display())))?;
+        let error = |e: String| anyhow::Error::msg(format!("{}:{e}", path.display()));
+        let mut lockfile = Lockfile::default();
+        for item in config::parse(&source).map_err(|e| error(e.to_string()))? {
+            match (item.section.as_str(), &item.value) {
+                ("libraries", Value::String(version)) => {
+                    let version = Version::parse(version).map_err(|e| error(format!("{}: {e}", item.line)))?;
+                    lockfile.versions.push((item.key, version));
+                }
+                _ => return Err(error(format!("{}: Expected a line such as `name = \"1.2.0\"` in [libraries].", item.line))),
+            }
+        }
+        Ok(lockfile)
+    }
+    pub fn write(&self, folder: &Path) -> Result<(), anyhow::Error> {
+        let mut text = String::from("# Written by beach. This pins the version of every library the project uses.\n[libraries]\n");
+        for (name, version) in &self.versions {
+            text += &format!("{name} = \"{version}\"\n");
+        }
+        std::fs::write(folder.join(LOCKFILE_NAME), text)?;
+        Ok(())
+    }
+    pub fn get(&self, name: &str) -> Option<Version> {
+        self.versions.iter().find(|(locked, _)| locked == name).map(|(_, version)| *version)
+    }
+}
+
+/// The folder libraries are found in when they aren't vendored: the one set
+/// in the manifest, or `$BEACH_REGISTRY`, or the `registry` folder of the
+/// beach install.
+pub fn registry_folder(project_folder: &Path, manifest: &Manifest) -> PathBuf {
+    if let Some(registry) = &manifest.registry {
+        return project_folder.join(registry);
+    }
+    if let Some(registry) = std::env::var_os("BEACH_REGISTRY") {
+        return PathBuf::from(registry);
+    }
+    let (mut path, _) = crate::utils::install_directory();
+    path.push("registry");
+    path
+}
+
+/// Finds every library the project in `project_folder` needs, including the
+/// ones needed by its libraries. Registry libraries are kept at
+/// `<registry>/<name>/<version>/`, and the newest version meeting the
+/// project's requirement is picked unless `beach.lock` already pins one. The
+/// lockfile is updated with any newly picked versions.
+pub fn resolve(project_folder: &Path, manifest: &Manifest, registry: &Path) -> Result<Vec<Library>, anyhow::Error> {
+    let mut lockfile = Lockfile::read(project_folder)?;
+    let mut libraries: Vec<Library> = vec![];
+    // Each dependency still to find, the folder of the project needing it and
+    // that project's name.
+    let mut pending: Vec<(Dependency, PathBuf, String)> = manifest.dependencies.iter()
+        .map(|dependency| (dependency.clone(), project_folder.to_path_buf(), manifest.name.clone()))
+        .collect();
+    let mut index = 0;
+    while index < pending.len() {
+        let (dependency, from, needed_by) = pending[index].clone();
+        index += 1;
+        let name = &dependency.name;
+        let locked = lockfile.get(name);
+        let folder = match &dependency.path {
+            Some(path) => from.join(path),
+            None => {
+                let version = pick_version(registry, &dependency, locked, &needed_by)?;
+                registry.join(name).join(version.to_string())
+            }
+        };
+        let library_manifest = Manifest::find(&folder)?.ok_or(anyhow::Error::msg(format!(
+            "Cannot find the library `{name}` needed by `{needed_by}`. (no `{}` in `{}`)",
+            super::MANIFEST_NAME,
+            folder.display()
+        )))?;
+        if library_manifest.kind != ProjectKind::Library {
+            return Err(anyhow::Error::msg(format!(
+                "`{}` isn't a library project, so `{needed_by}` can't use it.",
+                folder.display()
+            )));
+        }
+        if &library_manifest.name != name {
+            return Err(anyhow::Error::msg(format!(
+                "`{needed_by}` needs a library called `{name}`, but `{}` is called `{}`.",
+                folder.display(),
+                library_manifest.name
+            )));
+        }
+        let version = library_manifest.version;
+        if !dependency.requirement.matches(&version) {
+            return Err(anyhow::Error::msg(format!(
+                "`{needed_by}` needs `{name}` {}, but `{}` is version {version}.",
+                dependency.requirement,
+                folder.display()
+            )));
+        }
+        // Every library is only used at one version.
+        if let Some(existing) = libraries.iter().find(|library| &library.name == name) {
+            if existing.version != version {
+                return Err(anyhow::Error::msg(format!(
+                    "`{name}` is needed at both {} and {version}. Only one version of a library can be used.",
+                    existing.version
+                )));
+            }
+            continue;
+        }
+        match locked {
+            Some(locked) if locked != version => {
+                return Err(anyhow::Error::msg(format!(
+                    "`{LOCKFILE_NAME}` pins `{name}` to {locked}, but `{}` is version {version}. Remove `{name}` from `{LOCKFILE_NAME}` to use it.",
+                    folder.display()
+                )));
+            }
+            Some(_) => {}
+            None => lockfile.versions.push((name.clone(), version)),
+        }
+        for inner in &library_manifest.dependencies {
+            pending.push((inner.clone(), folder.clone(), name.clone()));
+        }
+        libraries.push(Library {
+            name: name.clone(),
+            version,
+            entry: folder.join(&library_manifest.entry),
+            folder,
+        });
+    }
+    // Libraries that are no longer used are dropped from the lockfile.
+    lockfile.versions.retain(|(name, _)| libraries.iter().any(|library| &library.name == name));
+    lockfile.versions.sort();
+    if lockfile != Lockfile::read(project_folder)? {
+        lockfile.write(project_folder)?;
+    }
+    Ok(libraries)
+}
+
+/// The version of a registry library to use: the one in the lockfile, or else
+/// the newest one in the registry that meets the requirement.
+fn pick_version(registry: &Path, dependency: &Dependency, locked: Option<Version>, needed_by: &str) -> Result<Version, anyhow::Error> {
+    let name = &dependency.name;
+    if let Some(locked) = locked {
+        if !dependency.requirement.matches(&locked) {
+            return Err(anyhow::Error::msg(format!(
+                "`{LOCKFILE_NAME}` pins `{name}` to {locked}, but `{needed_by}` needs {}. Remove `{name}` from `{LOCKFILE_NAME}` to pick a new version.",
+                dependency.requirement
+            )));
+        }
+        return Ok(locked);
+    }
+    let entries = std::fs::read_dir(registry.join(name)).map_err(|_| anyhow::Error::msg(format!(
+        "Cannot find the library `{name}` in the registry at `{}`.",
+        registry.display()
+    )))?;
+    let newest = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Version::parse(&entry.file_name().to_string_lossy()).ok())
+        .filter(|version| dependency.requirement.matches(version))
+        .max();
+    newest.ok_or(anyhow::Error::msg(format!(
+        "No version of `{name}` in the registry at `{}` matches {}.",
+        registry.display(),
+        dependency.requirement
+    )))
+}
+
+#[test]
+fn test_resolve() {
+    let folder = std::env::temp_dir().join(format!("beach_test_resolve_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    let registry = folder.join("registry");
+    let project = folder.join("project");
+    for version in ["1.0.0", "1.2.0", "2.0.0"] {
+        super::scaffold(&registry.join("geometry").join(version), "geometry", ProjectKind::Library).unwrap();
+        let path = registry.join("geometry").join(version).join(super::MANIFEST_NAME);
+        let text = std::fs::read_to_string(&path).unwrap().replace("0.1.0", version);
+        std::fs::write(&path, text).unwrap();
+    }
+    super::scaffold(&project.join("vendor").join("shapes"), "shapes", ProjectKind::Library).unwrap();
+    std::fs::write(project.join(super::MANIFEST_NAME), "\
+[project]
+name = \"project\"
+version = \"0.1.0\"
+
+[dependencies]
+geometry = \"1.0\"
+shapes = { path = \"vendor/shapes\" }
+").unwrap();
+    let manifest = Manifest::find(&project).unwrap().unwrap();
+
+    // The newest compatible version is picked, and then pinned.
+    let libraries = resolve(&project, &manifest, &registry).unwrap();
+    assert_eq!(libraries[0].version, Version::parse("1.2.0").unwrap());
+    assert_eq!(libraries[1].entry, project.join("vendor").join("shapes").join("lib.beach"));
+    assert_eq!(
+        std::fs::read_to_string(project.join(LOCKFILE_NAME)).unwrap(),
+        "# Written by beach. This pins the version of every library the project uses.\n[libraries]\ngeometry = \"1.2.0\"\nshapes = \"0.1.0\"\n"
+    );
+    std::fs::write(project.join(LOCKFILE_NAME), "[libraries]\ngeometry = \"1.0.0\"\n").unwrap();
+    let libraries = resolve(&project, &manifest, &registry).unwrap();
+    assert_eq!(libraries[0].version, Version::parse("1.0.0").unwrap());
+
+    // A pinned version has to still meet the manifest's requirement.
+    let mut manifest = manifest;
+    manifest.dependencies[0].requirement = super::Requirement::parse("2").unwrap();
+    assert_eq!(
+        resolve(&project, &manifest, &registry).unwrap_err().to_string(),
+        "`beach.lock` pins `geometry` to 1.0.0, but `project` needs 2.0.0. Remove `geometry` from `beach.lock` to pick a new version."
+    );
+    manifest.dependencies[0].requirement = super::Requirement::parse("3").unwrap();
+    std::fs::remove_file(project.join(LOCKFILE_NAME)).unwrap();
+    assert_eq!(
+        resolve(&project, &manifest, &registry).unwrap_err().to_string(),
+        format!("No version of `geometry` in the registry at `{}` matches 3.0.0.", registry.display())
+    );
+    std::fs::remove_dir_all(&folder).unwrap();
+}
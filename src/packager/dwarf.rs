@@ -0,0 +1,203 @@
+use crate::generator::generic::MachineCode;
+
+/// The DWARF sections describing a program, which let debuggers map each
+/// instruction back to the beach file and line it came from. Uses DWARF 4,
+/// which `gdb` and `lldb` both read.
+pub struct DebugSections {
+    pub abbrev: Vec<u8>,
+    pub info: Vec<u8>,
+    pub line: Vec<u8>,
+}
+
+// Tags, attributes and forms used by the compile unit. (DWARF 4, 7.5)
+const TAG_COMPILE_UNIT: u64 = 0x11;
+const AT_NAME: u64 = 0x03;
+const AT_STMT_LIST: u64 = 0x10;
+const AT_LOW_PC: u64 = 0x11;
+const AT_HIGH_PC: u64 = 0x12;
+const AT_LANGUAGE: u64 = 0x13;
+const AT_COMP_DIR: u64 = 0x1b;
+const AT_PRODUCER: u64 = 0x25;
+const FORM_ADDR: u64 = 0x01;
+const FORM_DATA2: u64 = 0x05;
+const FORM_DATA8: u64 = 0x07;
+const FORM_STRING: u64 = 0x08;
+const FORM_SEC_OFFSET: u64 = 0x17;
+/// There's no language code for beach, so it's described as assembly, which
+/// debuggers step through line by line without expecting anything else.
+const LANGUAGE_ASSEMBLY: u16 = 0x8001;
+
+// Line number program opcodes. (DWARF 4, 6.2.5)
+const LNS_COPY: u8 = 0x01;
+const LNS_ADVANCE_PC: u8 = 0x02;
+const LNS_ADVANCE_LINE: u8 = 0x03;
+const LNS_SET_FILE: u8 = 0x04;
+const LNE_END_SEQUENCE: u8 = 0x01;
+const LNE_SET_ADDRESS: u8 = 0x02;
+
+impl DebugSections {
+    /// Describes `code`, once it's loaded at `address`.
+    pub fn new(code: &MachineCode, address: u64) -> DebugSections {
+        // The first file is the main one, and names the compile unit.
+        let main = code.files.first().map(String::as_str).unwrap_or("");
+        let (directory, name) = match main.rsplit_once('/') {
+            Some((directory, name)) => (directory, name),
+            None => ("", main)
+        };
+
+        let mut abbrev = vec![];
+        uleb128(&mut abbrev, 1);
+        uleb128(&mut abbrev, TAG_COMPILE_UNIT);
+        // no children
+        abbrev.push(0);
+        for (attribute, form) in [
+            (AT_PRODUCER, FORM_STRING),
+            (AT_LANGUAGE, FORM_DATA2),
+            (AT_NAME, FORM_STRING),
+            (AT_COMP_DIR, FORM_STRING),
+            (AT_STMT_LIST, FORM_SEC_OFFSET),
+            (AT_LOW_PC, FORM_ADDR),
+            (AT_HIGH_PC, FORM_DATA8),
+        ] {
+            uleb128(&mut abbrev, attribute);
+            uleb128(&mut abbrev, form);
+        }
+        abbrev.extend_from_slice(&[0, 0]);
+        // end of the abbreviations
+        abbrev.push(0);
+
+        let mut unit = vec![];
+        // version, abbreviations offset, address size
+        unit.extend_from_slice(&4u16.to_le_bytes());
+        unit.extend_from_slice(&0u32.to_le_bytes());
+        unit.push(8);
+        uleb128(&mut unit, 1);
+        string(&mut unit, &format!("beach v{}", env!("CARGO_PKG_VERSION")));
+        unit.extend_from_slice(&LANGUAGE_ASSEMBLY.to_le_bytes());
+        string(&mut unit, name);
+        string(&mut unit, directory);
+        // the line table is the only one in `.debug_line`
+        unit.extend_from_slice(&0u32.to_le_bytes());
+        unit.extend_from_slice(&address.to_le_bytes());
+        // in DWARF 4, the high pc is the length of the code
+        unit.extend_from_slice(&(code.bytes.len() as u64).to_le_bytes());
+        let mut info = (unit.len() as u32).to_le_bytes().to_vec();
+        info.append(&mut unit);
+
+        DebugSections { abbrev, info, line: Self::line_table(code, address) }
+    }
+    fn line_table(code: &MachineCode, address: u64) -> Vec<u8> {
+        let mut header = vec![];
+        // minimum instruction length, maximum operations per instruction,
+        // lines are statements by default, line base, line range, opcode base
+        // and the operands of each standard opcode
+        header.extend_from_slice(&[1, 1, 1, (-5i8) as u8, 14, 13]);
+        header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+        // no include directories, as files are named by their full paths
+        header.push(0);
+        for file in &code.files {
+            string(&mut header, file);
+            // directory, modification time and length
+            header.extend_from_slice(&[0, 0, 0]);
+        }
+        header.push(0);
+
+        let mut program = vec![];
+        extended(&mut program, LNE_SET_ADDRESS, &address.to_le_bytes());
+        // Registers start at file 1, line 1.
+        let (mut file, mut line, mut offset) = (0, 1, 0);
+        for entry in &code.lines {
+            if entry.file != file {
+                file = entry.file;
+                program.push(LNS_SET_FILE);
+                uleb128(&mut program, file as u64 + 1);
+            }
+            if entry.line != line {
+                program.push(LNS_ADVANCE_LINE);
+                sleb128(&mut program, entry.line as i64 - line as i64);
+                line = entry.line;
+            }
+            if entry.offset != offset {
+                program.push(LNS_ADVANCE_PC);
+                uleb128(&mut program, (entry.offset - offset) as u64);
+                offset = entry.offset;
+            }
+            program.push(LNS_COPY);
+        }
+        program.push(LNS_ADVANCE_PC);
+        uleb128(&mut program, (code.bytes.len() - offset) as u64);
+        extended(&mut program, LNE_END_SEQUENCE, &[]);
+
+        let mut unit = vec![];
+        unit.extend_from_slice(&4u16.to_le_bytes());
+        unit.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        unit.append(&mut header);
+        unit.append(&mut program);
+        let mut line = (unit.len() as u32).to_le_bytes().to_vec();
+        line.append(&mut unit);
+        line
+    }
+}
+
+/// Writes `value` as an unsigned LEB128 number.
+fn uleb128(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+/// Writes `value` as a signed LEB128 number.
+fn sleb128(output: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        // Done once the rest is only sign bits, matching the sign bit of this
+        // byte.
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+/// Writes a null terminated string.
+fn string(output: &mut Vec<u8>, text: &str) {
+    output.extend_from_slice(text.as_bytes());
+    output.push(0);
+}
+
+/// Writes an extended line number opcode.
+fn extended(output: &mut Vec<u8>, opcode: u8, operands: &[u8]) {
+    output.push(0);
+    uleb128(output, operands.len() as u64 + 1);
+    output.push(opcode);
+    output.extend_from_slice(operands);
+}
+
+#[test]
+fn test_leb128() {
+    let encode = |value: i64| {
+        let (mut unsigned, mut signed) = (vec![], vec![]);
+        uleb128(&mut unsigned, value as u64);
+        sleb128(&mut signed, value);
+        (unsigned, signed)
+    };
+    // Examples from DWARF 4, figures 22 and 23.
+    assert_eq!(encode(2), (vec![2], vec![2]));
+    assert_eq!(encode(127).0, [127]);
+    assert_eq!(encode(128), (vec![0x80, 1], vec![0x80, 1]));
+    assert_eq!(encode(12857).0, [0xb9, 0x64]);
+    let mut signed = vec![];
+    sleb128(&mut signed, -2);
+    assert_eq!(signed, [0x7e]);
+    signed.clear();
+    sleb128(&mut signed, -129);
+    assert_eq!(signed, [0xff, 0x7e]);
+}
@@ -0,0 +1,201 @@
+use crate::generator::generic::{Architecture, MachineCode, SymbolKind};
+use crate::packager::dwarf::DebugSections;
+
+/// Where the executable is loaded in memory.
+const LOAD_ADDRESS: u64 = 0x400000;
+/// The size of a 64 bit ELF header.
+const HEADER_SIZE: u64 = 64;
+/// The size of a 64 bit ELF program header.
+const PROGRAM_HEADER_SIZE: u64 = 56;
+/// The size of a 64 bit ELF section header.
+const SECTION_HEADER_SIZE: u64 = 64;
+/// The size of a 64 bit ELF symbol.
+const SYMBOL_SIZE: u64 = 24;
+
+// Section types and flags.
+const SECTION_PROGRAM: u32 = 1;
+const SECTION_SYMBOLS: u32 = 2;
+const SECTION_STRINGS: u32 = 3;
+const SECTION_ALLOCATED: u64 = 0x2;
+const SECTION_EXECUTABLE: u64 = 0x4;
+
+/// A section listed in the section header table. Only debuggers and tools
+/// such as `objdump` read these, as the program header is what gets loaded.
+struct Section {
+    name: &'static str,
+    kind: u32,
+    flags: u64,
+    address: u64,
+    offset: u64,
+    size: u64,
+    /// The index of a related section, such as a symbol table's names.
+    link: u32,
+    /// Extra information that depends on the kind of section.
+    info: u32,
+    alignment: u64,
+    /// The size of each entry, for sections that are tables.
+    entry_size: u64,
+}
+
+/// Creates a statically linked 64 bit ELF executable that runs `code`. With
+/// `debug`, DWARF sections describe where each instruction came from.
+pub fn executable(code: &MachineCode, architecture: Architecture, debug: bool) -> Vec<u8> {
+    let machine: u16 = match architecture {
+        Architecture::AArch64 => 183
+    };
+    let code_offset = HEADER_SIZE + PROGRAM_HEADER_SIZE;
+    let loaded_size = code_offset + code.bytes.len() as u64;
+
+    // Everything after the code isn't loaded, so it's only described by
+    // sections.
+    let mut sections = vec![Section {
+        name: ".text",
+        kind: SECTION_PROGRAM,
+        flags: SECTION_ALLOCATED | SECTION_EXECUTABLE,
+        address: LOAD_ADDRESS + code_offset,
+        offset: code_offset,
+        size: code.bytes.len() as u64,
+        link: 0,
+        info: 0,
+        alignment: 4,
+        entry_size: 0,
+    }];
+    let mut extra = vec![];
+
+    // Every label is a symbol, so tools such as `nm` and debuggers can name
+    // each part of the program. Local symbols have to come first.
+    let mut symbols = vec![0x00; SYMBOL_SIZE as usize];
+    let mut symbol_names = vec![0];
+    let mut ordered: Vec<_> = code.symbols.iter().collect();
+    ordered.sort_by_key(|symbol| symbol.global);
+    let first_global = 1 + ordered.iter().filter(|symbol| !symbol.global).count() as u32;
+    for symbol in ordered {
+        let kind: u8 = match symbol.kind {
+            SymbolKind::Label => 0,
+            SymbolKind::Data => 1,
+            SymbolKind::Function => 2,
+        };
+        symbols.extend_from_slice(&(symbol_names.len() as u32).to_le_bytes());
+        symbols.push((symbol.global as u8) << 4 | kind);
+        // default visibility, defined in `.text`
+        symbols.push(0);
+        symbols.extend_from_slice(&1u16.to_le_bytes());
+        symbols.extend_from_slice(&(LOAD_ADDRESS + code_offset + symbol.offset as u64).to_le_bytes());
+        symbols.extend_from_slice(&(symbol.size as u64).to_le_bytes());
+        symbol_names.extend_from_slice(symbol.name.as_bytes());
+        symbol_names.push(0);
+    }
+    // names come from the next section, which is the `.strtab`
+    let names_index = sections.len() as u32 + 2;
+    let symbol_table = add_section(&mut sections, &mut extra, loaded_size, ".symtab", SECTION_SYMBOLS, &symbols, 8);
+    symbol_table.link = names_index;
+    symbol_table.info = first_global;
+    symbol_table.entry_size = SYMBOL_SIZE;
+    add_section(&mut sections, &mut extra, loaded_size, ".strtab", SECTION_STRINGS, &symbol_names, 1);
+
+    if debug {
+        let dwarf = DebugSections::new(code, LOAD_ADDRESS + code_offset);
+        add_section(&mut sections, &mut extra, loaded_size, ".debug_abbrev", SECTION_PROGRAM, &dwarf.abbrev, 1);
+        add_section(&mut sections, &mut extra, loaded_size, ".debug_info", SECTION_PROGRAM, &dwarf.info, 1);
+        add_section(&mut sections, &mut extra, loaded_size, ".debug_line", SECTION_PROGRAM, &dwarf.line, 1);
+    }
+    // The section names come last, including their own.
+    let mut names = vec![0];
+    let mut name_offsets = vec![];
+    for name in sections.iter().map(|section| section.name).chain([".shstrtab"]) {
+        name_offsets.push(names.len() as u32);
+        names.extend_from_slice(name.as_bytes());
+        names.push(0);
+    }
+    add_section(&mut sections, &mut extra, loaded_size, ".shstrtab", SECTION_STRINGS, &names, 1);
+    // the null section comes first
+    let section_count = sections.len() as u64 + 1;
+    let section_headers_offset = (loaded_size + extra.len() as u64).next_multiple_of(8);
+
+    let mut output = vec![];
+    // identification: magic number, 64 bit, little endian, version 1, System V
+    // ABI, then padding
+    output.extend_from_slice(b"\x7FELF");
+    output.extend_from_slice(&[2, 1, 1, 0]);
+    output.extend_from_slice(&[0x00; 8]);
+    // executable file
+    output.extend_from_slice(&2u16.to_le_bytes());
+    output.extend_from_slice(&machine.to_le_bytes());
+    // version
+    output.extend_from_slice(&1u32.to_le_bytes());
+    // entry point
+    output.extend_from_slice(&(LOAD_ADDRESS + code_offset + code.entry_point as u64).to_le_bytes());
+    // program headers directly follow this header, and section headers end
+    // the file
+    output.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    output.extend_from_slice(&section_headers_offset.to_le_bytes());
+    // flags
+    output.extend_from_slice(&0u32.to_le_bytes());
+    // sizes of this header, program headers and section headers
+    output.extend_from_slice(&(HEADER_SIZE as u16).to_le_bytes());
+    output.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    output.extend_from_slice(&1u16.to_le_bytes());
+    output.extend_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+    output.extend_from_slice(&(section_count as u16).to_le_bytes());
+    // the section name table is the last section
+    output.extend_from_slice(&((section_count - 1) as u16).to_le_bytes());
+
+    // One loadable segment maps the headers and code as readable and
+    // executable.
+    output.extend_from_slice(&1u32.to_le_bytes());
+    output.extend_from_slice(&0b101u32.to_le_bytes());
+    output.extend_from_slice(&0u64.to_le_bytes());
+    output.extend_from_slice(&LOAD_ADDRESS.to_le_bytes());
+    output.extend_from_slice(&LOAD_ADDRESS.to_le_bytes());
+    output.extend_from_slice(&loaded_size.to_le_bytes());
+    output.extend_from_slice(&loaded_size.to_le_bytes());
+    output.extend_from_slice(&0x1000u64.to_le_bytes());
+
+    output.extend_from_slice(&code.bytes);
+    output.append(&mut extra);
+    output.resize(section_headers_offset as usize, 0x00);
+
+    output.extend_from_slice(&[0x00; SECTION_HEADER_SIZE as usize]);
+    for (section, name_offset) in sections.iter().zip(name_offsets) {
+        output.extend_from_slice(&name_offset.to_le_bytes());
+        output.extend_from_slice(&section.kind.to_le_bytes());
+        output.extend_from_slice(&section.flags.to_le_bytes());
+        output.extend_from_slice(&section.address.to_le_bytes());
+        output.extend_from_slice(&section.offset.to_le_bytes());
+        output.extend_from_slice(&section.size.to_le_bytes());
+        output.extend_from_slice(&section.link.to_le_bytes());
+        output.extend_from_slice(&section.info.to_le_bytes());
+        output.extend_from_slice(&section.alignment.to_le_bytes());
+        output.extend_from_slice(&section.entry_size.to_le_bytes());
+    }
+    output
+}
+
+/// Lists `data` as a section that isn't loaded, adding it to `extra`, which is
+/// written from `extra_offset` in the file.
+fn add_section<'a>(
+    sections: &'a mut Vec<Section>,
+    extra: &mut Vec<u8>,
+    extra_offset: u64,
+    name: &'static str,
+    kind: u32,
+    data: &[u8],
+    alignment: u64
+) -> &'a mut Section {
+    let offset = (extra_offset + extra.len() as u64).next_multiple_of(alignment);
+    extra.resize((offset - extra_offset) as usize, 0x00);
+    extra.extend_from_slice(data);
+    sections.push(Section {
+        name,
+        kind,
+        flags: 0,
+        address: 0,
+        offset,
+        size: data.len() as u64,
+        link: 0,
+        info: 0,
+        alignment,
+        entry_size: 0,
+    });
+    sections.last_mut().unwrap()
+}
@@ -24,6 +24,22 @@ pub struct Header {
 }
 
 impl Header {
+    /// Creates a [Header] for a 64 bit, position independent executable that
+    /// is loaded by dyld.
+    pub fn executable_64(cpu_type: CPUType, cpu_subtype: u32, number_of_load_commands: u32, size_of_load_commands: u32) -> Header {
+        Header {
+            magic_number: 0xFEEDFACF,
+            cpu_type,
+            cpu_subtype,
+            file_type: FileType::DemandPagedExecutable as u32,
+            number_of_load_commands,
+            size_of_load_commands,
+            // no undefined symbols, dynamically linked, two level namespace,
+            // position independent
+            flags: 0x00000001 | 0x00000004 | 0x00000080 | 0x00200000,
+            _reserved: Some(0)
+        }
+    }
     /// Generates a [Header] from a type implementing [std::io::Read]. Assumes platform endianness
     /// is little.
     pub fn from_le_reader<R: std::io::Read>(reader: &mut R) -> Result<Header, anyhow::Error> {
@@ -4,25 +4,188 @@ pub struct LoadCommand {
     command_data: LoadCommands,
 }
 
+impl LoadCommand {
+    pub fn new(command_data: LoadCommands) -> LoadCommand {
+        LoadCommand {
+            command: command_data.command(),
+            command_size: command_data.size(),
+            command_data
+        }
+    }
+    /// The size of this command in bytes, including its command and size
+    /// fields.
+    pub fn size(&self) -> u32 {
+        self.command_size
+    }
+    pub fn write_le<W: std::io::Write>(&self, writer: &mut W) -> Result<(), anyhow::Error> {
+        writer.write_all(&self.command.to_le_bytes())?;
+        writer.write_all(&self.command_size.to_le_bytes())?;
+        match &self.command_data {
+            LoadCommands::SegmentLoad64(segment) => segment.write_le(writer)?,
+            LoadCommands::LoadDynamicLinker { path } => {
+                // offset of the path from the start of this command
+                writer.write_all(&12u32.to_le_bytes())?;
+                write_padded_string(writer, path, self.command_size as usize - 12)?;
+            }
+            LoadCommands::Main { entry_offset, stack_size } => {
+                writer.write_all(&entry_offset.to_le_bytes())?;
+                writer.write_all(&stack_size.to_le_bytes())?;
+            }
+            LoadCommands::LoadDynamicLibrary { path, current_version, compatibility_version } => {
+                // offset of the path from the start of this command
+                writer.write_all(&24u32.to_le_bytes())?;
+                // build timestamp, which dyld ignores
+                writer.write_all(&2u32.to_le_bytes())?;
+                writer.write_all(&current_version.to_le_bytes())?;
+                writer.write_all(&compatibility_version.to_le_bytes())?;
+                write_padded_string(writer, path, self.command_size as usize - 24)?;
+            }
+            LoadCommands::SymbolTable { symbol_offset, symbol_count, names_offset, names_size } => {
+                writer.write_all(&symbol_offset.to_le_bytes())?;
+                writer.write_all(&symbol_count.to_le_bytes())?;
+                writer.write_all(&names_offset.to_le_bytes())?;
+                writer.write_all(&names_size.to_le_bytes())?;
+            }
+            LoadCommands::DynamicSymbolTable { locals, externals } => {
+                // local symbols come first, then external ones, and there are
+                // no undefined symbols
+                for value in [0, *locals, *locals, *externals, locals + externals, 0] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                // every other table is empty without dynamic linking
+                writer.write_all(&[0x00; 48])?;
+            }
+            LoadCommands::BuildVersion { platform, minimum_os } => {
+                writer.write_all(&platform.to_le_bytes())?;
+                writer.write_all(&minimum_os.to_le_bytes())?;
+                // sdk version, and no tools listed
+                writer.write_all(&0u32.to_le_bytes())?;
+                writer.write_all(&0u32.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub enum LoadCommands {
     SegmentLoad64(SegmentLoad64),
+    /// The dynamic linker to load this executable with. (`/usr/lib/dyld`)
+    LoadDynamicLinker { path: String },
+    /// Where execution starts, as an offset into the file.
+    Main { entry_offset: u64, stack_size: u64 },
+    /// A dynamic library this executable needs.
+    LoadDynamicLibrary { path: String, current_version: u32, compatibility_version: u32 },
+    /// Where the symbol table and the names of its symbols are in the file.
+    SymbolTable { symbol_offset: u32, symbol_count: u32, names_offset: u32, names_size: u32 },
+    /// How the symbol table is grouped, as `locals` local symbols followed by
+    /// `externals` external ones. Beach programs don't use anything else from
+    /// this command.
+    DynamicSymbolTable { locals: u32, externals: u32 },
+    /// The platform and minimum OS version this executable was built for. OS
+    /// versions are encoded as `xxxx.yy.zz` in nibbles.
+    BuildVersion { platform: u32, minimum_os: u32 },
     // TODO: other commands
 }
 
+impl LoadCommands {
+    fn command(&self) -> u32 {
+        match self {
+            Self::SegmentLoad64(_) => 0x19,
+            Self::LoadDynamicLinker { .. } => 0x0E,
+            Self::Main { .. } => 0x80000028,
+            Self::LoadDynamicLibrary { .. } => 0x0C,
+            Self::SymbolTable { .. } => 0x02,
+            Self::DynamicSymbolTable { .. } => 0x0B,
+            Self::BuildVersion { .. } => 0x32,
+        }
+    }
+    fn size(&self) -> u32 {
+        match self {
+            Self::SegmentLoad64(segment) => 72 + 80 * segment.sections.len() as u32,
+            // strings are null terminated, and commands are padded to 8 bytes
+            Self::LoadDynamicLinker { path } => (12 + path.len() as u32 + 1).next_multiple_of(8),
+            Self::Main { .. } => 24,
+            Self::LoadDynamicLibrary { path, .. } => (24 + path.len() as u32 + 1).next_multiple_of(8),
+            Self::SymbolTable { .. } => 24,
+            Self::DynamicSymbolTable { .. } => 80,
+            Self::BuildVersion { .. } => 24,
+        }
+    }
+}
+
 pub struct SegmentLoad64 {
     /// Name of this segment. Maximum 16 characters long.
-    segment_name: String,
+    pub segment_name: String,
     /// The target virtual address of this segment.
-    virtual_address: u64,
+    pub virtual_address: u64,
     /// The size of this segment in virtual memory. Any excess size beyond what is being copied is
     /// zeroed out.
-    virtual_size: u64,
+    pub virtual_size: u64,
     /// Offset from the end of commands to this segment
-    file_offset: u64,
+    pub file_offset: u64,
     /// Size of this segment source
-    file_size: u64,
-    maximum_protection: u32,
-    inital_protection: u32,
-    number_of_sections: u32,
-    flags: u32,
+    pub file_size: u64,
+    pub maximum_protection: u32,
+    pub inital_protection: u32,
+    pub flags: u32,
+    pub sections: Vec<Section64>,
+}
+
+impl SegmentLoad64 {
+    fn write_le<W: std::io::Write>(&self, writer: &mut W) -> Result<(), anyhow::Error> {
+        write_padded_string(writer, &self.segment_name, 16)?;
+        writer.write_all(&self.virtual_address.to_le_bytes())?;
+        writer.write_all(&self.virtual_size.to_le_bytes())?;
+        writer.write_all(&self.file_offset.to_le_bytes())?;
+        writer.write_all(&self.file_size.to_le_bytes())?;
+        writer.write_all(&self.maximum_protection.to_le_bytes())?;
+        writer.write_all(&self.inital_protection.to_le_bytes())?;
+        writer.write_all(&(self.sections.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.flags.to_le_bytes())?;
+        for section in &self.sections {
+            section.write_le(writer, &self.segment_name)?;
+        }
+        Ok(())
+    }
+}
+
+/// A section of a [SegmentLoad64].
+pub struct Section64 {
+    /// Name of this section. Maximum 16 characters long.
+    pub section_name: String,
+    /// The target virtual address of this section.
+    pub address: u64,
+    pub size: u64,
+    /// Offset from the start of the file to this section.
+    pub file_offset: u32,
+    /// Alignment of this section, as a power of two.
+    pub alignment: u32,
+    pub flags: u32,
+}
+
+impl Section64 {
+    fn write_le<W: std::io::Write>(&self, writer: &mut W, segment_name: &str) -> Result<(), anyhow::Error> {
+        write_padded_string(writer, &self.section_name, 16)?;
+        write_padded_string(writer, segment_name, 16)?;
+        writer.write_all(&self.address.to_le_bytes())?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        writer.write_all(&self.file_offset.to_le_bytes())?;
+        writer.write_all(&self.alignment.to_le_bytes())?;
+        // no relocations
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&self.flags.to_le_bytes())?;
+        // reserved fields
+        writer.write_all(&[0x00; 12])?;
+        Ok(())
+    }
+}
+
+/// Writes `string` followed by zeroes, filling exactly `length` bytes.
+fn write_padded_string<W: std::io::Write>(writer: &mut W, string: &str, length: usize) -> Result<(), anyhow::Error> {
+    let mut bytes = string.as_bytes().to_vec();
+    assert!(bytes.len() < length, "Mach-O string `{string}` is too long.");
+    bytes.resize(length, 0x00);
+    writer.write_all(&bytes)?;
+    Ok(())
 }
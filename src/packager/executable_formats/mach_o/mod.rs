@@ -1,17 +1,233 @@
 mod header;
 mod load_command;
 
-pub struct MachO<'a> {
+use header::{CPUType, Header};
+use load_command::{LoadCommand, LoadCommands, Section64, SegmentLoad64};
+use crate::generator::generic::{Architecture, MachineCode};
+use crate::packager::dwarf::DebugSections;
+
+/// Where the `__TEXT` segment is loaded, above the 4GiB `__PAGEZERO` segment.
+const TEXT_ADDRESS: u64 = 0x1_0000_0000;
+/// Segments are aligned to 16KiB pages on AArch64.
+const PAGE_SIZE: u64 = 0x4000;
+/// The size of a 64 bit Mach-O header.
+const HEADER_SIZE: u64 = 32;
+
+pub struct MachO {
     header: header::Header,
     load_commands: Vec<load_command::LoadCommand>,
-    data: &'a [u8],
+    /// Everything following the load commands.
+    data: Vec<u8>,
 }
 
-impl <'a>MachO<'a> {
+impl MachO {
     pub fn from_reader<R: std::io::Read>(reader: &mut R) -> MachO {
         todo!()
     }
-    pub fn write<W: std::io::Write>(&mut self, writer: &mut W) {
-        todo!()
+    /// Creates an executable that runs `code`.
+    /// 
+    /// MacOS on AArch64 refuses to run unsigned executables, so the result
+    /// needs at least an ad-hoc signature before it will run.
+    /// (`codesign -s - [FILE]`)
+    /// 
+    /// With `debug`, a `__DWARF` segment describes where each instruction
+    /// came from.
+    pub fn executable(code: &MachineCode, architecture: Architecture, debug: bool) -> MachO {
+        let (cpu_type, cpu_subtype) = match architecture {
+            Architecture::AArch64 => (CPUType::ARM64, 0)
+        };
+        // The size of each load command doesn't depend on where the code ends
+        // up, so placeholder commands tell us where that is.
+        let placeholder_dwarf = debug.then(|| DebugSections::new(code, 0));
+        let placeholder_symbols = SymbolTable::new(code, 0);
+        let commands_size: u64 = Self::executable_commands(code, 0, placeholder_dwarf.as_ref(), &placeholder_symbols).iter()
+            .map(|command| command.size() as u64)
+            .sum();
+        let code_offset = (HEADER_SIZE + commands_size).next_multiple_of(16);
+        let dwarf = debug.then(|| DebugSections::new(code, TEXT_ADDRESS + code_offset));
+        let symbols = SymbolTable::new(code, code_offset);
+        let load_commands = Self::executable_commands(code, code_offset, dwarf.as_ref(), &symbols);
+        let mut data = vec![0x00; (code_offset - HEADER_SIZE - commands_size) as usize];
+        data.extend_from_slice(&code.bytes);
+        if let Some(dwarf) = dwarf {
+            data.extend_from_slice(&dwarf.abbrev);
+            data.extend_from_slice(&dwarf.info);
+            data.extend_from_slice(&dwarf.line);
+        }
+        // `__LINKEDIT` starts 8 byte aligned, for the symbols.
+        let data_offset = HEADER_SIZE + commands_size;
+        let linkedit_offset = (data_offset + data.len() as u64).next_multiple_of(8);
+        data.resize((linkedit_offset - data_offset) as usize, 0x00);
+        data.extend_from_slice(&symbols.symbols);
+        data.extend_from_slice(&symbols.names);
+        MachO {
+            header: Header::executable_64(cpu_type, cpu_subtype, load_commands.len() as u32, commands_size as u32),
+            load_commands,
+            data
+        }
     }
-}
\ No newline at end of file
+    fn executable_commands(
+        code: &MachineCode,
+        code_offset: u64,
+        dwarf: Option<&DebugSections>,
+        symbols: &SymbolTable
+    ) -> Vec<LoadCommand> {
+        let text_file_size = code_offset + code.bytes.len() as u64;
+        let text_size = text_file_size.next_multiple_of(PAGE_SIZE);
+        // Debug information follows the code, and `__LINKEDIT` must stay
+        // last in the file and in memory.
+        let mut file_size = text_file_size;
+        let mut linkedit_address = TEXT_ADDRESS + text_size;
+        let mut dwarf_segment = None;
+        if let Some(dwarf) = dwarf {
+            let mut sections = vec![];
+            for (name, data) in [
+                ("__debug_abbrev", &dwarf.abbrev),
+                ("__debug_info", &dwarf.info),
+                ("__debug_line", &dwarf.line),
+            ] {
+                sections.push(Section64 {
+                    section_name: String::from(name),
+                    address: linkedit_address + (file_size - text_file_size),
+                    size: data.len() as u64,
+                    file_offset: file_size as u32,
+                    alignment: 0,
+                    // debug information only
+                    flags: 0x02000000
+                });
+                file_size += data.len() as u64;
+            }
+            let dwarf_size = file_size - text_file_size;
+            dwarf_segment = Some(LoadCommand::new(LoadCommands::SegmentLoad64(SegmentLoad64 {
+                segment_name: String::from("__DWARF"),
+                virtual_address: linkedit_address,
+                virtual_size: dwarf_size.next_multiple_of(PAGE_SIZE),
+                file_offset: text_file_size,
+                file_size: dwarf_size,
+                maximum_protection: 0b001,
+                inital_protection: 0b001,
+                flags: 0,
+                sections
+            })));
+            linkedit_address += dwarf_size.next_multiple_of(PAGE_SIZE);
+        }
+        let mut commands = vec![
+            // Catches null pointers by mapping the low 4GiB as inaccessible.
+            LoadCommand::new(LoadCommands::SegmentLoad64(SegmentLoad64 {
+                segment_name: String::from("__PAGEZERO"),
+                virtual_address: 0,
+                virtual_size: TEXT_ADDRESS,
+                file_offset: 0,
+                file_size: 0,
+                maximum_protection: 0,
+                inital_protection: 0,
+                flags: 0,
+                sections: vec![]
+            })),
+            // The whole file is mapped as readable and executable.
+            LoadCommand::new(LoadCommands::SegmentLoad64(SegmentLoad64 {
+                segment_name: String::from("__TEXT"),
+                virtual_address: TEXT_ADDRESS,
+                virtual_size: text_size,
+                file_offset: 0,
+                file_size: text_file_size,
+                maximum_protection: 0b101,
+                inital_protection: 0b101,
+                flags: 0,
+                sections: vec![Section64 {
+                    section_name: String::from("__text"),
+                    address: TEXT_ADDRESS + code_offset,
+                    size: code.bytes.len() as u64,
+                    file_offset: code_offset as u32,
+                    alignment: 2,
+                    // contains only instructions, and some instructions
+                    flags: 0x80000000 | 0x00000400
+                }]
+            })),
+        ];
+        commands.extend(dwarf_segment);
+        let linkedit_offset = file_size.next_multiple_of(8);
+        let linkedit_size = (symbols.symbols.len() + symbols.names.len()) as u64;
+        commands.extend([
+            // The symbol table lives in the link edit segment.
+            LoadCommand::new(LoadCommands::SegmentLoad64(SegmentLoad64 {
+                segment_name: String::from("__LINKEDIT"),
+                virtual_address: linkedit_address,
+                virtual_size: linkedit_size.next_multiple_of(PAGE_SIZE).max(PAGE_SIZE),
+                file_offset: linkedit_offset,
+                file_size: linkedit_size,
+                maximum_protection: 0b001,
+                inital_protection: 0b001,
+                flags: 0,
+                sections: vec![]
+            })),
+            LoadCommand::new(LoadCommands::LoadDynamicLinker { path: String::from("/usr/lib/dyld") }),
+            LoadCommand::new(LoadCommands::Main {
+                entry_offset: code_offset + code.entry_point as u64,
+                stack_size: 0
+            }),
+            // Every MacOS executable must link libSystem, even if it's unused.
+            LoadCommand::new(LoadCommands::LoadDynamicLibrary {
+                path: String::from("/usr/lib/libSystem.B.dylib"),
+                current_version: 0x05276403,
+                compatibility_version: 0x00010000
+            }),
+            LoadCommand::new(LoadCommands::SymbolTable {
+                symbol_offset: linkedit_offset as u32,
+                symbol_count: symbols.locals + symbols.externals,
+                names_offset: (linkedit_offset + symbols.symbols.len() as u64) as u32,
+                names_size: symbols.names.len() as u32
+            }),
+            LoadCommand::new(LoadCommands::DynamicSymbolTable { locals: symbols.locals, externals: symbols.externals }),
+            // MacOS 11.0, the first version to run on AArch64
+            LoadCommand::new(LoadCommands::BuildVersion { platform: 1, minimum_os: 0x000B0000 }),
+        ]);
+        commands
+    }
+    pub fn write<W: std::io::Write>(&self, writer: &mut W) -> Result<(), anyhow::Error> {
+        self.header.write(writer)?;
+        for command in &self.load_commands {
+            command.write_le(writer)?;
+        }
+        writer.write_all(&self.data)?;
+        Ok(())
+    }
+}
+
+/// The symbols for every label in the code, as they're stored in
+/// `__LINKEDIT`.
+struct SymbolTable {
+    /// `nlist_64` entries, with local symbols first.
+    symbols: Vec<u8>,
+    /// The name of every symbol, following an empty one.
+    names: Vec<u8>,
+    locals: u32,
+    externals: u32,
+}
+
+impl SymbolTable {
+    fn new(code: &MachineCode, code_offset: u64) -> SymbolTable {
+        let mut table = SymbolTable { symbols: vec![], names: vec![0], locals: 0, externals: 0 };
+        let mut ordered: Vec<_> = code.symbols.iter().collect();
+        ordered.sort_by_key(|symbol| symbol.global);
+        for symbol in ordered {
+            table.symbols.extend_from_slice(&(table.names.len() as u32).to_le_bytes());
+            // defined in a section, and external if global
+            table.symbols.push(0x0E | symbol.global as u8);
+            // the `__text` section
+            table.symbols.push(1);
+            table.symbols.extend_from_slice(&0u16.to_le_bytes());
+            table.symbols.extend_from_slice(&(TEXT_ADDRESS + code_offset + symbol.offset as u64).to_le_bytes());
+            table.names.extend_from_slice(symbol.name.as_bytes());
+            table.names.push(0);
+            if symbol.global {
+                table.externals += 1;
+            }
+            else {
+                table.locals += 1;
+            }
+        }
+        table.names.resize(table.names.len().next_multiple_of(8), 0x00);
+        table
+    }
+}
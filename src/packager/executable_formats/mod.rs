@@ -1 +1,2 @@
-mod mach_o;
+pub mod elf;
+pub mod mach_o;
@@ -1 +1,35 @@
+mod dwarf;
 mod executable_formats;
+
+use crate::generator::generic::{Architecture, MachineCode};
+
+/// The executable file formats programs can be packaged as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packager {
+    /// Executable and Linkable Format, used by Linux and most other Unix-likes.
+    Elf,
+    /// Mach object files, used by MacOS.
+    MachO,
+}
+
+impl Packager {
+    /// A name for this format, as shown to users.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Elf => "ELF",
+            Self::MachO => "Mach-O",
+        }
+    }
+    /// Packages `code` as an executable file. With `debug`, the file includes
+    /// DWARF line tables built from the code's source locations.
+    pub fn package(&self, code: &MachineCode, architecture: Architecture, debug: bool) -> Result<Vec<u8>, anyhow::Error> {
+        match self {
+            Self::Elf => Ok(executable_formats::elf::executable(code, architecture, debug)),
+            Self::MachO => {
+                let mut output = vec![];
+                executable_formats::mach_o::MachO::executable(code, architecture, debug).write(&mut output)?;
+                Ok(output)
+            }
+        }
+    }
+}
@@ -1,37 +1,416 @@
-use super::intermediate::*;
+use std::collections::HashMap;
 
-pub fn line_to_intermediate(line: &str) -> Instruction {
-    let cleaned = line.trim();
-    if cleaned.ends_with(":") {
-        // TODO: label
-    }
-    let (opcode, args) = cleaned
-        .split_once(" ")
-        .unwrap_or_else(|| {return (cleaned, "")});
-
-    match opcode.to_lowercase().as_ref() {
-        "b" => {
-            if args.starts_with("#") {
-                if let Ok(mut val) = args.trim_start_matches("#").parse::<i32>() {
-                    if val.is_negative() {
-                        // move the sign bit to the proper ending
-                        val *= -1;
-                        val |= 0b1 << 27;
-                    }
-                    return Instruction::B { offset: val as u32 };
+use super::intermediate::*;
+use crate::generator::generic::{LineEntry, MachineCode, Symbol, SymbolKind};
 
+/// Assembles a file of AArch64 assembly, such as the output of
+/// [crate::generator::aarch64::AArch64AssemblyGenerator]. The label made
+/// global with `.global` is used as the entry point.
+///
+/// Values loaded with `ldr [register], =[value]` are put in a literal pool
+/// after everything else.
+pub fn assemble(source: &str) -> Result<MachineCode, anyhow::Error> {
+    // The first pass finds where every label ends up, so the second can refer
+    // to labels further down the file.
+    let mut labels = HashMap::new();
+    let mut symbols: Vec<Symbol> = vec![];
+    let mut kinds = HashMap::new();
+    let mut global = None;
+    let mut offset = 0;
+    // Where the last instruction or data ended, so symbols don't include
+    // the alignment padding after them.
+    let mut content_end: usize = 0;
+    // Each value in the literal pool, once.
+    let mut literals = vec![];
+    for line in lines(source) {
+        match classify(line)? {
+            Line::Label(label) => {
+                if let Some(previous) = symbols.last_mut() {
+                    previous.size = content_end.saturating_sub(previous.offset);
+                }
+                labels.insert(label.to_string(), offset);
+                symbols.push(Symbol {
+                    name: label.to_string(),
+                    offset,
+                    size: 0,
+                    kind: SymbolKind::Label,
+                    global: false
+                });
+            }
+            Line::Global(label) => global = Some(label.to_string()),
+            Line::Type(label, kind) => {
+                kinds.insert(label, kind);
+            }
+            Line::File(..) | Line::Location(..) => {}
+            Line::Bytes(bytes) => {
+                offset += bytes.len();
+                content_end = offset;
+            }
+            Line::Align(power) => offset = align(offset, power),
+            Line::Instruction(instruction) => {
+                if let Some(value) = literal(instruction)?
+                    && !literals.contains(&value)
+                {
+                    literals.push(value);
                 }
-                else {
-                    panic!("Unable to parse numeric value in B opcode!");
+                offset += 4;
+                content_end = offset;
+            }
+        }
+    }
+    let pool = align(offset, 3);
+    let literal_addresses: HashMap<i64, usize> = literals.iter()
+        .enumerate()
+        .map(|(index, value)| (*value, pool + index * 8))
+        .collect();
+    if let Some(last) = symbols.last_mut() {
+        last.size = content_end.saturating_sub(last.offset);
+    }
+    for symbol in &mut symbols {
+        symbol.kind = kinds.get(symbol.name.as_str()).copied().unwrap_or(SymbolKind::Label);
+        symbol.global = global.as_ref() == Some(&symbol.name);
+    }
+    let mut bytes = vec![];
+    let mut files = vec![];
+    let mut source_lines = vec![];
+    for line in lines(source) {
+        match classify(line)? {
+            Line::Label(_) | Line::Global(_) | Line::Type(..) => {}
+            Line::File(index, path) => {
+                if index != files.len() + 1 {
+                    return Err(anyhow::Error::msg(format!(
+                        "Expected source file {} to be numbered next, not {index}.", files.len() + 1
+                    )));
                 }
+                files.push(path);
             }
-            else {
-                // TODO: probably a label
-                todo!()
+            Line::Location(file, line) => {
+                if file == 0 || file > files.len() {
+                    return Err(anyhow::Error::msg(format!("Source file {file} was never numbered with `.file`.")));
+                }
+                source_lines.push(LineEntry { offset: bytes.len(), file: file - 1, line });
+            }
+            Line::Bytes(mut data) => bytes.append(&mut data),
+            Line::Align(power) => bytes.resize(align(bytes.len(), power), 0x00),
+            Line::Instruction(instruction) => {
+                let instruction = parse_instruction(instruction, &labels, &literal_addresses, bytes.len())?;
+                bytes.extend_from_slice(&super::bytecode::convert_instruction(instruction).to_le_bytes());
+            }
+        }
+    }
+    if !literals.is_empty() {
+        bytes.resize(pool, 0x00);
+        for value in literals {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let entry_point = match global {
+        Some(label) => *labels.get(&label).ok_or(anyhow::Error::msg(format!(
+            "The global label `{label}` is never defined."
+        )))?,
+        None => 0
+    };
+    Ok(MachineCode { bytes, entry_point, symbols, files, lines: source_lines })
+}
+
+pub fn line_to_intermediate(line: &str) -> Instruction {
+    parse_instruction(line.trim(), &HashMap::new(), &HashMap::new(), 0).unwrap()
+}
+
+/// The value `instruction` loads from the literal pool, if it's an
+/// `ldr [register], =[value]`.
+fn literal(instruction: &str) -> Result<Option<i64>, anyhow::Error> {
+    let (opcode, args) = instruction.split_once(' ').unwrap_or((instruction, ""));
+    if !opcode.eq_ignore_ascii_case("ldr") {
+        return Ok(None);
+    }
+    match parse_operands(args)?.as_slice() {
+        [Operand::Register(_), Operand::Literal(value)] => Ok(Some(*value)),
+        _ => Ok(None)
+    }
+}
+
+/// The meaningful lines of an assembly file, without comments.
+fn lines(source: &str) -> impl Iterator<Item = &str> {
+    source.lines()
+        .map(|line| line.split_once("//").map(|(code, _comment)| code).unwrap_or(line).trim())
+        .filter(|line| !line.is_empty())
+}
+
+enum Line<'a> {
+    Label(&'a str),
+    Global(&'a str),
+    /// `.type main, %function` or `.type message, %object`
+    Type(&'a str, SymbolKind),
+    /// `.file 1 "main.beach"`
+    File(usize, String),
+    /// `.loc 1 12`, which marks the source file and line that the following
+    /// instructions come from.
+    Location(usize, usize),
+    Bytes(Vec<u8>),
+    /// Pads to the next multiple of 2 to the power of this value.
+    Align(u32),
+    Instruction(&'a str),
+}
+
+fn classify(line: &str) -> Result<Line<'_>, anyhow::Error> {
+    if let Some(label) = line.strip_suffix(':') {
+        return Ok(Line::Label(label));
+    }
+    if let Some(label) = line.strip_prefix(".global ") {
+        return Ok(Line::Global(label.trim()));
+    }
+    if let Some(kind) = line.strip_prefix(".type ") {
+        let (label, kind) = kind.split_once(',').unwrap_or((kind, ""));
+        let kind = match kind.trim() {
+            "%function" | "@function" => SymbolKind::Function,
+            "%object" | "@object" => SymbolKind::Data,
+            _ => return Err(anyhow::Error::msg(format!("Expected `%function` or `%object` in `{line}`.")))
+        };
+        return Ok(Line::Type(label.trim(), kind));
+    }
+    if let Some(data) = line.strip_prefix(".byte ") {
+        let mut bytes = vec![];
+        for byte in data.split(',') {
+            let value = parse_number(byte.trim())?;
+            bytes.push(u8::try_from(value).map_err(|_| anyhow::Error::msg(format!(
+                "`{}` doesn't fit in a byte.", byte.trim()
+            )))?);
+        }
+        return Ok(Line::Bytes(bytes));
+    }
+    if let Some(file) = line.strip_prefix(".file ") {
+        let (index, path) = file.trim().split_once(' ').unwrap_or((file, ""));
+        let path = path.trim()
+            .strip_prefix('"')
+            .and_then(|path| path.strip_suffix('"'))
+            .ok_or(anyhow::Error::msg(format!("Expected a quoted path in `{line}`.")))?;
+        // `\"` and `\\` stand for `"` and `\`.
+        let mut unescaped = String::new();
+        let mut characters = path.chars();
+        while let Some(character) = characters.next() {
+            match character {
+                '\\' => unescaped.extend(characters.next()),
+                other => unescaped.push(other),
             }
         }
-        _ => todo!()
+        return Ok(Line::File(parse_number(index)? as usize, unescaped));
+    }
+    if let Some(location) = line.strip_prefix(".loc ") {
+        let mut numbers = location.split_whitespace();
+        let (Some(file), Some(source_line)) = (numbers.next(), numbers.next()) else {
+            return Err(anyhow::Error::msg(format!("Expected a file and line number in `{line}`.")));
+        };
+        return Ok(Line::Location(parse_number(file)? as usize, parse_number(source_line)? as usize));
     }
+    if let Some(power) = line.strip_prefix(".align ") {
+        return Ok(Line::Align(parse_number(power.trim())? as u32));
+    }
+    if line.starts_with('.') {
+        return Err(anyhow::Error::msg(format!("Unknown assembler directive `{line}`.")));
+    }
+    Ok(Line::Instruction(line))
+}
+
+fn align(offset: usize, power: u32) -> usize {
+    offset.next_multiple_of(1 << power)
+}
+
+/// A single operand of an instruction.
+#[derive(Debug)]
+enum Operand {
+    Register(Register),
+    Immediate(i64),
+    Label(String),
+    /// `[base, #offset]`, with `!` marking that the base register is updated.
+    Memory { base: Register, offset: i64, writeback: bool },
+    /// `lsl #amount`
+    Shift(u8),
+    /// `=value`, a value in the literal pool.
+    Literal(i64),
+}
+
+fn parse_instruction(line: &str, labels: &HashMap<String, usize>, literals: &HashMap<i64, usize>, location: usize) -> Result<Instruction, anyhow::Error> {
+    let (opcode, args) = line
+        .split_once(' ')
+        .unwrap_or((line, ""));
+    let operands = parse_operands(args)?;
+    let invalid = || anyhow::Error::msg(format!("Invalid operands in `{line}`."));
+    // Offsets to labels are relative to this instruction.
+    let relative = |operand: &Operand| -> Result<u32, anyhow::Error> {
+        match operand {
+            Operand::Immediate(value) => Ok(*value as u32),
+            Operand::Label(label) => {
+                let target = labels.get(label).ok_or(anyhow::Error::msg(format!(
+                    "The label `{label}` is never defined."
+                )))?;
+                Ok((*target as i64 - location as i64) as u32)
+            }
+            _ => Err(invalid())
+        }
+    };
+    let immediate_u16 = |operand: &Operand| -> Result<u16, anyhow::Error> {
+        match operand {
+            Operand::Immediate(value) => u16::try_from(*value).map_err(|_| invalid()),
+            _ => Err(invalid())
+        }
+    };
+
+    match (opcode.to_lowercase().as_str(), operands.as_slice()) {
+        ("b", [target]) => Ok(Instruction::B { offset: relative(target)? }),
+        ("bl", [target]) => Ok(Instruction::BL { offset: relative(target)? }),
+        ("cbz", [Operand::Register(register), target]) => {
+            Ok(Instruction::CBZ { register: *register, offset: relative(target)? })
+        }
+        ("br", [Operand::Register(target)]) => Ok(Instruction::BR { target: *target }),
+        ("blr", [Operand::Register(target)]) => Ok(Instruction::BLR { target: *target }),
+        ("ret", []) => Ok(Instruction::RET { target: Register::R30 }),
+        ("ret", [Operand::Register(target)]) => Ok(Instruction::RET { target: *target }),
+        ("mov", [Operand::Register(destination), Operand::Register(source)]) => {
+            Ok(Instruction::MOV { destination: *destination, source: *source })
+        }
+        ("mov" | "movz", [Operand::Register(destination), value]) => {
+            Ok(Instruction::MOVZ { destination: *destination, value: immediate_u16(value)?, shift: 0, half: false })
+        }
+        ("movz", [Operand::Register(destination), value, Operand::Shift(shift)]) => {
+            Ok(Instruction::MOVZ { destination: *destination, value: immediate_u16(value)?, shift: *shift, half: false })
+        }
+        ("movk", [Operand::Register(destination), value]) => {
+            Ok(Instruction::MOVK { destination: *destination, value: immediate_u16(value)?, shift: 0 })
+        }
+        ("movk", [Operand::Register(destination), value, Operand::Shift(shift)]) => {
+            Ok(Instruction::MOVK { destination: *destination, value: immediate_u16(value)?, shift: *shift })
+        }
+        ("adr", [Operand::Register(destination), target]) => {
+            Ok(Instruction::ADR { destination: *destination, offset: relative(target)? })
+        }
+        (
+            "stp",
+            [Operand::Register(first), Operand::Register(second), Operand::Memory { base, offset, writeback: true }]
+        ) => {
+            Ok(Instruction::STPPreIndex { first: *first, second: *second, base: *base, offset: *offset as i16 })
+        }
+        (
+            "ldp",
+            [Operand::Register(first), Operand::Register(second), Operand::Memory { base, offset: 0, writeback: false }, Operand::Immediate(offset)]
+        ) => {
+            Ok(Instruction::LDPPostIndex { first: *first, second: *second, base: *base, offset: *offset as i16 })
+        }
+        ("str", [Operand::Register(source), Operand::Memory { base, offset, writeback: true }]) => {
+            Ok(Instruction::STRPreIndex { source: *source, base: *base, offset: *offset as i16 })
+        }
+        (
+            "ldr",
+            [Operand::Register(destination), Operand::Memory { base, offset: 0, writeback: false }, Operand::Immediate(offset)]
+        ) => {
+            Ok(Instruction::LDRPostIndex { destination: *destination, base: *base, offset: *offset as i16 })
+        }
+        ("str", [Operand::Register(source), Operand::Memory { base, offset, writeback: false }]) => {
+            let offset = u16::try_from(*offset).map_err(|_| invalid())?;
+            Ok(Instruction::STROffset { source: *source, base: *base, offset })
+        }
+        ("ldr", [Operand::Register(destination), Operand::Literal(value)]) => {
+            let target = literals.get(value).ok_or(anyhow::Error::msg(format!(
+                "`{value}` isn't in the literal pool."
+            )))?;
+            Ok(Instruction::LDRLiteral { destination: *destination, offset: (*target as i64 - location as i64) as u32 })
+        }
+        ("ldr", [Operand::Register(destination), Operand::Memory { base, offset, writeback: false }]) => {
+            let offset = u16::try_from(*offset).map_err(|_| invalid())?;
+            Ok(Instruction::LDROffset { destination: *destination, base: *base, offset })
+        }
+        ("add", [Operand::Register(destination), Operand::Register(first), Operand::Register(second)]) => {
+            Ok(Instruction::ADDRegister { destination: *destination, first: *first, second: *second })
+        }
+        ("add", [Operand::Register(destination), Operand::Register(source), value]) => {
+            Ok(Instruction::ADDImmediate { destination: *destination, source: *source, value: immediate_u16(value)? })
+        }
+        ("sub", [Operand::Register(destination), Operand::Register(first), Operand::Register(second)]) => {
+            Ok(Instruction::SUBRegister { destination: *destination, first: *first, second: *second })
+        }
+        ("mul", [Operand::Register(destination), Operand::Register(first), Operand::Register(second)]) => {
+            Ok(Instruction::MUL { destination: *destination, first: *first, second: *second })
+        }
+        ("sub", [Operand::Register(destination), Operand::Register(source), value]) => {
+            Ok(Instruction::SUBImmediate { destination: *destination, source: *source, value: immediate_u16(value)? })
+        }
+        ("svc", [immediate]) => Ok(Instruction::SVC { immediate: immediate_u16(immediate)? }),
+        ("brk", [immediate]) => Ok(Instruction::BRK { immediate: immediate_u16(immediate)? }),
+        _ => Err(anyhow::Error::msg(format!("Unknown or unsupported instruction `{line}`.")))
+    }
+}
+
+fn parse_operands(args: &str) -> Result<Vec<Operand>, anyhow::Error> {
+    // Commas inside brackets belong to a single memory operand.
+    let mut pieces = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    for character in args.chars() {
+        match character {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                pieces.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(character);
+    }
+    if !current.trim().is_empty() {
+        pieces.push(current);
+    }
+
+    let mut operands = vec![];
+    for piece in pieces {
+        let piece = piece.trim();
+        if let Some(memory) = piece.strip_prefix('[') {
+            let (inside, after) = memory.split_once(']')
+                .ok_or(anyhow::Error::msg(format!("Unclosed `[` in `{args}`.")))?;
+            let (base, offset) = inside.split_once(',').unwrap_or((inside, "#0"));
+            let base = Register::from_name(base.trim())
+                .ok_or(anyhow::Error::msg(format!("Unknown register `{}`.", base.trim())))?;
+            operands.push(Operand::Memory {
+                base,
+                offset: parse_number(offset.trim())?,
+                writeback: after.trim() == "!"
+            });
+        }
+        else if let Some(value) = piece.strip_prefix('=') {
+            operands.push(Operand::Literal(parse_number(value.trim())?));
+        }
+        else if let Some(shift) = piece.strip_prefix("lsl ") {
+            operands.push(Operand::Shift(parse_number(shift.trim())? as u8));
+        }
+        else if let Some(register) = Register::from_name(piece) {
+            operands.push(Operand::Register(register));
+        }
+        else if piece.starts_with('#') || piece.starts_with(|character: char| character.is_ascii_digit() || character == '-') {
+            operands.push(Operand::Immediate(parse_number(piece)?));
+        }
+        else {
+            operands.push(Operand::Label(piece.to_string()));
+        }
+    }
+    Ok(operands)
+}
+
+/// Reads a number such as `#-16`, `#0x3e94` or `12`.
+fn parse_number(text: &str) -> Result<i64, anyhow::Error> {
+    let text = text.trim_start_matches('#');
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(positive) => (true, positive),
+        None => (false, text)
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        // Hexadecimal can set the sign bit, as with a 64 bit literal.
+        u64::from_str_radix(hex, 16).map(|value| value as i64)
+    }
+    else {
+        text.parse()
+    };
+    let value = value.map_err(|_| anyhow::Error::msg(format!("Unable to parse the number `{text}`.")))?;
+    Ok(if negative { -value } else { value })
 }
 
 #[test]
@@ -44,7 +423,6 @@ fn test_branch_instructions() {
     );
     assert_eq!(
         super::bytecode::convert_instruction(
-            // TODO: This fails bc HEX SCARRY AHAHAGHHAGHA
             line_to_intermediate("b #0x3e94")
         ),
         0x14000FA5
@@ -55,4 +433,57 @@ fn test_branch_instructions() {
         ),
         0x14000005
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_assemble() {
+    // Encodings checked against `llvm-mc -triple=aarch64 -show-encoding`.
+    let code = assemble("\
+.global _start
+.type message, %object
+message:
+.byte 0x48, 0x69
+.align 2
+.type _start, %function
+_start:
+stp x29, x30, [sp, #-16]!
+mov x29, sp
+adr x1, message
+movz x2, #2
+movk x2, #1, lsl #16
+str x0, [sp, #-16]!
+ldr x0, [sp], #16
+ldp x29, x30, [sp], #16
+mov x16, x2
+svc #0
+brk #1
+blr x9
+b _start
+ret
+bl _start
+sub sp, sp, #32
+str x19, [sp, #16]
+ldr x20, [sp, #8]
+add sp, sp, #32
+add x9, x10, x11
+cbz x12, _start
+sub x9, x10, x11
+mul x0, x1, x2
+").unwrap();
+    assert_eq!(code.entry_point, 4);
+    let words: Vec<u32> = code.bytes[4..].chunks(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    assert_eq!(words, vec![
+        0xA9BF7BFD, 0x910003FD, 0x10FFFFA1, 0xD2800042, 0xF2A00022,
+        0xF81F0FE0, 0xF84107E0, 0xA8C17BFD, 0xAA0203F0, 0xD4000001,
+        0xD4200020, 0xD63F0120, 0x17FFFFF4, 0xD65F03C0,
+        0x97FFFFF2, 0xD10083FF, 0xF9000BF3, 0xF94007F4, 0x910083FF,
+        0x8B0B0149, 0xB4FFFD8C, 0xCB0B0149, 0x9B027C20,
+    ]);
+    // Symbols don't include the padding after them.
+    assert_eq!(code.symbols, vec![
+        Symbol { name: String::from("message"), offset: 0, size: 2, kind: SymbolKind::Data, global: false },
+        Symbol { name: String::from("_start"), offset: 4, size: 92, kind: SymbolKind::Function, global: true },
+    ]);
+}
@@ -9,11 +9,6 @@ pub fn convert_instruction(instruction: Instruction) -> u32 {
     match instruction {
         Instruction::B { offset } => {
             let mut ins: u32 = 0b0001_01 << 26;
-            assert_eq!(
-                offset,
-                offset & mask(28),
-                "Branch instruction (B) contained offset larger than 28 bits."
-            );
             assert_eq!(
                 offset,
                 (offset >> 2) << 2,
@@ -25,16 +20,16 @@ pub fn convert_instruction(instruction: Instruction) -> u32 {
         Instruction::MOVZ { destination, value, shift, half } => {
             let mut ins: u32 = 0;
             if half {
-                // mark as half width register
-                ins |= 0b1 << 31;
                 assert!(
-                    !(shift > 16) || !(shift % 16 == 0),
+                    shift == 0 || shift == 16,
                     "Move and zero instruction (MOVZ) contained invalid shift."
                 );
             }
             else {
+                // mark as full width register
+                ins |= 0b1 << 31;
                 assert!(
-                    !(shift % 16 == 0) || (shift > 48),
+                    shift % 16 == 0 && shift <= 48,
                     "Move and zero instruction (MOVZ) contained invalid shift."
                 );
             }
@@ -49,12 +44,290 @@ pub fn convert_instruction(instruction: Instruction) -> u32 {
 
             return ins;
         }
-        Instruction::BDotXX { condition, offset } => {
-            let mut ins = 0;
-
-            todo!();
+        Instruction::MOVK { destination, value, shift } => {
+            assert!(
+                shift % 16 == 0 && shift <= 48,
+                "Move and keep instruction (MOVK) contained invalid shift."
+            );
+            let mut ins: u32 = 0b1_11_100101 << 23;
+            ins |= ((shift / 16) as u32) << 21;
+            ins |= (value as u32) << 5;
+            ins |= destination.to_5_bits() as u32;
+            return ins;
+        }
+        Instruction::MOV { destination, source } => {
+            if destination == Register::SP || source == Register::SP {
+                // `add destination, source, #0`, as `orr` can't read the stack
+                // pointer.
+                let mut ins: u32 = 0b1_0_0_100010 << 23;
+                ins |= (source.to_5_bits() as u32) << 5;
+                ins |= destination.to_5_bits() as u32;
+                return ins;
+            }
+            // `orr destination, xzr, source`
+            let mut ins: u32 = 0b1_01_01010 << 24;
+            ins |= (source.to_5_bits() as u32) << 16;
+            ins |= (Register::ZR.to_5_bits() as u32) << 5;
+            ins |= destination.to_5_bits() as u32;
+            return ins;
+        }
+        Instruction::ADR { destination, offset } => {
+            let mut ins: u32 = 0b0_00_10000 << 24;
+            // low two bits of the offset
+            ins |= (offset & 0b11) << 29;
+            // high 19 bits of the offset
+            ins |= ((offset >> 2) & mask(19)) << 5;
+            ins |= destination.to_5_bits() as u32;
             return ins;
         }
+        Instruction::BL { offset } => {
+            assert_eq!(
+                offset,
+                (offset >> 2) << 2,
+                "Branch with link instruction (BL) contained misaligned offset."
+            );
+            return 0x94000000 | (offset & mask(28)) >> 2;
+        }
+        Instruction::CBZ { register, offset } => {
+            assert_eq!(
+                offset,
+                (offset >> 2) << 2,
+                "Compare and branch instruction (CBZ) contained misaligned offset."
+            );
+            return 0xB4000000 | ((offset >> 2) & mask(19)) << 5 | register.to_5_bits() as u32;
+        }
+        Instruction::BR { target } => {
+            return 0xD61F0000 | (target.to_5_bits() as u32) << 5;
+        }
+        Instruction::BLR { target } => {
+            return 0xD63F0000 | (target.to_5_bits() as u32) << 5;
+        }
+        Instruction::RET { target } => {
+            return 0xD65F0000 | (target.to_5_bits() as u32) << 5;
+        }
+        Instruction::STPPreIndex { first, second, base, offset } => {
+            return 0xA9800000 | pair_operands(first, second, base, offset);
+        }
+        Instruction::LDPPostIndex { first, second, base, offset } => {
+            return 0xA8C00000 | pair_operands(first, second, base, offset);
+        }
+        Instruction::STRPreIndex { source, base, offset } => {
+            return 0xF8000C00 | indexed_operands(source, base, offset);
+        }
+        Instruction::LDRPostIndex { destination, base, offset } => {
+            return 0xF8400400 | indexed_operands(destination, base, offset);
+        }
+        Instruction::LDRLiteral { destination, offset } => {
+            assert_eq!(
+                offset,
+                (offset >> 2) << 2,
+                "Literal load instruction (LDR) contained misaligned offset."
+            );
+            return 0x58000000 | ((offset >> 2) & mask(19)) << 5 | destination.to_5_bits() as u32;
+        }
+        Instruction::STROffset { source, base, offset } => {
+            return 0xF9000000 | offset_operands(source, base, offset);
+        }
+        Instruction::LDROffset { destination, base, offset } => {
+            return 0xF9400000 | offset_operands(destination, base, offset);
+        }
+        Instruction::ADDImmediate { destination, source, value } => {
+            return 0x91000000 | immediate_operands(destination, source, value);
+        }
+        Instruction::SUBImmediate { destination, source, value } => {
+            return 0xD1000000 | immediate_operands(destination, source, value);
+        }
+        Instruction::ADDRegister { destination, first, second } => {
+            return 0x8B000000 | register_operands(destination, first, second);
+        }
+        Instruction::SUBRegister { destination, first, second } => {
+            return 0xCB000000 | register_operands(destination, first, second);
+        }
+        Instruction::MUL { destination, first, second } => {
+            // `madd destination, first, second, xzr`
+            return 0x9B007C00 | register_operands(destination, first, second);
+        }
+        Instruction::SVC { immediate } => {
+            return 0xD4000001 | (immediate as u32) << 5;
+        }
+        Instruction::BRK { immediate } => {
+            return 0xD4200000 | (immediate as u32) << 5;
+        }
         ins => todo!("ins not finished {:?}", ins)
     }
 }
+
+/// The instruction encoded by `word`, the reverse of [convert_instruction].
+/// Returns [None] for encodings [Instruction] can't represent.
+///
+/// `mov` to or from the stack pointer is encoded as `add`, so it's read back as
+/// [Instruction::ADDImmediate].
+pub fn decode_instruction(word: u32) -> Option<Instruction> {
+    // Register 31 is the stack pointer in some operands, and the zero
+    // register in the rest.
+    let register = |shift: u32| register_from_bits(word >> shift, false);
+    let stack_register = |shift: u32| register_from_bits(word >> shift, true);
+    let instruction = match word {
+        _ if word & 0xFC000000 == 0x14000000 => Instruction::B { offset: (word & mask(26)) << 2 },
+        _ if word & 0xFC000000 == 0x94000000 => Instruction::BL { offset: (word & mask(26)) << 2 },
+        _ if word & 0xFF000000 == 0xB4000000 => Instruction::CBZ {
+            register: register(0),
+            offset: ((word >> 5) & mask(19)) << 2
+        },
+        _ if word & 0x7F800000 == 0x52800000 => Instruction::MOVZ {
+            destination: register(0),
+            value: (word >> 5) as u16,
+            shift: ((word >> 21) & 0b11) as u8 * 16,
+            half: word >> 31 == 0
+        },
+        _ if word & 0xFF800000 == 0xF2800000 => Instruction::MOVK {
+            destination: register(0),
+            value: (word >> 5) as u16,
+            shift: ((word >> 21) & 0b11) as u8 * 16
+        },
+        // `orr destination, xzr, source`
+        _ if word & 0xFFE0FFE0 == 0xAA0003E0 => Instruction::MOV { destination: register(0), source: register(16) },
+        _ if word & 0x9F000000 == 0x10000000 => Instruction::ADR {
+            destination: register(0),
+            offset: ((word >> 29) & 0b11) | ((word >> 5) & mask(19)) << 2
+        },
+        _ if word & 0xFFFFFC1F == 0xD61F0000 => Instruction::BR { target: register(5) },
+        _ if word & 0xFFFFFC1F == 0xD63F0000 => Instruction::BLR { target: register(5) },
+        _ if word & 0xFFFFFC1F == 0xD65F0000 => Instruction::RET { target: register(5) },
+        _ if word & 0xFFC00000 == 0xA9800000 => Instruction::STPPreIndex {
+            first: register(0),
+            second: register(10),
+            base: stack_register(5),
+            offset: sign_extend(word >> 15, 7) as i16 * 8
+        },
+        _ if word & 0xFFC00000 == 0xA8C00000 => Instruction::LDPPostIndex {
+            first: register(0),
+            second: register(10),
+            base: stack_register(5),
+            offset: sign_extend(word >> 15, 7) as i16 * 8
+        },
+        _ if word & 0xFFE00C00 == 0xF8000C00 => Instruction::STRPreIndex {
+            source: register(0),
+            base: stack_register(5),
+            offset: sign_extend(word >> 12, 9) as i16
+        },
+        _ if word & 0xFFE00C00 == 0xF8400400 => Instruction::LDRPostIndex {
+            destination: register(0),
+            base: stack_register(5),
+            offset: sign_extend(word >> 12, 9) as i16
+        },
+        _ if word & 0xFF000000 == 0x58000000 => Instruction::LDRLiteral {
+            destination: register(0),
+            offset: ((word >> 5) & mask(19)) << 2
+        },
+        _ if word & 0xFFC00000 == 0xF9000000 => Instruction::STROffset {
+            source: register(0),
+            base: stack_register(5),
+            offset: ((word >> 10) & mask(12)) as u16 * 8
+        },
+        _ if word & 0xFFC00000 == 0xF9400000 => Instruction::LDROffset {
+            destination: register(0),
+            base: stack_register(5),
+            offset: ((word >> 10) & mask(12)) as u16 * 8
+        },
+        _ if word & 0xFFC00000 == 0x91000000 => Instruction::ADDImmediate {
+            destination: stack_register(0),
+            source: stack_register(5),
+            value: ((word >> 10) & mask(12)) as u16
+        },
+        _ if word & 0xFFC00000 == 0xD1000000 => Instruction::SUBImmediate {
+            destination: stack_register(0),
+            source: stack_register(5),
+            value: ((word >> 10) & mask(12)) as u16
+        },
+        _ if word & 0xFFE0FC00 == 0x8B000000 => Instruction::ADDRegister {
+            destination: register(0),
+            first: register(5),
+            second: register(16)
+        },
+        _ if word & 0xFFE0FC00 == 0xCB000000 => Instruction::SUBRegister {
+            destination: register(0),
+            first: register(5),
+            second: register(16)
+        },
+        _ if word & 0xFFE0FC00 == 0x9B007C00 => Instruction::MUL {
+            destination: register(0),
+            first: register(5),
+            second: register(16)
+        },
+        _ if word & 0xFFE0001F == 0xD4000001 => Instruction::SVC { immediate: (word >> 5) as u16 },
+        _ if word & 0xFFE0001F == 0xD4200000 => Instruction::BRK { immediate: (word >> 5) as u16 },
+        _ => return None
+    };
+    return Some(instruction);
+}
+
+/// The register numbered by the low 5 bits of `bits`. 31 is the stack pointer
+/// if `stack`, and the zero register otherwise.
+fn register_from_bits(bits: u32, stack: bool) -> Register {
+    match bits & mask(5) {
+        31 if stack => Register::SP,
+        31 => Register::ZR,
+        number => Register::NUMBERED[number as usize]
+    }
+}
+
+/// Reads the low `bits` bits of `value` as a signed number.
+fn sign_extend(value: u32, bits: u8) -> i32 {
+    ((value << (32 - bits)) as i32) >> (32 - bits)
+}
+
+/// Encodes the operands of `stp` and `ldp`, which scale their offset by 8.
+fn pair_operands(first: Register, second: Register, base: Register, offset: i16) -> u32 {
+    assert!(
+        offset % 8 == 0 && (-512..=504).contains(&offset),
+        "Register pair instruction (STP/LDP) contained invalid offset."
+    );
+    let mut ins = ((offset / 8) as u32 & mask(7)) << 15;
+    ins |= (second.to_5_bits() as u32) << 10;
+    ins |= (base.to_5_bits() as u32) << 5;
+    ins |= first.to_5_bits() as u32;
+    return ins;
+}
+
+/// Encodes the operands of `str` and `ldr` when they move their base register.
+fn indexed_operands(register: Register, base: Register, offset: i16) -> u32 {
+    assert!(
+        (-256..=255).contains(&offset),
+        "Indexed load or store instruction (STR/LDR) contained invalid offset."
+    );
+    let mut ins = (offset as u32 & mask(9)) << 12;
+    ins |= (base.to_5_bits() as u32) << 5;
+    ins |= register.to_5_bits() as u32;
+    return ins;
+}
+
+/// Encodes the operands of `str` and `ldr` with an unsigned offset, which is
+/// scaled by 8.
+fn offset_operands(register: Register, base: Register, offset: u16) -> u32 {
+    assert!(
+        offset.is_multiple_of(8) && offset <= 32760,
+        "Load or store instruction (STR/LDR) contained invalid offset."
+    );
+    let mut ins = ((offset / 8) as u32) << 10;
+    ins |= (base.to_5_bits() as u32) << 5;
+    ins |= register.to_5_bits() as u32;
+    return ins;
+}
+
+/// Encodes the operands of `add`, `sub` and `mul` on three registers.
+fn register_operands(destination: Register, first: Register, second: Register) -> u32 {
+    let mut ins = (second.to_5_bits() as u32) << 16;
+    ins |= (first.to_5_bits() as u32) << 5;
+    ins |= destination.to_5_bits() as u32;
+    return ins;
+}
+
+/// Encodes the operands of `add` and `sub` with an immediate value.
+fn immediate_operands(destination: Register, source: Register, value: u16) -> u32 {
+    assert!(value < 4096, "Immediate arithmetic instruction (ADD/SUB) contained invalid value.");
+    let mut ins = (value as u32) << 10;
+    ins |= (source.to_5_bits() as u32) << 5;
+    ins |= destination.to_5_bits() as u32;
+    return ins;
+}
@@ -0,0 +1,347 @@
+use std::io::Write;
+
+use super::bytecode::decode_instruction;
+use super::intermediate::*;
+use crate::generator::generic::MachineCode;
+use crate::platform::generic::SyscallConvention;
+
+/// Where the machine code is loaded. Nothing is mapped below it, so null
+/// pointers can't be read.
+const LOAD_ADDRESS: u64 = 0x10_0000;
+/// The stack grows down from here.
+const STACK_TOP: u64 = 0x7FFF_0000;
+const STACK_SIZE: u64 = 1024 * 1024;
+/// How many instructions can run before the program is assumed to be stuck.
+const MAX_STEPS: usize = 10_000_000;
+
+/// Runs `code` as a user mode AArch64 program, returning its exit code.
+///
+/// Only the instructions in [Instruction] are understood. The only system
+/// calls provided are the `write` and `exit` calls of `convention`, writing
+/// file descriptors `1` and `2` to `stdout` and `stderr`. Like Unix, exit
+/// codes are cut down to a byte.
+pub fn emulate(
+    code: &MachineCode,
+    convention: &SyscallConvention,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write
+) -> Result<i32, anyhow::Error> {
+    let mut emulator = Emulator {
+        registers: [0; 31],
+        stack_pointer: STACK_TOP,
+        program_counter: LOAD_ADDRESS + code.entry_point as u64,
+        image: code.bytes.clone(),
+        stack: vec![0; STACK_SIZE as usize],
+    };
+    let number_register = register_index(convention.number_register);
+    let argument_registers: Vec<usize> = convention.argument_registers.iter()
+        .map(|name| register_index(name))
+        .collect();
+    for _ in 0..MAX_STEPS {
+        let address = emulator.program_counter;
+        let error = |e: String| anyhow::Error::msg(format!("{}: {e}", describe(code, address)));
+        let word = emulator.read(address, 4).map_err(error)?;
+        let word = u32::from_le_bytes(word.try_into().unwrap());
+        let instruction = decode_instruction(word)
+            .ok_or_else(|| error(format!("0x{word:08X} isn't an instruction the emulator knows.")))?;
+        match instruction {
+            Instruction::SVC { .. } => {
+                let arguments: Vec<u64> = argument_registers.iter().map(|index| emulator.registers[*index]).collect();
+                let argument = |index: usize| arguments[index];
+                let number = emulator.registers[number_register];
+                if number == convention.write {
+                    let bytes = emulator.read(argument(1), argument(2)).map_err(error)?.to_vec();
+                    let output: &mut dyn Write = match argument(0) {
+                        1 => &mut *stdout,
+                        2 => &mut *stderr,
+                        descriptor => return Err(error(format!("file descriptor {descriptor} can't be written to."))),
+                    };
+                    output.write_all(&bytes)
+                        .and_then(|()| output.flush())
+                        .map_err(|e| error(format!("writing failed: {e}")))?;
+                    // `write` returns how much it wrote.
+                    emulator.registers[argument_registers[0]] = bytes.len() as u64;
+                }
+                else if number == convention.exit {
+                    return Ok((argument(0) & 0xff) as i32);
+                }
+                else {
+                    return Err(error(format!("system call {number} isn't provided by the emulator.")));
+                }
+                emulator.program_counter += 4;
+            }
+            Instruction::BRK { immediate } => {
+                return Err(error(format!("hit a breakpoint. (`brk #{immediate}`)")));
+            }
+            instruction => emulator.step(instruction).map_err(error)?
+        }
+    }
+    Err(anyhow::Error::msg(format!("The program didn't finish within {MAX_STEPS} instructions.")))
+}
+
+/// The index of the general purpose register called `name`.
+fn register_index(name: &str) -> usize {
+    let register = Register::from_name(name).expect("system call conventions name real registers");
+    Register::NUMBERED.iter().position(|numbered| *numbered == register).expect("system calls use x0 to x30")
+}
+
+/// Names where `address` is, for errors, using the closest label before it.
+fn describe(code: &MachineCode, address: u64) -> String {
+    let offset = address.wrapping_sub(LOAD_ADDRESS);
+    let symbol = code.symbols.iter()
+        .filter(|symbol| symbol.offset as u64 <= offset)
+        .max_by_key(|symbol| symbol.offset);
+    match symbol {
+        Some(symbol) if offset < code.bytes.len() as u64 => {
+            format!("0x{offset:X} ({}+0x{:X})", symbol.name, offset - symbol.offset as u64)
+        }
+        _ => format!("0x{offset:X}")
+    }
+}
+
+struct Emulator {
+    /// x0 to x30.
+    registers: [u64; 31],
+    stack_pointer: u64,
+    program_counter: u64,
+    /// The machine code and its data, loaded at [LOAD_ADDRESS].
+    image: Vec<u8>,
+    /// The [STACK_SIZE] bytes below [STACK_TOP].
+    stack: Vec<u8>,
+}
+
+impl Emulator {
+    fn get(&self, register: Register) -> u64 {
+        match register {
+            Register::ZR => 0,
+            Register::SP => self.stack_pointer,
+            Register::PC => self.program_counter,
+            numbered => self.registers[numbered.to_5_bits() as usize]
+        }
+    }
+    fn set(&mut self, register: Register, value: u64) {
+        match register {
+            Register::ZR => {}
+            Register::SP => self.stack_pointer = value,
+            Register::PC => self.program_counter = value,
+            numbered => self.registers[numbered.to_5_bits() as usize] = value
+        }
+    }
+    /// The memory `address` is in, and where it starts.
+    fn region(&mut self, address: u64, length: u64) -> Result<&mut [u8], String> {
+        let invalid = || format!("{length} bytes at 0x{address:X} aren't mapped.");
+        let end = address.checked_add(length).ok_or_else(invalid)?;
+        let (memory, start) = match address {
+            LOAD_ADDRESS.. if end <= LOAD_ADDRESS + self.image.len() as u64 => (&mut self.image, LOAD_ADDRESS),
+            _ if address >= STACK_TOP - STACK_SIZE && end <= STACK_TOP => (&mut self.stack, STACK_TOP - STACK_SIZE),
+            _ => return Err(invalid())
+        };
+        Ok(&mut memory[(address - start) as usize..(end - start) as usize])
+    }
+    fn read(&mut self, address: u64, length: u64) -> Result<&[u8], String> {
+        self.region(address, length).map(|memory| &*memory)
+    }
+    fn load(&mut self, address: u64) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.read(address, 8)?.try_into().unwrap()))
+    }
+    fn store(&mut self, address: u64, value: u64) -> Result<(), String> {
+        self.region(address, 8)?.copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+    /// Runs `instruction`, which isn't a system call or breakpoint.
+    fn step(&mut self, instruction: Instruction) -> Result<(), String> {
+        let pc = self.program_counter;
+        // Offsets are signed, and only use some of their bits.
+        let relative = |offset: u32, bits: u8| pc.wrapping_add(sign_extend(offset, bits) as u64);
+        let mut next = pc + 4;
+        match instruction {
+            Instruction::B { offset } => next = relative(offset, 28),
+            Instruction::BL { offset } => {
+                self.set(Register::R30, pc + 4);
+                next = relative(offset, 28);
+            }
+            Instruction::CBZ { register, offset } => {
+                if self.get(register) == 0 {
+                    next = relative(offset, 21);
+                }
+            }
+            Instruction::BR { target } => next = self.get(target),
+            Instruction::BLR { target } => {
+                next = self.get(target);
+                self.set(Register::R30, pc + 4);
+            }
+            Instruction::RET { target } => next = self.get(target),
+            Instruction::MOV { destination, source } => self.set(destination, self.get(source)),
+            Instruction::MOVZ { destination, value, shift, .. } => {
+                // 32 bit registers zero the top half anyway.
+                self.set(destination, (value as u64) << shift);
+            }
+            Instruction::MOVK { destination, value, shift } => {
+                let kept = self.get(destination) & !(0xFFFF << shift);
+                self.set(destination, kept | (value as u64) << shift);
+            }
+            Instruction::ADR { destination, offset } => self.set(destination, relative(offset, 21)),
+            Instruction::STPPreIndex { first, second, base, offset } => {
+                let address = self.get(base).wrapping_add(offset as u64);
+                self.store(address, self.get(first))?;
+                self.store(address + 8, self.get(second))?;
+                self.set(base, address);
+            }
+            Instruction::LDPPostIndex { first, second, base, offset } => {
+                let address = self.get(base);
+                let (a, b) = (self.load(address)?, self.load(address + 8)?);
+                self.set(first, a);
+                self.set(second, b);
+                self.set(base, address.wrapping_add(offset as u64));
+            }
+            Instruction::STRPreIndex { source, base, offset } => {
+                let address = self.get(base).wrapping_add(offset as u64);
+                self.store(address, self.get(source))?;
+                self.set(base, address);
+            }
+            Instruction::LDRPostIndex { destination, base, offset } => {
+                let address = self.get(base);
+                let value = self.load(address)?;
+                self.set(destination, value);
+                self.set(base, address.wrapping_add(offset as u64));
+            }
+            Instruction::LDRLiteral { destination, offset } => {
+                let value = self.load(relative(offset, 21))?;
+                self.set(destination, value);
+            }
+            Instruction::STROffset { source, base, offset } => {
+                self.store(self.get(base) + offset as u64, self.get(source))?;
+            }
+            Instruction::LDROffset { destination, base, offset } => {
+                let value = self.load(self.get(base) + offset as u64)?;
+                self.set(destination, value);
+            }
+            Instruction::ADDImmediate { destination, source, value } => {
+                self.set(destination, self.get(source).wrapping_add(value as u64));
+            }
+            Instruction::SUBImmediate { destination, source, value } => {
+                self.set(destination, self.get(source).wrapping_sub(value as u64));
+            }
+            Instruction::ADDRegister { destination, first, second } => {
+                self.set(destination, self.get(first).wrapping_add(self.get(second)));
+            }
+            Instruction::SUBRegister { destination, first, second } => {
+                self.set(destination, self.get(first).wrapping_sub(self.get(second)));
+            }
+            Instruction::MUL { destination, first, second } => {
+                self.set(destination, self.get(first).wrapping_mul(self.get(second)));
+            }
+            other => return Err(format!("`{other:?}` can't be emulated.")),
+        }
+        self.program_counter = next;
+        Ok(())
+    }
+}
+
+/// Reads the low `bits` bits of `value` as a signed number.
+fn sign_extend(value: u32, bits: u8) -> i64 {
+    (((value as u64) << (64 - bits)) as i64) >> (64 - bits)
+}
+
+#[test]
+fn test_decode() {
+    use super::bytecode::convert_instruction;
+    // The encodings from `assembly::test_assemble` read back as the
+    // instructions that made them.
+    let words = [
+        0xA9BF7BFD, 0x910003FD, 0x10FFFFA1, 0xD2800042, 0xF2A00022,
+        0xF81F0FE0, 0xF84107E0, 0xA8C17BFD, 0xAA0203F0, 0xD4000001,
+        0xD4200020, 0xD63F0120, 0x17FFFFF4, 0xD65F03C0,
+        0x97FFFFF2, 0xD10083FF, 0xF9000BF3, 0xF94007F4, 0x910083FF,
+        0x8B0B0149, 0xB4FFFD8C, 0xCB0B0149, 0x9B027C20,
+        // `ldr x9, #8`
+        0x58000049,
+    ];
+    for word in words {
+        let instruction = decode_instruction(word).unwrap();
+        assert_eq!(convert_instruction(instruction), word, "{instruction:?}");
+    }
+    assert_eq!(decode_instruction(0xA9BF7BFD), Some(Instruction::STPPreIndex {
+        first: Register::R29,
+        second: Register::R30,
+        base: Register::SP,
+        offset: -16
+    }));
+    // `mov x29, sp` is an `add`.
+    assert_eq!(decode_instruction(0x910003FD), Some(Instruction::ADDImmediate {
+        destination: Register::R29,
+        source: Register::SP,
+        value: 0
+    }));
+    assert_eq!(decode_instruction(0x52800020), Some(Instruction::MOVZ {
+        destination: Register::R0,
+        value: 1,
+        shift: 0,
+        half: true
+    }));
+    // `udiv x0, x1, x2`
+    assert_eq!(decode_instruction(0x9AC20820), None);
+}
+
+#[test]
+fn test_emulate() {
+    use super::assembly::assemble;
+    let run = |source: &str| {
+        let code = assemble(source).unwrap();
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let result = emulate(&code, &SyscallConvention::LINUX_AARCH64, &mut stdout, &mut stderr);
+        (result.map_err(|e| e.to_string()), String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+    };
+    // Calls, the stack and arithmetic all work together.
+    let source = "\
+.global _start
+message:
+.byte 0x48, 0x65, 0x6C, 0x6C, 0x6F
+.align 2
+write_twice:
+str x19, [sp, #-16]!
+mov x19, x2
+movz x8, #64
+svc #0
+movz x0, #1
+mov x2, x19
+add x1, x1, #1
+sub x2, x2, #1
+svc #0
+ldr x19, [sp], #16
+ret
+_start:
+movz x19, #7
+movz x0, #1
+adr x1, message
+movz x2, #5
+stp x29, x30, [sp, #-16]!
+bl write_twice
+ldp x29, x30, [sp], #16
+movz x0, #2
+add x0, x0, x19
+cbz x0, _start
+movk x0, #1, lsl #16
+movz x8, #93
+svc #0
+";
+    assert_eq!(run(source), (Ok(9), String::from("Helloello"), String::new()));
+    // Values in the literal pool are loaded from after the code.
+    assert_eq!(
+        run(".global _start\n_start:\nldr x0, =0x1234567800000021\nmovz x8, #93\nsvc #0\n").0,
+        Ok(0x21)
+    );
+    // Mistakes stop the program, naming where they happened.
+    assert_eq!(
+        run(".global _start\n_start:\nmovz x0, #2\nbrk #1\n").0,
+        Err(String::from("0x4 (_start+0x4): hit a breakpoint. (`brk #1`)"))
+    );
+    assert_eq!(
+        run(".global _start\n_start:\nmovz x8, #64\nmovz x0, #1\nmovz x1, #0\nmovz x2, #1\nsvc #0\n").0,
+        Err(String::from("0x10 (_start+0x10): 1 bytes at 0x0 aren't mapped."))
+    );
+    assert_eq!(
+        run(".global _start\n_start:\nb _start\n").0,
+        Err(String::from("The program didn't finish within 10000000 instructions."))
+    );
+}
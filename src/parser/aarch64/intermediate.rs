@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     /// ### {B}ranch to a signed offset.
     /// 
@@ -44,6 +44,22 @@ pub enum Instruction {
     /// not returning from one either. Use other instructions for those cases to
     /// avoid predictive misses.
     BDotXX { condition: Condition, offset: u32 },
+    /// ### {C}ompares a register and {B}ranches to a signed offset if it's
+    /// {Z}ero.
+    /// 
+    /// ## Encoding
+    /// - `cbz [register], #[offset]`
+    /// - `cbz [register], label`
+    /// 
+    /// ## Effect
+    /// if (register == 0) {
+    ///     PC = PC + offset
+    /// }
+    /// 
+    /// ## Notes
+    /// Only 21 bits of the offset are used, as with [Instruction::BDotXX]. The
+    /// flags are left as they were.
+    CBZ { register: Register, offset: u32 },
     /// ### {M}{O}{V}es the value of a register to another register.
     /// 
     /// ## Encoding
@@ -72,9 +88,237 @@ pub enum Instruction {
     /// `half` to true makes this operate on the destination register as a 32
     /// bit register.
     MOVZ { destination: Register, value: u16, shift: u8, half: bool },
+    /// ### {M}{O}{V}es a 16 bit immediate value into part of a register, {K}eeping
+    /// the rest of the register as it was.
+    /// 
+    /// ## Encoding
+    /// - `movk [destination], #[value]`
+    /// - `movk [destination], #[value], lsl #[shift]`
+    /// 
+    /// ## Effect
+    /// destination = (destination & !(0xFFFF << shift)) | (value << shift)
+    /// 
+    /// ## Notes
+    /// All other shift sizes besides 0, 16, 32, or 48 bits are invalid.
+    MOVK { destination: Register, value: u16, shift: u8 },
+    /// ### Forms the {A}{D}dress of a {R}elative offset.
+    /// 
+    /// ## Encoding
+    /// - `adr [destination], #[offset]`
+    /// - `adr [destination], label`
+    /// 
+    /// ## Effect
+    /// destination = PC + offset
+    /// 
+    /// ## Notes
+    /// Only 21 bits of the offset are used, as a signed value. All other bits
+    /// are ignored.
+    ADR { destination: Register, offset: u32 },
+    /// ### {B}ranches to the address in a {R}egister.
+    /// 
+    /// ## Encoding
+    /// - `br [target]`
+    /// 
+    /// ## Effect
+    /// PC = target
+    BR { target: Register },
+    /// ### {B}ranches to a signed offset, and {L}inks the return address into
+    /// x30. Used to call subroutines.
+    /// 
+    /// ## Encoding
+    /// - `bl #[offset]`
+    /// - `bl label`
+    /// 
+    /// ## Effect
+    /// x30 = PC + 4
+    /// PC = PC + offset
+    /// 
+    /// ## Notes
+    /// Only 28 bits of the offset are used, as with [Instruction::B].
+    BL { offset: u32 },
+    /// ### {B}ranches to the address in a register, and {L}inks the return
+    /// address into x30. Used to call subroutines.
+    /// 
+    /// ## Encoding
+    /// - `blr [target]`
+    /// 
+    /// ## Effect
+    /// x30 = PC + 4
+    /// PC = target
+    BLR { target: Register },
+    /// ### {RET}urns from a subroutine.
+    /// 
+    /// ## Encoding
+    /// - `ret`
+    /// - `ret [target]`
+    /// 
+    /// ## Effect
+    /// PC = target
+    /// 
+    /// ## Notes
+    /// `target` is x30 if not given.
+    RET { target: Register },
+    /// ### {ST}ores a {P}air of registers, moving the base register first.
+    /// 
+    /// ## Encoding
+    /// - `stp [first], [second], [[base], #[offset]]!`
+    /// 
+    /// ## Effect
+    /// base = base + offset
+    /// memory[base] = first
+    /// memory[base + 8] = second
+    /// 
+    /// ## Notes
+    /// `offset` must be a multiple of 8 between -512 and 504.
+    STPPreIndex { first: Register, second: Register, base: Register, offset: i16 },
+    /// ### {L}oa{D}s a {P}air of registers, moving the base register after.
+    /// 
+    /// ## Encoding
+    /// - `ldp [first], [second], [[base]], #[offset]`
+    /// 
+    /// ## Effect
+    /// first = memory[base]
+    /// second = memory[base + 8]
+    /// base = base + offset
+    /// 
+    /// ## Notes
+    /// `offset` must be a multiple of 8 between -512 and 504.
+    LDPPostIndex { first: Register, second: Register, base: Register, offset: i16 },
+    /// ### {ST}ores a {R}egister, moving the base register first.
+    /// 
+    /// ## Encoding
+    /// - `str [source], [[base], #[offset]]!`
+    /// 
+    /// ## Effect
+    /// base = base + offset
+    /// memory[base] = source
+    /// 
+    /// ## Notes
+    /// `offset` must be between -256 and 255.
+    STRPreIndex { source: Register, base: Register, offset: i16 },
+    /// ### {L}oa{D}s a {R}egister, moving the base register after.
+    /// 
+    /// ## Encoding
+    /// - `ldr [destination], [[base]], #[offset]`
+    /// 
+    /// ## Effect
+    /// destination = memory[base]
+    /// base = base + offset
+    /// 
+    /// ## Notes
+    /// `offset` must be between -256 and 255.
+    LDRPostIndex { destination: Register, base: Register, offset: i16 },
+    /// ### {L}oa{D}s a {R}egister from an offset from the program counter.
+    /// 
+    /// ## Encoding
+    /// - `ldr [destination], #[offset]`
+    /// - `ldr [destination], =[value]`, which loads `value` from a literal pool
+    /// 
+    /// ## Effect
+    /// destination = memory[PC + offset]
+    /// 
+    /// ## Notes
+    /// Only 21 bits of the offset are used, as with [Instruction::CBZ].
+    LDRLiteral { destination: Register, offset: u32 },
+    /// ### {ST}ores a {R}egister at an offset from the base register.
+    /// 
+    /// ## Encoding
+    /// - `str [source], [[base], #[offset]]`
+    /// 
+    /// ## Effect
+    /// memory[base + offset] = source
+    /// 
+    /// ## Notes
+    /// `offset` must be a multiple of 8 between 0 and 32760.
+    STROffset { source: Register, base: Register, offset: u16 },
+    /// ### {L}oa{D}s a {R}egister from an offset from the base register.
+    /// 
+    /// ## Encoding
+    /// - `ldr [destination], [[base], #[offset]]`
+    /// 
+    /// ## Effect
+    /// destination = memory[base + offset]
+    /// 
+    /// ## Notes
+    /// `offset` must be a multiple of 8 between 0 and 32760.
+    LDROffset { destination: Register, base: Register, offset: u16 },
+    /// ### {ADD}s an immediate value to a register.
+    /// 
+    /// ## Encoding
+    /// - `add [destination], [source], #[value]`
+    /// 
+    /// ## Effect
+    /// destination = source + value
+    /// 
+    /// ## Notes
+    /// `value` must be below 4096. Either register can be the stack pointer.
+    ADDImmediate { destination: Register, source: Register, value: u16 },
+    /// ### {SUB}tracts an immediate value from a register.
+    /// 
+    /// ## Encoding
+    /// - `sub [destination], [source], #[value]`
+    /// 
+    /// ## Effect
+    /// destination = source - value
+    /// 
+    /// ## Notes
+    /// `value` must be below 4096. Either register can be the stack pointer.
+    SUBImmediate { destination: Register, source: Register, value: u16 },
+    /// ### {ADD}s two {R}egisters.
+    /// 
+    /// ## Encoding
+    /// - `add [destination], [first], [second]`
+    /// 
+    /// ## Effect
+    /// destination = first + second
+    /// 
+    /// ## Notes
+    /// Neither register can be the stack pointer, as register 31 is the zero
+    /// register here.
+    ADDRegister { destination: Register, first: Register, second: Register },
+    /// ### {SUB}tracts two {R}egisters.
+    /// 
+    /// ## Encoding
+    /// - `sub [destination], [first], [second]`
+    /// 
+    /// ## Effect
+    /// destination = first - second
+    /// 
+    /// ## Notes
+    /// Neither register can be the stack pointer, as register 31 is the zero
+    /// register here.
+    SUBRegister { destination: Register, first: Register, second: Register },
+    /// ### {MUL}tiplies two registers.
+    /// 
+    /// ## Encoding
+    /// - `mul [destination], [first], [second]`
+    /// 
+    /// ## Effect
+    /// destination = first * second
+    /// 
+    /// ## Notes
+    /// This is `madd` adding the zero register. Only the low 64 bits of the
+    /// result are kept.
+    MUL { destination: Register, first: Register, second: Register },
+    /// ### {S}uper{V}isor {C}all, used for system calls.
+    /// 
+    /// ## Encoding
+    /// - `svc #[immediate]`
+    /// 
+    /// ## Effect
+    /// Hands control to the operating system.
+    SVC { immediate: u16 },
+    /// ### {B}rea{K}point.
+    /// 
+    /// ## Encoding
+    /// - `brk #[immediate]`
+    /// 
+    /// ## Effect
+    /// Stops the program, or hands control to an attached debugger.
+    BRK { immediate: u16 },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Condition {
     /// ### {E}{Q}ual
     /// 
@@ -156,7 +400,7 @@ impl Condition {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     /// General purpose registers with argument and return values conventions.
     R0, R1, R2, R3, R4, R5, R6, R7,
@@ -189,6 +433,28 @@ pub enum Register {
 }
 
 impl Register {
+    /// All registers that can be encoded, in encoding order. (see
+    /// [Register::to_5_bits])
+    pub const NUMBERED: [Register; 31] = [
+        Self::R0, Self::R1, Self::R2, Self::R3, Self::R4, Self::R5, Self::R6,
+        Self::R7, Self::R8, Self::R9, Self::R10, Self::R11, Self::R12,
+        Self::R13, Self::R14, Self::R15, Self::R16, Self::R17, Self::R18,
+        Self::R19, Self::R20, Self::R21, Self::R22, Self::R23, Self::R24,
+        Self::R25, Self::R26, Self::R27, Self::R28, Self::R29, Self::R30,
+    ];
+    /// Finds a 64 bit register from its name in assembly, such as `x0` or `sp`.
+    pub fn from_name(name: &str) -> Option<Register> {
+        match name.to_lowercase().as_str() {
+            "sp" => Some(Self::SP),
+            "xzr" => Some(Self::ZR),
+            "fp" => Some(Self::R29),
+            "lr" => Some(Self::R30),
+            other => {
+                let number: usize = other.strip_prefix('x')?.parse().ok()?;
+                Self::NUMBERED.get(number).copied()
+            }
+        }
+    }
     /// Used for bytecode generation. Only the low 5 bits are used.
     pub fn to_5_bits(&self) -> u8 {
         // I know this sucks. Oh well! It's Fast Enough:tm: and not so horrible
@@ -226,7 +492,10 @@ impl Register {
             Self::R29 => 0b11101,
             Self::R30 => 0b11110,
             Self::ZR  => 0b11111,
-            _ => panic!("This register is not valid in a 5 bit encoding context!")
+            // Instructions that can use the stack pointer read 0b11111 as it
+            // instead of the zero register.
+            Self::SP  => 0b11111,
+            Self::PC  => panic!("This register is not valid in a 5 bit encoding context!")
         }
     }
 }
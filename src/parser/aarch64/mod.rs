@@ -1,3 +1,4 @@
-mod assembly;
+pub mod assembly;
 mod bytecode;
-mod intermediate;
+pub mod emulator;
+pub mod intermediate;
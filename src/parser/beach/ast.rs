@@ -5,18 +5,43 @@ mod global_scope;
 
 pub mod user_token_format;
 
-use std::{iter::Peekable, ops::Add};
+use std::{ops::Add, path::PathBuf};
 
-use crate::utils::*;
 
-use user_token_format::{Syntax, Symbol, keywords::Keyword};
+use user_token_format::{Annotation, Syntax, Symbol, SymbolStream, keywords::Keyword};
 
-#[derive(Debug)]
+use super::typecheck::Diagnostic;
+
+#[derive(Debug, Clone)]
 pub struct Program {
     pub definitions: Vec<Definition>,
     pub global_tasks: Vec<Task>,
     pub main_tasks: Vec<Task>,
-    pending_file_additions: Vec<String>,
+    /// Where the `main` block starts, if this program has one.
+    pub main: Option<Annotation>,
+    /// The file this program was read from, if it was read from one.
+    pub path: Option<PathBuf>,
+    /// The names of every definition marked `public`, which other files can
+    /// use.
+    pub public: Vec<String>,
+    /// Names this program doesn't want from its imports, such as something
+    /// from the core library it defines itself. (`disable maybe;`)
+    pub disabled: Vec<String>,
+    /// Members of types and enums in the core library that couldn't be read,
+    /// and so were left out, along with why.
+    pub unloaded: Vec<Diagnostic>,
+    /// Whether members that can't be read are left out instead of failing.
+    /// Only the core library does this. (see [Program::from_core_lst])
+    leave_out_unreadable: bool,
+}
+
+/// The members read from the body of a type or enum declaration.
+#[derive(Default)]
+struct DeclarationBody {
+    data: Vec<(String, TypeIdentity, Annotation)>,
+    cases: Vec<(String, Option<TypeIdentity>, Annotation)>,
+    methods: Vec<Function>,
+    implementations: Vec<Implementation>,
 }
 
 impl Add for Program {
@@ -27,48 +52,383 @@ impl Add for Program {
         out.definitions.append(&mut two.definitions);
         out.global_tasks.append(&mut two.global_tasks);
         out.global_tasks.append(&mut two.main_tasks);
+        out.public.append(&mut two.public);
+        out.disabled.append(&mut two.disabled);
+        out.unloaded.append(&mut two.unloaded);
         return out;
     }
 }
 
 impl Program {
     pub fn from_lst(lst: Syntax, prefix: Option<String>) -> Result<Program, anyhow::Error> {
-        let mut program = Program {
+        let mut program = Program::empty();
+        let mut syms = SymbolStream::new(&lst);
+        global_scope::global_scope(&mut program, &mut syms)?;
+        Ok(program)
+    }
+    /// Reads a file of the core library. Unlike [Program::from_lst], methods
+    /// using something the parser can't read yet are left out of their type
+    /// or enum and listed in [Program::unloaded], so that the rest of the
+    /// core library can still be used.
+    pub fn from_core_lst(lst: Syntax) -> Result<Program, anyhow::Error> {
+        let mut program = Program::empty();
+        program.leave_out_unreadable = true;
+        let mut syms = SymbolStream::new(&lst);
+        global_scope::global_scope(&mut program, &mut syms)?;
+        program.leave_out_unreadable = false;
+        Ok(program)
+    }
+    fn empty() -> Program {
+        Program {
             definitions: vec![],
             global_tasks: vec![],
             main_tasks: vec![],
-            pending_file_additions: vec![]
-        };
-        let mut syms = lst.symbols.iter().peekable();
-        global_scope::global_scope(&mut program, &mut syms)?;
-        while !program.pending_file_additions.is_empty() {
-            let mut glob_addition = String::new();
-            for addition in &program.pending_file_additions {
-                glob_addition += "\n";
-                glob_addition += addition;
-            }
-            program.pending_file_additions.clear();
-            let parsed = crate::parser::parse_string_file(glob_addition);
-            let mut syms = parsed.symbols.iter().peekable();
-            global_scope::global_scope(&mut program, &mut syms)?;
+            main: None,
+            path: None,
+            public: vec![],
+            disabled: vec![],
+            unloaded: vec![],
+            leave_out_unreadable: false
         }
-        Ok(program)
     }
-    fn type_declaration<'a, I>(&mut self, syms: &mut Peekable<I>) -> Result<Definition, anyhow::Error>
-    where
-        I: Iterator<Item = &'a Symbol> {
+    fn type_declaration<'a>(&mut self, syms: &mut SymbolStream<'a>) -> Result<Definition, anyhow::Error> {
         if syms.peek().is_none() {
             return Err(anyhow::Error::msg(
                 "Unexpected EOF following keyword `type`. (TODO: ANNOTATIONS)"
             ));
         }
-        match syms.next().unwrap() {
-            wrongsym => todo!("H: {:?}", wrongsym)
+        let name = match syms.next().unwrap() {
+            Symbol::Label(lbl) => lbl.clone(),
+            wrongsym => {
+                return Err(anyhow::Error::msg(format!(
+                    "Expected a type name following keyword `type`, found {:?}. (TODO: ANNOTATIONS)", wrongsym
+                )));
+            }
+        };
+        let generics = Self::declaration_generics(syms)?;
+        if syms.next() != Some(&Symbol::OpenBrace) {
+            return Err(anyhow::Error::msg(
+                "Expected an opening brace following a type declaration. (TODO: ANNOTATIONS)"
+            ));
+        }
+        let self_type = Self::self_type(&name, &generics, false);
+        let body = self.declaration_body(syms, &generics, &self_type, false)?;
+        Ok(Definition::Type {
+            name,
+            generics,
+            data: body.data,
+            methods: body.methods,
+            implementations: body.implementations,
+            file: None
+        })
+    }
+    fn enum_declaration<'a>(&mut self, syms: &mut SymbolStream<'a>) -> Result<Definition, anyhow::Error> {
+        let name = match syms.next() {
+            Some(Symbol::Label(lbl)) => lbl.clone(),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "Expected an enum name following keyword `enum`. (TODO: ANNOTATIONS)"
+                ));
+            }
+        };
+        let generics = Self::declaration_generics(syms)?;
+        if syms.next() != Some(&Symbol::OpenBrace) {
+            return Err(anyhow::Error::msg(
+                "Expected an opening brace following an enum declaration. (TODO: ANNOTATIONS)"
+            ));
+        }
+        let self_type = Self::self_type(&name, &generics, true);
+        let body = self.declaration_body(syms, &generics, &self_type, true)?;
+        Ok(Definition::Enum {
+            name,
+            generics,
+            cases: body.cases,
+            methods: body.methods,
+            implementations: body.implementations,
+            file: None
+        })
+    }
+    /// Reads the generic parameters and where clause of a type or enum, such as
+    /// `<G, E> where E: error`.
+    fn declaration_generics<'a>(syms: &mut SymbolStream<'a>) -> Result<Vec<GenericParameter>, anyhow::Error> {
+        let mut generics = GenericParameter::list_from_symbols(syms)?;
+        let clauses = GenericParameter::where_from_symbols(syms)?;
+        GenericParameter::apply_where(&mut generics, clauses)?;
+        Ok(generics)
+    }
+    /// The type `&self` refers to inside the methods of a type or enum.
+    fn self_type(name: &str, generics: &[GenericParameter], is_enum: bool) -> TypeIdentity {
+        if generics.is_empty() {
+            if is_enum {
+                TypeIdentity::Enum(name.to_string())
+            }
+            else {
+                TypeIdentity::Structured(name.to_string())
+            }
+        }
+        else {
+            TypeIdentity::Instance {
+                name: name.to_string(),
+                arguments: generics.iter().map(|generic| TypeIdentity::Generic(generic.name.clone())).collect()
+            }
         }
     }
-    fn trait_declaration<'a, I>(&mut self, syms: &mut Peekable<I>) -> Result<Definition, anyhow::Error>
-    where
-        I: Iterator<Item = &'a Symbol> {
+    /// Reads the members of a type or enum up to and including its closing
+    /// brace.
+    fn declaration_body<'a>(
+        &mut self,
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter],
+        self_type: &TypeIdentity,
+        is_enum: bool
+    ) -> Result<DeclarationBody, anyhow::Error> {
+        let mut body = DeclarationBody::default();
+        loop {
+            match syms.next() {
+                None => {
+                    return Err(anyhow::Error::msg(
+                        "Unexpected EOF while parsing a type or enum. (TODO: ANNOTATIONS)"
+                    ));
+                }
+                Some(Symbol::CloseBrace) => break,
+                Some(Symbol::Comment(_)) | Some(Symbol::Comments(_)) => {},
+                // Visibility and safety modifiers don't change how members are
+                // read.
+                Some(Symbol::Keyword(Keyword::Kpublic)) => {},
+                Some(Symbol::Label(lbl)) if lbl == "unsafe" => {},
+                Some(Symbol::Label(lbl)) if lbl == "var" => {
+                    let member_name = match syms.next() {
+                        Some(Symbol::Label(member_name)) => member_name.clone(),
+                        _ => {
+                            return Err(anyhow::Error::msg(
+                                "Expected a name following `var`. (TODO: ANNOTATIONS)"
+                            ));
+                        }
+                    };
+                    let location = syms.location();
+                    let method_generics = GenericParameter::list_from_symbols(syms)?;
+                    match syms.next() {
+                        Some(Symbol::Is) if method_generics.is_empty() => {
+                            let data_type = TypeIdentity::from_symbols(syms, generics)?;
+                            if syms.next() != Some(&Symbol::PhraseEnd) {
+                                return Err(anyhow::Error::msg(format!(
+                                    "Expected `;` following the field `{member_name}`. (TODO: ANNOTATIONS)"
+                                )));
+                            }
+                            body.data.push((member_name, data_type, location));
+                        }
+                        Some(Symbol::Set) => {
+                            // Core methods using something that can't be read
+                            // yet are left out, so the rest of the type can
+                            // still be used.
+                            let start = syms.clone();
+                            match self.closure_function(
+                                syms, member_name.clone(), method_generics, generics, Some(self_type)
+                            ) {
+                                Ok(method) => body.methods.push(method),
+                                Err(e) if !self.leave_out_unreadable => return Err(e),
+                                Err(e) => {
+                                    *syms = start;
+                                    if !skip_member(syms) {
+                                        return Err(e);
+                                    }
+                                    self.unloaded.push(Diagnostic {
+                                        message: format!("`{member_name}` was left out because it couldn't be read: {e}"),
+                                        location,
+                                        file: None
+                                    });
+                                }
+                            }
+                        }
+                        _ => {
+                            return Err(anyhow::Error::msg(format!(
+                                "Expected `: TYPE;` or `= |...| {{ ... }}` following `var {member_name}`. (TODO: ANNOTATIONS)"
+                            )));
+                        }
+                    }
+                }
+                Some(Symbol::Label(lbl)) if lbl == "case" && is_enum => {
+                    let case_name = match syms.next() {
+                        Some(Symbol::Label(case_name)) => case_name.clone(),
+                        _ => {
+                            return Err(anyhow::Error::msg(
+                                "Expected a name following `case`. (TODO: ANNOTATIONS)"
+                            ));
+                        }
+                    };
+                    let location = syms.location();
+                    let mut case_type = None;
+                    if syms.peek() == Some(&&Symbol::Is) {
+                        syms.next();
+                        case_type = Some(TypeIdentity::from_symbols(syms, generics)?);
+                    }
+                    if syms.next() != Some(&Symbol::PhraseEnd) {
+                        return Err(anyhow::Error::msg(format!(
+                            "Expected `;` following the case `{case_name}`. (TODO: ANNOTATIONS)"
+                        )));
+                    }
+                    body.cases.push((case_name, case_type, location));
+                }
+                Some(Symbol::Keyword(Keyword::Ktrait)) => {
+                    let trait_name = match syms.next() {
+                        Some(Symbol::Label(trait_name)) => trait_name.clone(),
+                        _ => {
+                            return Err(anyhow::Error::msg(
+                                "Expected a trait name following keyword `trait`. (TODO: ANNOTATIONS)"
+                            ));
+                        }
+                    };
+                    let location = syms.location();
+                    let mut methods = vec![];
+                    match syms.next() {
+                        Some(Symbol::PhraseEnd) => {},
+                        Some(Symbol::OpenBrace) => {
+                            let unloaded = self.unloaded.len();
+                            let inner = self.declaration_body(syms, generics, self_type, false)?;
+                            // A trait missing some of its methods isn't
+                            // implemented.
+                            if self.unloaded.len() > unloaded {
+                                self.unloaded.push(Diagnostic {
+                                    message: format!("`{trait_name}` was left out because some of its methods couldn't be read."),
+                                    location,
+                                    file: None
+                                });
+                                continue;
+                            }
+                            if !inner.data.is_empty() || !inner.implementations.is_empty() {
+                                return Err(anyhow::Error::msg(format!(
+                                    "Implementations of `{trait_name}` may only contain methods. (TODO: ANNOTATIONS)"
+                                )));
+                            }
+                            methods = inner.methods;
+                        }
+                        _ => {
+                            return Err(anyhow::Error::msg(format!(
+                                "Expected `;` or `{{` following `trait {trait_name}`. (TODO: ANNOTATIONS)"
+                            )));
+                        }
+                    }
+                    body.implementations.push(Implementation { trait_name, requirements: vec![], methods });
+                }
+                Some(Symbol::Label(lbl)) if lbl == "extension" => {
+                    // `extension where G: printable { ... }` implements traits
+                    // only when the where clause holds.
+                    let requirements = GenericParameter::where_from_symbols(syms)?;
+                    for requirement in &requirements {
+                        if !generics.iter().any(|generic| generic.name == requirement.name) {
+                            return Err(anyhow::Error::msg(format!(
+                                "The extension constrains `{}`, which is not a generic parameter here. (TODO: ANNOTATIONS)",
+                                requirement.name
+                            )));
+                        }
+                    }
+                    if syms.next() != Some(&Symbol::OpenBrace) {
+                        return Err(anyhow::Error::msg(
+                            "Expected an opening brace following an extension. (TODO: ANNOTATIONS)"
+                        ));
+                    }
+                    let inner = self.declaration_body(syms, generics, self_type, is_enum)?;
+                    if !inner.data.is_empty() || !inner.cases.is_empty() || !inner.methods.is_empty() {
+                        return Err(anyhow::Error::msg(
+                            "Extensions may only contain trait implementations. (TODO: ANNOTATIONS)"
+                        ));
+                    }
+                    for mut implementation in inner.implementations {
+                        implementation.requirements.extend(requirements.clone());
+                        body.implementations.push(implementation);
+                    }
+                }
+                Some(sym) => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Unexpected symbol {:?} in a type or enum body. (TODO: ANNOTATIONS)", sym
+                    )));
+                }
+            }
+        }
+        Ok(body)
+    }
+    /// Reads a function written as a closure, starting at its arguments:
+    /// `|a: u8, b: u8| -> u8 where ... { ... }`. A trailing `;` is discarded.
+    /// `outer_generics` are the generic parameters of the type or enum the
+    /// function is a method of, if any.
+    fn closure_function<'a>(
+        &mut self,
+        syms: &mut SymbolStream<'a>,
+        name: String,
+        mut generics: Vec<GenericParameter>,
+        outer_generics: &[GenericParameter],
+        self_type: Option<&TypeIdentity>
+    ) -> Result<Function, anyhow::Error> {
+        let argument_symbols = match syms.next() {
+            Some(Symbol::Closure(inner)) => inner,
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Expected a closure (`|...|`) defining `{name}`. (TODO: ANNOTATIONS)"
+                )));
+            }
+        };
+        let location = syms.location();
+        let mut scope = outer_generics.to_vec();
+        scope.extend(generics.iter().cloned());
+        let arguments = FunctionArgument::list_from_symbols(argument_symbols, location, &scope, self_type)?;
+        let returns = TypeIdentity::return_from_symbols(syms, &scope)?;
+        let clauses = GenericParameter::where_from_symbols(syms)?;
+        GenericParameter::apply_where(&mut generics, clauses)?;
+        scope.truncate(outer_generics.len());
+        scope.extend(generics.iter().cloned());
+        if syms.next() != Some(&Symbol::OpenBrace) {
+            return Err(anyhow::Error::msg(format!(
+                "Expected an opening brace to start the body of `{name}`. (TODO: ANNOTATIONS)"
+            )));
+        }
+        let code = self.code_block(syms, &scope, false)?;
+        if syms.peek() == Some(&&Symbol::PhraseEnd) {
+            syms.next();
+        }
+        Ok(Function { name, generics, arguments, returns, code, location, file: None })
+    }
+    /// Reads a global `var`, which is either a function or a constant.
+    fn variable_declaration<'a>(&mut self, syms: &mut SymbolStream<'a>) -> Result<Definition, anyhow::Error> {
+        let name = match syms.next() {
+            Some(Symbol::Label(lbl)) => lbl.clone(),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "Expected a name following `var`. (TODO: ANNOTATIONS)"
+                ));
+            }
+        };
+        let generics = GenericParameter::list_from_symbols(syms)?;
+        if syms.next() != Some(&Symbol::Set) {
+            return Err(anyhow::Error::msg(format!(
+                "Expected `=` following `var {name}`. (TODO: ANNOTATIONS)"
+            )));
+        }
+        match syms.peek() {
+            Some(Symbol::Closure(_)) => {
+                Ok(Definition::Function(self.closure_function(syms, name, generics, &[], None)?))
+            }
+            Some(Symbol::String(_)) | Some(Symbol::Integer(_)) if generics.is_empty() => {
+                let value = match Evaluatable::from_symbols(syms, Symbol::PhraseEnd) {
+                    Evaluatable::Value { value } => value,
+                    _ => {
+                        return Err(anyhow::Error::msg(format!(
+                            "The value of `{name}` must be a plain value. (TODO: ANNOTATIONS)"
+                        )));
+                    }
+                };
+                if syms.next() != Some(&Symbol::PhraseEnd) {
+                    return Err(anyhow::Error::msg(format!(
+                        "Expected `;` following the value of `{name}`. (TODO: ANNOTATIONS)"
+                    )));
+                }
+                Ok(Definition::GlobalConstant { label: name, value })
+            }
+            _ => Err(anyhow::Error::msg(format!(
+                "Expected a closure or a constant value for `var {name}`. (TODO: ANNOTATIONS)"
+            )))
+        }
+    }
+    fn trait_declaration<'a>(&mut self, syms: &mut SymbolStream<'a>) -> Result<Definition, anyhow::Error> {
         if syms.peek().is_none() {
             return Err(anyhow::Error::msg(
                 "Unexpected EOF following keyword `trait`. (TODO: ANNOTATIONS)"
@@ -88,180 +448,475 @@ impl Program {
                 "Expected an opening brace following a trait declaration. (TODO: ANNOTATIONS)"
             ));
         }
-        // TODO: loop over insides to get all the methods
+        let self_type = TypeIdentity::Trait(trait_name.clone());
+        let mut requires = vec![];
+        let mut methods = vec![];
         loop {
-            // If there's no more symbols, we've hit an invalid EOF
-            if syms.next().is_none() {
-                return Err(anyhow::Error::msg(
-                    "Unexpected EOF while parsing trait (TODO: ANNOTATIONS)"
-                ));
-            }
-            // If we've hit a closing brace, we're done!
-            if syms.next() == Some(&Symbol::CloseBrace) {
-                break;
+            match syms.next() {
+                // If there's no more symbols, we've hit an invalid EOF
+                None => {
+                    return Err(anyhow::Error::msg(
+                        "Unexpected EOF while parsing trait (TODO: ANNOTATIONS)"
+                    ));
+                }
+                // If we've hit a closing brace, we're done!
+                Some(Symbol::CloseBrace) => break,
+                Some(Symbol::Comment(_)) | Some(Symbol::Comments(_)) => {},
+                Some(Symbol::Keyword(Keyword::Kpublic)) => {},
+                Some(Symbol::Keyword(Keyword::Ktrait)) => {
+                    // `trait printable;` requires implementors to also
+                    // implement `printable`.
+                    if let (Some(Symbol::Label(required)), Some(Symbol::PhraseEnd)) = (syms.next(), syms.next()) {
+                        requires.push(required.clone());
+                    }
+                    else {
+                        return Err(anyhow::Error::msg(
+                            "Expected `trait NAME;` inside a trait. (TODO: ANNOTATIONS)"
+                        ));
+                    }
+                }
+                Some(Symbol::Label(lbl)) if lbl == "var" => {
+                    // Methods are declared by their signature alone:
+                    // `var to_string: |&self| -> string;`
+                    let method_name = match syms.next() {
+                        Some(Symbol::Label(method_name)) => method_name.clone(),
+                        _ => {
+                            return Err(anyhow::Error::msg(
+                                "Expected a method name following `var`. (TODO: ANNOTATIONS)"
+                            ));
+                        }
+                    };
+                    let generics = GenericParameter::list_from_symbols(syms)?;
+                    let argument_symbols = match (syms.next(), syms.next()) {
+                        (Some(Symbol::Is), Some(Symbol::Closure(inner))) => inner,
+                        _ => {
+                            return Err(anyhow::Error::msg(format!(
+                                "Expected a closure signature following `var {method_name}:`. (TODO: ANNOTATIONS)"
+                            )));
+                        }
+                    };
+                    let location = syms.location();
+                    let arguments = FunctionArgument::list_from_symbols(argument_symbols, location, &generics, Some(&self_type))?;
+                    let returns = TypeIdentity::return_from_symbols(syms, &generics)?;
+                    if syms.next() != Some(&Symbol::PhraseEnd) {
+                        return Err(anyhow::Error::msg(format!(
+                            "Expected `;` following the signature of `{method_name}`. (TODO: ANNOTATIONS)"
+                        )));
+                    }
+                    methods.push(Function { name: method_name, generics, arguments, returns, code: vec![], location, file: None });
+                }
+                Some(sym) => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Unexpected symbol {:?} in a trait. (TODO: ANNOTATIONS)", sym
+                    )));
+                }
             }
         }
-        todo!();
-        return Ok(Definition::Trait {
+        Ok(Definition::Trait {
             name: trait_name.to_string(),
-            methods: vec![]
+            requires,
+            methods
         })
     }
-    fn compiler_directive<'a, I>(&mut self, syms: &mut Peekable<I>, data: &String) -> Result<(), anyhow::Error>
-    where
-        I: Iterator<Item = &'a Symbol> {
-        if data == "core" {
-            // we can ignore this directive, which is not used here.
-        }
-        if let Some(&Symbol::Label(lbl)) = syms.peek() {
-            match lbl.as_str() {
-                "core" => {
-                    // discard the "core" label
-                    syms.next();
-                    if syms.peek().is_none() {
+    fn compiler_directive<'a>(&mut self, syms: &mut SymbolStream<'a>, data: &String) -> Result<(), anyhow::Error> {
+        match data.as_str() {
+            "core" => {
+                if syms.peek().is_none() {
+                    return Err(anyhow::Error::msg(
+                        "Unexpected end of file following the compiler tag `!!core`. (TODO: ANNOTATIONS)"
+                    ));
+                }
+                // check that there is at least one lable following our "core" delegation
+                match syms.peek() {
+                    Some(&Symbol::Label(_)) => {}, // ignore
+                    _ => {
                         return Err(anyhow::Error::msg(
-                            "Unexpected end of file following the compiler tag `!!core`. (TODO: ANNOTATIONS)"
+                            "Unexpected symbol following the compiler tag `!!core`. (TODO: ANNOTATIONS)"
                         ));
                     }
-                    // check that there is at least one lable following our "core" delegation
+                }
+                let location = syms.location();
+                let mut path = vec![];
+                while let Some(&Symbol::Label(lbl)) = syms.peek() {
+                    // discard label
+                    syms.next();
+                    path.push(lbl.clone());
                     match syms.peek() {
-                        Some(&Symbol::Label(_)) => {}, // ignore
-                        _ => {
+                        Some(&&Symbol::Divide) => {
+                            // our path continues!
+                            // throw away our "/" so we loop
+                            syms.next();
+                        }
+                        Some(&&Symbol::PhraseEnd) => {
+                            // our path ends here.
+                        }
+                        Some(_) => {
                             return Err(anyhow::Error::msg(
-                                "Unexpected symbol following the compiler tag `!!core`. (TODO: ANNOTATIONS)"
+                                "Unexpected symbol during compiler directive. (TODO: ANNOTATIONS)"
                             ));
                         }
-                    }
-                    let mut path = install_directory();
-                    path.push("std");
-                    path.push("core");
-                    while let Some(&Symbol::Label(lbl)) = syms.peek() {
-                        // discard label
-                        syms.next();
-                        match syms.peek() {
-                            Some(&&Symbol::Divide) => {
-                                // our path continues!
-                                // add to our path as a dir
-                                path.push(lbl);
-                                // throw away our "/" so we loop
-                                syms.next();
-                            }
-                            Some(&&Symbol::PhraseEnd) => {
-                                // our path ends here.
-                                path.push(format!("{}.beach", lbl));
-                            }
-                            Some(_) => {
-                                return Err(anyhow::Error::msg(
-                                    "Unexpected symbol during compiler directive. (TODO: ANNOTATIONS)"
-                                ));
-                            }
-                            None => {
-                                return Err(anyhow::Error::msg(
-                                    "Unexpected end of file during compiler directive. (TODO: ANNOTATIONS)"
-                                ));
-                            }
+                        None => {
+                            return Err(anyhow::Error::msg(
+                                "Unexpected end of file during compiler directive. (TODO: ANNOTATIONS)"
+                            ));
                         }
                     }
-                    // check we end with a PhraseEnd like we should
-                    if syms.peek() != Some(&&Symbol::PhraseEnd) {
-                        return Err(anyhow::Error::msg(
-                            "Expected a semicolon following a compiler directive. (TODO: ANNOTATIONS)"
-                        ));
-                    }
-                    // TODO: do something with the path we found
-                    let core_string = std::fs::read_to_string(path)?;
-                    self.pending_file_additions.push(core_string);
-                    // throw away PhraseEnd, and we're done!
+                }
+                // check we end with a PhraseEnd like we should
+                if syms.peek() != Some(&&Symbol::PhraseEnd) {
+                    return Err(anyhow::Error::msg(
+                        "Expected a semicolon following a compiler directive. (TODO: ANNOTATIONS)"
+                    ));
+                }
+                // The file itself is loaded by the module graph.
+                self.definitions.push(Definition::Core { label: path.join("/"), location });
+                // throw away PhraseEnd, and we're done!
+                syms.next();
+                Ok(())
+            }
+            "syscall" | "unreachable" => Err(anyhow::Error::msg(format!(
+                "{}: The compiler directive `!!{}` can only be used inside a function.",
+                syms.location(), data
+            ))),
+            unknown => Err(anyhow::Error::msg(format!(
+                "{}: Unknown compiler directive `!!{}`.",
+                syms.location(), unknown
+            )))
+        }
+    }
+    /// Reads a compiler directive used as a statement inside a code block, such
+    /// as `!!syscall(1, (code));`, following the [Symbol::Compiler] itself.
+    fn statement_directive<'a>(syms: &mut SymbolStream<'a>, data: &str, is_unsafe: bool) -> Result<Task, anyhow::Error> {
+        let location = syms.location();
+        match data {
+            "syscall" => {
+                if !is_unsafe {
+                    return Err(anyhow::Error::msg(format!(
+                        "{location}: `!!syscall` can only be used inside an `unsafe` block."
+                    )));
+                }
+                if syms.next() != Some(&Symbol::OpenParenthesis) {
+                    return Err(anyhow::Error::msg(format!(
+                        "{location}: Expected `(` following `!!syscall`."
+                    )));
+                }
+                let number = Self::directive_value(syms)?;
+                let mut arguments = vec![];
+                syms.skip_comments();
+                if syms.peek() == Some(&&Symbol::Also) {
                     syms.next();
-                },
-                _ => unimplemented!("unknown label {}", lbl)
+                    syms.skip_comments();
+                    // The arguments are given as a tuple, as in `(1, pointer, length)`.
+                    if syms.next() != Some(&Symbol::OpenParenthesis) {
+                        return Err(anyhow::Error::msg(format!(
+                            "{}: Expected a tuple of arguments following the system call number.",
+                            syms.location()
+                        )));
+                    }
+                    loop {
+                        syms.skip_comments();
+                        if syms.peek() == Some(&&Symbol::CloseParenthesis) {
+                            syms.next();
+                            break;
+                        }
+                        arguments.push(Self::directive_value(syms)?);
+                        syms.skip_comments();
+                        if syms.peek() == Some(&&Symbol::Also) {
+                            syms.next();
+                        }
+                    }
+                    syms.skip_comments();
+                }
+                if syms.next() != Some(&Symbol::CloseParenthesis) || syms.next() != Some(&Symbol::PhraseEnd) {
+                    return Err(anyhow::Error::msg(format!(
+                        "{}: Expected `);` to end the system call.",
+                        syms.location()
+                    )));
+                }
+                Ok(Task::Syscall { number, arguments, result: None, location })
+            }
+            "unreachable" => {
+                if syms.next() != Some(&Symbol::PhraseEnd) {
+                    return Err(anyhow::Error::msg(format!(
+                        "{location}: Expected `;` following `!!unreachable`."
+                    )));
+                }
+                Ok(Task::Unreachable)
+            }
+            unknown => Err(anyhow::Error::msg(format!(
+                "{location}: The compiler directive `!!{unknown}` can't be used inside a function."
+            )))
+        }
+    }
+    /// Reads a single value given to a compiler directive.
+    fn directive_value<'a>(syms: &mut SymbolStream<'a>) -> Result<Value, anyhow::Error> {
+        syms.skip_comments();
+        if syms.peek().is_none() {
+            return Err(anyhow::Error::msg("Unexpected end of file inside a compiler directive."));
+        }
+        match Evaluatable::from_symbols(syms, Symbol::Also) {
+            Evaluatable::Value { value } => Ok(value),
+            _ => Err(anyhow::Error::msg(format!(
+                "{}: Compiler directives only take plain values.",
+                syms.location()
+            )))
+        }
+    }
+    /// Reads `let NAME = VALUE;` or `let NAME: TYPE = VALUE;`, following the
+    /// `let` itself. Values that aren't plain, such as `output.to_string()` or
+    /// `!!syscall(...)`, are evaluated into the label. Declared types are
+    /// checked by setting the label to the evaluated value.
+    fn let_statement<'a>(
+        &mut self,
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter],
+        is_unsafe: bool
+    ) -> Result<Vec<Task>, anyhow::Error> {
+        let location = syms.location();
+        let label = match syms.next() {
+            Some(Symbol::Label(label)) if !label.contains('.') => label.clone(),
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "{}: Expected a name following `let`.",
+                    syms.location()
+                )));
+            }
+        };
+        let mut type_ = None;
+        if syms.peek() == Some(&&Symbol::Is) {
+            syms.next();
+            match syms.next() {
+                Some(Symbol::Label(name)) => type_ = Some(qualified_path(syms, name)?),
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "{}: Expected a type following `let {label}:`.",
+                        syms.location()
+                    )));
+                }
             }
-            Ok(())
+        }
+        if syms.next() != Some(&Symbol::Set) {
+            return Err(anyhow::Error::msg(format!(
+                "{}: Expected `=` following `let {label}`.",
+                syms.location()
+            )));
+        }
+        syms.skip_comments();
+        let mut tasks = vec![];
+        let evaluated_label = match type_ {
+            Some(_) => String::from("compiler_ast_let_eval"),
+            None => label.clone()
+        };
+        let mut evaluated = vec![];
+        if let Some(Symbol::Compiler(directive)) = syms.peek().copied() {
+            syms.next();
+            let mut task = Self::statement_directive(syms, directive, is_unsafe)?;
+            let Task::Syscall { result, .. } = &mut task
+            else {
+                return Err(anyhow::Error::msg(format!(
+                    "{location}: `!!{directive}` doesn't give a value to set `{label}` to."
+                )));
+            };
+            *result = Some(evaluated_label.clone());
+            tasks.push(task);
         }
         else {
-            Err(anyhow::Error::msg(
-                "Expected a keyword following the compiler directive `!!` (TODO: ANNOTATIONS)"
-            ))
+            let (task, mut call_evaluated) = self.expression(syms, generics, &mut tasks)?;
+            evaluated.append(&mut call_evaluated);
+            if syms.next() != Some(&Symbol::PhraseEnd) {
+                return Err(anyhow::Error::msg(format!(
+                    "{}: Expected `;` following the value of `{label}`.",
+                    syms.location()
+                )));
+            }
+            match task {
+                Evaluatable::Value { value } => {
+                    tasks.push(Task::Set { label, type_, value, location });
+                    return Ok(tasks);
+                }
+                task => tasks.push(Task::Evaluate { label: evaluated_label.clone(), task })
+            }
+        }
+        if type_.is_some() {
+            tasks.push(Task::Set { label, type_, value: Value::Label(evaluated_label.clone()), location });
+            evaluated.push(evaluated_label);
+        }
+        for label in evaluated {
+            tasks.push(Task::FreeEvaluated { label });
+        }
+        Ok(tasks)
+    }
+    /// Reads a value, some math, or a call such as `output.to_string()`.
+    /// Arguments of the call that have to be computed first are added to
+    /// `tasks`, and the labels they were evaluated into are returned so they
+    /// can be freed once the call is made.
+    fn expression<'a>(
+        &mut self,
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter],
+        tasks: &mut Vec<Task>
+    ) -> Result<(Evaluatable, Vec<String>), anyhow::Error> {
+        if syms.peek().is_none() {
+            return Err(anyhow::Error::msg("Unexpected end of file while reading a value. (TODO: ANNOTATIONS)"));
+        }
+        let task = Evaluatable::from_symbols(syms, Symbol::PhraseEnd);
+        let Evaluatable::Value { value: Value::Label(l) } = &task
+        else {
+            return Ok((task, vec![]));
+        };
+        if !matches!(syms.peek(), Some(Symbol::Module | Symbol::LessThan | Symbol::OpenParenthesis)) {
+            return Ok((task, vec![]));
+        }
+        let location = syms.location();
+        // Functions from other namespaces, as in `shapes~area(5)`
+        let l = qualified_path(syms, l)?;
+        let call_generics = TypeIdentity::arguments_from_symbols(syms, generics)?;
+        if syms.next() != Some(&Symbol::OpenParenthesis) {
+            return Err(anyhow::Error::msg(format!(
+                "{}: Expected `(` following `{l}`.",
+                syms.location()
+            )));
         }
+        let (arguments, evaluated) = Self::call_arguments(syms, tasks);
+        Ok((Evaluatable::Call { label: self.alias_target(&l), generics: call_generics, arguments, location }, evaluated))
     }
-    fn main_scope<'a, I>(&mut self, syms: &mut Peekable<I>) -> Result<(), anyhow::Error>
-    where
-        I: Iterator<Item = &'a Symbol> {
+    /// Reads the arguments of a call, up to and including the closing
+    /// parenthesis. Arguments that have to be computed first, such as
+    /// `code - 1`, are evaluated into labels that are given to the call in
+    /// their place, which are returned so they can be freed after it.
+    fn call_arguments<'a>(syms: &mut SymbolStream<'a>, tasks: &mut Vec<Task>) -> (Vec<Value>, Vec<String>) {
+        // we should expect a comma seperated list of `Evaluatable`s now,
+        // ending with CloseParenthesis
+        let mut evaluated = vec![];
+        let mut arguments: Vec<Value> = vec![];
+        while syms.peek() != Some(&&Symbol::CloseParenthesis) {
+            // TODO: recursive function calls could have collisions...
+            let task = Evaluatable::from_symbols(syms, Symbol::Also);
+            if let Evaluatable::Value { value } = task {
+                arguments.push(value);
+            }
+            else {
+                let label = format!("compiler_ast_call_eval_{}", evaluated.len());
+                tasks.push(Task::Evaluate { label: label.clone(), task });
+                arguments.push(Value::Label(label.clone()));
+                evaluated.push(label);
+            }
+            // if we have another argument, throw away the comma between args.
+            if syms.peek() == Some(&&Symbol::Also) {
+                syms.next();
+            }
+        }
+        // throw away close parrens
+        syms.next();
+        (arguments, evaluated)
+    }
+    /// The function `name` refers to, following the aliases in this file.
+    /// (`print => stdout;`)
+    fn alias_target(&self, name: &str) -> String {
+        let mut target = name.to_string();
+        for def in &self.definitions {
+            if let Definition::Alias {
+                from: aptfr,
+                to: aptto,
+                export: _
+            } = &def {
+                if name == aptfr {
+                    target = aptto.clone();
+                }
+            }
+        }
+        target
+    }
+    /// Reads one side of the condition of an `if`, which has to be a plain
+    /// value.
+    fn condition_value<'a>(syms: &mut SymbolStream<'a>) -> Result<Value, anyhow::Error> {
+        if syms.peek().is_none() {
+            return Err(anyhow::Error::msg("Unexpected end of file inside the condition of an `if`. (TODO: ANNOTATIONS)"));
+        }
+        match Evaluatable::from_symbols(syms, Symbol::Equals) {
+            Evaluatable::Value { value } => Ok(value),
+            _ => Err(anyhow::Error::msg(format!(
+                "{}: Only plain values can be compared in an `if` yet.",
+                syms.location()
+            )))
+        }
+    }
+    fn main_scope<'a>(&mut self, syms: &mut SymbolStream<'a>) -> Result<(), anyhow::Error> {
+        let mut tasks = self.code_block(syms, &[], false)?;
+        self.main_tasks.append(&mut tasks);
+        Ok(())
+    }
+    /// Reads the statements of a code block up to and including its closing
+    /// brace. `generics` lists the generic parameters in scope, and
+    /// `is_unsafe` is set inside `unsafe` blocks.
+    fn code_block<'a>(
+        &mut self,
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter],
+        is_unsafe: bool
+    ) -> Result<Vec<Task>, anyhow::Error> {
+        let mut tasks = vec![];
         while syms.peek().is_some() {
             match syms.next().unwrap() {
                 Symbol::Comment(_) | Symbol::Comments(_) => {},
+                Symbol::Label(l) if l == "unsafe" && syms.peek() == Some(&&Symbol::OpenBrace) => {
+                    // `unsafe` only changes what's allowed inside it, so its
+                    // tasks are run as part of this block.
+                    syms.next();
+                    let mut unsafe_tasks = self.code_block(syms, generics, true)?;
+                    tasks.append(&mut unsafe_tasks);
+                }
+                Symbol::Compiler(directive) => {
+                    tasks.push(Self::statement_directive(syms, directive, is_unsafe)?);
+                }
+                Symbol::Label(l) if l == "if" => {
+                    let location = syms.location();
+                    let a = Self::condition_value(syms)?;
+                    if syms.next() != Some(&Symbol::Equals) {
+                        return Err(anyhow::Error::msg(format!(
+                            "{}: Only `==` can be used in the condition of an `if` yet.",
+                            syms.location()
+                        )));
+                    }
+                    let b = Self::condition_value(syms)?;
+                    if syms.next() != Some(&Symbol::OpenBrace) {
+                        return Err(anyhow::Error::msg(format!(
+                            "{}: Expected `{{` following the condition of an `if`.",
+                            syms.location()
+                        )));
+                    }
+                    let if_tasks = self.code_block(syms, generics, is_unsafe)?;
+                    tasks.push(Task::If { a, b, tasks: if_tasks, location });
+                }
                 Symbol::Label(l) => {
+                    let location = syms.location();
+                    // Functions from other namespaces, as in `shapes~area(5);`
+                    let l = &qualified_path(syms, l)?;
+                    // Explicit generic arguments, as in `convert<u8>(5);`
+                    let call_generics = TypeIdentity::arguments_from_symbols(syms, generics)?;
                     match syms.peek() {
                         Some(Symbol::OpenParenthesis) => {
                             // This is a function call!
                             // throw away open parrens
                             syms.next();
-                            // we should expect a comma seperated list of `Evaluatable`s now,
-                            // ending with CloseParenthesis, PhraseEnd
-                            let mut eval_idx = 0;
-                            let mut not_eval = vec![];
-                            while syms.peek() != Some(&&Symbol::CloseParenthesis) {
-                                // TODO: recursive function calls could have collisions...
-                                // TODO: doc this weird shit or improve it
-                                let task = Evaluatable::from_symbols(syms, Symbol::Also);
-                                if let Evaluatable::Value { value } = task {
-                                    not_eval.push(Some(value));
-                                    // if we have another argument, throw away the comma between args.
-                                    if syms.peek() == Some(&&Symbol::Also) {
-                                        syms.next();
-                                    }
-                                    continue;
-                                }
-                                self.main_tasks.push(Task::Evaluate {
-                                    label: format!("compiler_ast_call_eval_{}", eval_idx),
-                                    task
-                                });
-                                not_eval.push(None);
-                                eval_idx += 1;
-                                // if we have another argument, throw away the comma between args.
-                                if syms.peek() == Some(&&Symbol::Also) {
-                                    syms.next();
-                                }
-                            }
-                            // throw away close parrens
-                            syms.next();
+                            let (arguments, evaluated) = Self::call_arguments(syms, &mut tasks);
                             // last sym should be PhraseEnd
                             if syms.peek() != Some(&&Symbol::PhraseEnd) {
-                                // if it's not, panic
-                                panic!("Expected `;` following a function call. (TODO: ANNOTATIONS)");
+                                return Err(anyhow::Error::msg(format!(
+                                    "{}: Expected `;` following a function call.",
+                                    syms.location()
+                                )));
                             }
                             // throw away PhraseEnd
                             syms.next();
-                            let mut arguments: Vec<Value> = vec![];
-                            for (idx, i) in not_eval.iter().enumerate() {
-                                if let Some(val) = i {
-                                    arguments.push(val.clone());
-                                }
-                                else {
-                                    arguments.push(Value::Label(format!("compiler_ast_call_eval_{idx}")));
-                                }
-                            }
-                            let mut target = l.clone();
-                            for def in &self.definitions {
-                                if let Definition::Alias {
-                                    from: aptfr,
-                                    to: aptto,
-                                    export: _
-                                } = &def {
-                                    if l == aptfr {
-                                        target = aptto.clone();
-                                    }
-                                }
-                            }
-                            self.main_tasks.push(Task::Call { function_identifier: target.clone(), arguments });
-                            for (idx, i) in not_eval.iter().enumerate() {
-                                if i.is_none() {
-                                    self.main_tasks.push(
-                                        Task::FreeEvaluated {
-                                            label: format!("compiler_ast_call_eval_{idx}")
-                                        }
-                                    );
-                                }
+                            tasks.push(Task::Call {
+                                function_identifier: self.alias_target(l),
+                                generics: call_generics,
+                                arguments,
+                                location
+                            });
+                            for label in evaluated {
+                                tasks.push(Task::FreeEvaluated { label });
                             }
                         }
                         Some(Symbol::PhraseEnd) => {
@@ -269,40 +924,133 @@ impl Program {
                             // throw away phrase end
                             syms.next();
                             // add to tasks
-                            self.main_tasks.push(Task::Call {
+                            tasks.push(Task::Call {
                                 function_identifier: l.clone(),
-                                arguments: vec![]
+                                generics: call_generics,
+                                arguments: vec![],
+                                location
                             });
                         }
-                        Some(sym) => { todo!("TODO ICE Label(_) -> sym ({:?})", sym) }
-                        None => { panic!("Abrupt EOF before closing the `main` segment, and before terminating a line.") }
+                        Some(sym) => {
+                            return Err(anyhow::Error::msg(format!(
+                                "{}: Unexpected symbol {:?} following `{l}`.",
+                                syms.location(), sym
+                            )));
+                        }
+                        None => {
+                            return Err(anyhow::Error::msg(
+                                "Abrupt EOF before closing the `main` segment, and before terminating a line. (TODO: ANNOTATIONS)"
+                            ));
+                        }
                     }
                 }
                 Symbol::CloseBrace => {
-                    // end of main block
-                    return Ok(());
+                    // end of block
+                    return Ok(tasks);
                 }
                 Symbol::Keyword(sym_kywrd) => {
                     match sym_kywrd {
                         Keyword::Kreturn => {
-                            // return from main
-                            // TODO: may not work inside deeper blocks
-                            // should be followed by a PhraseEnd
-                            if syms.peek() != Some(&&Symbol::PhraseEnd) {
-                                panic!("Expected `;` following keyword `return`.");
+                            // return from this block's function
+                            let location = syms.location();
+                            if syms.peek() == Some(&&Symbol::PhraseEnd) {
+                                // throw away PhraseEnd
+                                syms.next();
+                                tasks.push(Task::ExitBlock);
+                                continue;
                             }
-                            // throw away PhraseEnd
-                            syms.next();
-                            self.main_tasks.push(Task::ExitBlock);
+                            // Returned values that have to be computed first
+                            // are evaluated into a label that's returned in
+                            // their place.
+                            let (task, evaluated) = self.expression(syms, generics, &mut tasks)?;
+                            let value = match task {
+                                Evaluatable::Value { value } => value,
+                                task => {
+                                    let label = String::from("compiler_ast_return_eval");
+                                    tasks.push(Task::Evaluate { label: label.clone(), task });
+                                    Value::Label(label)
+                                }
+                            };
+                            if syms.next() != Some(&Symbol::PhraseEnd) {
+                                return Err(anyhow::Error::msg(format!(
+                                    "{}: Expected `;` following the value returned.",
+                                    syms.location()
+                                )));
+                            }
+                            tasks.push(Task::Return { value, location });
+                            for label in evaluated {
+                                tasks.push(Task::FreeEvaluated { label });
+                            }
+                        }
+                        Keyword::Klet => {
+                            let mut let_tasks = self.let_statement(syms, generics, is_unsafe)?;
+                            tasks.append(&mut let_tasks);
+                        }
+                        keyword => {
+                            return Err(anyhow::Error::msg(format!(
+                                "{}: Keyword {:?} can't be used in a code block yet.",
+                                syms.location(), keyword
+                            )));
                         }
-                        _ => todo!()
                     }
                 }
-                sym => { todo!("TODO ICE sym ({:?})", sym) }
+                sym => {
+                    return Err(anyhow::Error::msg(format!(
+                        "{}: Unexpected symbol {:?} in a code block.",
+                        syms.location(), sym
+                    )));
+                }
             }
         }
         return Err(anyhow::Error::msg(
-            "Expected a } to close the main block before the end of the file. (TODO: ANNOTATIONS)"
+            "Expected a } to close the block before the end of the file. (TODO: ANNOTATIONS)"
         ));
     }
 }
+
+/// Skips the rest of a method written as a closure, up to and including the
+/// closing brace of its body and any `;` after it. Returns false if the file
+/// ends first.
+fn skip_member<'a>(syms: &mut SymbolStream<'a>) -> bool {
+    let mut depth = 0;
+    loop {
+        match syms.next() {
+            None => return false,
+            Some(Symbol::OpenBrace) => depth += 1,
+            Some(Symbol::CloseBrace) if depth == 0 => return false,
+            Some(Symbol::CloseBrace) => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    if syms.peek() == Some(&&Symbol::PhraseEnd) {
+        syms.next();
+    }
+    return true;
+}
+
+/// Reads the rest of a path such as `shapes~circle~area`, given its first
+/// part. Paths without a `~` are just `first`.
+fn qualified_path<'a>(syms: &mut SymbolStream<'a>, first: &str) -> Result<String, anyhow::Error> {
+    let mut path = first.to_string();
+    while syms.peek() == Some(&&Symbol::Module) {
+        syms.next();
+        match syms.next() {
+            Some(Symbol::Label(part)) => {
+                path += "~";
+                path += part;
+            }
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "{}: Expected a name following a module seperator. (`~`)",
+                    syms.location()
+                )));
+            }
+        }
+    }
+    Ok(path)
+}
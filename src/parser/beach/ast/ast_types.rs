@@ -1,59 +1,156 @@
-use std::iter::Peekable;
-
 use crate::utils::*;
-use crate::parser::beach::ast::user_token_format::Symbol;
+use crate::parser::beach::ast::user_token_format::{Annotation, Symbol, SymbolStream};
+use crate::parser::beach::ir::Operator;
 
 /// Describes a generic definition of something important to the program.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Definition {
-    /// A standard library import from beach/std/core/*
+    /// A standard library import, or a platform feature. (`include io:stdout;`)
     System { label: String },
-    /// A project file import from the root file
-    File { label: String },
-    /// An external library from the TODO(beach package manager)
-    Library { name: String, version: String },
+    /// A project file import, relative to the importing file. (`file utils:math;`
+    /// or `file shapes:*;`)
+    File { label: String, location: Annotation },
+    /// An import from the core library, relative to its folder.
+    /// (`!!core types;`)
+    Core { label: String, location: Annotation },
+    /// A library project listed in `beach.toml`, optionally with a version
+    /// requirement it has to meet. (`library geometry;` or
+    /// `library geometry "1.2";`) The version is empty if none was given.
+    Library { name: String, version: String, location: Annotation },
     Alias { from: String, to: String, export: bool },
     GlobalConstant { label: String, value: Value },
     Function(Function),
     Type {
         name: String,
-        data: Vec<(String, TypeIdentity)>,
-        methods: Vec<Function>
+        generics: Vec<GenericParameter>,
+        /// Each field, its type, and where it was declared.
+        data: Vec<(String, TypeIdentity, Annotation)>,
+        methods: Vec<Function>,
+        implementations: Vec<Implementation>,
+        /// The file this type was written in, once it's known.
+        file: Option<std::path::PathBuf>
+    },
+    Enum {
+        name: String,
+        generics: Vec<GenericParameter>,
+        /// Each case, the type of the data it carries if any, and where it
+        /// was declared.
+        cases: Vec<(String, Option<TypeIdentity>, Annotation)>,
+        methods: Vec<Function>,
+        implementations: Vec<Implementation>,
+        /// The file this enum was written in, once it's known.
+        file: Option<std::path::PathBuf>
     },
-    Trait { name: String, methods: Vec<Function> }
+    /// `requires` lists traits that anything implementing this trait must
+    /// also implement. (`public trait printable;` inside a trait)
+    Trait { name: String, requires: Vec<String>, methods: Vec<Function> }
+}
+
+impl Definition {
+    /// The name this definition can be refered to by, if it has one.
+    pub fn name(&self) -> Option<&String> {
+        match self {
+            Definition::Alias { from: name, .. } |
+            Definition::GlobalConstant { label: name, .. } |
+            Definition::Function(Function { name, .. }) |
+            Definition::Type { name, .. } |
+            Definition::Enum { name, .. } |
+            Definition::Trait { name, .. } => Some(name),
+            Definition::System { .. } | Definition::File { .. } |
+            Definition::Core { .. } | Definition::Library { .. } => None
+        }
+    }
+    pub fn name_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Definition::Alias { from: name, .. } |
+            Definition::GlobalConstant { label: name, .. } |
+            Definition::Function(Function { name, .. }) |
+            Definition::Type { name, .. } |
+            Definition::Enum { name, .. } |
+            Definition::Trait { name, .. } => Some(name),
+            Definition::System { .. } | Definition::File { .. } |
+            Definition::Core { .. } | Definition::Library { .. } => None
+        }
+    }
 }
 
 /// Describes a task to be completed by the program at runtime.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Task {
-    Set { label: String, type_: Option<String>, value: Value },
-    Call { function_identifier: String, arguments: Vec<Value> },
+    /// Names a plain value, as in `let code: u8 = 5;`. `type_` is the type it
+    /// was declared as, if any.
+    Set { label: String, type_: Option<String>, value: Value, location: Annotation },
+    /// `generics` holds any explicitly provided generic arguments, such as
+    /// `u8` in `convert<u8>(5);`. Missing ones are inferred from `arguments`.
+    Call {
+        function_identifier: String,
+        generics: Vec<TypeIdentity>,
+        arguments: Vec<Value>,
+        location: Annotation
+    },
     Evaluate { label: String, task: Evaluatable },
     FreeEvaluated { label: String },
     ExitBlock,
+    /// Returns `value` from the function, as in `return self;`.
+    Return { value: Value, location: Annotation },
+    /// Runs `tasks` only if `a == b`, as in `if rv == -1 { ... }`.
+    If { a: Value, b: Value, tasks: Vec<Task>, location: Annotation },
+    /// Asks the operating system to do something, as in
+    /// `!!syscall(4, (1, output.pointer, output.length));`. Only allowed inside
+    /// `unsafe` blocks. `result` names what the system call returned, as in
+    /// `let rv = !!syscall(...);`.
+    Syscall { number: Value, arguments: Vec<Value>, result: Option<String>, location: Annotation },
+    /// A point that execution should never reach. (`!!unreachable;`)
+    Unreachable,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Evaluatable {
-    Call { label: String, arguments: Vec<Value> },
-    Math { a: Value, b: Value },
+    /// What calling `label` returns, as in `output.to_string()`. `generics`
+    /// works as it does in [Task::Call].
+    Call { label: String, generics: Vec<TypeIdentity>, arguments: Vec<Value>, location: Annotation },
+    /// `a operator b`, as in `code - 1`.
+    Math { operator: Operator, a: Value, b: Value },
     Value { value: Value }
 }
 
 impl Evaluatable {
-    pub fn from_symbols<'a, I>(syms: &mut Peekable<I>, end: Symbol) -> Self
-    where
-        I: Iterator<Item = &'a Symbol> {
+    pub fn from_symbols<'a>(syms: &mut SymbolStream<'a>, end: Symbol) -> Self {
+        let value = Self::value_from_symbols(syms);
+        let operator = match syms.peek() {
+            Some(Symbol::Add) => Operator::Add,
+            Some(Symbol::Subtract) => Operator::Subtract,
+            Some(Symbol::Multiply) => Operator::Multiply,
+            _ => return Self::Value { value }
+        };
+        syms.next();
+        return Self::Math { operator, a: value, b: Self::value_from_symbols(syms) };
+    }
+    fn value_from_symbols<'a>(syms: &mut SymbolStream<'a>) -> Value {
         match syms.next().expect("Called with null sym, should be impossible") {
             Symbol::String(symstr) => {
-                return Self::Value { value: Value::String(symstr.clone()) }
+                return Value::String(symstr.clone())
             }
             Symbol::Integer(symint) => {
-                return Self::Value { value: Value::Integer(symint.clone()) }
+                return Value::Integer(symint.clone())
+            }
+            Symbol::Float(symfloat) => {
+                return Value::Float(symfloat.clone())
+            }
+            Symbol::Complex(symcplx) => {
+                return Value::Complex(symcplx.clone())
+            }
+            Symbol::Label(symlabel) => {
+                return Value::Label(symlabel.clone())
+            }
+            // negative numbers, as in `-1`
+            Symbol::Subtract => match syms.next() {
+                Some(Symbol::Integer(symint)) => return Value::Integer(-symint),
+                Some(Symbol::Float(symfloat)) => return Value::Float(-symfloat),
+                sym => todo!("evaluatble from_symbols negated sym ({:?})", sym)
             }
             sym => todo!("evaluatble from_symbols sym ({:?})", sym)
         }
-        todo!();
     }
 }
 
@@ -68,12 +165,18 @@ pub enum Value {
     Label(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
+    pub generics: Vec<GenericParameter>,
     pub arguments: Vec<FunctionArgument>,
     pub returns: TypeIdentity,
-    pub code: Vec<Task>
+    pub code: Vec<Task>,
+    /// Where the closure defining this function starts.
+    pub location: Annotation,
+    /// The file this function was written in, once it's known. (see
+    /// [crate::modules::ModuleGraph])
+    pub file: Option<std::path::PathBuf>
 }
 
 #[derive(Debug, Clone)]
@@ -82,18 +185,516 @@ pub struct FunctionArgument {
     pub arg_type: TypeIdentity,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The compiler types from the spec's type table. (F.5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PrimitiveType {
     ArchUnsigned, // usize
     ArchSigned, // isize
-    U8, // u8
-    I8, // i8
+    U8, U16, U32, U64, U128, U256, U512, // u8-u512
+    I8, I16, I32, I64, I128, I256, I512, // i8-i512
+    F16, F32, F64, F128, // f16-f128
+    C32, C64, C128, C256, // c32-c256
+    Nothing, // nothing, ()
+    Never, // never, !
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypeIdentity {
     Primitive(PrimitiveType),
     Trait(String),
     Enum(String),
     Structured(String),
+    /// A generic parameter of the surrounding type or function, such as `G` in
+    /// `result<G, E>`.
+    Generic(String),
+    /// A generic type or enum given generic arguments, such as `result<u8, E>`.
+    Instance { name: String, arguments: Vec<TypeIdentity> },
+    /// A function type, such as `|in: G| -> N`.
+    Closure { arguments: Vec<TypeIdentity>, returns: Box<TypeIdentity> },
+}
+
+/// A generic parameter on a type, enum or function, along with the traits any
+/// type substituted in for it must implement.
+///
+/// `enum result<G, E> where E: error` has the two parameters `G` (with no
+/// constraints) and `E` (constrained to `error`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericParameter {
+    pub name: String,
+    pub constraints: Vec<String>,
+}
+
+/// A trait implemented by a type or enum, such as `public trait printable;`.
+///
+/// `requirements` lists extra constraints on the implementor's generic
+/// parameters that must hold for this implementation to apply, which is how
+/// `extension where G: printable { ... }` is stored.
+#[derive(Debug, Clone)]
+pub struct Implementation {
+    pub trait_name: String,
+    pub requirements: Vec<GenericParameter>,
+    pub methods: Vec<Function>,
+}
+
+impl PrimitiveType {
+    const ALL: &[PrimitiveType] = &[
+        Self::ArchUnsigned, Self::ArchSigned,
+        Self::U8, Self::U16, Self::U32, Self::U64, Self::U128, Self::U256, Self::U512,
+        Self::I8, Self::I16, Self::I32, Self::I64, Self::I128, Self::I256, Self::I512,
+        Self::F16, Self::F32, Self::F64, Self::F128,
+        Self::C32, Self::C64, Self::C128, Self::C256,
+        Self::Nothing, Self::Never,
+    ];
+    /// Finds the primitive type with the name `name`, if there is one.
+    pub fn from_name(name: &str) -> Option<PrimitiveType> {
+        Self::ALL.iter().find(|primitive| primitive.name() == name).copied()
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ArchUnsigned => "usize",
+            Self::ArchSigned => "isize",
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::U128 => "u128",
+            Self::U256 => "u256",
+            Self::U512 => "u512",
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::I128 => "i128",
+            Self::I256 => "i256",
+            Self::I512 => "i512",
+            Self::F16 => "f16",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+            Self::F128 => "f128",
+            Self::C32 => "c32",
+            Self::C64 => "c64",
+            Self::C128 => "c128",
+            Self::C256 => "c256",
+            Self::Nothing => "nothing",
+            Self::Never => "never",
+        }
+    }
+    /// The width of this type, in bits. `usize` and `isize` depend on the
+    /// platform, so they return [None].
+    pub fn bit_width(&self) -> Option<usize> {
+        match self {
+            Self::ArchUnsigned | Self::ArchSigned => None,
+            Self::U8 | Self::I8 => Some(8),
+            Self::U16 | Self::I16 | Self::F16 => Some(16),
+            Self::U32 | Self::I32 | Self::F32 | Self::C32 => Some(32),
+            Self::U64 | Self::I64 | Self::F64 | Self::C64 => Some(64),
+            Self::U128 | Self::I128 | Self::F128 | Self::C128 => Some(128),
+            Self::U256 | Self::I256 | Self::C256 => Some(256),
+            Self::U512 | Self::I512 => Some(512),
+            Self::Nothing | Self::Never => Some(0),
+        }
+    }
+    pub fn is_integer(&self) -> bool {
+        self.is_signed() || matches!(
+            self,
+            Self::ArchUnsigned | Self::U8 | Self::U16 | Self::U32 |
+            Self::U64 | Self::U128 | Self::U256 | Self::U512
+        )
+    }
+    /// Whether this is a signed integer type.
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Self::ArchSigned | Self::I8 | Self::I16 | Self::I32 |
+            Self::I64 | Self::I128 | Self::I256 | Self::I512
+        )
+    }
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::F16 | Self::F32 | Self::F64 | Self::F128)
+    }
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Self::C32 | Self::C64 | Self::C128 | Self::C256)
+    }
+    /// The built-in traits this type implements.
+    pub fn traits(&self) -> &'static [&'static str] {
+        if self.is_integer() {
+            &["integer", "number", "printable"]
+        }
+        else if self.is_float() {
+            &["float", "number", "printable"]
+        }
+        else if self.is_complex() {
+            &["complex", "printable"]
+        }
+        else {
+            &[]
+        }
+    }
+}
+
+impl TypeIdentity {
+    /// Converts a lone type name into a [TypeIdentity]. `generics` lists the
+    /// generic parameters that are in scope where the name was found.
+    ///
+    /// Names that aren't primitives or generic parameters are assumed to be
+    /// [TypeIdentity::Structured] until they are resolved against the
+    /// program's declarations.
+    pub fn from_name(name: &str, generics: &[GenericParameter]) -> TypeIdentity {
+        if let Some(primitive) = PrimitiveType::from_name(name) {
+            TypeIdentity::Primitive(primitive)
+        }
+        else if generics.iter().any(|generic| generic.name == name) {
+            TypeIdentity::Generic(name.to_string())
+        }
+        else {
+            TypeIdentity::Structured(name.to_string())
+        }
+    }
+    /// Reads a type such as `u8`, `result<G, E>` or `|in: G| -> N` from `syms`.
+    pub fn from_symbols<'a>(
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter]
+    ) -> Result<TypeIdentity, anyhow::Error> {
+        let (read, closed_outer) = TypeIdentity::from_symbols_nested(syms, generics)?;
+        if closed_outer {
+            return Err(anyhow::Error::msg(
+                "Unexpected `>>` following a type. (TODO: ANNOTATIONS)"
+            ));
+        }
+        Ok(read)
+    }
+    /// Reads an optional list of generic arguments, such as `<u8, string>`.
+    /// Returns an empty list if `syms` doesn't start with `<`.
+    pub fn arguments_from_symbols<'a>(
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter]
+    ) -> Result<Vec<TypeIdentity>, anyhow::Error> {
+        if syms.peek() != Some(&&Symbol::LessThan) {
+            return Ok(vec![]);
+        }
+        let (arguments, closed_outer) = TypeIdentity::arguments_from_symbols_nested(syms, generics)?;
+        if closed_outer {
+            return Err(anyhow::Error::msg(
+                "Unexpected `>>` closing a list of generic arguments. (TODO: ANNOTATIONS)"
+            ));
+        }
+        Ok(arguments)
+    }
+    // The lexer reads the end of `result<u8, maybe<u8>>` as one `>>`, so the
+    // nested readers also return whether they used up the `>` belonging to
+    // the list they're inside of.
+    fn from_symbols_nested<'a>(
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter]
+    ) -> Result<(TypeIdentity, bool), anyhow::Error> {
+        match syms.next() {
+            Some(Symbol::Label(name)) => {
+                // Types from other namespaces, as in `shapes~circle`
+                let name = &super::qualified_path(syms, name)?;
+                if syms.peek() != Some(&&Symbol::LessThan) {
+                    return Ok((TypeIdentity::from_name(name, generics), false));
+                }
+                let (arguments, closed_outer) = TypeIdentity::arguments_from_symbols_nested(syms, generics)?;
+                Ok((TypeIdentity::Instance { name: name.clone(), arguments }, closed_outer))
+            }
+            Some(Symbol::Closure(inner)) => {
+                let arguments = FunctionArgument::list_from_symbols(inner, syms.location(), generics, None)?
+                    .into_iter()
+                    .map(|argument| argument.arg_type)
+                    .collect();
+                if syms.peek() != Some(&&Symbol::Becomes) {
+                    return Ok((TypeIdentity::Closure {
+                        arguments,
+                        returns: Box::new(TypeIdentity::Primitive(PrimitiveType::Nothing))
+                    }, false));
+                }
+                syms.next();
+                let (returns, closed_outer) = TypeIdentity::from_symbols_nested(syms, generics)?;
+                Ok((TypeIdentity::Closure { arguments, returns: Box::new(returns) }, closed_outer))
+            }
+            Some(sym) => Err(anyhow::Error::msg(format!(
+                "Expected a type, found {:?}. (TODO: ANNOTATIONS)", sym
+            ))),
+            None => Err(anyhow::Error::msg(
+                "Unexpected end of file while reading a type. (TODO: ANNOTATIONS)"
+            ))
+        }
+    }
+    fn arguments_from_symbols_nested<'a>(
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter]
+    ) -> Result<(Vec<TypeIdentity>, bool), anyhow::Error> {
+        // throw away <
+        syms.next();
+        let mut arguments = vec![];
+        loop {
+            let (argument, closed_this) = TypeIdentity::from_symbols_nested(syms, generics)?;
+            arguments.push(argument);
+            if closed_this {
+                return Ok((arguments, false));
+            }
+            match syms.next() {
+                Some(Symbol::Also) => {},
+                Some(Symbol::MoreThan) => return Ok((arguments, false)),
+                Some(Symbol::RightShift) => return Ok((arguments, true)),
+                _ => {
+                    return Err(anyhow::Error::msg(
+                        "Expected `,` or `>` in a list of generic arguments. (TODO: ANNOTATIONS)"
+                    ));
+                }
+            }
+        }
+    }
+    /// Reads the optional `-> TYPE` following a closure's arguments. Closures
+    /// without one return `nothing`.
+    pub fn return_from_symbols<'a>(
+        syms: &mut SymbolStream<'a>,
+        generics: &[GenericParameter]
+    ) -> Result<TypeIdentity, anyhow::Error> {
+        if syms.peek() == Some(&&Symbol::Becomes) {
+            syms.next();
+            TypeIdentity::from_symbols(syms, generics)
+        }
+        else {
+            Ok(TypeIdentity::Primitive(PrimitiveType::Nothing))
+        }
+    }
+    /// Returns this type with every generic parameter replaced by its entry in
+    /// `substitutions`. Parameters without an entry are left as they are.
+    pub fn substitute(&self, substitutions: &[(String, TypeIdentity)]) -> TypeIdentity {
+        match self {
+            TypeIdentity::Generic(name) => {
+                for (generic, concrete) in substitutions {
+                    if generic == name {
+                        return concrete.clone();
+                    }
+                }
+                self.clone()
+            }
+            TypeIdentity::Instance { name, arguments } => TypeIdentity::Instance {
+                name: name.clone(),
+                arguments: arguments.iter().map(|arg| arg.substitute(substitutions)).collect()
+            },
+            TypeIdentity::Closure { arguments, returns } => TypeIdentity::Closure {
+                arguments: arguments.iter().map(|arg| arg.substitute(substitutions)).collect(),
+                returns: Box::new(returns.substitute(substitutions))
+            },
+            _ => self.clone()
+        }
+    }
+    /// Whether this type still refers to a generic parameter anywhere.
+    pub fn is_generic(&self) -> bool {
+        match self {
+            TypeIdentity::Generic(_) => true,
+            TypeIdentity::Instance { arguments, .. } => arguments.iter().any(|arg| arg.is_generic()),
+            TypeIdentity::Closure { arguments, returns } => {
+                arguments.iter().any(|arg| arg.is_generic()) || returns.is_generic()
+            }
+            _ => false
+        }
+    }
+}
+
+impl std::fmt::Display for TypeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeIdentity::Primitive(primitive) => write!(f, "{}", primitive.name()),
+            TypeIdentity::Trait(name) | TypeIdentity::Enum(name) |
+            TypeIdentity::Structured(name) | TypeIdentity::Generic(name) => write!(f, "{name}"),
+            TypeIdentity::Instance { name, arguments } => {
+                write!(f, "{name}<")?;
+                for (idx, argument) in arguments.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{argument}")?;
+                }
+                write!(f, ">")
+            }
+            TypeIdentity::Closure { arguments, returns } => {
+                write!(f, "|")?;
+                for (idx, argument) in arguments.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{argument}")?;
+                }
+                write!(f, "| -> {returns}")
+            }
+        }
+    }
+}
+
+impl FunctionArgument {
+    /// Reads the arguments of a closure, such as `&self, mapper: |in: G| -> N`.
+    /// `self_type` is the type `&self` refers to, if the closure is a method.
+    /// `location` is where the closure itself starts.
+    pub fn list_from_symbols(
+        symbols: &[Symbol],
+        location: Annotation,
+        generics: &[GenericParameter],
+        self_type: Option<&TypeIdentity>
+    ) -> Result<Vec<FunctionArgument>, anyhow::Error> {
+        let mut syms = SymbolStream::unannotated(symbols, location);
+        let mut arguments = vec![];
+        while syms.peek().is_some() {
+            match syms.next().unwrap() {
+                Symbol::BitAnd => {
+                    if syms.next() != Some(&Symbol::Label(String::from("self"))) {
+                        return Err(anyhow::Error::msg(
+                            "Expected `self` following `&` in a closure's arguments. (TODO: ANNOTATIONS)"
+                        ));
+                    }
+                    if let Some(self_type) = self_type {
+                        arguments.push(FunctionArgument {
+                            name: String::from("self"),
+                            arg_type: self_type.clone()
+                        });
+                    }
+                    else {
+                        return Err(anyhow::Error::msg(
+                            "`&self` can only be used in methods. (TODO: ANNOTATIONS)"
+                        ));
+                    }
+                }
+                Symbol::Label(name) => {
+                    if syms.next() != Some(&Symbol::Is) {
+                        return Err(anyhow::Error::msg(format!(
+                            "Expected `:` and a type following the argument `{name}`. (TODO: ANNOTATIONS)"
+                        )));
+                    }
+                    let arg_type = TypeIdentity::from_symbols(&mut syms, generics)?;
+                    arguments.push(FunctionArgument { name: name.clone(), arg_type });
+                }
+                sym => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Unexpected symbol {:?} in a closure's arguments. (TODO: ANNOTATIONS)", sym
+                    )));
+                }
+            }
+            // arguments are seperated by commas
+            match syms.next() {
+                Some(Symbol::Also) | None => {},
+                Some(sym) => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Expected `,` between closure arguments, found {:?}. (TODO: ANNOTATIONS)", sym
+                    )));
+                }
+            }
+        }
+        Ok(arguments)
+    }
+}
+
+impl GenericParameter {
+    /// Reads an optional list of generic parameters, such as `<G, E: error>`.
+    /// Returns an empty list if `syms` doesn't start with `<`.
+    pub fn list_from_symbols<'a>(syms: &mut SymbolStream<'a>) -> Result<Vec<GenericParameter>, anyhow::Error> {
+        let mut generics = vec![];
+        if syms.peek() != Some(&&Symbol::LessThan) {
+            return Ok(generics);
+        }
+        // throw away <
+        syms.next();
+        loop {
+            if let Some(Symbol::Label(name)) = syms.next() {
+                if generics.iter().any(|generic: &GenericParameter| &generic.name == name) {
+                    return Err(anyhow::Error::msg(format!(
+                        "The generic parameter `{name}` is declared twice. (TODO: ANNOTATIONS)"
+                    )));
+                }
+                let mut constraints = vec![];
+                if syms.peek() == Some(&&Symbol::Is) {
+                    syms.next();
+                    constraints = GenericParameter::constraints_from_symbols(syms)?;
+                }
+                generics.push(GenericParameter { name: name.clone(), constraints });
+            }
+            else {
+                return Err(anyhow::Error::msg(
+                    "Expected the name of a generic parameter. (TODO: ANNOTATIONS)"
+                ));
+            }
+            match syms.next() {
+                Some(Symbol::Also) => {},
+                Some(Symbol::MoreThan) => break,
+                _ => {
+                    return Err(anyhow::Error::msg(
+                        "Expected `,` or `>` in a list of generic parameters. (TODO: ANNOTATIONS)"
+                    ));
+                }
+            }
+        }
+        Ok(generics)
+    }
+    /// Reads an optional where clause, such as `where E: error, G: printable`,
+    /// returning each constrained name and the traits it must implement.
+    pub fn where_from_symbols<'a>(syms: &mut SymbolStream<'a>) -> Result<Vec<GenericParameter>, anyhow::Error> {
+        let mut clauses = vec![];
+        if syms.peek() != Some(&&Symbol::Label(String::from("where"))) {
+            return Ok(clauses);
+        }
+        // throw away `where`
+        syms.next();
+        loop {
+            if let Some(Symbol::Label(name)) = syms.next() {
+                if syms.next() != Some(&Symbol::Is) {
+                    return Err(anyhow::Error::msg(format!(
+                        "Expected `:` following `{name}` in a where clause. (TODO: ANNOTATIONS)"
+                    )));
+                }
+                let constraints = GenericParameter::constraints_from_symbols(syms)?;
+                clauses.push(GenericParameter { name: name.clone(), constraints });
+            }
+            else {
+                return Err(anyhow::Error::msg(
+                    "Expected the name of a generic parameter in a where clause. (TODO: ANNOTATIONS)"
+                ));
+            }
+            if syms.peek() == Some(&&Symbol::Also) {
+                syms.next();
+            }
+            else {
+                break;
+            }
+        }
+        Ok(clauses)
+    }
+    /// Applies the clauses of a where clause to the generic parameters they
+    /// constrain.
+    pub fn apply_where(generics: &mut [GenericParameter], clauses: Vec<GenericParameter>) -> Result<(), anyhow::Error> {
+        for clause in clauses {
+            if let Some(generic) = generics.iter_mut().find(|generic| generic.name == clause.name) {
+                generic.constraints.extend(clause.constraints);
+            }
+            else {
+                return Err(anyhow::Error::msg(format!(
+                    "The where clause constrains `{}`, which is not a generic parameter here. (TODO: ANNOTATIONS)",
+                    clause.name
+                )));
+            }
+        }
+        Ok(())
+    }
+    /// Reads one or more trait names joined by `+`.
+    fn constraints_from_symbols<'a>(syms: &mut SymbolStream<'a>) -> Result<Vec<String>, anyhow::Error> {
+        let mut constraints = vec![];
+        loop {
+            if let Some(Symbol::Label(trait_name)) = syms.next() {
+                constraints.push(trait_name.clone());
+            }
+            else {
+                return Err(anyhow::Error::msg(
+                    "Expected a trait name in a generic constraint. (TODO: ANNOTATIONS)"
+                ));
+            }
+            if syms.peek() == Some(&&Symbol::Add) {
+                syms.next();
+            }
+            else {
+                break;
+            }
+        }
+        Ok(constraints)
+    }
 }
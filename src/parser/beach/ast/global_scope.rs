@@ -1,22 +1,113 @@
-use std::iter::Peekable;
-
-use crate::parser::beach::ast::user_token_format::Symbol;
+use crate::parser::beach::ast::user_token_format::{Symbol, SymbolStream};
 use crate::parser::beach::ast::user_token_format::keywords::Keyword;
 
 use super::Program;
 use super::ast_types::*;
 
-pub fn global_scope<'a, I>(
+pub fn global_scope<'a>(
     program: &mut Program,
-    syms: &mut Peekable<I>
-) -> Result<(), anyhow::Error>
-where
-    I: Iterator<Item = &'a Symbol> {
+    syms: &mut SymbolStream<'a>
+) -> Result<(), anyhow::Error> {
+    scope(program, syms, false)
+}
+
+/// Reads definitions into `program`. Inside a namespace this stops after the
+/// namespace's closing brace, otherwise it reads to the end of the file.
+fn scope<'a>(
+    program: &mut Program,
+    syms: &mut SymbolStream<'a>,
+    in_namespace: bool
+) -> Result<(), anyhow::Error> {
+    // Set after reading `public`, for the definition that follows.
+    let mut public = false;
     while syms.peek().is_some() {
+        let defined = program.definitions.len();
+        let publicize = public;
+        public = false;
         match syms.next().unwrap() {
-            Symbol::Comment(_) | Symbol::Comments(_) => {}
+            Symbol::Comment(_) | Symbol::Comments(_) => {
+                public = publicize;
+                continue;
+            }
+            Symbol::CloseBrace if in_namespace => {
+                if publicize {
+                    return Err(anyhow::Error::msg(format!(
+                        "{}: `public` must be followed by a definition.",
+                        syms.location()
+                    )));
+                }
+                return Ok(());
+            }
             Symbol::Keyword(kwrd) => {
                 match kwrd {
+                    Keyword::Kpublic => {
+                        if publicize {
+                            return Err(anyhow::Error::msg(format!(
+                                "{}: `public` was given twice.",
+                                syms.location()
+                            )));
+                        }
+                        public = true;
+                        continue;
+                    }
+                    Keyword::Kdisable => {
+                        // expects a name, then PhraseEnd (`disable maybe;`)
+                        let name = match syms.next() {
+                            Some(Symbol::Label(name)) => super::qualified_path(syms, name)?,
+                            _ => {
+                                return Err(anyhow::Error::msg(format!(
+                                    "{}: Expected a name following keyword `disable`.",
+                                    syms.location()
+                                )));
+                            }
+                        };
+                        if syms.next() != Some(&Symbol::PhraseEnd) {
+                            return Err(anyhow::Error::msg(format!(
+                                "{}: Expected `;` following `disable {name}`.",
+                                syms.location()
+                            )));
+                        }
+                        program.disabled.push(name);
+                    }
+                    Keyword::Knamespace => {
+                        // expects Label(_), OpenBrace, then definitions up to
+                        // a CloseBrace (`namespace shapes { ... }`)
+                        let location = syms.location();
+                        let name = match syms.next() {
+                            Some(Symbol::Label(name)) => name.clone(),
+                            _ => {
+                                return Err(anyhow::Error::msg(format!(
+                                    "{}: Expected a name following keyword `namespace`.",
+                                    syms.location()
+                                )));
+                            }
+                        };
+                        if syms.next() != Some(&Symbol::OpenBrace) {
+                            return Err(anyhow::Error::msg(format!(
+                                "{}: Expected `{{` following `namespace {name}`.",
+                                syms.location()
+                            )));
+                        }
+                        let mut inner = Program::empty();
+                        scope(&mut inner, syms, true)?;
+                        if inner.main.is_some() {
+                            return Err(anyhow::Error::msg(format!(
+                                "{location}: The `main` block can't be inside a namespace."
+                            )));
+                        }
+                        // Everything inside is named from the namespace.
+                        for mut definition in inner.definitions {
+                            if let Some(definition_name) = definition.name_mut() {
+                                *definition_name = format!("{name}~{definition_name}");
+                            }
+                            program.definitions.push(definition);
+                        }
+                        for public_name in inner.public {
+                            program.public.push(format!("{name}~{public_name}"));
+                        }
+                        program.global_tasks.append(&mut inner.global_tasks);
+                        program.disabled.append(&mut inner.disabled);
+                    }
                     Keyword::Ksystem => {
                         // expects Label(_), PhraseEnd
                         if let Some(&&Symbol::Label(ref label)) = syms.peek() {
@@ -35,7 +126,83 @@ where
                             }
                         }
                     }
+                    Keyword::Kinclude => {
+                        // expects Label(_), then any number of Is, Label(_),
+                        // then PhraseEnd (`include io:stdout;`)
+                        let mut path = vec![];
+                        loop {
+                            match syms.next() {
+                                Some(Symbol::Label(label)) => path.push(label.clone()),
+                                Some(Symbol::Is) if !path.is_empty() => {}
+                                Some(Symbol::PhraseEnd) if !path.is_empty() => break,
+                                _ => {
+                                    return Err(anyhow::Error::msg(format!(
+                                        "{}: Expected a path such as `io:stdout` and a `;` following keyword `include`.",
+                                        syms.location()
+                                    )));
+                                }
+                            }
+                        }
+                        program.definitions.push(Definition::System { label: path.join(":") });
+                    }
+                    Keyword::Kfile => {
+                        // expects Label(_), then any number of Is, Label(_),
+                        // optionally ending in Is, Multiply, then PhraseEnd
+                        // (`file utils:math;` or `file shapes:*;`)
+                        let location = syms.location();
+                        let mut path = vec![];
+                        loop {
+                            match (syms.next(), path.last().map(String::as_str)) {
+                                (Some(Symbol::Label(label)), None | Some(":")) => path.push(label.clone()),
+                                (Some(Symbol::Multiply), Some(":")) => path.push(String::from("*")),
+                                (Some(Symbol::Is), Some(last)) if last != ":" && last != "*" => path.push(String::from(":")),
+                                (Some(Symbol::PhraseEnd), Some(last)) if last != ":" => break,
+                                _ => {
+                                    return Err(anyhow::Error::msg(format!(
+                                        "{}: Expected a path such as `utils:math` or `shapes:*` and a `;` following keyword `file`.",
+                                        syms.location()
+                                    )));
+                                }
+                            }
+                        }
+                        program.definitions.push(Definition::File { label: path.concat(), location });
+                    }
+                    Keyword::Klibrary => {
+                        // expects Label(_), optionally String(_), then
+                        // PhraseEnd (`library geometry;` or
+                        // `library geometry "1.2";`)
+                        let location = syms.location();
+                        let name = match syms.next() {
+                            Some(Symbol::Label(name)) => name.clone(),
+                            _ => {
+                                return Err(anyhow::Error::msg(format!(
+                                    "{}: Expected a library name following keyword `library`.",
+                                    syms.location()
+                                )));
+                            }
+                        };
+                        let version = match syms.next() {
+                            Some(Symbol::String(version)) => {
+                                if syms.next() != Some(&Symbol::PhraseEnd) {
+                                    return Err(anyhow::Error::msg(format!(
+                                        "{}: Expected `;` following `library {name} \"{version}\"`.",
+                                        syms.location()
+                                    )));
+                                }
+                                version.clone()
+                            }
+                            Some(Symbol::PhraseEnd) => String::new(),
+                            _ => {
+                                return Err(anyhow::Error::msg(format!(
+                                    "{}: Expected a version or `;` following `library {name}`.",
+                                    syms.location()
+                                )));
+                            }
+                        };
+                        program.definitions.push(Definition::Library { name, version, location });
+                    }
                     Keyword::Kmain => {
+                        program.main = Some(syms.location());
                         if Some(&&Symbol::OpenBrace) == syms.peek() {
                             syms.next();
                             program.main_scope(syms)?;
@@ -54,9 +221,17 @@ where
                         let def = program.trait_declaration(syms)?;
                         program.definitions.push(def);
                     }
+                    Keyword::Kenum => {
+                        let def = program.enum_declaration(syms)?;
+                        program.definitions.push(def);
+                    }
                     unknown_keyword => todo!("keyword {:?}", unknown_keyword)
                 }
             }
+            Symbol::Label(l) if l == "var" => {
+                let def = program.variable_declaration(syms)?;
+                program.definitions.push(def);
+            }
             Symbol::Label(l) => {
                 // Running a function if we find OpenParenthesis, PhraseEnd
                 if let Some(&&Symbol::OpenParenthesis) = syms.peek() {
@@ -70,12 +245,18 @@ where
                         // ...
                         todo!();
                     }
-                    program.global_tasks.push(Task::Call { function_identifier: l.to_string(), arguments });
+                    program.global_tasks.push(Task::Call {
+                        function_identifier: l.to_string(),
+                        generics: vec![],
+                        arguments,
+                        location: syms.location()
+                    });
                 }
-                // Creating an alias if we find Alias, PhraseEnd
-                if let Some(&&Symbol::Alias) = syms.peek() {
-                    // Alias found
-                    syms.next();
+                // Creating an alias if we find Alias or ExportedAlias, PhraseEnd
+                if let Some(&&(Symbol::Alias | Symbol::ExportedAlias)) = syms.peek() {
+                    // Alias found, exported aliases (`=>!`) can also be used
+                    // by files importing this one.
+                    let export = syms.next() == Some(&Symbol::ExportedAlias);
                     // check for Label(_)
                     if let Some(&&Symbol::Label(ref outlabel)) = syms.peek() {
                         syms.next();
@@ -98,7 +279,7 @@ where
                         if let Some(&&Symbol::PhraseEnd) = syms.peek() {
                             // PhraseEnd found! Statement complete!
                             syms.next();
-                            program.definitions.push(Definition::Alias { from: l.clone(), to: out_lab_with_refs.join("~"), export: false });
+                            program.definitions.push(Definition::Alias { from: l.clone(), to: out_lab_with_refs.join("~"), export });
                         }
                         else {
                             return Err(anyhow::Error::msg(
@@ -118,6 +299,25 @@ where
             }
             k => todo!("{:?}", k)
         }
+        if publicize {
+            match &program.definitions[defined..] {
+                [definition] if definition.name().is_some() => {
+                    let name = definition.name().unwrap().clone();
+                    program.public.push(name);
+                }
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "{}: `public` must be followed by a definition.",
+                        syms.location()
+                    )));
+                }
+            }
+        }
+    }
+    if in_namespace {
+        return Err(anyhow::Error::msg(
+            "Expected a } to close the namespace before the end of the file."
+        ));
     }
     Ok(())
 }
@@ -12,16 +12,113 @@ pub struct Syntax {
 }
 
 impl Syntax {
-    pub fn from_string(from: String) -> Syntax {
-        let mut reader = StringReader::from_string(from);
-        let symbols = Symbol::read_all_symbols(&mut reader);
-        Syntax { symbols, annotations: vec![] }
+    pub fn from_string(from: String) -> Result<Syntax, anyhow::Error> {
+        // Byte offsets of the start of every line, for turning reader
+        // locations into line and column numbers.
+        let mut line_starts = vec![0];
+        for (idx, character) in from.char_indices() {
+            if character == '\n' {
+                line_starts.push(idx + 1);
+            }
+        }
+        let mut reader = StringReader::from_string(from.clone());
+        let mut symbols = vec![];
+        let mut annotations = vec![];
+        loop {
+            reader.skip_whitespace();
+            let location = reader.location();
+            match Symbol::next(&mut reader)? {
+                Some(symbol) => {
+                    let line = line_starts.partition_point(|start| *start <= location);
+                    let line_start = line_starts[line - 1];
+                    annotations.push(Annotation {
+                        line,
+                        column: from[line_start..location].chars().count() + 1
+                    });
+                    symbols.push(symbol);
+                }
+                None => break
+            }
+        }
+        Ok(Syntax { symbols, annotations })
     }
 }
 
-#[derive(Debug, Clone)]
+/// Where a [Symbol] starts in its source file. Lines and columns both count up
+/// from one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Annotation {
-    // TODO
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Steps through a list of [Symbol]s like a peekable iterator, while keeping
+/// track of where each one came from.
+#[derive(Clone)]
+pub struct SymbolStream<'a> {
+    symbols: &'a [Symbol],
+    annotations: &'a [Annotation],
+    /// Used for symbols that have no annotation of their own, such as the
+    /// insides of a [Symbol::Closure].
+    fallback: Annotation,
+    index: usize,
+    upcoming: Option<&'a Symbol>,
+}
+
+impl <'a>SymbolStream<'a> {
+    pub fn new(syntax: &'a Syntax) -> SymbolStream<'a> {
+        SymbolStream {
+            symbols: &syntax.symbols,
+            annotations: &syntax.annotations,
+            fallback: Annotation::default(),
+            index: 0,
+            upcoming: syntax.symbols.first()
+        }
+    }
+    /// Streams symbols that don't have annotations, all of which are treated
+    /// as if they were found at `location`.
+    pub fn unannotated(symbols: &'a [Symbol], location: Annotation) -> SymbolStream<'a> {
+        SymbolStream {
+            symbols,
+            annotations: &[],
+            fallback: location,
+            index: 0,
+            upcoming: symbols.first()
+        }
+    }
+    /// Returns the next symbol without moving past it.
+    pub fn peek(&self) -> Option<&&'a Symbol> {
+        self.upcoming.as_ref()
+    }
+    /// Where the most recently read symbol starts.
+    pub fn location(&self) -> Annotation {
+        self.index.checked_sub(1)
+            .and_then(|idx| self.annotations.get(idx))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+    /// Throws away any comments coming up next.
+    pub fn skip_comments(&mut self) {
+        while let Some(Symbol::Comment(_) | Symbol::Comments(_)) = self.peek() {
+            self.next();
+        }
+    }
+}
+
+impl <'a>Iterator for SymbolStream<'a> {
+    type Item = &'a Symbol;
+    fn next(&mut self) -> Option<&'a Symbol> {
+        let symbol = self.symbols.get(self.index)?;
+        self.index += 1;
+        self.upcoming = self.symbols.get(self.index);
+        Some(symbol)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -84,8 +181,11 @@ pub const RESERVED_LABEL_SYMBOLS: &[char] = &[
 ];
 
 impl Symbol {
-    pub fn next(reader: &mut StringReader) -> Option<Symbol> {
-        let first_char = reader.next_non_whitespace_char()?;
+    pub fn next(reader: &mut StringReader) -> Result<Option<Symbol>, anyhow::Error> {
+        let Some(first_char) = reader.next_non_whitespace_char()
+        else {
+            return Ok(None);
+        };
         let second_char = reader.peek_char();
         let peaked_word = format!("{}{}", first_char, reader.peek_word());
 
@@ -93,59 +193,67 @@ impl Symbol {
             if &peaked_word.as_str() == keyword {
                 reader.read_word();
                 //println!("{} -> {:?}", peaked_word, keywords::KEYWORDS_TYPED[index]);
-                return Some(Symbol::Keyword(keywords::KEYWORDS_TYPED[index]));
+                return Ok(Some(Symbol::Keyword(keywords::KEYWORDS_TYPED[index])));
             }
         }
 
         match first_char {
             // Exclusive one char symbols
-            ';' => return Some(Symbol::PhraseEnd),
-            '~' => return Some(Symbol::Module),
-            '+' => return Some(Symbol::Add),
-            '%' => return Some(Symbol::Modulo),
-            ':' => return Some(Symbol::Is),
-            '{' => return Some(Symbol::OpenBrace),
-            '}' => return Some(Symbol::CloseBrace),
-            '[' => return Some(Symbol::OpenBracket),
-            ']' => return Some(Symbol::CloseBracket),
-            '(' => return Some(Symbol::OpenParenthesis),
-            ')' => return Some(Symbol::CloseParenthesis),
-            ',' => return Some(Symbol::Also),
+            ';' => return Ok(Some(Symbol::PhraseEnd)),
+            '~' => return Ok(Some(Symbol::Module)),
+            '+' => return Ok(Some(Symbol::Add)),
+            '%' => return Ok(Some(Symbol::Modulo)),
+            ':' => return Ok(Some(Symbol::Is)),
+            '{' => return Ok(Some(Symbol::OpenBrace)),
+            '}' => return Ok(Some(Symbol::CloseBrace)),
+            '[' => return Ok(Some(Symbol::OpenBracket)),
+            ']' => return Ok(Some(Symbol::CloseBracket)),
+            '(' => return Ok(Some(Symbol::OpenParenthesis)),
+            ')' => return Ok(Some(Symbol::CloseParenthesis)),
+            ',' => return Ok(Some(Symbol::Also)),
             '!' => {
                 if second_char == Some('=') {
                     reader.read_char();
-                    return Some(Symbol::DoesNotEqual);
+                    return Ok(Some(Symbol::DoesNotEqual));
                 }
                 else if second_char == Some('!') {
                     reader.read_char();
-                    return Some(Symbol::Compiler(reader.read_until(';')));
+                    // Only the directive's name belongs to this symbol, its
+                    // arguments are read as normal symbols.
+                    return Ok(Some(Symbol::Compiler(reader.read_word())));
                 }
                 else {
-                    return Some(Symbol::LogicNot);
+                    return Ok(Some(Symbol::LogicNot));
                 }
             }
             '*' => {
                 if second_char == Some('*') {
                     reader.read_char();
-                    return Some(Symbol::Power);
+                    return Ok(Some(Symbol::Power));
                 }
                 else {
-                    return Some(Symbol::Multiply);
+                    return Ok(Some(Symbol::Multiply));
                 }
             }
             '"' => {
                 let mut output_string = String::new();
                 let mut last_char = '"';
-                let mut this_char = reader.read_char()?;
+                let Some(mut this_char) = reader.read_char()
+                else {
+                    return Ok(None);
+                };
                 loop {
                     if this_char == '"' && last_char != '\\' {
                         break;
                     }
                     output_string += &this_char.to_string();
                     last_char = this_char;
-                    this_char = reader.read_char()?;
+                    this_char = match reader.read_char() {
+                        Some(this_char) => this_char,
+                        None => return Ok(None)
+                    };
                 }
-                return Some(Symbol::String(output_string));
+                return Ok(Some(Symbol::String(output_string)));
             }
             '=' => {
                 if second_char == Some('>') {
@@ -156,18 +264,18 @@ impl Symbol {
                     if next == Some('!') {
                         // throw away !
                         reader.read_char();
-                        return Some(Symbol::ExportedAlias);
+                        return Ok(Some(Symbol::ExportedAlias));
                     }
                     else {
-                        return Some(Symbol::Alias);
+                        return Ok(Some(Symbol::Alias));
                     }
                 }
                 else if second_char == Some('=') {
                     reader.read_char();
-                    return Some(Symbol::Equals);
+                    return Ok(Some(Symbol::Equals));
                 }
                 else {
-                    return Some(Symbol::Set);
+                    return Ok(Some(Symbol::Set));
                 }
             }
             '/' => {
@@ -175,9 +283,9 @@ impl Symbol {
                     // throw away the next slash
                     reader.read_char();
                     // read the rest of the line to a comment
-                    return Some(Symbol::Comment(
+                    return Ok(Some(Symbol::Comment(
                         reader.read_line()
-                    ));
+                    )));
                 }
                 else if second_char == Some('*') {
                     // throw away *
@@ -193,104 +301,163 @@ impl Symbol {
                             comment_out += "*";
                         }
                     }
-                    return Some(Symbol::Comments(comment_out));
+                    return Ok(Some(Symbol::Comments(comment_out)));
                 }
                 else {
-                    return Some(Symbol::Divide);
+                    return Ok(Some(Symbol::Divide));
                 }
             }
             '&' => {
                 if second_char == Some('&') {
                     reader.read_char();
-                    return Some(Symbol::LogicAnd);
+                    return Ok(Some(Symbol::LogicAnd));
                 }
                 else {
-                    return Some(Symbol::BitAnd);
+                    return Ok(Some(Symbol::BitAnd));
                 }
             }
             '^' => {
                 if second_char == Some('^') {
                     reader.read_char();
-                    return Some(Symbol::LogicXor);
+                    return Ok(Some(Symbol::LogicXor));
                 }
                 else {
-                    return Some(Symbol::BitXor);
+                    return Ok(Some(Symbol::BitXor));
                 }
             }
             '-' => {
                 if second_char == Some('>') {
                     reader.read_char();
-                    return Some(Symbol::Becomes);
+                    return Ok(Some(Symbol::Becomes));
                 }
                 else {
-                    return Some(Symbol::Subtract);
+                    return Ok(Some(Symbol::Subtract));
                 }
             }
             '<' => {
                 if second_char == Some('-') {
                     reader.read_char();
-                    return Some(Symbol::Parent);
+                    return Ok(Some(Symbol::Parent));
                 }
                 else if second_char == Some('=') {
                     reader.read_char();
-                    return Some(Symbol::LessThanOrEqual);
+                    return Ok(Some(Symbol::LessThanOrEqual));
                 }
                 else if second_char == Some('<') {
                     reader.read_char();
-                    return Some(Symbol::LeftShift);
+                    return Ok(Some(Symbol::LeftShift));
                 }
                 else {
-                    return Some(Symbol::LessThan);
+                    return Ok(Some(Symbol::LessThan));
                 }
             }
             '>' => {
                 if second_char == Some('=') {
                     reader.read_char();
-                    return Some(Symbol::MoreThanOrEqual);
+                    return Ok(Some(Symbol::MoreThanOrEqual));
                 }
                 else if second_char == Some('>') {
                     reader.read_char();
-                    return Some(Symbol::RightShift);
+                    return Ok(Some(Symbol::RightShift));
                 }
                 else {
-                    return Some(Symbol::MoreThan);
+                    return Ok(Some(Symbol::MoreThan));
                 }
             }
             // TODO: this whole thing with `|` is problematic :(
             '|' => {
                 if second_char != Some('|') && second_char != Some(' ') {
-                    return Some(Symbol::Closure(
+                    return Ok(Some(Symbol::Closure(
                         Symbol::read_all_symbols(
                             &mut StringReader::from_string(
                                 // TODO: THIS IS PROBLEMATIC!!! (recursion)
-                                reader.read_until('|')
+                                read_closure_arguments(reader)?
                             )
-                        )
-                    ))
+                        )?
+                    )))
                 }
                 else if second_char == Some(' ') {
-                    return Some(Symbol::BitOr);
+                    return Ok(Some(Symbol::BitOr));
                 }
                 else {
-                    return Some(Symbol::LogicOr);
+                    return Ok(Some(Symbol::LogicOr));
                 }
             },
             _ => {}
         }
         // TODO: differentiate numeric values and other types
         reader.read_word();
-        if let Ok(ival) = peaked_word.parse::<i64>() {
-            return Some(Symbol::Integer(Bigint::from_i64(ival)));
+        if let Ok(integer) = peaked_word.parse::<Bigint>() {
+            return Ok(Some(Symbol::Integer(integer)));
         }
-        return Some(Symbol::Label(peaked_word));
+        // Decimal and complex numbers start with a digit, apart from the
+        // special values.
+        let numeric = peaked_word.starts_with(|character: char| character.is_ascii_digit())
+            || peaked_word == "NaN"
+            || peaked_word == "∞";
+        if numeric {
+            if let Ok(float) = peaked_word.parse::<Bigfloat>() {
+                return Ok(Some(Symbol::Float(float)));
+            }
+            if let Ok(complex) = peaked_word.parse::<Bigcplx>() {
+                return Ok(Some(Symbol::Complex(complex)));
+            }
+        }
+        return Ok(Some(Symbol::Label(peaked_word)));
     }
-    pub fn read_all_symbols(reader: &mut StringReader) -> Vec<Symbol> {
+    pub fn read_all_symbols(reader: &mut StringReader) -> Result<Vec<Symbol>, anyhow::Error> {
         //println!("Reading all symbols!");
         let mut symbols = vec![];
-        while let Some(symbol) = Symbol::next(reader) {
+        while let Some(symbol) = Symbol::next(reader)? {
             //println!("Symbol::{:?}", symbol);
             symbols.push(symbol);
         }
-        symbols
+        Ok(symbols)
     }
 }
+
+/// Reads the inside of a closure's argument list, up to (but not including) its
+/// closing `|`. Arguments that are closures themselves
+/// (`mapper: |in: G| -> N`) open a nested list, which we can tell apart from
+/// the closing `|` because it directly follows a `:`.
+fn read_closure_arguments(reader: &mut StringReader) -> Result<String, anyhow::Error> {
+    let mut output = String::new();
+    let mut depth = 0;
+    let mut last_char = '|';
+    loop {
+        let Some(this_char) = reader.read_char()
+        else {
+            return Err(anyhow::Error::msg(
+                "Unexpected end of file inside a closure's arguments. Expected a closing `|`. (TODO: ANNOTATIONS)"
+            ));
+        };
+        if this_char == '|' {
+            if last_char == ':' {
+                depth += 1;
+            }
+            else if depth == 0 {
+                break;
+            }
+            else {
+                depth -= 1;
+            }
+        }
+        if !this_char.is_whitespace() {
+            last_char = this_char;
+        }
+        output.push(this_char);
+    }
+    Ok(output)
+}
+
+#[test]
+fn test_closure_arguments() {
+    let syntax = Syntax::from_string(String::from("var map = |value: u8, mapper: |in: u8| -> u8| -> u8 {}")).unwrap();
+    assert!(matches!(&syntax.symbols[3], Symbol::Closure(arguments) if arguments.len() == 9));
+    // An unfinished closure is reported, not a crash.
+    let error = Syntax::from_string(String::from("var broken = |value: u8")).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Unexpected end of file inside a closure's arguments. Expected a closing `|`. (TODO: ANNOTATIONS)"
+    );
+}
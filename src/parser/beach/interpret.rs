@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use super::ir::{CodeSegment, Executable, GeneratableTask, ImmediateOrRefrence, Operator};
+use crate::platform::generic::SyscallConvention;
+
+/// Where data starts in the interpreter's memory. Lower addresses are left
+/// unused so that null pointers can't be read.
+const DATA_START: u64 = 0x1000_0000;
+/// Code labels are given addresses too, so they can be passed around, but
+/// nothing can be read from them.
+const CODE_START: u64 = 0x1000;
+/// How many calls can be waiting to return before the program is stopped, in
+/// place of overflowing a real stack.
+const MAX_CALL_DEPTH: usize = 4096;
+
+/// Runs `program` without compiling it, returning its exit code. It must not
+/// have had its registers allocated.
+///
+/// Values are 64 bit integers that wrap on overflow, and refrences are the
+/// address of what they refer to. The only system calls provided are the
+/// `write` and `exit` calls of `convention`, writing file descriptors `1` and
+/// `2` to `stdout` and `stderr`. Like Unix, exit codes are cut down to a
+/// byte, and returning from the main block exits with `0`.
+pub fn interpret(
+    program: &Executable,
+    convention: &SyscallConvention,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write
+) -> Result<i32, anyhow::Error> {
+    let mut interpreter = Interpreter {
+        program,
+        convention,
+        memory: vec![],
+        addresses: HashMap::new(),
+        stdout,
+        stderr,
+    };
+    for data in &program.data {
+        // Everything is aligned to 8 bytes, as registers are.
+        while !interpreter.memory.len().is_multiple_of(8) {
+            interpreter.memory.push(0);
+        }
+        interpreter.addresses.insert(&data.label, DATA_START + interpreter.memory.len() as u64);
+        match &data.default {
+            Some(default) => interpreter.memory.extend(default),
+            None => interpreter.memory.resize(interpreter.memory.len() + data.size, 0),
+        }
+    }
+    for (index, segment) in program.code_sections.iter().enumerate() {
+        interpreter.addresses.insert(&segment.label, CODE_START + index as u64 * 16);
+    }
+    interpreter.run()
+}
+
+struct Interpreter<'a> {
+    program: &'a Executable,
+    convention: &'a SyscallConvention,
+    /// Every piece of data, one after another from [DATA_START].
+    memory: Vec<u8>,
+    /// The address of every label.
+    addresses: HashMap<&'a str, u64>,
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+}
+
+/// A function that's running, or waiting for a call to return.
+struct Frame<'a> {
+    segment: &'a CodeSegment,
+    /// The index of the next task to run.
+    next: usize,
+    arguments: Vec<u64>,
+    /// What the function returns, as it's set.
+    results: Vec<u64>,
+    virtual_registers: HashMap<usize, u64>,
+    /// The source line that's running, if it's known.
+    line: Option<(&'a Path, usize)>,
+}
+
+impl <'a>Frame<'a> {
+    fn new(segment: &'a CodeSegment, arguments: Vec<u64>) -> Frame<'a> {
+        Frame { segment, next: 0, arguments, results: vec![], virtual_registers: HashMap::new(), line: None }
+    }
+    /// Where the frame is, for errors.
+    fn location(&self) -> String {
+        let function = match self.segment.label.as_str() {
+            "" => "the main block",
+            label => label
+        };
+        match self.line {
+            Some((file, line)) => format!("{}:{line} (in {function})", file.display()),
+            // `next` has already moved past the task that's running.
+            None => format!("{function}, task {}", self.next.saturating_sub(1))
+        }
+    }
+}
+
+impl <'a>Interpreter<'a> {
+    fn run(&mut self) -> Result<i32, anyhow::Error> {
+        let mut frames = vec![Frame::new(&self.program.entry_point, vec![])];
+        // Arguments set for the next call.
+        let mut call_arguments: Vec<u64> = vec![];
+        // What the last call or system call returned.
+        let mut results: Vec<u64> = vec![];
+        loop {
+            let depth = frames.len();
+            let frame = frames.last_mut().expect("the main block is never returned from");
+            let segment = frame.segment;
+            let Some(task) = segment.tasks.get(frame.next)
+            else {
+                // Running off the end of a function returns from it, and off
+                // the end of the main block exits.
+                if depth == 1 {
+                    return Ok(0);
+                }
+                results = frames.pop().expect("only functions return").results;
+                continue;
+            };
+            frame.next += 1;
+            let error = |frame: &Frame, e: String| anyhow::Error::msg(format!("{}: {e}", frame.location()));
+            match task {
+                GeneratableTask::SourceLine { file, line, .. } => frame.line = Some((file, *line)),
+                GeneratableTask::SetCallArgument { argument_number, argument_value } => {
+                    let value = self.value(frame, argument_value).map_err(|e| error(frame, e))?;
+                    if call_arguments.len() <= *argument_number {
+                        call_arguments.resize(argument_number + 1, 0);
+                    }
+                    call_arguments[*argument_number] = value;
+                }
+                GeneratableTask::Call(label) => {
+                    let Some(segment) = self.program.code_sections.iter().find(|segment| &segment.label == label)
+                    else {
+                        return Err(error(frame, format!("`{label}` isn't a function.")));
+                    };
+                    if depth >= MAX_CALL_DEPTH {
+                        return Err(error(frame, format!("calling `{label}` overflowed the stack.")));
+                    }
+                    frames.push(Frame::new(segment, std::mem::take(&mut call_arguments)));
+                }
+                GeneratableTask::EndCall => {
+                    if depth == 1 {
+                        return Ok(0);
+                    }
+                    results = frames.pop().expect("only functions return").results;
+                }
+                GeneratableTask::ReadArgument { argument_number, destination } => {
+                    let value = frame.arguments.get(*argument_number).copied().unwrap_or(0);
+                    Self::set(frame, destination, value).map_err(|e| error(frame, e))?;
+                }
+                GeneratableTask::SetResult { result_number, value } => {
+                    let value = self.value(frame, value).map_err(|e| error(frame, e))?;
+                    if frame.results.len() <= *result_number {
+                        frame.results.resize(result_number + 1, 0);
+                    }
+                    frame.results[*result_number] = value;
+                }
+                GeneratableTask::ReadResult { result_number, destination } => {
+                    let value = results.get(*result_number).copied().unwrap_or(0);
+                    Self::set(frame, destination, value).map_err(|e| error(frame, e))?;
+                }
+                GeneratableTask::Copy { destination, source } => {
+                    let value = self.value(frame, source).map_err(|e| error(frame, e))?;
+                    Self::set(frame, destination, value).map_err(|e| error(frame, e))?;
+                }
+                GeneratableTask::Operation { operator, destination, a, b } => {
+                    let a = self.value(frame, a).map_err(|e| error(frame, e))?;
+                    let b = self.value(frame, b).map_err(|e| error(frame, e))?;
+                    let result = match operator {
+                        Operator::Add => a.wrapping_add(b),
+                        Operator::Subtract => a.wrapping_sub(b),
+                        Operator::Multiply => a.wrapping_mul(b),
+                    };
+                    Self::set(frame, destination, result).map_err(|e| error(frame, e))?;
+                }
+                GeneratableTask::Syscall { number, arguments } => {
+                    let number = self.value(frame, number).map_err(|e| error(frame, e))?;
+                    let arguments: Result<Vec<u64>, String> = arguments.iter()
+                        .map(|argument| self.value(frame, argument))
+                        .collect();
+                    let arguments = arguments.map_err(|e| error(frame, e))?;
+                    match self.syscall(number, &arguments).map_err(|e| error(frame, e))? {
+                        Syscalled::Returned(result) => results = vec![result],
+                        Syscalled::Exited(code) => return Ok(code),
+                    }
+                }
+                GeneratableTask::Unreachable => {
+                    return Err(error(frame, String::from("reached `!!unreachable`.")));
+                }
+                GeneratableTask::GoTo(label) => {
+                    let Some(target) = label_position(segment, label)
+                    else {
+                        return Err(error(frame, format!("`{label}` isn't a label in this function.")));
+                    };
+                    frame.next = target + 1;
+                }
+                GeneratableTask::GoToIfZero { label, value } => {
+                    if self.value(frame, value).map_err(|e| error(frame, e))? != 0 {
+                        continue;
+                    }
+                    let Some(target) = label_position(segment, label)
+                    else {
+                        return Err(error(frame, format!("`{label}` isn't a label in this function.")));
+                    };
+                    frame.next = target + 1;
+                }
+                GeneratableTask::Label(_) => {}
+                other => {
+                    return Err(error(frame, format!("`{other}` can't be interpreted.")));
+                }
+            }
+        }
+    }
+    /// Reads `operand` in `frame`.
+    fn value(&self, frame: &Frame, operand: &ImmediateOrRefrence) -> Result<u64, String> {
+        match operand {
+            ImmediateOrRefrence::Immediate(bytes) if bytes.len() <= 8 => {
+                let mut value = [0; 8];
+                value[..bytes.len()].copy_from_slice(bytes);
+                Ok(u64::from_le_bytes(value))
+            }
+            ImmediateOrRefrence::Immediate(bytes) => Err(format!("{} bytes is too wide for a register.", bytes.len())),
+            ImmediateOrRefrence::Refrence(label) => self.addresses.get(label.as_str())
+                .copied()
+                .ok_or(format!("`{label}` isn't defined.")),
+            ImmediateOrRefrence::Virtual(register) => frame.virtual_registers.get(register)
+                .copied()
+                .ok_or(format!("%{register} is used before it's set.")),
+            ImmediateOrRefrence::Register(_) | ImmediateOrRefrence::Stack(_) => {
+                Err(String::from("IR with allocated registers can't be interpreted."))
+            }
+        }
+    }
+    fn set(frame: &mut Frame, destination: &ImmediateOrRefrence, value: u64) -> Result<(), String> {
+        match destination {
+            ImmediateOrRefrence::Virtual(register) => {
+                frame.virtual_registers.insert(*register, value);
+                Ok(())
+            }
+            other => Err(format!("`{other}` can't be set.")),
+        }
+    }
+    /// The `length` bytes of memory at `address`.
+    fn read(&self, address: u64, length: u64) -> Result<&[u8], String> {
+        let invalid = || format!("{length} bytes at 0x{address:x} can't be read.");
+        let start = address.checked_sub(DATA_START).ok_or_else(invalid)? as usize;
+        let end = start.checked_add(length as usize).ok_or_else(invalid)?;
+        self.memory.get(start..end).ok_or_else(invalid)
+    }
+    /// Makes a system call.
+    fn syscall(&mut self, number: u64, arguments: &[u64]) -> Result<Syscalled, String> {
+        let argument = |index: usize| arguments.get(index).copied().unwrap_or(0);
+        if number == self.convention.write {
+            let bytes = self.read(argument(1), argument(2))?.to_vec();
+            let output: &mut dyn Write = match argument(0) {
+                1 => &mut *self.stdout,
+                2 => &mut *self.stderr,
+                descriptor => return Err(format!("file descriptor {descriptor} can't be written to.")),
+            };
+            output.write_all(&bytes)
+                .and_then(|()| output.flush())
+                .map_err(|e| format!("writing failed: {e}"))?;
+            // Like the kernel, `write` returns how many bytes it wrote.
+            return Ok(Syscalled::Returned(bytes.len() as u64));
+        }
+        if number == self.convention.exit {
+            return Ok(Syscalled::Exited((argument(0) & 0xff) as i32));
+        }
+        Err(format!("system call {number} isn't provided by the interpreter."))
+    }
+}
+
+/// What a system call did.
+enum Syscalled {
+    /// It returned this.
+    Returned(u64),
+    /// It ended the program with this exit code.
+    Exited(i32),
+}
+
+/// Where the [GeneratableTask::Label] named `label` is in `segment`.
+fn label_position(segment: &CodeSegment, label: &str) -> Option<usize> {
+    segment.tasks.iter().position(|task| matches!(task, GeneratableTask::Label(name) if name == label))
+}
+
+#[test]
+fn test_interpret() {
+    use crate::parser::beach::ast::{Program, user_token_format::Syntax};
+    let convention = SyscallConvention::LINUX_AARCH64;
+    let run = |program: &Executable| {
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        let result = interpret(program, &convention, &mut stdout, &mut stderr);
+        (result.map_err(|e| e.to_string()), String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+    };
+    let source = String::from("
+        type string {
+            var pointer: usize;
+            var length: usize;
+        }
+        var write = |descriptor: u8, output: string| -> nothing {
+            unsafe {
+                !!syscall(64, (descriptor, output.pointer, output.length));
+            }
+            return;
+        };
+        var exit = |code: i16| -> never {
+            unsafe {
+                !!syscall(93, (code));
+            }
+            !!unreachable;
+        };
+        main {
+            write(1, \"Hello, \");
+            write(1, \"world!\");
+            write(2, \"oops\");
+            exit(3);
+            write(1, \"Never printed\");
+        }
+    ");
+    let program = Program::from_lst(Syntax::from_string(source).unwrap(), Some(String::from("main.beach"))).unwrap();
+    let executable = Executable::from_ast(program).unwrap();
+    assert_eq!(run(&executable), (Ok(3), String::from("Hello, world!"), String::from("oops")));
+
+    // Hand-written IR can do what the front end can't yet.
+    let executable: Executable = "
+        data message 3 = \"abc\"
+        function print_from {
+            %0 = argument 0
+            %1 = add %0, @message
+            %2 = subtract 0x03, %0
+            syscall 0x40 (0x01, %1, %2)
+        }
+        function recurse {
+            call recurse
+        }
+        main {
+            set_argument 0 = 0x01
+            call print_from
+            %0 = multiply 0x02, 0x0100
+            syscall 0x5d (%0)
+        }
+    ".parse().unwrap();
+    // 512 is cut down to a byte, as Unix does.
+    assert_eq!(run(&executable), (Ok(0), String::from("bc"), String::new()));
+
+    // Mistakes stop the program, pointing at where they happened.
+    let mut executable = executable;
+    executable.entry_point.tasks.insert(0, "line \"main.beach\":2 \"main {\"".parse().unwrap());
+    executable.entry_point.tasks.insert(1, "call recurse".parse().unwrap());
+    assert_eq!(run(&executable).0, Err(String::from("recurse, task 0: calling `recurse` overflowed the stack.")));
+    executable.entry_point.tasks[1] = "syscall 0x40 (0x01, @message, 0x04)".parse().unwrap();
+    assert_eq!(run(&executable).0, Err(String::from("main.beach:2 (in the main block): 4 bytes at 0x10000000 can't be read.")));
+    executable.entry_point.tasks[1] = "unreachable".parse().unwrap();
+    assert_eq!(run(&executable).0, Err(String::from("main.beach:2 (in the main block): reached `!!unreachable`.")));
+}
@@ -1,6 +1,13 @@
-use crate::{parser::beach::ast::{Definition, Task, Value}, generator::generic::{AssemblyGenerator, HardwareData}};
+use std::{collections::HashMap, path::{Path, PathBuf}};
 
-#[derive(Debug)]
+use crate::{parser::beach::ast::{Definition, Evaluatable, PrimitiveType, Task, TypeIdentity, Value}, generator::generic::{AssemblyGenerator, Extension, HardwareData}};
+use crate::generator::aarch64::AArch64AssemblyGenerator;
+use crate::platform::generic::Platform;
+use crate::utils::{Bigcplx, Bigfloat, Bigint, FloatFormat};
+
+use super::typecheck::POINTER_WIDTH;
+
+#[derive(Debug, Clone)]
 pub struct Executable {
     pub platform_requirements: Vec<String>,
     pub data: Vec<Data>,
@@ -8,85 +15,536 @@ pub struct Executable {
     /// Section that should be run on startup
     pub entry_point: CodeSegment,
     internal_data_index: usize,
+    internal_label_index: usize,
 }
 
 impl Executable {
-    fn empty() -> Self {
+    pub(super) fn empty() -> Self {
         Executable {
             platform_requirements: vec![],
             data: vec![],
             code_sections: vec![],
             entry_point: CodeSegment::new(""),
             internal_data_index: 0,
+            internal_label_index: 0,
         }
     }
-    pub fn from_ast(ast: super::ast::Program) -> Self {
+    pub fn from_ast(mut ast: super::ast::Program) -> Result<Self, anyhow::Error> {
+        // Every generic function needs a concrete copy before it can become a
+        // code segment.
+        super::monomorphize::monomorphize(&mut ast)?;
+        let diagnostics = super::typecheck::check(&mut ast);
+        if !diagnostics.is_empty() {
+            let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+            return Err(anyhow::Error::msg(messages.join("\n")));
+        }
         let mut program = Self::empty();
+        let mut functions = vec![];
+        let mut layouts = HashMap::new();
+        let mut aliases = HashMap::new();
         for def in ast.definitions {
-            if let Definition::System { label } = def {
-                program.platform_requirements.push(label.clone());
+            match def {
+                Definition::System { label } => {
+                    program.platform_requirements.push(label.clone());
+                }
+                Definition::Function(function) => functions.push(function),
+                Definition::Type { name, data, .. } => {
+                    layouts.insert(name, data.into_iter().map(|(field, field_type, _)| (field, field_type)).collect());
+                }
+                Definition::Alias { from, to, .. } => {
+                    aliases.insert(from, to);
+                }
+                _ => {}
+            }
+        }
+        // Argument types decide how values are passed to each function.
+        let signatures: HashMap<String, Vec<TypeIdentity>> = functions.iter()
+            .map(|function| (
+                function.name.clone(),
+                function.arguments.iter().map(|argument| argument.arg_type.clone()).collect()
+            ))
+            .collect();
+        let results: HashMap<String, TypeIdentity> = functions.iter()
+            .map(|function| (function.name.clone(), function.returns.clone()))
+            .collect();
+        // Every source file is read once, so that source lines can be written
+        // beside the code made from them.
+        let mut sources = HashMap::new();
+        for file in functions.iter().filter_map(|function| function.file.as_ref()).chain(ast.path.as_ref()) {
+            if !sources.contains_key(file) {
+                let text = crate::stdlib::read_file(file).unwrap_or_default();
+                sources.insert(file.clone(), text.lines().map(String::from).collect());
+            }
+        }
+        let mut lowering = Lowering {
+            signatures,
+            results,
+            returns: TypeIdentity::Primitive(PrimitiveType::Nothing),
+            layouts,
+            locals: vec![],
+            aliases,
+            sources
+        };
+        for function in functions {
+            let mut segment = CodeSegment {
+                label: assembly_label(&function.name),
+                tasks: vec![]
+            };
+            let file = function.file.as_deref();
+            lowering.mark_source(&mut segment, file, function.location.line);
+            lowering.locals = vec![];
+            lowering.returns = function.returns.clone();
+            for argument in &function.arguments {
+                let mut slots = lowering.slots(&argument.name, &argument.arg_type, 0);
+                lowering.locals.append(&mut slots);
+            }
+            // Arguments are moved out of the registers they're passed in, as
+            // calls and system calls need those registers. The register
+            // allocator decides where they go.
+            for index in 0..lowering.locals.len() {
+                segment.add_task(GeneratableTask::ReadArgument {
+                    argument_number: index,
+                    destination: ImmediateOrRefrence::Virtual(index)
+                });
+            }
+            let ends_with_return = matches!(function.code.last(), Some(Task::ExitBlock | Task::Return { .. } | Task::Unreachable));
+            program.lower_tasks(function.code, &mut lowering, &mut segment, file, false)?;
+            // Falling off the end of a function returns from it.
+            if !ends_with_return {
+                segment.add_task(GeneratableTask::EndCall);
             }
+            program.code_sections.push(segment);
         }
-        for task in ast.main_tasks {
+        lowering.locals = vec![];
+        lowering.returns = TypeIdentity::Primitive(PrimitiveType::Nothing);
+        let mut entry_point = CodeSegment::new("");
+        if let Some(main) = ast.main {
+            lowering.mark_source(&mut entry_point, ast.path.as_deref(), main.line);
+        }
+        program.lower_tasks(ast.main_tasks, &mut lowering, &mut entry_point, ast.path.as_deref(), true)?;
+        program.entry_point = entry_point;
+        Ok(program)
+    }
+    /// Gives every virtual register a real register or stack slot, using the
+    /// registers `G` has.
+    pub fn allocate_registers<G: AssemblyGenerator>(&mut self) {
+        for section in &mut self.code_sections {
+            crate::generator::allocator::allocate::<G>(section, true);
+        }
+        crate::generator::allocator::allocate::<G>(&mut self.entry_point, false);
+    }
+    /// Converts `tasks`, written in `file`, into [GeneratableTask]s at the end
+    /// of `segment`. `is_main` marks the tasks of the main block, which exit
+    /// through the platform's exit code instead of returning. Fails on tasks
+    /// that can't be compiled yet.
+    fn lower_tasks(
+        &mut self,
+        tasks: Vec<Task>,
+        lowering: &mut Lowering,
+        segment: &mut CodeSegment,
+        file: Option<&Path>,
+        is_main: bool
+    ) -> Result<(), anyhow::Error> {
+        // Values are evaluated for the call after them, so they come from the
+        // same line.
+        let mut lines = vec![None; tasks.len()];
+        let mut line = None;
+        for (index, task) in tasks.iter().enumerate().rev() {
             match task {
-                Task::ExitBlock => {}, //program.entry_point.add_task(GeneratableTask::EndCall),
-                Task::Call { function_identifier: label, arguments } => {
-                    for (argument_index, argument_value) in arguments.iter().enumerate() {
-                        match argument_value {
-                            Value::Label(label) => {
-                                program.entry_point.add_task(GeneratableTask::SetCallArgument {
-                                    argument_number: argument_index,
-                                    argument_value: ImmediateOrRefrence::Refrence(label.clone())
-                                });
-                            }
-                            Value::String(data) => {
-                                let string_as_bytes = data.as_bytes();
-                                let local_label = format!("compiler_ir_const_data_allocation_{}", program.internal_data_index);
-                                program.data.push(Data {
-                                    label: local_label.clone(),
-                                    size: string_as_bytes.len(),
-                                    default: Some(string_as_bytes.to_vec())
-                                });
-                                program.internal_data_index += 1;
-                                program.entry_point.add_task(GeneratableTask::SetCallArgument {
-                                    argument_number: argument_index,
-                                    argument_value: ImmediateOrRefrence::Refrence(local_label)
-                                });
-                            }
-                            Value::Integer(data) => {
-                                if data.bit_width() <= 32 {
-                                    program.entry_point.add_task(GeneratableTask::SetCallArgument {
-                                        argument_number: argument_index,
-                                        argument_value: ImmediateOrRefrence::Immediate(data.to_le_bytes().unwrap())
-                                    });
-                                }
-                                else {
-                                    let local_label = format!("compiler_ir_const_data_allocation_{}", program.internal_data_index);
-                                    program.data.push(Data {
-                                        label: local_label.clone(),
-                                        size: (data.bit_width() as f32 / 8.0).floor() as usize,
-                                        default: Some(data.to_le_bytes().unwrap())
-                                    });
-                                    program.internal_data_index += 1;
-                                    program.entry_point.add_task(GeneratableTask::SetCallArgument {
-                                        argument_number: argument_index,
-                                        argument_value: ImmediateOrRefrence::Refrence(local_label)
-                                    });
-                                }
-                            }
-                            _ => todo!()
-                        }
+                Task::Call { location, .. } | Task::Syscall { location, .. } |
+                Task::Evaluate { task: Evaluatable::Call { location, .. }, .. } |
+                Task::Set { location, .. } | Task::Return { location, .. } |
+                Task::If { location, .. } => line = Some(location.line),
+                Task::Evaluate { .. } => {}
+                _ => line = None
+            }
+            lines[index] = line;
+        }
+        for (task, line) in tasks.into_iter().zip(lines) {
+            if let Some(line) = line {
+                lowering.mark_source(segment, file, line);
+            }
+            // Errors point at the line being lowered, where it's known.
+            let at = |message: String| {
+                let place = match (file, line) {
+                    (Some(file), Some(line)) => format!("{}:{line}: ", file.display()),
+                    (None, Some(line)) => format!("{line}: "),
+                    _ => String::new()
+                };
+                anyhow::Error::msg(format!("{place}{message}"))
+            };
+            match task {
+                Task::ExitBlock => {
+                    if !is_main {
+                        segment.add_task(GeneratableTask::EndCall);
+                    }
+                }
+                Task::Call { function_identifier: label, arguments, .. } => {
+                    self.lower_call(&label, &arguments, lowering, segment).map_err(at)?;
+                }
+                Task::Evaluate { label: evaluated, task: Evaluatable::Call { label, arguments, .. } } => {
+                    let label = self.lower_call(&label, &arguments, lowering, segment).map_err(at)?;
+                    let returns = lowering.results.get(&label).cloned()
+                        .unwrap_or(TypeIdentity::Primitive(PrimitiveType::Nothing));
+                    lowering.free(&evaluated);
+                    // Functions returning nothing have no results to read.
+                    if returns == TypeIdentity::Primitive(PrimitiveType::Nothing) {
+                        continue;
+                    }
+                    for (result_number, name) in lowering.slots(&evaluated, &returns, 0).into_iter().enumerate() {
+                        let destination = lowering.local(&name);
+                        segment.add_task(GeneratableTask::ReadResult { result_number, destination });
+                    }
+                }
+                Task::Syscall { number, arguments, result, .. } => {
+                    let number = self.lower_value(&number, None, lowering).map_err(at)?.remove(0);
+                    let mut lowered = vec![];
+                    for argument in &arguments {
+                        lowered.append(&mut self.lower_value(argument, None, lowering).map_err(at)?);
+                    }
+                    segment.add_task(GeneratableTask::Syscall { number, arguments: lowered });
+                    if let Some(result) = result {
+                        lowering.free(&result);
+                        let destination = lowering.local(&result);
+                        segment.add_task(GeneratableTask::ReadResult { result_number: 0, destination });
+                    }
+                }
+                Task::Unreachable => segment.add_task(GeneratableTask::Unreachable),
+                Task::Evaluate { label, task: Evaluatable::Math { operator, a, b } } => {
+                    // Operations work on whole registers, so constants are
+                    // made as wide as one, keeping the sign of negative ones.
+                    let register = TypeIdentity::Primitive(PrimitiveType::ArchSigned);
+                    let a = self.lower_value(&a, Some(&register), lowering).map_err(at)?.remove(0);
+                    let b = self.lower_value(&b, Some(&register), lowering).map_err(at)?.remove(0);
+                    lowering.free(&label);
+                    let destination = lowering.local(&label);
+                    segment.add_task(GeneratableTask::Operation { operator, destination, a, b });
+                }
+                Task::Evaluate { label, task: Evaluatable::Value { value } } => {
+                    self.lower_set(&label, None, &value, lowering, segment).map_err(at)?;
+                }
+                Task::Set { label, type_, value, .. } => {
+                    self.lower_set(&label, type_.as_deref(), &value, lowering, segment).map_err(at)?;
+                }
+                Task::FreeEvaluated { label } => lowering.free(&label),
+                Task::Return { value, .. } => {
+                    let returns = lowering.returns.clone();
+                    for (result_number, value) in self.lower_value(&value, Some(&returns), lowering).map_err(at)?.into_iter().enumerate() {
+                        segment.add_task(GeneratableTask::SetResult { result_number, value });
+                    }
+                    if !is_main {
+                        segment.add_task(GeneratableTask::EndCall);
+                    }
+                }
+                Task::If { a, b, tasks, .. } => {
+                    // `a == b` when `a - b` is zero.
+                    let register = TypeIdentity::Primitive(PrimitiveType::ArchSigned);
+                    let a = self.lower_value(&a, Some(&register), lowering).map_err(at)?.remove(0);
+                    let b = self.lower_value(&b, Some(&register), lowering).map_err(at)?.remove(0);
+                    let index = self.internal_label_index;
+                    self.internal_label_index += 1;
+                    let (then, end) = (format!("compiler_ir_if_{index}"), format!("compiler_ir_if_end_{index}"));
+                    let difference = lowering.local(&then);
+                    segment.add_task(GeneratableTask::Operation {
+                        operator: Operator::Subtract,
+                        destination: difference.clone(),
+                        a,
+                        b
+                    });
+                    segment.add_task(GeneratableTask::GoToIfZero { label: then.clone(), value: difference });
+                    segment.add_task(GeneratableTask::GoTo(end.clone()));
+                    segment.add_task(GeneratableTask::Label(then));
+                    let defined = lowering.locals.len();
+                    self.lower_tasks(tasks, lowering, segment, file, is_main)?;
+                    // Labels set inside the `if` are only in scope inside it.
+                    for local in &mut lowering.locals[defined..] {
+                        local.clear();
                     }
-                    program.entry_point.add_task(GeneratableTask::Call(label));
+                    segment.add_task(GeneratableTask::Label(end));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Passes `arguments` to the function `label` refers to and calls it,
+    /// returning the function called. Fails on arguments that can't be
+    /// compiled yet.
+    fn lower_call(
+        &mut self,
+        label: &str,
+        arguments: &[Value],
+        lowering: &mut Lowering,
+        segment: &mut CodeSegment
+    ) -> Result<String, String> {
+        let label = lowering.target(label);
+        let expected = lowering.signatures.get(&label).cloned();
+        let mut argument_number = 0;
+        for (argument_index, argument_value) in arguments.iter().enumerate() {
+            let expected = expected.as_ref().and_then(|arguments| arguments.get(argument_index));
+            for argument_value in self.lower_value(argument_value, expected, lowering)? {
+                segment.add_task(GeneratableTask::SetCallArgument {
+                    argument_number,
+                    argument_value
+                });
+                argument_number += 1;
+            }
+        }
+        segment.add_task(GeneratableTask::Call(assembly_label(&label)));
+        Ok(label)
+    }
+    /// Sets `label` to `value`, declared as the type named `type_` if it was
+    /// declared with one. Copies of a label keep the names of its pieces, so
+    /// that `copy.length` can be used after `let copy = output;`.
+    fn lower_set(
+        &mut self,
+        label: &str,
+        type_: Option<&str>,
+        value: &Value,
+        lowering: &mut Lowering,
+        segment: &mut CodeSegment
+    ) -> Result<(), String> {
+        let expected = match (type_, value) {
+            (Some(type_), _) => Some(TypeIdentity::from_name(type_, &[])),
+            (None, Value::String(_)) => Some(TypeIdentity::Structured(String::from("string"))),
+            _ => None
+        };
+        let sources = self.lower_value(value, expected.as_ref(), lowering)?;
+        let copied = match value {
+            Value::Label(source) => lowering.find(source),
+            _ => vec![]
+        };
+        let names = match (value, &expected) {
+            (Value::Label(source), _) if !copied.is_empty() => copied.into_iter()
+                .map(|(_, name)| format!("{label}{}", &name[source.len()..]))
+                .collect(),
+            (_, Some(expected)) => lowering.slots(label, expected, 0),
+            _ => vec![label.to_string()]
+        };
+        lowering.free(label);
+        for (name, source) in names.iter().zip(sources) {
+            let destination = lowering.local(name);
+            segment.add_task(GeneratableTask::Copy { destination, source });
+        }
+        Ok(())
+    }
+    /// Converts a value into the register sized pieces it's passed as. Values
+    /// given to an argument of type `expected` are passed the same way that
+    /// argument is read. (see [Lowering::slots]) Fails on values that can't be
+    /// compiled yet.
+    fn lower_value(&mut self, value: &Value, expected: Option<&TypeIdentity>, lowering: &Lowering) -> Result<Vec<ImmediateOrRefrence>, String> {
+        match value {
+            Value::Label(label) => {
+                // Arguments of the function being lowered were read into
+                // virtual registers, numbered in the order they're passed.
+                let slots: Vec<ImmediateOrRefrence> = lowering.find(label).into_iter()
+                    .map(|(index, _)| ImmediateOrRefrence::Virtual(index))
+                    .collect();
+                if !slots.is_empty() {
+                    return Ok(slots);
+                }
+                return Ok(vec![ImmediateOrRefrence::Refrence(assembly_label(label))]);
+            }
+            Value::String(data) => {
+                let string_as_bytes = data.as_bytes();
+                let local_label = format!("compiler_ir_const_data_allocation_{}", self.internal_data_index);
+                self.data.push(Data {
+                    label: local_label.clone(),
+                    size: string_as_bytes.len(),
+                    default: Some(string_as_bytes.to_vec())
+                });
+                self.internal_data_index += 1;
+                let mut lowered = vec![ImmediateOrRefrence::Refrence(local_label)];
+                // A `string` is passed as its pointer followed by its length.
+                let expected_slots = expected.map(|expected| lowering.slots("", expected, 0).len());
+                if expected_slots == Some(2) {
+                    let length = Bigint::from_i64(string_as_bytes.len() as i64);
+                    // lengths are `usize`s
+                    lowered.push(ImmediateOrRefrence::Immediate(length.to_le_bytes(POINTER_WIDTH / 8).unwrap()));
+                }
+                return Ok(lowered);
+            }
+            Value::Integer(data) => {
+                if let Some(TypeIdentity::Primitive(primitive)) = expected
+                    && (primitive.is_float() || primitive.is_complex())
+                {
+                    // Integers given to floats are stored as floats.
+                    return self.lower_value(&Value::Float(Bigfloat::from_bigint(data)), expected, lowering);
+                }
+                // Integers are passed as wide as the argument they're given to,
+                // falling back to the size of the literal for calls that
+                // weren't type checked. (such as platform features)
+                let width = match expected {
+                    Some(TypeIdentity::Primitive(primitive)) if primitive.is_integer() => {
+                        primitive.bit_width().unwrap_or(POINTER_WIDTH)
+                    }
+                    _ => data.bit_width()
+                };
+                // Type checking made sure the value fits, and negative numbers
+                // are sign extended.
+                let bytes = data.to_le_bytes(width.div_ceil(8)).unwrap();
+                return Ok(vec![self.lower_constant(bytes)]);
+            }
+            Value::Float(data) => {
+                // Decimal numbers are rounded to the type they're given to,
+                // and are `f64`s otherwise.
+                let bytes = match expected {
+                    Some(TypeIdentity::Primitive(primitive)) if primitive.is_complex() => {
+                        Bigcplx::from_real(data.clone()).to_ieee_bytes(complex_format(primitive.bit_width().unwrap()))
+                    }
+                    Some(TypeIdentity::Primitive(primitive)) if primitive.is_float() => {
+                        data.to_ieee_bytes(FloatFormat::from_width(primitive.bit_width().unwrap()).unwrap())
+                    }
+                    _ => data.to_ieee_bytes(FloatFormat::Binary64)
+                };
+                return Ok(vec![self.lower_constant(bytes)]);
+            }
+            Value::Complex(data) => {
+                // Complex numbers are `c128`s unless given to another complex
+                // type.
+                let width = match expected {
+                    Some(TypeIdentity::Primitive(primitive)) if primitive.is_complex() => primitive.bit_width().unwrap(),
+                    _ => 128
+                };
+                return Ok(vec![self.lower_constant(data.to_ieee_bytes(complex_format(width)))]);
+            }
+            Value::Bool(_) => return Err(String::from("Booleans can't be compiled yet."))
+        }
+    }
+    /// Passes constants that fit in a register as immediates, and puts wider
+    /// ones in data.
+    fn lower_constant(&mut self, bytes: Vec<u8>) -> ImmediateOrRefrence {
+        if bytes.len() <= REGISTER_BYTES {
+            return ImmediateOrRefrence::Immediate(bytes);
+        }
+        let local_label = format!("compiler_ir_const_data_allocation_{}", self.internal_data_index);
+        self.data.push(Data {
+            label: local_label.clone(),
+            size: bytes.len(),
+            default: Some(bytes)
+        });
+        self.internal_data_index += 1;
+        return ImmediateOrRefrence::Refrence(local_label);
+    }
+}
+
+/// The width of a register in bytes. Every platform beach targets is generated
+/// for AArch64, so constants are split to fit its registers.
+const REGISTER_BYTES: usize = AArch64AssemblyGenerator::REGISTER_WIDTH as usize;
+
+/// The format of each part of a complex number `width` bits wide.
+fn complex_format(width: usize) -> FloatFormat {
+    FloatFormat::from_width(width / 2).expect("complex types are twice the width of a float type")
+}
+
+/// What's known about the program while lowering it.
+struct Lowering {
+    /// The argument types of every function, by name.
+    signatures: HashMap<String, Vec<TypeIdentity>>,
+    /// The return type of every function, by name.
+    results: HashMap<String, TypeIdentity>,
+    /// What the function being lowered returns.
+    returns: TypeIdentity,
+    /// The fields of every type, by name.
+    layouts: HashMap<String, Vec<(String, TypeIdentity)>>,
+    /// The register sized pieces of the arguments of the function being
+    /// lowered, in the order they're passed, followed by the values evaluated
+    /// in its body. Each is kept in the virtual register numbered by its
+    /// index.
+    locals: Vec<String>,
+    /// What every alias refers to, by name.
+    aliases: HashMap<String, String>,
+    /// The lines of every source file.
+    sources: HashMap<PathBuf, Vec<String>>,
+}
+
+impl Lowering {
+    /// Gives the evaluated value `name` the next virtual register.
+    fn local(&mut self, name: &str) -> ImmediateOrRefrence {
+        self.locals.push(name.to_string());
+        ImmediateOrRefrence::Virtual(self.locals.len() - 1)
+    }
+    /// The virtual registers holding the register sized pieces of the argument
+    /// or evaluated value `label`, along with the name of each piece.
+    fn find(&self, label: &str) -> Vec<(usize, String)> {
+        let prefix = format!("{label}.");
+        self.locals.iter()
+            .enumerate()
+            .filter(|(_, slot)| *slot == label || slot.starts_with(&prefix))
+            .map(|(index, slot)| (index, slot.clone()))
+            .collect()
+    }
+    /// Forgets the evaluated value `name`, so that its name can be used
+    /// again. Its virtual registers are never reused, keeping the IR in SSA
+    /// form.
+    fn free(&mut self, name: &str) {
+        let prefix = format!("{name}.");
+        for local in &mut self.locals {
+            if local == name || local.starts_with(&prefix) {
+                local.clear();
+            }
+        }
+    }
+    /// Notes that the next tasks in `segment` come from `line` of `file`,
+    /// unless the last note already says so.
+    fn mark_source(&self, segment: &mut CodeSegment, file: Option<&Path>, line: usize) {
+        let Some(file) = file else {
+            return;
+        };
+        let last = segment.tasks.iter().rev().find_map(|task| match task {
+            GeneratableTask::SourceLine { file, line, .. } => Some((file.as_path(), *line)),
+            _ => None
+        });
+        if last == Some((file, line)) {
+            return;
+        }
+        let text = self.sources.get(file)
+            .and_then(|lines| lines.get(line.wrapping_sub(1)))
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default();
+        segment.add_task(GeneratableTask::SourceLine { file: file.to_path_buf(), line, text });
+    }
+    /// The function `name` refers to, following any aliases of it.
+    /// (`print => stdout;`)
+    fn target(&self, name: &str) -> String {
+        let mut name = name;
+        // Type checking has already rejected calls through aliases that loop.
+        for _ in 0..64 {
+            match self.aliases.get(name) {
+                Some(to) => name = to,
+                None => break
+            }
+        }
+        name.to_string()
+    }
+    /// The names of the register sized pieces a value called `name` of type
+    /// `identity` is passed as. Types with fields are passed one field at a
+    /// time, (`output.pointer`, `output.length`) and everything else whole.
+    fn slots(&self, name: &str, identity: &TypeIdentity, depth: usize) -> Vec<String> {
+        let fields = match identity {
+            TypeIdentity::Structured(type_name) => self.layouts.get(type_name),
+            _ => None
+        };
+        match fields {
+            // Types that contain themselves can't be passed anyways.
+            Some(fields) if !fields.is_empty() && depth < 64 => {
+                let mut slots = vec![];
+                for (field, field_type) in fields {
+                    let mut field_slots = self.slots(&format!("{name}.{field}"), field_type, depth + 1);
+                    slots.append(&mut field_slots);
                 }
-                _ => todo!()
+                return slots;
             }
+            _ => return vec![name.to_string()]
         }
-        program
     }
 }
 
-#[derive(Debug)]
+/// Turns a full path such as `io~stdout` into a name assemblers accept.
+fn assembly_label(name: &str) -> String {
+    name.replace('~', ".")
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CodeSegment {
     pub label: String,
     pub tasks: Vec<GeneratableTask>
@@ -99,74 +557,476 @@ impl CodeSegment {
             tasks: vec![]
         }
     }
-    fn add_task(&mut self, task: GeneratableTask) {
+    pub fn add_task(&mut self, task: GeneratableTask) {
         self.tasks.push(task);
     }
+    /// The tasks that can run after each task in this segment. Running carries
+    /// on to the next task unless a task returns, stops the program or goes
+    /// to a label, and conditional branches carry on to both.
+    pub fn successors(&self) -> Vec<Vec<usize>> {
+        let labels: HashMap<&str, usize> = self.tasks.iter()
+            .enumerate()
+            .filter_map(|(index, task)| match task {
+                GeneratableTask::Label(label) => Some((label.as_str(), index)),
+                _ => None
+            })
+            .collect();
+        self.tasks.iter()
+            .enumerate()
+            .map(|(index, task)| {
+                let mut next = match task {
+                    GeneratableTask::EndCall | GeneratableTask::Unreachable => vec![],
+                    GeneratableTask::GoTo(label) => labels.get(label.as_str()).copied().into_iter().collect(),
+                    GeneratableTask::GoToIfZero { label, .. } => {
+                        std::iter::once(index + 1).chain(labels.get(label.as_str()).copied()).collect()
+                    }
+                    _ => vec![index + 1]
+                };
+                next.retain(|successor| *successor < self.tasks.len());
+                next
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum GeneratableTask {
+    /// Continues from the [GeneratableTask::Label] named this in the same
+    /// segment.
     GoTo(String),
+    /// Continues from the [GeneratableTask::Label] named `label` in the same
+    /// segment if `value` is zero, or from the next task if it isn't.
+    GoToIfZero { label: String, value: ImmediateOrRefrence },
+    /// A place in the segment that [GeneratableTask::GoTo] can continue from.
+    Label(String),
     SetCallArgument { argument_number: usize, argument_value: ImmediateOrRefrence },
     Call(String),
     EndCall,
     Add(String, String),
     Set(String),
-    RequiredExtension(Vec<String>)
+    /// Checks that the platform has every [Extension] named here, by
+    /// [Extension::name], as the tasks following it can't be made without
+    /// them.
+    RequiredExtension(Vec<String>),
+    /// Makes a system call, following the platform's system call convention.
+    Syscall { number: ImmediateOrRefrence, arguments: Vec<ImmediateOrRefrence> },
+    /// Stops the program, since it should never get here.
+    Unreachable,
+    /// Marks that the tasks following it were made from `line` of `file`,
+    /// which reads `text`.
+    SourceLine { file: PathBuf, line: usize, text: String },
+    /// Copies the `argument_number`th register sized piece of the current
+    /// function's arguments out of the register it was passed in.
+    ReadArgument { argument_number: usize, destination: ImmediateOrRefrence },
+    /// Sets the `result_number`th register sized piece of what the current
+    /// function returns, before a [GeneratableTask::EndCall].
+    SetResult { result_number: usize, value: ImmediateOrRefrence },
+    /// Copies the `result_number`th register sized piece of what the last call
+    /// or system call returned out of the register it was returned in.
+    ReadResult { result_number: usize, destination: ImmediateOrRefrence },
+    /// Sets the virtual register `destination` to `source`.
+    Copy { destination: ImmediateOrRefrence, source: ImmediateOrRefrence },
+    /// Sets the virtual register `destination` to `a operator b`, on
+    /// register sized integers that wrap on overflow.
+    Operation { operator: Operator, destination: ImmediateOrRefrence, a: ImmediateOrRefrence, b: ImmediateOrRefrence },
+    /// Starts a function's stack frame, saving the callee saved registers in
+    /// `saved` and reserving `size` bytes for spilled values. Added by the
+    /// register allocator.
+    EnterFrame { saved: Vec<String>, size: usize },
+    /// Undoes [GeneratableTask::EnterFrame] before returning.
+    ExitFrame { saved: Vec<String>, size: usize },
 }
 
 impl GeneratableTask {
-    pub fn call_generator<G: AssemblyGenerator>(&self) -> String {
+    /// The assembly for this task, using the first of `extensions` that
+    /// can do the job in place of generic assembly. Fails if the platform
+    /// can't make assembly for it.
+    pub fn call_generator<G: AssemblyGenerator>(&self, platform: &Platform<G>, extensions: &[Extension]) -> Result<String, anyhow::Error> {
         match self {
             Self::SetCallArgument { argument_number, argument_value } => {
-                let mut workspace = String::new();
-                let reg = G::ARGUMENT_REGISTERS.get(*argument_number)
-                    .expect("TODO: Too many arguments!")
-                    .to_string();
-                workspace += &G::push(
-                    HardwareData::ImmediateRegister(reg.clone())
-                );
-                workspace += &G::set(
-                    crate::generator::generic::HardwareData::ImmediateRegister(
-                        reg
-                    ),
-                    argument_value.into_hardware_data()
-                );
-                return workspace;
+                let reg = argument_register::<G>(*argument_number)?;
+                // Values the register allocator placed never live in argument
+                // registers, so setting one can't overwrite another.
+                return set::<G>(reg, argument_value.into_hardware_data::<G>(), extensions);
             }
             Self::Call(to_call) => {
                 return G::call(HardwareData::Label(to_call.clone()));
             }
             Self::EndCall => {
-                return G::endcall();
+                return Ok(G::endcall());
+            }
+            Self::Syscall { number, arguments } => {
+                let convention = &platform.syscall_convention;
+                let mut workspace = String::new();
+                for (index, argument) in arguments.iter().enumerate() {
+                    let register = convention.argument_registers.get(index).ok_or(anyhow::Error::msg(format!(
+                        "System calls on this platform take at most {} arguments.", convention.argument_registers.len()
+                    )))?;
+                    workspace += &set::<G>(
+                        HardwareData::ImmediateRegister(register.to_string()),
+                        argument.into_hardware_data::<G>(),
+                        extensions
+                    )?;
+                }
+                workspace += &set::<G>(
+                    HardwareData::ImmediateRegister(convention.number_register.to_string()),
+                    number.into_hardware_data::<G>(),
+                    extensions
+                )?;
+                workspace += convention.instruction;
+                return Ok(workspace);
+            }
+            Self::Unreachable => {
+                return Ok(G::trap());
+            }
+            Self::SourceLine { file, line, text } => {
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                return Ok(G::comment(&format!("{name}:{line}: {text}")));
+            }
+            Self::ReadArgument { argument_number, destination } => {
+                let reg = argument_register::<G>(*argument_number)?;
+                return G::set(destination.into_hardware_data::<G>(), reg);
             }
-            _ => todo!("{:?}", self)
+            Self::SetResult { result_number, value } => {
+                let reg = result_register::<G>(*result_number)?;
+                return set::<G>(reg, value.into_hardware_data::<G>(), extensions);
+            }
+            Self::ReadResult { result_number, destination } => {
+                let reg = result_register::<G>(*result_number)?;
+                return G::set(destination.into_hardware_data::<G>(), reg);
+            }
+            Self::Copy { destination, source } => {
+                return set::<G>(destination.into_hardware_data::<G>(), source.into_hardware_data::<G>(), extensions);
+            }
+            Self::Operation { operator, destination, a, b } => {
+                let destination = destination.into_hardware_data::<G>();
+                let (a, b) = (a.into_hardware_data::<G>(), b.into_hardware_data::<G>());
+                let selected = extensions.iter().find_map(|extension| match (extension, operator) {
+                    (Extension::AddStore(add_store), Operator::Add) => add_store(a.clone(), b.clone(), destination.clone()),
+                    _ => None
+                });
+                if let Some(output) = selected {
+                    return Ok(output);
+                }
+                // The register allocator never gives `destination` the same
+                // register as `b`, so setting it first can't overwrite `b`.
+                let mut workspace = set::<G>(destination.clone(), a, extensions)?;
+                workspace += &match operator {
+                    Operator::Add => G::add(b, destination)?,
+                    Operator::Subtract => G::subtract(b, destination)?,
+                    Operator::Multiply => G::multiply(b, destination)?,
+                };
+                return Ok(workspace);
+            }
+            Self::GoTo(label) => {
+                return G::goto(HardwareData::Label(label.clone()));
+            }
+            Self::GoToIfZero { label, value } => {
+                let (label, value) = (HardwareData::Label(label.clone()), value.into_hardware_data::<G>());
+                // There's no generic assembly for a branch, so any of the
+                // platform's extensions will do if the preferred ones can't.
+                return extensions.iter().chain(G::EXTENSIONS.iter())
+                    .find_map(|extension| match extension {
+                        Extension::GotoIfZero(goto_if_zero) => goto_if_zero(label.clone(), value.clone()),
+                        _ => None
+                    })
+                    .ok_or(anyhow::Error::msg(format!(
+                        "This platform has no `goto_if_zero` extension that can test {value:?}."
+                    )));
+            }
+            Self::Label(label) => {
+                return Ok(G::label(label.clone()));
+            }
+            Self::EnterFrame { saved, size } => {
+                return Ok(G::enter_frame(saved, *size));
+            }
+            Self::ExitFrame { saved, size } => {
+                return Ok(G::exit_frame(saved, *size));
+            }
+            Self::RequiredExtension(required) => {
+                for name in required {
+                    if !G::EXTENSIONS.iter().any(|extension| extension.name() == name) {
+                        return Err(anyhow::Error::msg(format!("This platform doesn't have the `{name}` extension.")));
+                    }
+                }
+                return Ok(String::new());
+            }
+            Self::Add(..) | Self::Set(..) => {
+                return Err(anyhow::Error::msg(format!(
+                    "`{self}` names its values by label, so it has to be lowered to virtual registers before assembly can be made for it."
+                )));
+            }
+        }
+    }
+    /// The values this task reads or writes, for the register allocator.
+    pub fn operands_mut(&mut self) -> Vec<&mut ImmediateOrRefrence> {
+        match self {
+            Self::ReadArgument { destination, .. } | Self::ReadResult { destination, .. } => vec![destination],
+            Self::Copy { destination, source } => vec![destination, source],
+            Self::Operation { destination, a, b, .. } => vec![destination, a, b],
+            _ => self.uses_mut()
         }
     }
+    /// The values this task reads.
+    pub fn uses_mut(&mut self) -> Vec<&mut ImmediateOrRefrence> {
+        match self {
+            Self::SetCallArgument { argument_value, .. } | Self::SetResult { value: argument_value, .. } => vec![argument_value],
+            Self::Syscall { number, arguments } => std::iter::once(number).chain(arguments.iter_mut()).collect(),
+            Self::Copy { source, .. } => vec![source],
+            Self::Operation { a, b, .. } => vec![a, b],
+            Self::GoToIfZero { value, .. } => vec![value],
+            _ => vec![]
+        }
+    }
+    /// The virtual register this task sets, if any. Tasks that only set a
+    /// virtual register have no other effects.
+    pub fn definition(&self) -> Option<usize> {
+        match self {
+            Self::ReadArgument { destination: ImmediateOrRefrence::Virtual(register), .. } |
+            Self::ReadResult { destination: ImmediateOrRefrence::Virtual(register), .. } |
+            Self::Copy { destination: ImmediateOrRefrence::Virtual(register), .. } |
+            Self::Operation { destination: ImmediateOrRefrence::Virtual(register), .. } => Some(*register),
+            _ => None
+        }
+    }
+}
+
+/// The register that passes the `argument_number`th register sized piece of
+/// a function's arguments.
+fn argument_register<G: AssemblyGenerator>(argument_number: usize) -> Result<HardwareData, anyhow::Error> {
+    let register = G::ARGUMENT_REGISTERS.get(argument_number).ok_or(anyhow::Error::msg(format!(
+        "Functions on this platform take at most {} register sized arguments.", G::ARGUMENT_REGISTERS.len()
+    )))?;
+    return Ok(HardwareData::ImmediateRegister(register.to_string()));
 }
 
-#[derive(Debug)]
-enum ImmediateOrRefrence {
+/// The register that returns the `result_number`th register sized piece of
+/// what a function returns.
+fn result_register<G: AssemblyGenerator>(result_number: usize) -> Result<HardwareData, anyhow::Error> {
+    let register = G::RESULT_REGISTERS.get(result_number).ok_or(anyhow::Error::msg(format!(
+        "Functions on this platform return at most {} register sized results.", G::RESULT_REGISTERS.len()
+    )))?;
+    return Ok(HardwareData::ImmediateRegister(register.to_string()));
+}
+
+/// Sets `location` equal to `value`, loading immediates with the first of
+/// `extensions` that can.
+fn set<G: AssemblyGenerator>(location: HardwareData, value: HardwareData, extensions: &[Extension]) -> Result<String, anyhow::Error> {
+    if let HardwareData::Immediate(_) = value {
+        let selected = extensions.iter().find_map(|extension| match extension {
+            Extension::LoadImmediate(load_immediate) => load_immediate(location.clone(), value.clone()),
+            _ => None
+        });
+        if let Some(output) = selected {
+            return Ok(output);
+        }
+    }
+    return G::set(location, value);
+}
+
+/// Operations on two integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImmediateOrRefrence {
     // Try not to pass things larger than ~4 bytes as immediate arguments.
     // (basically strings and large data)
     // Immediate data must be stored in LE order if numeric.
     Immediate(Vec<u8>),
-    Refrence(String)
+    Refrence(String),
+    /// A value in the `n`th virtual register of the current function, which
+    /// the register allocator replaces with a real register or stack slot.
+    Virtual(usize),
+    /// A value in a real register.
+    Register(String),
+    /// A value spilled to the current function's stack frame, as a byte
+    /// offset from the stack pointer.
+    Stack(usize),
 }
 
 impl ImmediateOrRefrence {
-    pub fn into_hardware_data(&self) -> HardwareData {
+    pub fn into_hardware_data<G: AssemblyGenerator>(&self) -> HardwareData {
         match self {
             Self::Immediate(data) => HardwareData::Immediate(data.clone()),
-            Self::Refrence(ref_name) => HardwareData::Label(ref_name.clone())
+            Self::Refrence(ref_name) => HardwareData::Label(ref_name.clone()),
+            Self::Virtual(_) => unreachable!("Virtual registers are allocated before generating assembly."),
+            Self::Register(register) => HardwareData::ImmediateRegister(register.clone()),
+            Self::Stack(offset) => HardwareData::StackSlot(*offset),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Data {
     pub label: String,
     pub size: usize,
     // default data is in LE order if relevant.
     pub default: Option<Vec<u8>>
 }
+
+#[test]
+fn test_lower_syscall() {
+    use crate::parser::beach::ast::{Program, user_token_format::Syntax};
+    let source = String::from("
+        type string {
+            var pointer: usize;
+            var length: usize;
+        }
+        var stdout = |output: string| -> nothing {
+            unsafe {
+                // write(1, pointer, length)
+                !!syscall(4, (1, output.pointer, output.length));
+            }
+            return;
+        };
+        main {
+            stdout(\"Hello!\");
+        }
+    ");
+    let program = Program::from_lst(Syntax::from_string(source).unwrap(), None).unwrap();
+    let mut executable = Executable::from_ast(program).unwrap();
+    executable.allocate_registers::<crate::generator::aarch64::AArch64AssemblyGenerator>();
+    let platform = crate::platform::get_all_platforms().remove(0);
+    let stdout: String = executable.code_sections[0].tasks.iter()
+        .map(|task| task.call_generator(&platform, &[]))
+        .collect::<Result<String, _>>()
+        .unwrap();
+    // The string's pointer and length are moved out of the way of the file
+    // descriptor.
+    assert_eq!(stdout, "\
+mov x9, x0
+mov x10, x1
+movz x0, #1
+mov x1, x9
+mov x2, x10
+movz x16, #4
+svc #0
+ret\n");
+    let main: String = executable.entry_point.tasks.iter()
+        .map(|task| task.call_generator(&platform, &[]))
+        .collect::<Result<String, _>>()
+        .unwrap();
+    assert!(main.contains("adr x0, compiler_ir_const_data_allocation_0\n"));
+    assert!(main.contains("movz x1, #6\n"));
+
+    let unsafe_source = String::from("
+        var exit = |code: i16| -> never {
+            !!syscall(1, (code));
+        };
+    ");
+    let error = Program::from_lst(Syntax::from_string(unsafe_source).unwrap(), None).unwrap_err();
+    assert_eq!(error.to_string(), "3:13: `!!syscall` can only be used inside an `unsafe` block.");
+}
+
+#[test]
+fn test_select_extensions() {
+    use crate::generator::aarch64::AArch64AssemblyGenerator;
+    use crate::generator::generic::Priority;
+    let platform = crate::platform::get_all_platforms().remove(0);
+    let register = |name: &str| ImmediateOrRefrence::Register(String::from(name));
+    let add = |b| GeneratableTask::Operation { operator: Operator::Add, destination: register("x9"), a: register("x10"), b };
+    let branch = GeneratableTask::GoToIfZero { label: String::from("done"), value: register("x12") };
+    let constant = GeneratableTask::Copy {
+        destination: register("x9"),
+        source: ImmediateOrRefrence::Immediate(vec![0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE])
+    };
+    let generate = |task: &GeneratableTask, extensions| task.call_generator(&platform, extensions).unwrap();
+
+    // Backends with a fused add or branch use it for either priority...
+    for priority in [Priority::Performance, Priority::Size] {
+        let extensions = priority.extensions::<AArch64AssemblyGenerator>();
+        assert_eq!(generate(&add(register("x11")), extensions), "add x9, x10, x11\n");
+        assert_eq!(generate(&add(ImmediateOrRefrence::Immediate(vec![0x10])), extensions), "add x9, x10, #16\n");
+        assert_eq!(generate(&branch, extensions), "cbz x12, done\n");
+    }
+    // ...and generic assembly does the same without it.
+    assert_eq!(generate(&add(register("x11")), &[]), "mov x9, x10\nadd x9, x9, x11\n");
+
+    // A constant with four parts is built in place for speed, and loaded
+    // from the literal pool for size.
+    let fast = generate(&constant, Priority::Performance.extensions::<AArch64AssemblyGenerator>());
+    let small = generate(&constant, Priority::Size.extensions::<AArch64AssemblyGenerator>());
+    assert_eq!(fast, "movz x9, #12816\nmovk x9, #30292, lsl #16\nmovk x9, #47768, lsl #32\nmovk x9, #65244, lsl #48\n");
+    assert_eq!(small, "ldr x9, =0xFEDCBA9876543210\n");
+    let fast = AArch64AssemblyGenerator::assemble(&fast).unwrap();
+    let small = AArch64AssemblyGenerator::assemble(&small).unwrap();
+    assert_eq!((fast.bytes.len(), small.bytes.len()), (16, 16));
+    // The literal pool starts 8 byte aligned.
+    assert_eq!(&small.bytes[8..], &0xFEDCBA9876543210u64.to_le_bytes());
+    // A constant with fewer parts is no smaller in the literal pool.
+    let short = GeneratableTask::Copy { destination: register("x9"), source: ImmediateOrRefrence::Immediate(vec![0x10, 0x32, 0x54, 0x76]) };
+    assert_eq!(generate(&short, Priority::Size.extensions::<AArch64AssemblyGenerator>()), "movz x9, #12816\nmovk x9, #30292, lsl #16\n");
+
+    // Branches have no generic assembly, so the platform's extension is used
+    // even when the priority leaves it out.
+    assert_eq!(generate(&branch, &[]), "cbz x12, done\n");
+    let required = |names: &[&str]| GeneratableTask::RequiredExtension(names.iter().map(|name| name.to_string()).collect());
+    assert_eq!(generate(&required(&["goto_if_zero", "add_store"]), &[]), "");
+    let error = required(&["stack_push"]).call_generator(&platform, &[]).unwrap_err();
+    assert_eq!(error.to_string(), "This platform doesn't have the `stack_push` extension.");
+    let error = GeneratableTask::Set(String::from("value")).call_generator(&platform, &[]).unwrap_err();
+    assert_eq!(error.to_string(), "`set value` names its values by label, so it has to be lowered to virtual registers before assembly can be made for it.");
+    let wide = GeneratableTask::Copy { destination: register("x9"), source: ImmediateOrRefrence::Immediate(vec![1; 16]) };
+    assert!(wide.call_generator(&platform, &[]).unwrap_err().to_string().starts_with("AArch64 can't set"));
+}
+
+#[test]
+fn test_lower_floats() {
+    use crate::parser::beach::ast::{Program, user_token_format::Syntax};
+    let source = String::from("
+        var measure = |half: f16, single: f32, double: f64, complex: c64, wide: c128| -> nothing {
+            return;
+        };
+        main {
+            measure(0.1, 2, 58.3, 1.5-2i, 4i);
+        }
+    ");
+    let program = Program::from_lst(Syntax::from_string(source).unwrap(), None).unwrap();
+    let executable = Executable::from_ast(program).unwrap();
+    let arguments: Vec<&ImmediateOrRefrence> = executable.entry_point.tasks.iter()
+        .filter_map(|task| match task {
+            GeneratableTask::SetCallArgument { argument_value, .. } => Some(argument_value),
+            _ => None
+        })
+        .collect();
+    let immediate = |bytes: &[u8]| ImmediateOrRefrence::Immediate(bytes.to_vec());
+    assert_eq!(arguments[0], &immediate(&[0x66, 0x2e]));
+    // Integers given to floats are converted.
+    assert_eq!(arguments[1], &immediate(&2.0f32.to_le_bytes()));
+    assert_eq!(arguments[2], &immediate(&58.3f64.to_le_bytes()));
+    let mut complex = 1.5f32.to_le_bytes().to_vec();
+    complex.extend((-2.0f32).to_le_bytes());
+    assert_eq!(arguments[3], &immediate(&complex));
+    // Values wider than a register go in data.
+    let mut wide = 0.0f64.to_le_bytes().to_vec();
+    wide.extend(4.0f64.to_le_bytes());
+    assert_eq!(executable.data.len(), 1);
+    assert_eq!(executable.data[0].default, Some(wide));
+}
+
+#[test]
+fn test_lower_unsupported() {
+    use crate::parser::beach::ast::{Program, user_token_format::Syntax};
+    let source = String::from("
+        var check = |code: u8| -> nothing {
+            return;
+        };
+        main {
+            check(0);
+        }
+    ");
+    let lower = |task: Task| {
+        let mut program = Program::from_lst(Syntax::from_string(source.clone()).unwrap(), None).unwrap();
+        program.path = Some(std::path::PathBuf::from("main.beach"));
+        program.main_tasks.insert(0, task);
+        return Executable::from_ast(program).map(|_| ()).unwrap_err().to_string();
+    };
+    // Tasks the parser can't make yet fail instead of panicking.
+    let flag = Task::Evaluate { label: String::from("flag"), task: Evaluatable::Value { value: Value::Bool(true) } };
+    assert_eq!(lower(flag), "main.beach:6: Booleans can't be compiled yet.");
+}
@@ -0,0 +1,536 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::ir::{CodeSegment, Data, Executable, GeneratableTask, ImmediateOrRefrence, Operator};
+
+// The IR's text format, which `beach build ir` writes out, and which `beach
+// build` and `beach run` compile when given a `.ir` file as `input`. Every
+// task is on a line of its own, and reads back into the same task:
+//
+//     require linux_syscalls
+//     data compiler_ir_const_data_allocation_0 6 = "Hello!"
+//     function stdout {
+//         %0 = argument 0
+//         %1 = argument 1
+//         syscall 0x04 (0x01, %0, %1)
+//         return
+//     }
+//     main {
+//         set_argument 0 = @compiler_ir_const_data_allocation_0
+//         set_argument 1 = 0x0000000000000006
+//         call stdout
+//     }
+//
+// Values are written as:
+// - `0x...` for immediates, as a big endian hex number with two digits for
+//   every byte, so `0x0006` is the two bytes `06 00`
+// - `@label` for refrences to labels
+// - `%n` for virtual registers
+// - `$name` for real registers
+// - `[n]` for stack slots, `n` bytes above the stack pointer
+
+impl Display for Executable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for requirement in &self.platform_requirements {
+            writeln!(f, "require {requirement}")?;
+        }
+        for data in &self.data {
+            write!(f, "data {} {}", data.label, data.size)?;
+            match &data.default {
+                // Text is easier to read as text.
+                Some(bytes) if is_text(bytes) => writeln!(f, " = {:?}", String::from_utf8_lossy(bytes))?,
+                Some(bytes) => {
+                    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+                    writeln!(f, " = [{}]", bytes.join(" "))?;
+                }
+                None => writeln!(f)?
+            }
+        }
+        for segment in &self.code_sections {
+            writeln!(f, "function {} {{", segment.label)?;
+            write_tasks(f, segment)?;
+            writeln!(f, "}}")?;
+        }
+        writeln!(f, "main {{")?;
+        write_tasks(f, &self.entry_point)?;
+        writeln!(f, "}}")
+    }
+}
+
+fn write_tasks(f: &mut Formatter<'_>, segment: &CodeSegment) -> std::fmt::Result {
+    for task in &segment.tasks {
+        writeln!(f, "    {task}")?;
+    }
+    Ok(())
+}
+
+/// Whether `bytes` can be written as a string and read back unchanged.
+fn is_text(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => !text.is_empty() && text.chars().all(|character| !character.is_control() || character == '\n'),
+        Err(_) => false
+    }
+}
+
+impl Display for GeneratableTask {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GoTo(label) => write!(f, "goto {label}"),
+            Self::GoToIfZero { label, value } => write!(f, "goto_if_zero {label}, {value}"),
+            Self::Label(label) => write!(f, "label {label}"),
+            Self::SetCallArgument { argument_number, argument_value } => {
+                write!(f, "set_argument {argument_number} = {argument_value}")
+            }
+            Self::Call(label) => write!(f, "call {label}"),
+            Self::EndCall => write!(f, "return"),
+            Self::Add(a, b) => write!(f, "add {a} {b}"),
+            Self::Set(label) => write!(f, "set {label}"),
+            Self::RequiredExtension(extensions) => write!(f, "require_extension ({})", extensions.join(", ")),
+            Self::Syscall { number, arguments } => write!(f, "syscall {number} ({})", list(arguments)),
+            Self::Unreachable => write!(f, "unreachable"),
+            Self::SourceLine { file, line, text } => write!(f, "line {:?}:{line} {text:?}", file.to_string_lossy()),
+            Self::ReadArgument { argument_number, destination } => {
+                write!(f, "{destination} = argument {argument_number}")
+            }
+            Self::SetResult { result_number, value } => write!(f, "set_result {result_number} = {value}"),
+            Self::ReadResult { result_number, destination } => write!(f, "{destination} = result {result_number}"),
+            Self::Copy { destination, source } => write!(f, "{destination} = {source}"),
+            Self::Operation { operator, destination, a, b } => {
+                let operator = match operator {
+                    Operator::Add => "add",
+                    Operator::Subtract => "subtract",
+                    Operator::Multiply => "multiply",
+                };
+                write!(f, "{destination} = {operator} {a}, {b}")
+            }
+            Self::EnterFrame { saved, size } => write!(f, "enter_frame {size} ({})", registers(saved)),
+            Self::ExitFrame { saved, size } => write!(f, "exit_frame {size} ({})", registers(saved)),
+        }
+    }
+}
+
+fn registers(registers: &[String]) -> String {
+    registers.iter().map(|register| format!("${register}")).collect::<Vec<String>>().join(", ")
+}
+
+fn list(values: &[ImmediateOrRefrence]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl Display for ImmediateOrRefrence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Immediate(bytes) => {
+                write!(f, "0x")?;
+                for byte in bytes.iter().rev() {
+                    write!(f, "{byte:02x}")?;
+                }
+                Ok(())
+            }
+            Self::Refrence(label) => write!(f, "@{label}"),
+            Self::Virtual(register) => write!(f, "%{register}"),
+            Self::Register(register) => write!(f, "${register}"),
+            Self::Stack(offset) => write!(f, "[{offset}]"),
+        }
+    }
+}
+
+impl FromStr for ImmediateOrRefrence {
+    type Err = anyhow::Error;
+    fn from_str(value: &str) -> Result<ImmediateOrRefrence, anyhow::Error> {
+        let invalid = || anyhow::Error::msg(format!("`{value}` isn't a value."));
+        if let Some(digits) = value.strip_prefix("0x") {
+            if digits.is_empty() || digits.len() % 2 != 0 {
+                return Err(anyhow::Error::msg(format!("`{value}` needs two hex digits for every byte.")));
+            }
+            return Ok(Self::Immediate(hex_bytes(digits).ok_or_else(invalid)?.into_iter().rev().collect()));
+        }
+        if let Some(offset) = value.strip_prefix('[').and_then(|offset| offset.strip_suffix(']')) {
+            return Ok(Self::Stack(offset.parse().map_err(|_| invalid())?));
+        }
+        let (prefix, name) = value.split_at(value.chars().next().map_or(0, char::len_utf8));
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        match prefix {
+            "@" => Ok(Self::Refrence(name.to_string())),
+            "%" => Ok(Self::Virtual(name.parse().map_err(|_| invalid())?)),
+            "$" => Ok(Self::Register(name.to_string())),
+            _ => Err(invalid())
+        }
+    }
+}
+
+/// Reads pairs of hex digits as bytes, in the order they're written.
+fn hex_bytes(digits: &str) -> Option<Vec<u8>> {
+    if !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(digits.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+impl FromStr for Executable {
+    type Err = anyhow::Error;
+    /// Reads IR written by [Executable]'s [Display] implementation.
+    fn from_str(text: &str) -> Result<Executable, anyhow::Error> {
+        let mut program = Executable::empty();
+        let mut segment: Option<CodeSegment> = None;
+        let mut main = None;
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let error = |e: anyhow::Error| anyhow::Error::msg(format!("line {line_number}: {e}"));
+            if let Some(current) = segment.as_mut() {
+                if line != "}" {
+                    current.add_task(line.parse().map_err(error)?);
+                    continue;
+                }
+                let finished = segment.take().unwrap();
+                if finished.label.is_empty() {
+                    main = Some(finished);
+                }
+                else {
+                    program.code_sections.push(finished);
+                }
+                continue;
+            }
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match keyword {
+                "require" => program.platform_requirements.push(rest.to_string()),
+                "data" => program.data.push(parse_data(rest).map_err(error)?),
+                "function" => {
+                    let Some(label) = rest.strip_suffix('{').map(str::trim).filter(|label| !label.is_empty())
+                    else {
+                        return Err(error(anyhow::Error::msg("Expected `function [LABEL] {`.")));
+                    };
+                    segment = Some(CodeSegment { label: label.to_string(), tasks: vec![] });
+                }
+                "main" if rest == "{" => {
+                    if main.is_some() {
+                        return Err(error(anyhow::Error::msg("There's more than one `main` block.")));
+                    }
+                    segment = Some(CodeSegment::new(""));
+                }
+                other => {
+                    return Err(error(anyhow::Error::msg(format!(
+                        "Expected `require`, `data`, `function` or `main`, not `{other}`."
+                    ))));
+                }
+            }
+        }
+        if let Some(unfinished) = segment {
+            return Err(anyhow::Error::msg(format!("`{}` is missing its closing `}}`.", match unfinished.label.as_str() {
+                "" => "main",
+                label => label
+            })));
+        }
+        program.entry_point = main.ok_or(anyhow::Error::msg("The IR has no `main` block."))?;
+        Ok(program)
+    }
+}
+
+/// Reads `[LABEL] [SIZE]`, optionally followed by `= "text"` or
+/// `= [hex bytes]`.
+fn parse_data(text: &str) -> Result<Data, anyhow::Error> {
+    let (declaration, default) = match text.split_once(" = ") {
+        Some((declaration, default)) => (declaration, Some(default.trim())),
+        None => (text, None)
+    };
+    let Some((label, size)) = declaration.trim().split_once(' ')
+    else {
+        return Err(anyhow::Error::msg("Expected `data [LABEL] [SIZE]`."));
+    };
+    let size = size.trim().parse::<usize>()
+        .map_err(|_| anyhow::Error::msg(format!("`{size}` isn't a size.")))?;
+    let default = match default {
+        None => None,
+        Some(default) if default.starts_with('"') => {
+            let (text, rest) = parse_string(default)?;
+            if !rest.is_empty() {
+                return Err(anyhow::Error::msg(format!("Unexpected `{rest}` after the data.")));
+            }
+            Some(text.into_bytes())
+        }
+        Some(default) => {
+            let bytes = default.strip_prefix('[')
+                .and_then(|bytes| bytes.strip_suffix(']'))
+                .ok_or(anyhow::Error::msg("Expected data to be a string or a list of bytes in `[]`."))?;
+            let bytes: Result<Vec<u8>, _> = bytes.split_whitespace().map(|byte| u8::from_str_radix(byte, 16)).collect();
+            Some(bytes.map_err(|_| anyhow::Error::msg("Expected every byte to be two hex digits."))?)
+        }
+    };
+    if default.as_ref().is_some_and(|default| default.len() != size) {
+        return Err(anyhow::Error::msg(format!("`{label}` has {size} bytes, but its default doesn't.")));
+    }
+    Ok(Data { label: label.to_string(), size, default })
+}
+
+/// Reads a string written with [std::fmt::Debug] from the start of `text`,
+/// returning it and the text that follows.
+fn parse_string(text: &str) -> Result<(String, &str), anyhow::Error> {
+    let unterminated = || anyhow::Error::msg(format!("`{text}` has an unfinished string."));
+    let mut characters = text.char_indices();
+    if !matches!(characters.next(), Some((_, '"'))) {
+        return Err(anyhow::Error::msg(format!("Expected a string, not `{text}`.")));
+    }
+    let mut string = String::new();
+    while let Some((index, character)) = characters.next() {
+        match character {
+            '"' => return Ok((string, text[index + 1..].trim_start())),
+            '\\' => {
+                let (_, escaped) = characters.next().ok_or_else(unterminated)?;
+                match escaped {
+                    'n' => string.push('\n'),
+                    'r' => string.push('\r'),
+                    't' => string.push('\t'),
+                    '0' => string.push('\0'),
+                    '\\' | '"' | '\'' => string.push(escaped),
+                    'u' => {
+                        // \u{...}
+                        let rest = &text[index + 2..];
+                        let end = rest.find('}').ok_or_else(unterminated)?;
+                        let code = rest.get(1..end)
+                            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or(anyhow::Error::msg(format!("`{}` isn't a character.", &rest[..=end])))?;
+                        string.push(code);
+                        for _ in 0..=end {
+                            characters.next();
+                        }
+                    }
+                    other => return Err(anyhow::Error::msg(format!("`\\{other}` isn't an escape."))),
+                }
+            }
+            _ => string.push(character)
+        }
+    }
+    Err(unterminated())
+}
+
+/// Reads a value list such as `(%0, 0x01)` or `($x19, $x20)`.
+fn parse_list(text: &str) -> Result<Vec<&str>, anyhow::Error> {
+    let inside = text.trim()
+        .strip_prefix('(')
+        .and_then(|inside| inside.strip_suffix(')'))
+        .ok_or(anyhow::Error::msg(format!("Expected a list in `()`, not `{text}`.")))?;
+    if inside.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(inside.split(',').map(str::trim).collect())
+}
+
+fn parse_number(text: &str) -> Result<usize, anyhow::Error> {
+    text.trim().parse().map_err(|_| anyhow::Error::msg(format!("`{text}` isn't a number.")))
+}
+
+impl FromStr for GeneratableTask {
+    type Err = anyhow::Error;
+    fn from_str(line: &str) -> Result<GeneratableTask, anyhow::Error> {
+        // Tasks that set a value start with it. Source lines can have ` = ` in
+        // their text, but never set anything.
+        if let Some((destination, source)) = line.split_once(" = ")
+            && !line.starts_with("line ")
+        {
+            if let Some(argument_number) = destination.strip_prefix("set_argument ") {
+                return Ok(Self::SetCallArgument {
+                    argument_number: parse_number(argument_number)?,
+                    argument_value: source.trim().parse()?
+                });
+            }
+            if let Some(result_number) = destination.strip_prefix("set_result ") {
+                return Ok(Self::SetResult {
+                    result_number: parse_number(result_number)?,
+                    value: source.trim().parse()?
+                });
+            }
+            let destination: ImmediateOrRefrence = destination.trim().parse()?;
+            let (operation, operands) = source.trim().split_once(' ').unwrap_or((source.trim(), ""));
+            let operator = match operation {
+                "argument" => {
+                    return Ok(Self::ReadArgument { argument_number: parse_number(operands)?, destination });
+                }
+                "result" => {
+                    return Ok(Self::ReadResult { result_number: parse_number(operands)?, destination });
+                }
+                "add" => Operator::Add,
+                "subtract" => Operator::Subtract,
+                "multiply" => Operator::Multiply,
+                _ => return Ok(Self::Copy { destination, source: source.trim().parse()? })
+            };
+            let Some((a, b)) = operands.split_once(',')
+            else {
+                return Err(anyhow::Error::msg(format!("Expected `{operation} [VALUE], [VALUE]`.")));
+            };
+            return Ok(Self::Operation { operator, destination, a: a.trim().parse()?, b: b.trim().parse()? });
+        }
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let task = match keyword {
+            "goto" => Self::GoTo(rest.to_string()),
+            "goto_if_zero" => {
+                let Some((label, value)) = rest.split_once(',')
+                else {
+                    return Err(anyhow::Error::msg("Expected `goto_if_zero [LABEL], [VALUE]`."));
+                };
+                Self::GoToIfZero { label: label.trim().to_string(), value: value.trim().parse()? }
+            }
+            "label" => Self::Label(rest.to_string()),
+            "call" => Self::Call(rest.to_string()),
+            "return" => Self::EndCall,
+            "unreachable" => Self::Unreachable,
+            "add" => {
+                let Some((a, b)) = rest.split_once(' ')
+                else {
+                    return Err(anyhow::Error::msg("Expected `add [LABEL] [LABEL]`."));
+                };
+                Self::Add(a.to_string(), b.trim().to_string())
+            }
+            "set" => Self::Set(rest.to_string()),
+            "require_extension" => Self::RequiredExtension(
+                parse_list(rest)?.into_iter().map(str::to_string).collect()
+            ),
+            "syscall" => {
+                let (number, arguments) = rest.split_once(' ').unwrap_or((rest, ""));
+                let arguments: Result<Vec<ImmediateOrRefrence>, _> = parse_list(arguments)?.into_iter()
+                    .map(str::parse)
+                    .collect();
+                Self::Syscall { number: number.parse()?, arguments: arguments? }
+            }
+            "line" => {
+                let (file, rest) = parse_string(rest)?;
+                let (line, text) = rest.strip_prefix(':')
+                    .and_then(|rest| rest.split_once(' '))
+                    .ok_or(anyhow::Error::msg("Expected `line \"[FILE]\":[LINE] \"[TEXT]\"`."))?;
+                let (text, rest) = parse_string(text.trim())?;
+                if !rest.is_empty() {
+                    return Err(anyhow::Error::msg(format!("Unexpected `{rest}` after the line's text.")));
+                }
+                Self::SourceLine { file: PathBuf::from(file), line: parse_number(line)?, text }
+            }
+            "enter_frame" | "exit_frame" => {
+                let (size, saved) = rest.split_once(' ').unwrap_or((rest, "()"));
+                let saved: Result<Vec<String>, _> = parse_list(saved)?.into_iter()
+                    .map(|register| match register.parse()? {
+                        ImmediateOrRefrence::Register(register) => Ok(register),
+                        _ => Err(anyhow::Error::msg(format!("`{register}` isn't a register.")))
+                    })
+                    .collect();
+                let (size, saved) = (parse_number(size)?, saved?);
+                match keyword {
+                    "enter_frame" => Self::EnterFrame { saved, size },
+                    _ => Self::ExitFrame { saved, size }
+                }
+            }
+            other => return Err(anyhow::Error::msg(format!("`{other}` isn't a task."))),
+        };
+        Ok(task)
+    }
+}
+
+#[test]
+fn test_ir_text() {
+    use crate::parser::beach::ast::{Program, user_token_format::Syntax};
+    let source = String::from("
+        type string {
+            var pointer: usize;
+            var length: usize;
+        }
+        var stdout = |output: string| -> nothing {
+            unsafe {
+                !!syscall(4, (1, output.pointer, output.length));
+            }
+            return;
+        };
+        main {
+            stdout(\"Hello, \\\"world\\\"!\");
+        }
+    ");
+    let program = Program::from_lst(Syntax::from_string(source).unwrap(), Some(String::from("main.beach"))).unwrap();
+    let mut executable = Executable::from_ast(program).unwrap();
+    // Printing the IR and reading it back changes nothing, before and after
+    // registers are allocated.
+    for allocated in [false, true] {
+        if allocated {
+            executable.allocate_registers::<crate::generator::aarch64::AArch64AssemblyGenerator>();
+        }
+        let text = executable.to_string();
+        let read: Executable = text.parse().unwrap();
+        assert_eq!(read.code_sections, executable.code_sections);
+        assert_eq!(read.entry_point, executable.entry_point);
+        assert_eq!(read.data, executable.data);
+        assert_eq!(read.to_string(), text);
+    }
+
+    // Hand-written IR uses every kind of value and task.
+    let text = "
+        ; comments and blank lines are skipped
+        require linux_syscalls
+        data table 3 = [01 ff 7f]
+        data buffer 8
+        function helper {
+            %0 = argument 0
+            %1 = add %0, 0x0102
+            %2 = multiply %1, @table
+            $x9 = [16]
+            goto helper
+            line \"src/\\u{1f3d6}.beach\":7 \"var hi = \\\"hi\\\";\"
+            enter_frame 32 ($x19, $x20)
+            exit_frame 0 ()
+            label done
+            set_result 0 = %2
+            return
+        }
+        main {
+            set_argument 0 = 0x2a
+            call helper
+            %0 = result 0
+            syscall 0x5d (%0)
+            unreachable
+        }
+    ";
+    let read: Executable = text.parse().unwrap();
+    assert_eq!(read.platform_requirements, ["linux_syscalls"]);
+    assert_eq!(read.data[0].default, Some(vec![0x01, 0xff, 0x7f]));
+    assert_eq!(read.data[1].default, None);
+    let helper = &read.code_sections[0].tasks;
+    assert_eq!(helper[1], GeneratableTask::Operation {
+        operator: Operator::Add,
+        destination: ImmediateOrRefrence::Virtual(1),
+        a: ImmediateOrRefrence::Virtual(0),
+        b: ImmediateOrRefrence::Immediate(vec![0x02, 0x01])
+    });
+    assert_eq!(helper[3], GeneratableTask::Copy {
+        destination: ImmediateOrRefrence::Register(String::from("x9")),
+        source: ImmediateOrRefrence::Stack(16)
+    });
+    assert_eq!(helper[5], GeneratableTask::SourceLine {
+        file: PathBuf::from("src/🏖.beach"),
+        line: 7,
+        text: String::from("var hi = \"hi\";")
+    });
+    assert_eq!(helper[6], GeneratableTask::EnterFrame { saved: vec![String::from("x19"), String::from("x20")], size: 32 });
+    assert_eq!(helper[7], GeneratableTask::ExitFrame { saved: vec![], size: 0 });
+    assert_eq!(helper[8], GeneratableTask::Label(String::from("done")));
+    assert_eq!(helper[9], GeneratableTask::SetResult { result_number: 0, value: ImmediateOrRefrence::Virtual(2) });
+    assert_eq!(read.entry_point.tasks[2], GeneratableTask::ReadResult { result_number: 0, destination: ImmediateOrRefrence::Virtual(0) });
+    assert_eq!(read.entry_point.tasks.len(), 5);
+    assert_eq!(read.to_string().parse::<Executable>().unwrap().to_string(), read.to_string());
+
+    // Mistakes point at their line.
+    for (invalid, expected) in [
+        ("main {\n    jump somewhere\n}", "line 2: `jump` isn't a task."),
+        ("main {\n    %0 = 0x123\n}", "line 2: `0x123` needs two hex digits for every byte."),
+        ("data text 2 = \"abc\"\nmain {\n}", "line 1: `text` has 2 bytes, but its default doesn't."),
+        ("function helper {\n    return\n", "`helper` is missing its closing `}`."),
+        ("require linux_syscalls", "The IR has no `main` block."),
+    ] {
+        assert_eq!(invalid.parse::<Executable>().unwrap_err().to_string(), expected);
+    }
+}
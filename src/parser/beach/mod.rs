@@ -1,9 +1,21 @@
 // abstract syntax tree
 pub mod ast;
+// generic instantiation
+mod monomorphize;
+// semantic analysis
+mod typecheck;
 // intermediate representation
 mod ir;
+// the intermediate representation as text
+mod ir_text;
+// optimisation
+mod optimise;
+// running programs without compiling them
+mod interpret;
 
-pub use ir::Executable;
+pub use ir::{CodeSegment, Executable, GeneratableTask, ImmediateOrRefrence};
+pub use optimise::optimise;
+pub use interpret::interpret;
 pub use ast::Definition;
 
 
@@ -0,0 +1,1038 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::parser::beach::ast::{Definition, Evaluatable, Function, GenericParameter, PrimitiveType, Program, Task, TypeIdentity, Value};
+use crate::parser::beach::ast::user_token_format::Annotation;
+
+/// Replaces every generic function in `program` with one concrete copy for
+/// each set of generic arguments it's called with, checking those arguments
+/// against the traits their parameters are constrained to. Calls are rewritten
+/// to point at the concrete copies, so every function left in `program` can be
+/// turned into exactly one [CodeSegment](super::ir::CodeSegment).
+///
+/// Generic types and enums are replaced the same way, with one concrete copy
+/// (and copies of its methods) for each set of generic arguments they're used
+/// with, such as `result<u8, failure>`. Every use of them is rewritten to name
+/// the copy instead.
+pub fn monomorphize(program: &mut Program) -> Result<(), anyhow::Error> {
+    // Arguments that take any type implementing a trait, as in
+    // `|output: printable|`, are generic arguments constrained to it.
+    let traits: Vec<String> = program.definitions.iter()
+        .filter_map(|definition| match definition {
+            Definition::Trait { name, .. } => Some(name.clone()),
+            _ => None
+        })
+        .collect();
+    for definition in &mut program.definitions {
+        if let Definition::Function(function) = definition {
+            trait_arguments_to_generics(function, &traits);
+        }
+    }
+
+    // Generic functions are only templates, so they don't stay in the program.
+    let mut templates = HashMap::new();
+    for definition in std::mem::take(&mut program.definitions) {
+        match definition {
+            Definition::Function(function) if !function.generics.is_empty() => {
+                templates.insert(function.name.clone(), function);
+            }
+            definition => program.definitions.push(definition),
+        }
+    }
+
+    // Concrete signatures and fields can still name generic types, like
+    // `result<u8, string>`, which have to meet their own constraints.
+    for definition in &program.definitions {
+        match definition {
+            Definition::Function(function) => {
+                for argument in &function.arguments {
+                    check_type(program, &argument.arg_type)?;
+                }
+                check_type(program, &function.returns)?;
+            }
+            Definition::Type { data, .. } => {
+                for (_name, data_type, _location) in data {
+                    check_type(program, data_type)?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut instantiated = vec![];
+    // The main block is in the main file, which linking keeps as the
+    // program's path.
+    let main_file = program.path.clone();
+    let mut tasks = std::mem::take(&mut program.main_tasks);
+    instantiate_calls(program, &templates, &mut instantiated, &mut tasks, &[], main_file.as_deref())?;
+    program.main_tasks = tasks;
+    let mut tasks = std::mem::take(&mut program.global_tasks);
+    instantiate_calls(program, &templates, &mut instantiated, &mut tasks, &[], None)?;
+    program.global_tasks = tasks;
+    // New instantiations are pushed onto the end of `definitions`, so this also
+    // walks the bodies of anything we stamp out along the way.
+    let mut idx = 0;
+    while idx < program.definitions.len() {
+        if let Definition::Function(function) = &mut program.definitions[idx] {
+            let mut tasks = std::mem::take(&mut function.code);
+            let locals: Vec<(String, TypeIdentity)> = function.arguments.iter()
+                .map(|argument| (argument.name.clone(), argument.arg_type.clone()))
+                .collect();
+            let file = function.file.clone();
+            instantiate_calls(program, &templates, &mut instantiated, &mut tasks, &locals, file.as_deref())?;
+            if let Definition::Function(function) = &mut program.definitions[idx] {
+                function.code = tasks;
+            }
+        }
+        idx += 1;
+    }
+    instantiate_declarations(program)?;
+    Ok(())
+}
+
+/// Gives `function` a generic parameter for each argument typed as one of
+/// `traits`, constrained to that trait. Parameters are named after the trait,
+/// or after the trait and the argument if that name is taken.
+fn trait_arguments_to_generics(function: &mut Function, traits: &[String]) {
+    for argument in &mut function.arguments {
+        let TypeIdentity::Structured(trait_name) = &argument.arg_type
+        else {
+            continue;
+        };
+        if !traits.contains(trait_name) {
+            continue;
+        }
+        let mut name = written_name(trait_name).to_string();
+        if function.generics.iter().any(|generic| generic.name == name) {
+            name = format!("{name}_{}", argument.name);
+        }
+        function.generics.push(GenericParameter { name: name.clone(), constraints: vec![trait_name.clone()] });
+        argument.arg_type = TypeIdentity::Generic(name);
+    }
+}
+
+/// Replaces every use of a generic type or enum in `program` with a concrete
+/// copy of it, then removes the generic originals.
+fn instantiate_declarations(program: &mut Program) -> Result<(), anyhow::Error> {
+    let mut instantiated: Vec<String> = vec![];
+    // As with functions, copies are pushed onto the end of `definitions` so
+    // that the types inside them are rewritten too.
+    let mut idx = 0;
+    while idx < program.definitions.len() {
+        if is_generic_declaration(&program.definitions[idx]) {
+            idx += 1;
+            continue;
+        }
+        let mut definition = program.definitions[idx].clone();
+        let mut pending = vec![];
+        for identity in types_in(&mut definition) {
+            *identity = concrete_type(program, identity, &mut pending);
+        }
+        program.definitions[idx] = definition;
+        for (name, arguments) in pending {
+            let mangled = mangled_name(&name, &arguments);
+            if instantiated.contains(&mangled) {
+                continue;
+            }
+            instantiated.push(mangled.clone());
+            let template = program.definitions.iter()
+                .find(|definition| is_generic_declaration(definition) && definition.name() == Some(&name))
+                .expect("only generic types and enums are instantiated")
+                .clone();
+            let copy = instantiate_declaration(program, &template, &arguments, mangled)?;
+            program.definitions.push(copy);
+        }
+        idx += 1;
+    }
+    program.definitions.retain(|definition| !is_generic_declaration(definition));
+    Ok(())
+}
+
+/// Whether `definition` is a generic type or enum, which is only a template.
+fn is_generic_declaration(definition: &Definition) -> bool {
+    match definition {
+        Definition::Type { generics, .. } | Definition::Enum { generics, .. } => !generics.is_empty(),
+        _ => false
+    }
+}
+
+/// Every type named in the signatures, fields and cases of `definition`.
+fn types_in(definition: &mut Definition) -> Vec<&mut TypeIdentity> {
+    match definition {
+        Definition::Function(function) => function_types(function),
+        Definition::Type { data, methods, implementations, .. } => {
+            let mut types: Vec<&mut TypeIdentity> = data.iter_mut().map(|(_name, data_type, _location)| data_type).collect();
+            types.extend(methods.iter_mut().flat_map(function_types));
+            types.extend(implementations.iter_mut().flat_map(|implementation| &mut implementation.methods).flat_map(function_types));
+            types
+        }
+        Definition::Enum { cases, methods, implementations, .. } => {
+            let mut types: Vec<&mut TypeIdentity> = cases.iter_mut().filter_map(|(_name, case_type, _location)| case_type.as_mut()).collect();
+            types.extend(methods.iter_mut().flat_map(function_types));
+            types.extend(implementations.iter_mut().flat_map(|implementation| &mut implementation.methods).flat_map(function_types));
+            types
+        }
+        _ => vec![]
+    }
+}
+
+/// Every type in the signature of `function`.
+fn function_types(function: &mut Function) -> Vec<&mut TypeIdentity> {
+    let mut types: Vec<&mut TypeIdentity> = function.arguments.iter_mut().map(|argument| &mut argument.arg_type).collect();
+    types.push(&mut function.returns);
+    types
+}
+
+/// `identity` with each generic type or enum inside it replaced by the name of
+/// its concrete copy. Copies that need making are added to `pending`, as the
+/// name of the template and its generic arguments. Types still naming a generic
+/// parameter are left alone, as they belong to generic methods.
+fn concrete_type(program: &Program, identity: &TypeIdentity, pending: &mut Vec<(String, Vec<TypeIdentity>)>) -> TypeIdentity {
+    if identity.is_generic() {
+        return identity.clone();
+    }
+    match identity {
+        TypeIdentity::Instance { name, arguments } => {
+            let template = program.definitions.iter()
+                .find(|definition| is_generic_declaration(definition) && definition.name() == Some(name));
+            let mangled = mangled_name(name, arguments);
+            let concrete = match template {
+                Some(Definition::Enum { .. }) => TypeIdentity::Enum(mangled),
+                Some(_) => TypeIdentity::Structured(mangled),
+                // Already reported by `check_type`.
+                None => return identity.clone()
+            };
+            pending.push((name.clone(), arguments.clone()));
+            concrete
+        }
+        TypeIdentity::Closure { arguments, returns } => TypeIdentity::Closure {
+            arguments: arguments.iter().map(|argument| concrete_type(program, argument, pending)).collect(),
+            returns: Box::new(concrete_type(program, returns, pending))
+        },
+        other => other.clone()
+    }
+}
+
+/// Stamps out a copy of the generic type or enum `template`, called `name`,
+/// with its generic parameters replaced by `concrete`. Trait implementations
+/// from an `extension` are only kept if its where clause holds for `concrete`.
+fn instantiate_declaration(
+    program: &Program,
+    template: &Definition,
+    concrete: &[TypeIdentity],
+    name: String
+) -> Result<Definition, anyhow::Error> {
+    let (template_name, generics, methods, implementations) = match template {
+        Definition::Type { name, generics, methods, implementations, .. } |
+        Definition::Enum { name, generics, methods, implementations, .. } => (name, generics, methods, implementations),
+        _ => unreachable!("only types and enums are instantiated")
+    };
+    check_constraints(program, template_name, generics, concrete)?;
+    let substitutions: Vec<(String, TypeIdentity)> = generics.iter()
+        .zip(concrete)
+        .map(|(generic, concrete)| (generic.name.clone(), concrete.clone()))
+        .collect();
+    let methods = methods.iter()
+        .map(|method| substitute_function(method, &substitutions, method.name.clone()))
+        .collect();
+    let mut concrete_implementations = vec![];
+    for implementation in implementations {
+        let applies = implementation.requirements.iter().all(|requirement| {
+            match substitutions.iter().find(|(generic, _)| *generic == requirement.name) {
+                Some((_, argument)) => requirement.constraints.iter()
+                    .all(|constraint| implements(program, argument, constraint)),
+                None => false
+            }
+        });
+        if applies {
+            let mut implementation = implementation.clone();
+            implementation.requirements.clear();
+            implementation.methods = implementation.methods.iter()
+                .map(|method| substitute_function(method, &substitutions, method.name.clone()))
+                .collect();
+            concrete_implementations.push(implementation);
+        }
+    }
+    Ok(match template {
+        Definition::Type { data, file, .. } => Definition::Type {
+            name,
+            generics: vec![],
+            data: data.iter()
+                .map(|(data_name, data_type, location)| (data_name.clone(), data_type.substitute(&substitutions), *location))
+                .collect(),
+            methods,
+            implementations: concrete_implementations,
+            file: file.clone()
+        },
+        Definition::Enum { cases, file, .. } => Definition::Enum {
+            name,
+            generics: vec![],
+            cases: cases.iter()
+                .map(|(case_name, case_type, location)| (
+                    case_name.clone(),
+                    case_type.as_ref().map(|case_type| case_type.substitute(&substitutions)),
+                    *location
+                ))
+                .collect(),
+            methods,
+            implementations: concrete_implementations,
+            file: file.clone()
+        },
+        _ => unreachable!("only types and enums are instantiated")
+    })
+}
+
+/// Points every call to a generic function in `tasks` at a concrete copy of
+/// it, adding that copy to `program` if it doesn't exist yet. Calls to methods,
+/// as in `output.to_string()`, are pointed at a copy of the method too. (see
+/// [method_call]) `locals` are the labels in scope with known types, such as
+/// the arguments of the function `tasks` belong to, and `file` is where they
+/// were written.
+fn instantiate_calls(
+    program: &mut Program,
+    templates: &HashMap<String, Function>,
+    instantiated: &mut Vec<String>,
+    tasks: &mut [Task],
+    locals: &[(String, TypeIdentity)],
+    file: Option<&Path>
+) -> Result<(), anyhow::Error> {
+    // The labels in scope whose types are known, from what they were declared
+    // or set to.
+    let mut declared = locals.to_vec();
+    for task in tasks {
+        match task {
+            Task::Set { label, type_, value, .. } => {
+                let known = match type_ {
+                    Some(type_) => Some(TypeIdentity::from_name(type_, &[])),
+                    None => value_type(value, &declared)
+                };
+                if let Some(known) = known {
+                    declared.push((label.clone(), known));
+                }
+            }
+            Task::Evaluate { label, task: Evaluatable::Value { value } } => {
+                if let Some(known) = value_type(value, &declared) {
+                    declared.push((label.clone(), known));
+                }
+            }
+            Task::Evaluate { label: evaluated, task: Evaluatable::Call { label, generics, arguments, location } } => {
+                let at = |message: String| anyhow::Error::msg(format!("{}: {message}", source_location(file, *location)));
+                let called = instantiate_call(program, templates, instantiated, label, generics, arguments, &declared).map_err(at)?;
+                if let Some(returns) = returns_of(program, &called) {
+                    declared.push((evaluated.clone(), returns));
+                }
+            }
+            Task::Call { function_identifier, generics, arguments, location } => {
+                let at = |message: String| anyhow::Error::msg(format!("{}: {message}", source_location(file, *location)));
+                instantiate_call(program, templates, instantiated, function_identifier, generics, arguments, &declared).map_err(at)?;
+            }
+            Task::Syscall { result: Some(result), .. } => {
+                // System calls return register sized integers.
+                declared.push((result.clone(), TypeIdentity::Primitive(PrimitiveType::ArchSigned)));
+            }
+            Task::If { tasks, .. } => {
+                instantiate_calls(program, templates, instantiated, tasks, &declared, file)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Points a call to `label` with `arguments` at a concrete copy of the
+/// generic function or method it calls, if it calls one, returning the
+/// function that's called. Fails with a message for the call's location.
+fn instantiate_call(
+    program: &mut Program,
+    templates: &HashMap<String, Function>,
+    instantiated: &mut Vec<String>,
+    label: &mut String,
+    generics: &mut Vec<TypeIdentity>,
+    arguments: &mut Vec<Value>,
+    declared: &[(String, TypeIdentity)]
+) -> Result<String, String> {
+    if let Some((method, value)) = method_call(program, label, declared)? {
+        *label = method;
+        arguments.insert(0, value);
+    }
+    let target = alias_target(program, label);
+    let Some(template) = templates.get(&target)
+    else {
+        if !generics.is_empty() {
+            return Err(format!("`{}` isn't generic, but was given generic arguments.", written_name(label)));
+        }
+        return Ok(target);
+    };
+    let concrete = infer_generics(template, generics, arguments, declared)?;
+    check_constraints(program, &template.name, &template.generics, &concrete).map_err(|e| e.to_string())?;
+    let name = mangled_name(&template.name, &concrete);
+    if !instantiated.contains(&name) {
+        instantiated.push(name.clone());
+        program.definitions.push(Definition::Function(instantiate(template, &concrete, name.clone())));
+    }
+    *label = name.clone();
+    generics.clear();
+    Ok(name)
+}
+
+/// If `label` calls a method of a value in `declared`, as in
+/// `output.to_string`, adds a copy of the method to `program` as a function
+/// taking the value as its first argument, if there isn't one already.
+/// Returns the name of that function, (`string~to_string`) and the value.
+/// Fails on methods of primitives, which can't be compiled yet.
+fn method_call(program: &mut Program, label: &str, declared: &[(String, TypeIdentity)]) -> Result<Option<(String, Value)>, String> {
+    let Some((base, method)) = label.rsplit_once('.')
+    else {
+        return Ok(None);
+    };
+    let base = Value::Label(base.to_string());
+    let type_name = match value_type(&base, declared) {
+        Some(TypeIdentity::Structured(name) | TypeIdentity::Enum(name)) => name,
+        Some(TypeIdentity::Primitive(primitive)) => {
+            return Err(format!("`{method}` can't be called on `{}` yet.", primitive.name()));
+        }
+        _ => return Ok(None)
+    };
+    let name = format!("{type_name}~{method}");
+    let exists = program.definitions.iter().any(|definition| match definition {
+        Definition::Function(function) => function.name == name,
+        _ => false
+    });
+    if exists {
+        return Ok(Some((name, base)));
+    }
+    let mut function = None;
+    for definition in &program.definitions {
+        match definition {
+            Definition::Type { name: def_name, methods, implementations, file, .. } |
+            Definition::Enum { name: def_name, methods, implementations, file, .. } if *def_name == type_name => {
+                let found = methods.iter()
+                    .chain(implementations.iter().flat_map(|implementation| &implementation.methods))
+                    .find(|function| function.name == method && function.generics.is_empty());
+                if let Some(found) = found {
+                    let mut found = found.clone();
+                    found.name = name.clone();
+                    found.file = file.clone();
+                    function = Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(function) = function
+    else {
+        return Ok(None);
+    };
+    program.definitions.push(Definition::Function(function));
+    Ok(Some((name, base)))
+}
+
+/// The function `name` refers to, following any aliases of it.
+/// (`print => stdout;`)
+fn alias_target(program: &Program, name: &str) -> String {
+    let mut name = name.to_string();
+    // Type checking rejects aliases that loop.
+    for _ in 0..64 {
+        let to = program.definitions.iter().find_map(|definition| match definition {
+            Definition::Alias { from, to, .. } if *from == name => Some(to.clone()),
+            _ => None
+        });
+        match to {
+            Some(to) => name = to,
+            None => break
+        }
+    }
+    name
+}
+
+/// The type the function called `name` returns, if it's in `program`.
+fn returns_of(program: &Program, name: &str) -> Option<TypeIdentity> {
+    program.definitions.iter().find_map(|definition| match definition {
+        Definition::Function(function) if function.name == name => Some(function.returns.clone()),
+        _ => None
+    })
+}
+
+/// Where `location` is, in `file` if it's known, for error messages.
+fn source_location(file: Option<&Path>, location: Annotation) -> String {
+    match file {
+        Some(file) => format!("{}:{location}", file.display()),
+        None => location.to_string()
+    }
+}
+
+/// The name a definition was declared with, without the path linking gave it.
+/// (`quit` for `main~quit`)
+fn written_name(name: &str) -> &str {
+    name.rsplit('~').next().unwrap_or(name)
+}
+
+/// Works out the generic arguments for a call to `template`. Arguments given
+/// explicitly come first; the rest are inferred from the types of the values
+/// passed in, which are literals or labels in `declared`.
+fn infer_generics(
+    template: &Function,
+    explicit: &[TypeIdentity],
+    arguments: &[Value],
+    declared: &[(String, TypeIdentity)]
+) -> Result<Vec<TypeIdentity>, String> {
+    let name = written_name(&template.name);
+    if explicit.len() > template.generics.len() {
+        return Err(format!(
+            "`{name}` takes {} generic arguments, but {} were given.",
+            template.generics.len(), explicit.len()
+        ));
+    }
+    let mut substitutions: Vec<(String, TypeIdentity)> = template.generics.iter()
+        .zip(explicit)
+        .map(|(generic, concrete)| (generic.name.clone(), concrete.clone()))
+        .collect();
+    for (argument, value) in template.arguments.iter().zip(arguments) {
+        if let Some(value_type) = value_type(value, declared) {
+            unify(&argument.arg_type, &value_type, &mut substitutions);
+        }
+    }
+    let mut concrete = vec![];
+    for generic in &template.generics {
+        match substitutions.iter().find(|(name, _)| *name == generic.name) {
+            Some((_, found)) => concrete.push(found.clone()),
+            None => {
+                return Err(format!(
+                    "Couldn't infer the generic parameter `{}` of `{name}`. Try giving it explicitly, as in `{name}<...>(...)`.",
+                    generic.name
+                ));
+            }
+        }
+    }
+    Ok(concrete)
+}
+
+/// The type of `value`, if it's a literal or a label in `declared`.
+fn value_type(value: &Value, declared: &[(String, TypeIdentity)]) -> Option<TypeIdentity> {
+    match value {
+        // The latest declaration of a label is the one in scope.
+        Value::Label(label) => declared.iter().rev()
+            .find(|(name, _)| name == label)
+            .map(|(_, known)| known.clone()),
+        _ => literal_type(value)
+    }
+}
+
+/// The type a literal value has when nothing else narrows it down. Labels
+/// can't be typed without resolving them, so they return [None].
+fn literal_type(value: &Value) -> Option<TypeIdentity> {
+    match value {
+        Value::String(_) => Some(TypeIdentity::Structured(String::from("string"))),
+        Value::Integer(_) => Some(TypeIdentity::Primitive(PrimitiveType::ArchSigned)),
+        Value::Bool(_) => Some(TypeIdentity::Enum(String::from("boolean"))),
+        _ => None
+    }
+}
+
+/// Matches `concrete` against `pattern`, recording what each generic parameter
+/// in `pattern` stands for. Parameters that already have a substitution keep
+/// it.
+fn unify(pattern: &TypeIdentity, concrete: &TypeIdentity, substitutions: &mut Vec<(String, TypeIdentity)>) {
+    match (pattern, concrete) {
+        (TypeIdentity::Generic(name), _) if !substitutions.iter().any(|(generic, _)| generic == name) => {
+            substitutions.push((name.clone(), concrete.clone()));
+        }
+        (
+            TypeIdentity::Instance { name, arguments },
+            TypeIdentity::Instance { name: concrete_name, arguments: concrete_arguments }
+        ) if name == concrete_name => {
+            for (argument, concrete_argument) in arguments.iter().zip(concrete_arguments) {
+                unify(argument, concrete_argument, substitutions);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Checks that each of `concrete` implements the traits required of the
+/// matching entry in `generics`. `owner` names what the generics belong to.
+fn check_constraints(
+    program: &Program,
+    owner: &str,
+    generics: &[GenericParameter],
+    concrete: &[TypeIdentity]
+) -> Result<(), anyhow::Error> {
+    for (generic, concrete) in generics.iter().zip(concrete) {
+        check_type(program, concrete)?;
+        for trait_name in &generic.constraints {
+            if !implements(program, concrete, trait_name) {
+                return Err(anyhow::Error::msg(format!(
+                    "`{concrete}` does not implement the trait `{trait_name}`, which `{owner}` requires of its generic parameter `{}`. (TODO: ANNOTATIONS)",
+                    generic.name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the generic arguments inside `checked` (such as `u8` and
+/// `string` in `result<u8, string>`) meet their constraints. Types that still
+/// contain generic parameters are checked once they're instantiated instead.
+fn check_type(program: &Program, checked: &TypeIdentity) -> Result<(), anyhow::Error> {
+    if checked.is_generic() {
+        return Ok(());
+    }
+    match checked {
+        TypeIdentity::Instance { name, arguments } => {
+            match generics_of(program, name) {
+                Some(generics) => {
+                    if generics.len() != arguments.len() {
+                        return Err(anyhow::Error::msg(format!(
+                            "`{name}` takes {} generic arguments, but `{checked}` gives {}. (TODO: ANNOTATIONS)",
+                            generics.len(), arguments.len()
+                        )));
+                    }
+                    check_constraints(program, name, generics, arguments)
+                }
+                None => Err(anyhow::Error::msg(format!(
+                    "`{name}` is not a generic type or enum, but was given generic arguments in `{checked}`. (TODO: ANNOTATIONS)"
+                )))
+            }
+        }
+        TypeIdentity::Closure { arguments, returns } => {
+            for argument in arguments {
+                check_type(program, argument)?;
+            }
+            check_type(program, returns)
+        }
+        _ => Ok(())
+    }
+}
+
+/// The generic parameters of the type or enum called `name`, if it is one.
+fn generics_of<'a>(program: &'a Program, name: &str) -> Option<&'a Vec<GenericParameter>> {
+    for definition in &program.definitions {
+        match definition {
+            Definition::Type { name: def_name, generics, .. } |
+            Definition::Enum { name: def_name, generics, .. } if def_name == name && !generics.is_empty() => {
+                return Some(generics);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `checked` implements the trait `trait_name`, either directly or
+/// through another trait that requires it.
+pub fn implements(program: &Program, checked: &TypeIdentity, trait_name: &str) -> bool {
+    let (name, arguments) = match checked {
+        TypeIdentity::Primitive(primitive) => {
+            return primitive.traits().iter().any(|builtin| {
+                *builtin == trait_name || trait_requires(program, builtin, trait_name, 0)
+            });
+        }
+        TypeIdentity::Trait(name) => {
+            return name == trait_name || trait_requires(program, name, trait_name, 0);
+        }
+        TypeIdentity::Structured(name) | TypeIdentity::Enum(name) => (name, &vec![]),
+        TypeIdentity::Instance { name, arguments } => (name, arguments),
+        TypeIdentity::Generic(_) | TypeIdentity::Closure { .. } => return false,
+    };
+    for definition in &program.definitions {
+        match definition {
+            Definition::Type { name: def_name, generics, implementations, .. } |
+            Definition::Enum { name: def_name, generics, implementations, .. } if def_name == name => {
+                for implementation in implementations {
+                    // Implementations inside an `extension` only count when
+                    // its where clause holds for these generic arguments.
+                    let applies = implementation.requirements.iter().all(|requirement| {
+                        let position = generics.iter().position(|generic| generic.name == requirement.name);
+                        match position.and_then(|position| arguments.get(position)) {
+                            Some(argument) => requirement.constraints.iter()
+                                .all(|constraint| implements(program, argument, constraint)),
+                            None => false
+                        }
+                    });
+                    if applies && (
+                        implementation.trait_name == trait_name ||
+                        trait_requires(program, &implementation.trait_name, trait_name, 0)
+                    ) {
+                        return true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Whether implementing `from` means also implementing `required`, such as
+/// `error` requiring `printable`.
+fn trait_requires(program: &Program, from: &str, required: &str, depth: usize) -> bool {
+    // Traits requiring each other in a loop shouldn't hang the compiler.
+    if depth > 64 {
+        return false;
+    }
+    for definition in &program.definitions {
+        match definition {
+            Definition::Trait { name, requires, .. } if name == from => {
+                return requires.iter().any(|next| {
+                    next == required || trait_requires(program, next, required, depth + 1)
+                });
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Names a concrete copy of a generic function so it can be used as a label.
+/// `convert<u8, result<u8, string>>` becomes `convert__u8_result_u8_string_`.
+fn mangled_name(name: &str, concrete: &[TypeIdentity]) -> String {
+    let arguments: Vec<String> = concrete.iter().map(mangled_type).collect();
+    format!("{name}__{}", arguments.join("_"))
+}
+
+fn mangled_type(mangled: &TypeIdentity) -> String {
+    match mangled {
+        TypeIdentity::Instance { name, arguments } => {
+            let arguments: Vec<String> = arguments.iter().map(mangled_type).collect();
+            format!("{name}_{}_", arguments.join("_"))
+        }
+        TypeIdentity::Closure { arguments, returns } => {
+            let arguments: Vec<String> = arguments.iter().map(mangled_type).collect();
+            format!("closure_{}_returns_{}_", arguments.join("_"), mangled_type(returns))
+        }
+        other => other.to_string()
+    }
+}
+
+/// Stamps out a copy of `template` with its generic parameters replaced by
+/// `concrete`.
+fn instantiate(template: &Function, concrete: &[TypeIdentity], name: String) -> Function {
+    let substitutions: Vec<(String, TypeIdentity)> = template.generics.iter()
+        .zip(concrete)
+        .map(|(generic, concrete)| (generic.name.clone(), concrete.clone()))
+        .collect();
+    let mut function = substitute_function(template, &substitutions, name);
+    function.generics = vec![];
+    function
+}
+
+/// A copy of `template` called `name`, with the generic parameters in
+/// `substitutions` replaced throughout its signature and body. Generic
+/// parameters of its own that aren't substituted are kept.
+fn substitute_function(template: &Function, substitutions: &[(String, TypeIdentity)], name: String) -> Function {
+    let code = substitute_tasks(&template.code, substitutions);
+    Function {
+        name,
+        generics: template.generics.clone(),
+        arguments: template.arguments.iter().map(|argument| {
+            let mut argument = argument.clone();
+            argument.arg_type = argument.arg_type.substitute(substitutions);
+            argument
+        }).collect(),
+        returns: template.returns.substitute(substitutions),
+        code,
+        location: template.location,
+        file: template.file.clone()
+    }
+}
+
+/// Copies of `tasks` with the generic parameters in `substitutions` replaced
+/// in the generic arguments of their calls.
+fn substitute_tasks(tasks: &[Task], substitutions: &[(String, TypeIdentity)]) -> Vec<Task> {
+    tasks.iter().map(|task| {
+        let mut task = task.clone();
+        match &mut task {
+            Task::Call { generics, .. } | Task::Evaluate { task: Evaluatable::Call { generics, .. }, .. } => {
+                for generic in generics {
+                    *generic = generic.substitute(substitutions);
+                }
+            }
+            Task::If { tasks, .. } => *tasks = substitute_tasks(tasks, substitutions),
+            _ => {}
+        }
+        task
+    }).collect()
+}
+
+#[cfg(test)]
+fn test_program(source: &str) -> Program {
+    Program::from_lst(crate::parser::parse_string_file(source.to_string()).unwrap(), None).unwrap()
+}
+
+#[test]
+fn test_monomorphize_instances() {
+    let mut program = test_program("
+        trait printable {
+            public var to_string: |&self| -> string;
+        }
+        var show<T> = |value: T| -> nothing where T: printable {
+            return;
+        };
+        var show_twice<T: printable> = |value: T| -> nothing {
+            show<T>(value);
+            show<T>(value);
+        };
+        main {
+            show<u8>(5);
+            show(6);
+            show_twice<u8>(7);
+        }
+    ");
+    monomorphize(&mut program).unwrap();
+    let mut names = vec![];
+    for definition in &program.definitions {
+        if let Definition::Function(function) = definition {
+            assert!(function.generics.is_empty());
+            names.push(function.name.clone());
+        }
+    }
+    // `show<u8>` is used twice but only stamped out once.
+    assert_eq!(names, vec!["show__u8", "show__isize", "show_twice__u8"]);
+    let called: Vec<&String> = program.main_tasks.iter().filter_map(|task| match task {
+        Task::Call { function_identifier, .. } => Some(function_identifier),
+        _ => None
+    }).collect();
+    assert_eq!(called, vec!["show__u8", "show__isize", "show_twice__u8"]);
+}
+
+#[test]
+fn test_monomorphize_constraints() {
+    let source = "
+        trait printable {
+            public var to_string: |&self| -> string;
+        }
+        trait error {
+            public trait printable;
+        }
+        type failure {
+            var code: u8;
+            public trait error;
+        }
+        type silent {
+            var code: u8;
+        }
+        enum result<G, E> where E: error {
+            case good: G;
+            case bad: E;
+            extension where G: printable {
+                public trait printable;
+            }
+        }
+        var report<T> = |value: T| -> nothing where T: printable {
+            return;
+        };
+    ";
+    // `failure` is printable through `error`, and so is any result of
+    // printable things.
+    let mut program = test_program(&format!("{source}
+        var check = |problem: failure, outcome: result<u8, failure>| -> nothing {{
+            report<failure>(problem);
+            report<result<u8, failure>>(outcome);
+        }};
+    "));
+    monomorphize(&mut program).unwrap();
+
+    let mut program = test_program(&format!("{source}
+        var check = |outcome: result<silent, failure>| -> nothing {{
+            report<result<silent, failure>>(outcome);
+        }};
+    "));
+    let error = monomorphize(&mut program).unwrap_err().to_string();
+    assert!(error.contains("`result<silent, failure>` does not implement the trait `printable`"), "{error}");
+
+    let mut program = test_program(&format!("{source}
+        var check = |outcome: result<u8, silent>| -> nothing {{
+            report<result<u8, silent>>(outcome);
+        }};
+    "));
+    let error = monomorphize(&mut program).unwrap_err().to_string();
+    assert!(error.contains("`silent` does not implement the trait `error`"), "{error}");
+}
+
+#[test]
+fn test_monomorphize_declarations() {
+    use crate::parser::beach::ast::user_token_format::Annotation;
+    let mut program = test_program("
+        trait printable {
+            public var to_string: |&self| -> string;
+        }
+        type box<T> {
+            var value: T;
+            public var get = |&self| -> T {
+                return;
+            };
+        }
+        enum maybe<A> {
+            case yes: A;
+            case no;
+            extension where A: printable {
+                public trait printable;
+            }
+        }
+        var open = |small: box<u8>, large: maybe<box<u16>>, again: box<u8>| -> nothing {
+            return;
+        };
+    ");
+    monomorphize(&mut program).unwrap();
+    let mut declarations = vec![];
+    for definition in &program.definitions {
+        match definition {
+            Definition::Type { name, generics, data, methods, .. } => {
+                assert!(generics.is_empty());
+                declarations.push(name.clone());
+                let value = if name == "box__u8" { PrimitiveType::U8 } else { PrimitiveType::U16 };
+                // Fields keep where they were declared in the template.
+                let location = Annotation { line: 6, column: 17 };
+                assert_eq!(data, &vec![(String::from("value"), TypeIdentity::Primitive(value), location)]);
+                // Methods are copied along with their type.
+                assert_eq!(methods[0].arguments[0].arg_type, TypeIdentity::Structured(name.clone()));
+                assert_eq!(methods[0].returns, TypeIdentity::Primitive(value));
+            }
+            Definition::Enum { name, generics, cases, implementations, .. } => {
+                assert!(generics.is_empty());
+                declarations.push(name.clone());
+                let location = Annotation { line: 12, column: 18 };
+                assert_eq!(cases[0], (String::from("yes"), Some(TypeIdentity::Structured(String::from("box__u16"))), location));
+                // `box<u16>` isn't printable, so the extension doesn't apply.
+                assert!(implementations.is_empty());
+            }
+            _ => {}
+        }
+    }
+    // `box<u8>` is used twice but only stamped out once, and the generic
+    // originals are gone.
+    assert_eq!(declarations, vec!["box__u8", "maybe__box_u16_", "box__u16"]);
+    let open = program.definitions.iter().find_map(|definition| match definition {
+        Definition::Function(function) if function.name == "open" => Some(function),
+        _ => None
+    }).unwrap();
+    let arguments: Vec<TypeIdentity> = open.arguments.iter().map(|argument| argument.arg_type.clone()).collect();
+    assert_eq!(arguments, vec![
+        TypeIdentity::Structured(String::from("box__u8")),
+        TypeIdentity::Enum(String::from("maybe__box_u16_")),
+        TypeIdentity::Structured(String::from("box__u8")),
+    ]);
+
+    // Constraints are checked on every copy.
+    let mut program = test_program("
+        trait error {}
+        enum result<G, E> where E: error {
+            case good: G;
+            case bad: E;
+        }
+        type wrapper<T> {
+            var inner: result<T, T>;
+        }
+        var open = |wrapped: wrapper<u8>| -> nothing {
+            return;
+        };
+    ");
+    let error = monomorphize(&mut program).unwrap_err().to_string();
+    assert!(error.contains("`u8` does not implement the trait `error`"), "{error}");
+}
+
+#[test]
+fn test_monomorphize_inference() {
+    let mut program = test_program("
+        var quit<T> = |code: T| -> nothing {
+            return;
+        };
+        var leave = |code: usize, other: u8| -> nothing {
+            quit(code);
+            quit(named);
+            quit(copied);
+        };
+    ");
+    // Labels are inferred from the types they were declared with, or the
+    // types of what they were set to.
+    let set = |label: &str, type_: Option<&str>, value: Value| Task::Set {
+        label: String::from(label),
+        type_: type_.map(String::from),
+        value,
+        location: Annotation::default()
+    };
+    for definition in &mut program.definitions {
+        if let Definition::Function(function) = definition && function.name == "leave" {
+            function.code.insert(1, set("named", Some("u16"), Value::Integer(crate::utils::Bigint::from_i64(5))));
+            function.code.insert(3, set("copied", None, Value::Label(String::from("other"))));
+        }
+    }
+    monomorphize(&mut program).unwrap();
+    let names: Vec<&String> = program.definitions.iter().filter_map(|definition| match definition {
+        Definition::Function(function) => Some(&function.name),
+        _ => None
+    }).collect();
+    assert_eq!(names, vec!["leave", "quit__usize", "quit__u16", "quit__u8"]);
+
+    // Errors say where the call is, and name what was written.
+    let mut program = test_program("
+        var quit<T> = |code: T| -> nothing {
+            return;
+        };
+        main {
+            quit(unknown);
+        }
+    ");
+    program.path = Some(std::path::PathBuf::from("main.beach"));
+    for definition in &mut program.definitions {
+        if let Some(name) = definition.name_mut() {
+            *name = format!("main~{name}");
+        }
+    }
+    for task in &mut program.main_tasks {
+        if let Task::Call { function_identifier, .. } = task {
+            *function_identifier = format!("main~{function_identifier}");
+        }
+    }
+    assert_eq!(
+        monomorphize(&mut program).unwrap_err().to_string(),
+        "main.beach:6:13: Couldn't infer the generic parameter `T` of `quit`. Try giving it explicitly, as in `quit<...>(...)`."
+    );
+}
+
+#[test]
+fn test_monomorphize_methods() {
+    let source = "
+        trait printable {
+            public var to_string: |&self| -> string;
+        }
+        type string {
+            var pointer: usize;
+            var length: usize;
+            public trait printable {
+                public var to_string = |&self| -> string {
+                    return self;
+                }
+            }
+        }
+        var show = |output: printable| -> nothing {
+            let text = output.to_string();
+        };
+        main {
+            show(\"hi\");
+        }
+    ";
+    // Arguments that take a trait are generic, and the methods they call are
+    // lifted out into functions taking the value as `self`.
+    let mut program = test_program(source);
+    monomorphize(&mut program).unwrap();
+    let names: Vec<&String> = program.definitions.iter().filter_map(|definition| match definition {
+        Definition::Function(function) => Some(&function.name),
+        _ => None
+    }).collect();
+    assert_eq!(names, vec!["show__string", "string~to_string"]);
+    let show = program.definitions.iter().find_map(|definition| match definition {
+        Definition::Function(function) if function.name == "show__string" => Some(function),
+        _ => None
+    }).unwrap();
+    assert!(matches!(
+        &show.code[0],
+        Task::Evaluate { task: Evaluatable::Call { label, arguments, .. }, .. }
+            if label == "string~to_string" && matches!(&arguments[..], [Value::Label(output)] if output == "output")
+    ));
+
+    // Primitives don't have methods yet.
+    let mut program = test_program(&source.replace("show(\"hi\");", "show(5);"));
+    assert!(monomorphize(&mut program).unwrap_err().to_string().ends_with("`to_string` can't be called on `isize` yet."));
+}
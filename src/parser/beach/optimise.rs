@@ -0,0 +1,434 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ir::{CodeSegment, Executable, GeneratableTask, ImmediateOrRefrence, Operator};
+use crate::generator::generic::AssemblyGenerator;
+use crate::utils::Bigint;
+
+/// Optimises `program` at `level`, from `0` to [crate::package::MAX_OPTIMISATION].
+///
+/// The IR is in SSA form: every virtual register is set by exactly one task,
+/// before it's used, so a value can be replaced anywhere it's used.
+///
+/// - `0` changes nothing.
+/// - `1` removes unused logic (E.1.1.1): tasks that can't be reached, values
+///   that are never used, functions that are never called and data that's
+///   never read.
+/// - `2` also computes logic that only uses constants while compiling
+///   (E.1.1.2), and replaces copies with what they copy.
+/// - `3` repeats every pass until none of them change anything.
+///
+/// Fails if the IR isn't in SSA form, which can happen with hand-written IR.
+pub fn optimise<G: AssemblyGenerator>(program: &mut Executable, level: u8) -> Result<(), anyhow::Error> {
+    if level == 0 {
+        return Ok(());
+    }
+    for segment in program.code_sections.iter_mut().chain(std::iter::once(&mut program.entry_point)) {
+        if let Err(error) = check_ssa(segment) {
+            let name = match segment.label.as_str() {
+                "" => String::from("The main block"),
+                label => format!("`{label}`")
+            };
+            return Err(anyhow::Error::msg(format!("{name} isn't in SSA form: {error}.")));
+        }
+    }
+    loop {
+        let mut changed = false;
+        for segment in program.code_sections.iter_mut().chain(std::iter::once(&mut program.entry_point)) {
+            if level >= 2 {
+                changed |= fold_constants(segment, G::REGISTER_WIDTH as usize);
+                changed |= propagate_copies(segment);
+            }
+            changed |= eliminate_dead_code(segment);
+        }
+        changed |= remove_unused_functions(program);
+        changed |= prune_data(program);
+        if level < 3 || !changed {
+            return Ok(());
+        }
+    }
+}
+
+/// Checks that every virtual register in `segment` is set once, and that
+/// every path through the segment sets it before it's used.
+fn check_ssa(segment: &mut CodeSegment) -> Result<(), String> {
+    let mut defined = HashSet::new();
+    for task in &segment.tasks {
+        match task.definition() {
+            Some(register) if !defined.insert(register) => {
+                return Err(format!("virtual register {register} is set more than once"));
+            }
+            _ => {}
+        }
+    }
+    // The registers set on every path to each task, or [None] if no path
+    // reaches it.
+    let successors = segment.successors();
+    let mut set_before: Vec<Option<HashSet<usize>>> = vec![None; segment.tasks.len()];
+    let mut waiting = vec![];
+    if !segment.tasks.is_empty() {
+        set_before[0] = Some(HashSet::new());
+        waiting.push(0);
+    }
+    while let Some(index) = waiting.pop() {
+        let mut set_after = set_before[index].clone().expect("only reached tasks wait");
+        set_after.extend(segment.tasks[index].definition());
+        for successor in &successors[index] {
+            match &mut set_before[*successor] {
+                Some(set) => {
+                    let before = set.len();
+                    set.retain(|register| set_after.contains(register));
+                    if set.len() == before {
+                        continue;
+                    }
+                }
+                unreached => *unreached = Some(set_after.clone())
+            }
+            waiting.push(*successor);
+        }
+    }
+    for (task, set) in segment.tasks.iter_mut().zip(&set_before) {
+        let Some(set) = set else { continue };
+        for used in task.uses_mut() {
+            match used {
+                ImmediateOrRefrence::Virtual(register) if !set.contains(register) => {
+                    return Err(format!("virtual register {register} is used before it's set"));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces operations on two constants with their result. Operations work on
+/// whole registers, so immediates are read as a register holds them: any
+/// bytes they leave out are zero.
+fn fold_constants(segment: &mut CodeSegment, width: usize) -> bool {
+    let mut changed = false;
+    for task in &mut segment.tasks {
+        let GeneratableTask::Operation {
+            operator,
+            destination,
+            a: ImmediateOrRefrence::Immediate(a),
+            b: ImmediateOrRefrence::Immediate(b)
+        } = task else {
+            continue;
+        };
+        let register = |bytes: &[u8]| {
+            let mut bytes = bytes.to_vec();
+            bytes.resize(width.max(bytes.len()), 0x00);
+            Bigint::from_le_bytes(&bytes[..width])
+        };
+        let (a, b) = (register(a), register(b));
+        let result = match operator {
+            Operator::Add => &a + &b,
+            Operator::Subtract => &a - &b,
+            Operator::Multiply => &a * &b,
+        };
+        // Registers wrap on overflow, and immediates are as short as they can
+        // be.
+        let mut bytes = result.wrapping_le_bytes(width);
+        while bytes.len() > 1 && bytes.last() == Some(&0x00) {
+            bytes.pop();
+        }
+        *task = GeneratableTask::Copy {
+            destination: destination.clone(),
+            source: ImmediateOrRefrence::Immediate(bytes)
+        };
+        changed = true;
+    }
+    changed
+}
+
+/// Replaces every use of a copied value with what it was copied from, leaving
+/// the copy unused.
+fn propagate_copies(segment: &mut CodeSegment) -> bool {
+    let copies: HashMap<usize, ImmediateOrRefrence> = segment.tasks.iter()
+        .filter_map(|task| match task {
+            GeneratableTask::Copy { destination: ImmediateOrRefrence::Virtual(register), source } => {
+                Some((*register, source.clone()))
+            }
+            _ => None
+        })
+        .collect();
+    let mut changed = false;
+    for task in &mut segment.tasks {
+        for used in task.uses_mut() {
+            // Copies of copies are followed to the first value.
+            while let ImmediateOrRefrence::Virtual(register) = used {
+                let Some(source) = copies.get(register) else {
+                    break;
+                };
+                *used = source.clone();
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Removes tasks that can never run, as no path through the segment reaches
+/// them, and tasks that set values that are never used.
+fn eliminate_dead_code(segment: &mut CodeSegment) -> bool {
+    let length = segment.tasks.len();
+    let reached = reachable(segment);
+    let mut index = 0;
+    segment.tasks.retain(|_| {
+        index += 1;
+        reached[index - 1]
+    });
+    // Removing an unused value can leave the values it used unused, so this
+    // goes until nothing else is removed.
+    loop {
+        let mut used = HashSet::new();
+        for task in &mut segment.tasks {
+            for operand in task.uses_mut() {
+                if let ImmediateOrRefrence::Virtual(register) = operand {
+                    used.insert(*register);
+                }
+            }
+        }
+        let before = segment.tasks.len();
+        segment.tasks.retain(|task| match task.definition() {
+            Some(register) => used.contains(&register),
+            None => true
+        });
+        if segment.tasks.len() == before {
+            break;
+        }
+    }
+    segment.tasks.len() != length
+}
+
+/// Which tasks in `segment` can run, starting from the first task.
+fn reachable(segment: &CodeSegment) -> Vec<bool> {
+    let successors = segment.successors();
+    let mut reached = vec![false; segment.tasks.len()];
+    let mut waiting = vec![0];
+    while let Some(index) = waiting.pop() {
+        if index >= reached.len() || reached[index] {
+            continue;
+        }
+        reached[index] = true;
+        waiting.extend(&successors[index]);
+    }
+    reached
+}
+
+/// The labels `segment` calls or refers to.
+fn references(segment: &mut CodeSegment) -> Vec<String> {
+    let mut labels = vec![];
+    for task in &mut segment.tasks {
+        if let GeneratableTask::Call(label) = task {
+            labels.push(label.clone());
+        }
+        for operand in task.uses_mut() {
+            if let ImmediateOrRefrence::Refrence(label) = operand {
+                labels.push(label.clone());
+            }
+        }
+    }
+    labels
+}
+
+/// Removes functions that can't be reached from the main block.
+fn remove_unused_functions(program: &mut Executable) -> bool {
+    let mut reached = HashSet::new();
+    let mut waiting = references(&mut program.entry_point);
+    while let Some(label) = waiting.pop() {
+        if !reached.insert(label.clone()) {
+            continue;
+        }
+        if let Some(segment) = program.code_sections.iter_mut().find(|segment| segment.label == label) {
+            waiting.append(&mut references(segment));
+        }
+    }
+    let before = program.code_sections.len();
+    program.code_sections.retain(|segment| reached.contains(&segment.label));
+    program.code_sections.len() != before
+}
+
+/// Removes data that no code refers to.
+fn prune_data(program: &mut Executable) -> bool {
+    let mut referenced = HashSet::new();
+    for segment in program.code_sections.iter_mut().chain(std::iter::once(&mut program.entry_point)) {
+        referenced.extend(references(segment));
+    }
+    let before = program.data.len();
+    program.data.retain(|data| referenced.contains(&data.label));
+    program.data.len() != before
+}
+
+#[test]
+fn test_optimise() {
+    use crate::generator::aarch64::AArch64AssemblyGenerator;
+    use crate::parser::beach::ast::{Evaluatable, Program, Task, Value, user_token_format::Syntax};
+    let source = String::from("
+        type string {
+            var pointer: usize;
+            var length: usize;
+        }
+        var stdout = |output: string| -> nothing {
+            unsafe {
+                !!syscall(4, (1, output.pointer, output.length));
+            }
+            return;
+        };
+        var unused = |ignored: string| -> nothing {
+            stdout(\"Never printed\");
+            return;
+            stdout(\"Never reached\");
+        };
+        main {
+            stdout(\"Hello!\");
+        }
+    ");
+    let executable = || {
+        let program = Program::from_lst(Syntax::from_string(source.clone()).unwrap(), None).unwrap();
+        Executable::from_ast(program).unwrap()
+    };
+
+    // Unused functions and their data are removed.
+    let mut program = executable();
+    assert_eq!(program.code_sections.len(), 2);
+    optimise::<AArch64AssemblyGenerator>(&mut program, 1).unwrap();
+    let labels: Vec<&str> = program.code_sections.iter().map(|segment| segment.label.as_str()).collect();
+    assert_eq!(labels, ["stdout"]);
+    assert_eq!(program.data.len(), 1);
+    assert_eq!(program.data[0].default.as_deref(), Some(&b"Hello!"[..]));
+
+    // (2 + 3) * 7 is computed while compiling at level 3, but level 2 only
+    // goes through the tasks once.
+    let virtual_register = ImmediateOrRefrence::Virtual;
+    let constant = |value: u8| ImmediateOrRefrence::Immediate(vec![value]);
+    let tasks = vec![
+        GeneratableTask::Operation { operator: Operator::Add, destination: virtual_register(0), a: constant(2), b: constant(3) },
+        GeneratableTask::Copy { destination: virtual_register(1), source: virtual_register(0) },
+        GeneratableTask::Operation { operator: Operator::Multiply, destination: virtual_register(2), a: virtual_register(1), b: constant(7) },
+        GeneratableTask::Operation { operator: Operator::Subtract, destination: virtual_register(3), a: constant(2), b: constant(3) },
+        GeneratableTask::Operation { operator: Operator::Add, destination: virtual_register(4), a: constant(1), b: constant(1) },
+        GeneratableTask::Syscall { number: virtual_register(2), arguments: vec![virtual_register(3)] },
+    ];
+    let mut program = executable();
+    program.entry_point.tasks = tasks.clone();
+    optimise::<AArch64AssemblyGenerator>(&mut program, 2).unwrap();
+    assert!(matches!(
+        &program.entry_point.tasks[0],
+        GeneratableTask::Operation { operator: Operator::Multiply, a, .. } if a == &constant(5)
+    ));
+    let mut program = executable();
+    program.entry_point.tasks = tasks;
+    optimise::<AArch64AssemblyGenerator>(&mut program, 3).unwrap();
+    // Subtraction wraps around, and the unused value is gone.
+    assert_eq!(program.entry_point.tasks.len(), 1);
+    assert!(matches!(
+        &program.entry_point.tasks[0],
+        GeneratableTask::Syscall { number, arguments }
+            if number == &constant(35) && arguments == &[ImmediateOrRefrence::Immediate(vec![0xff; 8])]
+    ));
+    // Nothing is left that uses `stdout`.
+    assert!(program.code_sections.is_empty() && program.data.is_empty());
+
+    // Arithmetic in a program is computed while compiling.
+    let source = String::from("
+        var quit = |code: u8| -> never {
+            unsafe {
+                !!syscall(93, (code));
+            }
+        };
+        main {
+            quit(6 * 7);
+        }
+    ");
+    let mut program = Executable::from_ast(Program::from_lst(Syntax::from_string(source.clone()).unwrap(), None).unwrap()).unwrap();
+    assert!(program.entry_point.tasks.iter().any(|task| matches!(task, GeneratableTask::Operation { operator: Operator::Multiply, .. })));
+    optimise::<AArch64AssemblyGenerator>(&mut program, 2).unwrap();
+    assert!(!program.entry_point.tasks.iter().any(|task| matches!(task, GeneratableTask::Operation { .. })));
+    assert!(program.entry_point.tasks.contains(&GeneratableTask::SetCallArgument { argument_number: 0, argument_value: constant(42) }));
+    // Negative constants keep their sign across the whole register.
+    let mut program = Program::from_lst(Syntax::from_string(source.clone()).unwrap(), None).unwrap();
+    for task in &mut program.main_tasks {
+        if let Task::Evaluate { task: Evaluatable::Math { a, .. }, .. } = task {
+            *a = Value::Integer(Bigint::from_i64(-1));
+        }
+    }
+    let mut program = Executable::from_ast(program).unwrap();
+    optimise::<AArch64AssemblyGenerator>(&mut program, 2).unwrap();
+    let negative_seven = ImmediateOrRefrence::Immediate(Bigint::from_i64(-7).wrapping_le_bytes(8));
+    assert!(program.entry_point.tasks.contains(&GeneratableTask::SetCallArgument { argument_number: 0, argument_value: negative_seven }));
+
+    // Code after going somewhere else is only kept if something goes to it.
+    let mut program: Executable = "
+        main {
+            goto skip
+            call never
+            label back
+            syscall 0x5d (0x00)
+            label skip
+            goto back
+            call after
+        }
+    ".parse().unwrap();
+    optimise::<AArch64AssemblyGenerator>(&mut program, 1).unwrap();
+    assert_eq!(program.entry_point.tasks, vec![
+        GeneratableTask::GoTo(String::from("skip")),
+        GeneratableTask::Label(String::from("back")),
+        GeneratableTask::Syscall { number: ImmediateOrRefrence::Immediate(vec![0x5d]), arguments: vec![constant(0)] },
+        GeneratableTask::Label(String::from("skip")),
+        GeneratableTask::GoTo(String::from("back")),
+    ]);
+
+    // Hand-written IR might not be in SSA form.
+    let mut program: Executable = "main {\n    %0 = 0x01\n    %0 = 0x02\n    syscall 0x5d (%0)\n}".parse().unwrap();
+    assert_eq!(
+        optimise::<AArch64AssemblyGenerator>(&mut program, 1).unwrap_err().to_string(),
+        "The main block isn't in SSA form: virtual register 0 is set more than once."
+    );
+    // ...which is fine when it isn't optimised.
+    optimise::<AArch64AssemblyGenerator>(&mut program, 0).unwrap();
+    // Values have to be set on every path to where they're used, even if the
+    // task setting them is written earlier.
+    let mut program: Executable = "
+        main {
+            goto_if_zero skip, 0x00
+            %0 = 0x01
+            label skip
+            syscall 0x5d (%0)
+        }
+    ".parse().unwrap();
+    assert_eq!(
+        optimise::<AArch64AssemblyGenerator>(&mut program, 1).unwrap_err().to_string(),
+        "The main block isn't in SSA form: virtual register 0 is used before it's set."
+    );
+    // ...but can be set after where they're used, if every path sets them
+    // first.
+    let mut program: Executable = "
+        main {
+            goto start
+            label use
+            syscall 0x5d (%0)
+            label start
+            %0 = 0x01
+            goto use
+        }
+    ".parse().unwrap();
+    optimise::<AArch64AssemblyGenerator>(&mut program, 1).unwrap();
+
+    // Immediates are as wide as a register when folded, so narrow ones are
+    // read the way registers hold them.
+    let mut segment = CodeSegment::new("");
+    segment.tasks = vec![
+        GeneratableTask::Operation { operator: Operator::Subtract, destination: virtual_register(0), a: constant(0xff), b: constant(0x01) },
+        GeneratableTask::Operation {
+            operator: Operator::Add,
+            destination: virtual_register(1),
+            a: ImmediateOrRefrence::Immediate(vec![0xff; 8]),
+            b: constant(0x02)
+        },
+    ];
+    fold_constants(&mut segment, 8);
+    assert_eq!(segment.tasks, vec![
+        GeneratableTask::Copy { destination: virtual_register(0), source: constant(0xfe) },
+        GeneratableTask::Copy { destination: virtual_register(1), source: constant(0x01) },
+    ]);
+}
@@ -0,0 +1,751 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::generator::{aarch64::AArch64AssemblyGenerator, generic::AssemblyGenerator};
+use crate::parser::beach::ast::{Definition, Evaluatable, Function, PrimitiveType, Program, Task, TypeIdentity, Value};
+use crate::parser::beach::ast::user_token_format::Annotation;
+
+use super::monomorphize::implements;
+
+/// The width of pointers in bits. Every platform beach targets is generated
+/// for AArch64, so integers passed as a `usize` or `isize` are checked
+/// against its pointers.
+pub(super) const POINTER_WIDTH: usize = AArch64AssemblyGenerator::POINTER_WIDTH as usize * 8;
+
+/// A problem found in a program, along with where it was found.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub location: Annotation,
+    /// The file `location` is in, if it's known.
+    pub file: Option<PathBuf>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Sets the file of every diagnostic in `diagnostics` found after the first
+/// `start`, which were found in `file`.
+fn found_in(diagnostics: &mut [Diagnostic], start: usize, file: Option<&PathBuf>) {
+    for diagnostic in &mut diagnostics[start..] {
+        diagnostic.file = file.cloned();
+    }
+}
+
+/// Resolves every type and label in `program` to a declaration, and checks
+/// that every call is given arguments of the types it expects.
+///
+/// Named types are rewritten in place to the kind of declaration they refer to
+/// ([TypeIdentity::Trait], [TypeIdentity::Enum] or
+/// [TypeIdentity::Structured]), and type aliases such as `ptr => usize;` are
+/// replaced by the type they alias. Expects generics to already be
+/// monomorphized.
+pub fn check(program: &mut Program) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    // Resolve the types in every declaration first, so calls are checked
+    // against resolved types.
+    let names = TypeNames::collect(program);
+    for definition in &mut program.definitions {
+        let start = diagnostics.len();
+        match definition {
+            Definition::Function(function) => {
+                for argument in &mut function.arguments {
+                    names.resolve_in_place(&mut argument.arg_type, function.location, &mut diagnostics);
+                }
+                names.resolve_in_place(&mut function.returns, function.location, &mut diagnostics);
+                found_in(&mut diagnostics, start, function.file.as_ref());
+            }
+            Definition::Type { data, file, .. } => {
+                for (_name, data_type, location) in data {
+                    names.resolve_in_place(data_type, *location, &mut diagnostics);
+                }
+                found_in(&mut diagnostics, start, file.as_ref());
+            }
+            Definition::Enum { cases, file, .. } => {
+                for (_name, data_type, location) in cases {
+                    if let Some(data_type) = data_type {
+                        names.resolve_in_place(data_type, *location, &mut diagnostics);
+                    }
+                }
+                found_in(&mut diagnostics, start, file.as_ref());
+            }
+            _ => {}
+        }
+    }
+
+    let mut functions = HashMap::new();
+    let mut constants = HashMap::new();
+    for definition in &program.definitions {
+        match definition {
+            Definition::Function(function) => {
+                functions.insert(function.name.clone(), function);
+            }
+            Definition::GlobalConstant { label, value } => {
+                constants.insert(label.clone(), value);
+            }
+            _ => {}
+        }
+    }
+    let checker = Checker { program, functions, constants, names: &names };
+
+    // The main block is in the main file, which linking keeps as the
+    // program's path.
+    checker.check_tasks(&program.main_tasks, &[], None, HashMap::new(), &mut diagnostics);
+    found_in(&mut diagnostics, 0, program.path.as_ref());
+    checker.check_tasks(&program.global_tasks, &[], None, HashMap::new(), &mut diagnostics);
+    for definition in &program.definitions {
+        if let Definition::Function(function) = definition {
+            let locals: Vec<(&str, &TypeIdentity)> = function.arguments.iter()
+                .map(|argument| (argument.name.as_str(), &argument.arg_type))
+                .collect();
+            let start = diagnostics.len();
+            checker.check_tasks(&function.code, &locals, Some(&function.returns), HashMap::new(), &mut diagnostics);
+            found_in(&mut diagnostics, start, function.file.as_ref());
+        }
+    }
+    diagnostics.sort_by(|a, b| {
+        (&a.file, a.location.line, a.location.column).cmp(&(&b.file, b.location.line, b.location.column))
+    });
+    return diagnostics;
+}
+
+/// What each declared type name refers to.
+struct TypeNames {
+    traits: Vec<String>,
+    enums: Vec<String>,
+    types: Vec<String>,
+    aliases: HashMap<String, String>,
+}
+
+impl TypeNames {
+    fn collect(program: &Program) -> Self {
+        let mut names = TypeNames {
+            traits: vec![],
+            enums: vec![],
+            types: vec![],
+            aliases: HashMap::new(),
+        };
+        for definition in &program.definitions {
+            match definition {
+                Definition::Trait { name, .. } => names.traits.push(name.clone()),
+                Definition::Enum { name, .. } => names.enums.push(name.clone()),
+                Definition::Type { name, .. } => names.types.push(name.clone()),
+                Definition::Alias { from, to, .. } => {
+                    names.aliases.insert(from.clone(), to.clone());
+                }
+                _ => {}
+            }
+        }
+        return names;
+    }
+    fn resolve_in_place(&self, identity: &mut TypeIdentity, location: Annotation, diagnostics: &mut Vec<Diagnostic>) {
+        match self.resolve(identity, 0) {
+            Ok(resolved) => *identity = resolved,
+            Err(message) => diagnostics.push(Diagnostic { message, location, file: None }),
+        }
+    }
+    fn resolve(&self, identity: &TypeIdentity, depth: usize) -> Result<TypeIdentity, String> {
+        match identity {
+            TypeIdentity::Structured(name) => {
+                if self.traits.contains(name) {
+                    return Ok(TypeIdentity::Trait(name.clone()));
+                }
+                if self.enums.contains(name) {
+                    return Ok(TypeIdentity::Enum(name.clone()));
+                }
+                if self.types.contains(name) {
+                    return Ok(identity.clone());
+                }
+                if let Some(to) = self.aliases.get(name) {
+                    // Aliases of aliases are fine, aliases of themselves aren't.
+                    if depth > 64 {
+                        return Err(format!("The type alias `{name}` never resolves to a type."));
+                    }
+                    return self.resolve(&TypeIdentity::from_name(to, &[]), depth + 1);
+                }
+                return Err(format!("Cannot find the type `{name}`."));
+            }
+            TypeIdentity::Instance { name, arguments } => {
+                let mut resolved = vec![];
+                for argument in arguments {
+                    resolved.push(self.resolve(argument, depth)?);
+                }
+                return Ok(TypeIdentity::Instance { name: name.clone(), arguments: resolved });
+            }
+            TypeIdentity::Closure { arguments, returns } => {
+                let mut resolved = vec![];
+                for argument in arguments {
+                    resolved.push(self.resolve(argument, depth)?);
+                }
+                return Ok(TypeIdentity::Closure {
+                    arguments: resolved,
+                    returns: Box::new(self.resolve(returns, depth)?)
+                });
+            }
+            _ => return Ok(identity.clone()),
+        }
+    }
+}
+
+struct Checker<'a> {
+    program: &'a Program,
+    functions: HashMap<String, &'a Function>,
+    constants: HashMap<String, &'a Value>,
+    names: &'a TypeNames,
+}
+
+/// What's known about a label created by evaluating something earlier in a
+/// block.
+#[derive(Clone)]
+enum Evaluated<'a> {
+    /// Its type, from its declaration or from what it was set to.
+    Typed(TypeIdentity),
+    /// The literal it was set to, which takes the type of whatever it's given
+    /// to.
+    Literal(&'a Value),
+    /// Nothing, as what it was set to couldn't be checked.
+    Unknown,
+}
+
+impl Checker<'_> {
+    /// Finds a function by name, following any aliases of it. (`print => stdout;`)
+    fn function(&self, name: &str) -> Option<&Function> {
+        let mut name = name;
+        for _ in 0..64 {
+            if let Some(function) = self.functions.get(name) {
+                return Some(function);
+            }
+            name = self.names.aliases.get(name)?;
+        }
+        return None;
+    }
+    /// Checks a block of tasks. `locals` are the labels in scope that aren't
+    /// global, such as the arguments of the function being checked, and
+    /// `returns` is what that function returns. `evaluated` holds the labels
+    /// created by evaluating something earlier, in the blocks around this one.
+    fn check_tasks<'b>(
+        &self,
+        tasks: &'b [Task],
+        locals: &[(&str, &TypeIdentity)],
+        returns: Option<&TypeIdentity>,
+        mut evaluated: HashMap<&'b str, Evaluated<'b>>,
+        diagnostics: &mut Vec<Diagnostic>
+    ) {
+        for task in tasks {
+            match task {
+                Task::Call { function_identifier, arguments, location, .. } => {
+                    self.check_call(function_identifier, arguments, *location, locals, &evaluated, diagnostics);
+                }
+                Task::Evaluate { label, task } => {
+                    let result = match task {
+                        Evaluatable::Value { value } => self.evaluate(value, locals, &evaluated),
+                        Evaluatable::Call { label, arguments, location, .. } => {
+                            self.check_call(label, arguments, *location, locals, &evaluated, diagnostics);
+                            let returns = match self.value_type(&Value::Label(label.clone()), locals, &evaluated) {
+                                Ok(Some(TypeIdentity::Closure { returns, .. })) => Some(*returns),
+                                _ => None
+                            };
+                            returns.map_or(Evaluated::Unknown, Evaluated::Typed)
+                        }
+                        // Arithmetic gives the type of whichever side has one.
+                        Evaluatable::Math { a, b, .. } => {
+                            match (self.evaluate(a, locals, &evaluated), self.evaluate(b, locals, &evaluated)) {
+                                (Evaluated::Typed(typed), _) | (_, Evaluated::Typed(typed)) => Evaluated::Typed(typed),
+                                _ => Evaluated::Unknown
+                            }
+                        }
+                    };
+                    evaluated.insert(label, result);
+                }
+                Task::Set { label, type_, value, location } => {
+                    let result = match type_ {
+                        Some(type_) => match self.names.resolve(&TypeIdentity::from_name(type_, &[]), 0) {
+                            Ok(declared) => {
+                                let value = Self::literal(value, &evaluated);
+                                match self.value_type(value, locals, &evaluated) {
+                                    Ok(value_type) => if let Err(found) = self.assignable(value, value_type.as_ref(), &declared) {
+                                        diagnostics.push(Diagnostic {
+                                            message: format!("`{label}` is declared as `{declared}`, but {found} was given."),
+                                            location: *location,
+                                            file: None
+                                        });
+                                    },
+                                    Err(message) => diagnostics.push(Diagnostic { message, location: *location, file: None })
+                                }
+                                Evaluated::Typed(declared)
+                            }
+                            // Missing names are reported when the module is
+                            // resolved.
+                            Err(_) => Evaluated::Unknown
+                        },
+                        None => self.evaluate(value, locals, &evaluated)
+                    };
+                    evaluated.insert(label, result);
+                }
+                Task::FreeEvaluated { label } => {
+                    evaluated.remove(label.as_str());
+                }
+                Task::Syscall { number, arguments, result, location } => {
+                    // System calls return register sized integers.
+                    if let Some(result) = result {
+                        evaluated.insert(result, Evaluated::Typed(TypeIdentity::Primitive(PrimitiveType::ArchSigned)));
+                    }
+                    if !matches!(number, Value::Integer(_)) {
+                        diagnostics.push(Diagnostic {
+                            message: String::from("System call numbers must be integers."),
+                            location: *location,
+                            file: None
+                        });
+                    }
+                    // System calls only deal in register sized integers.
+                    for (index, argument) in arguments.iter().enumerate() {
+                        let argument = Self::literal(argument, &evaluated);
+                        let argument_type = match self.value_type(argument, locals, &evaluated) {
+                            Ok(argument_type) => argument_type,
+                            Err(message) => {
+                                diagnostics.push(Diagnostic { message, location: *location, file: None });
+                                continue;
+                            }
+                        };
+                        let fits = match (argument, &argument_type) {
+                            (Value::Integer(integer), _) => {
+                                integer.fits(POINTER_WIDTH, false) || integer.fits(POINTER_WIDTH, true)
+                            }
+                            (Value::Label(_), Some(TypeIdentity::Primitive(primitive))) => primitive.is_integer(),
+                            (Value::Label(_), None) => true,
+                            _ => false
+                        };
+                        if !fits {
+                            diagnostics.push(Diagnostic {
+                                message: format!(
+                                    "Argument {} of a system call should be a register sized integer.",
+                                    index + 1
+                                ),
+                                location: *location,
+                                file: None
+                            });
+                        }
+                    }
+                }
+                Task::Return { value, location } => {
+                    let Some(returns) = returns
+                    else {
+                        diagnostics.push(Diagnostic {
+                            message: String::from("Only functions can return a value."),
+                            location: *location,
+                            file: None
+                        });
+                        continue;
+                    };
+                    let value = Self::literal(value, &evaluated);
+                    match self.value_type(value, locals, &evaluated) {
+                        Ok(value_type) => if let Err(found) = self.assignable(value, value_type.as_ref(), returns) {
+                            diagnostics.push(Diagnostic {
+                                message: format!("This function returns `{returns}`, but {found} was returned."),
+                                location: *location,
+                                file: None
+                            });
+                        },
+                        Err(message) => diagnostics.push(Diagnostic { message, location: *location, file: None })
+                    }
+                }
+                Task::If { a, b, tasks, location } => {
+                    let sides = [Self::literal(a, &evaluated), Self::literal(b, &evaluated)];
+                    let mut side_types = vec![];
+                    for side in sides {
+                        match self.value_type(side, locals, &evaluated) {
+                            Ok(side_type) => side_types.push(side_type),
+                            Err(message) => {
+                                diagnostics.push(Diagnostic { message, location: *location, file: None });
+                                side_types.push(None);
+                            }
+                        }
+                    }
+                    // Literals are compared as the type of the other side, if
+                    // it has one.
+                    let compared = side_types.iter()
+                        .flatten()
+                        .next()
+                        .cloned()
+                        .unwrap_or(TypeIdentity::Primitive(PrimitiveType::ArchSigned));
+                    if !matches!(&compared, TypeIdentity::Primitive(primitive) if primitive.is_integer()) {
+                        diagnostics.push(Diagnostic {
+                            message: format!("Only integers can be compared yet, not `{compared}`."),
+                            location: *location,
+                            file: None
+                        });
+                    }
+                    else {
+                        for (side, side_type) in sides.into_iter().zip(&side_types) {
+                            if let Err(found) = self.assignable(side, side_type.as_ref(), &compared) {
+                                diagnostics.push(Diagnostic {
+                                    message: format!("Both sides of `==` should be `{compared}`, but {found} was given."),
+                                    location: *location,
+                                    file: None
+                                });
+                            }
+                        }
+                    }
+                    // Labels set inside the `if` are only in scope inside it.
+                    self.check_tasks(tasks, locals, returns, evaluated.clone(), diagnostics);
+                }
+                Task::ExitBlock | Task::Unreachable => {}
+            }
+        }
+    }
+    /// Checks that `function_identifier` is a function, given `arguments` of
+    /// the types it expects.
+    fn check_call(
+        &self,
+        function_identifier: &str,
+        arguments: &[Value],
+        location: Annotation,
+        locals: &[(&str, &TypeIdentity)],
+        evaluated: &HashMap<&str, Evaluated>,
+        diagnostics: &mut Vec<Diagnostic>
+    ) {
+        // Labels set to literals are checked as those literals.
+        let arguments: Vec<&Value> = arguments.iter()
+            .map(|argument| Self::literal(argument, evaluated))
+            .collect();
+        let mut argument_types = vec![];
+        for argument in &arguments {
+            match self.value_type(argument, locals, evaluated) {
+                Ok(value_type) => argument_types.push(value_type),
+                Err(message) => {
+                    diagnostics.push(Diagnostic { message, location, file: None });
+                    argument_types.push(None);
+                }
+            }
+        }
+        let expected: Vec<TypeIdentity> = if let Some(function) = self.function(function_identifier) {
+            function.arguments.iter().map(|argument| argument.arg_type.clone()).collect()
+        }
+        else if let Some((_name, TypeIdentity::Closure { arguments, .. })) = locals.iter().find(|(name, _)| *name == function_identifier) {
+            arguments.clone()
+        }
+        else {
+            diagnostics.push(Diagnostic {
+                message: format!("Cannot find the function `{function_identifier}`."),
+                location,
+                file: None
+            });
+            return;
+        };
+        if expected.len() != arguments.len() {
+            diagnostics.push(Diagnostic {
+                message: format!(
+                    "`{function_identifier}` takes {} argument(s), but {} were given.",
+                    expected.len(), arguments.len()
+                ),
+                location,
+                file: None
+            });
+            return;
+        }
+        for (index, ((value, value_type), expected)) in arguments.into_iter().zip(&argument_types).zip(&expected).enumerate() {
+            if let Err(found) = self.assignable(value, value_type.as_ref(), expected) {
+                diagnostics.push(Diagnostic {
+                    message: format!(
+                        "Argument {} of `{function_identifier}` should be `{expected}`, but {found} was given.",
+                        index + 1
+                    ),
+                    location,
+                    file: None
+                });
+            }
+        }
+    }
+    /// What's known about a label set to `value`.
+    fn evaluate<'b>(&self, value: &'b Value, locals: &[(&str, &TypeIdentity)], evaluated: &HashMap<&str, Evaluated<'b>>) -> Evaluated<'b> {
+        let Value::Label(label) = value
+        else {
+            return Evaluated::Literal(value);
+        };
+        if let Some(known) = evaluated.get(label.as_str()) {
+            return known.clone();
+        }
+        match self.value_type(value, locals, evaluated) {
+            Ok(Some(value_type)) => return Evaluated::Typed(value_type),
+            _ => return Evaluated::Unknown
+        }
+    }
+    /// The literal `value` was set to, if it's a label set to one, or `value`
+    /// otherwise.
+    fn literal<'b>(value: &'b Value, evaluated: &HashMap<&str, Evaluated<'b>>) -> &'b Value {
+        if let Value::Label(label) = value
+            && let Some(Evaluated::Literal(literal)) = evaluated.get(label.as_str())
+        {
+            return literal;
+        }
+        return value;
+    }
+    /// Finds the type of a value. Returns `Ok(None)` for literals, which are
+    /// checked by [Checker::assignable] against whatever they're given to, and
+    /// for labels whose types aren't known.
+    fn value_type(&self, value: &Value, locals: &[(&str, &TypeIdentity)], evaluated: &HashMap<&str, Evaluated>) -> Result<Option<TypeIdentity>, String> {
+        let Value::Label(label) = value
+        else {
+            return Ok(None);
+        };
+        // Fields of a value, as in `output.length`.
+        if let Some((base, field)) = label.rsplit_once('.') {
+            let Some(base_type) = self.value_type(&Value::Label(base.to_string()), locals, evaluated)?
+            else {
+                return Ok(None);
+            };
+            return match self.field_type(&base_type, field) {
+                Some(field_type) => Ok(Some(field_type)),
+                None => Err(format!("`{base}` is `{base_type}`, which has no field `{field}`."))
+            };
+        }
+        if let Some((_name, local_type)) = locals.iter().find(|(name, _)| name == label) {
+            return Ok(Some((*local_type).clone()));
+        }
+        if let Some(known) = evaluated.get(label.as_str()) {
+            match known {
+                Evaluated::Typed(value_type) => return Ok(Some(value_type.clone())),
+                _ => return Ok(None)
+            }
+        }
+        if let Some(constant) = self.constants.get(label) {
+            return self.value_type(constant, &[], &HashMap::new());
+        }
+        if let Some(function) = self.function(label) {
+            return Ok(Some(TypeIdentity::Closure {
+                arguments: function.arguments.iter().map(|argument| argument.arg_type.clone()).collect(),
+                returns: Box::new(function.returns.clone())
+            }));
+        }
+        return Err(format!("Cannot find `{label}` in this scope."));
+    }
+    /// Finds the type of the field `field` in values of type `of`.
+    fn field_type(&self, of: &TypeIdentity, field: &str) -> Option<TypeIdentity> {
+        let TypeIdentity::Structured(name) = of
+        else {
+            return None;
+        };
+        for definition in &self.program.definitions {
+            match definition {
+                Definition::Type { name: type_name, data, .. } if type_name == name => {
+                    return data.iter()
+                        .find(|(data_name, _, _)| data_name == field)
+                        .map(|(_, data_type, _)| data_type.clone());
+                }
+                _ => {}
+            }
+        }
+        return None;
+    }
+    /// Checks that `value` can be given where `expected` is wanted. On failure,
+    /// returns a description of what was given instead.
+    fn assignable(&self, value: &Value, value_type: Option<&TypeIdentity>, expected: &TypeIdentity) -> Result<(), String> {
+        // Generic arguments are resolved by monomorphization, which checks
+        // their constraints itself.
+        if let TypeIdentity::Generic(_) = expected {
+            return Ok(());
+        }
+        match value {
+            Value::Integer(integer) => {
+                let fits = match expected {
+                    TypeIdentity::Primitive(primitive) if primitive.is_integer() => {
+                        integer.fits(primitive.bit_width().unwrap_or(POINTER_WIDTH), primitive.is_signed())
+                    }
+                    TypeIdentity::Primitive(primitive) => primitive.is_float() || primitive.is_complex(),
+                    TypeIdentity::Trait(name) => {
+                        implements(self.program, &TypeIdentity::Primitive(PrimitiveType::ArchSigned), name)
+                    }
+                    _ => false
+                };
+                if fits {
+                    return Ok(());
+                }
+                match expected {
+                    TypeIdentity::Primitive(primitive) if primitive.is_integer() => {
+                        return Err(format!("an integer that doesn't fit in `{}`", primitive.name()));
+                    }
+                    _ => return Err(String::from("an integer"))
+                }
+            }
+            Value::Float(_) => {
+                let fits = match expected {
+                    TypeIdentity::Primitive(primitive) => primitive.is_float() || primitive.is_complex(),
+                    TypeIdentity::Trait(name) => {
+                        implements(self.program, &TypeIdentity::Primitive(PrimitiveType::F64), name)
+                    }
+                    _ => false
+                };
+                if fits {
+                    return Ok(());
+                }
+                return Err(String::from("a decimal number"));
+            }
+            Value::Complex(_) => {
+                // Complex numbers can't lose their imaginary part.
+                let fits = match expected {
+                    TypeIdentity::Primitive(primitive) => primitive.is_complex(),
+                    TypeIdentity::Trait(name) => {
+                        implements(self.program, &TypeIdentity::Primitive(PrimitiveType::C128), name)
+                    }
+                    _ => false
+                };
+                if fits {
+                    return Ok(());
+                }
+                return Err(String::from("a complex number"));
+            }
+            Value::String(_) => {
+                return self.matches(&TypeIdentity::Structured(String::from("string")), expected)
+                    .then_some(())
+                    .ok_or(String::from("a string"));
+            }
+            Value::Bool(_) => {
+                return self.matches(&TypeIdentity::Enum(String::from("boolean")), expected)
+                    .then_some(())
+                    .ok_or(String::from("a boolean"));
+            }
+            Value::Label(label) => {
+                let Some(value_type) = value_type
+                else {
+                    return Ok(());
+                };
+                return self.matches(value_type, expected)
+                    .then_some(())
+                    .ok_or(format!("`{label}`, which is `{value_type}`,"));
+            }
+        }
+    }
+    /// If something of type `found` can be given where `expected` is wanted.
+    fn matches(&self, found: &TypeIdentity, expected: &TypeIdentity) -> bool {
+        if found == expected {
+            return true;
+        }
+        match (found, expected) {
+            (_, TypeIdentity::Trait(name)) => implements(self.program, found, name),
+            // Types from `TypeIdentity::from_name` haven't been through
+            // [TypeNames::resolve], so compare them by name.
+            (TypeIdentity::Enum(a), TypeIdentity::Structured(b)) |
+            (TypeIdentity::Structured(a), TypeIdentity::Enum(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+#[test]
+fn test_typecheck() {
+    use crate::parser::beach::ast::user_token_format::Syntax;
+    let source = String::from("
+        type string {}
+        byte => u8;
+        var small = |value: byte| {};
+        var named = |value: string| {};
+        type holder {
+            var inner: absent;
+        }
+        main {
+            small(255);
+            small(256);
+            named(\"beach\");
+            named(5);
+            missing();
+            small(nowhere);
+        }
+    ");
+    let mut program = Program::from_lst(Syntax::from_string(source).unwrap(), None).unwrap();
+    let diagnostics = check(&mut program);
+    let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, vec![
+        // Fields are reported where they're declared.
+        "7:17: Cannot find the type `absent`.",
+        "11:13: Argument 1 of `small` should be `u8`, but an integer that doesn't fit in `u8` was given.",
+        "13:13: Argument 1 of `named` should be `string`, but an integer was given.",
+        "14:13: Cannot find the function `missing`.",
+        "15:13: Cannot find `nowhere` in this scope.",
+    ]);
+}
+
+#[test]
+fn test_typecheck_files() {
+    use crate::parser::beach::ast::user_token_format::Syntax;
+    let source = String::from("
+        var small = |value: u8| {};
+        var helper = |unused: u8| {
+            small(256);
+        };
+        main {
+            small(300);
+        }
+    ");
+    let mut program = Program::from_lst(Syntax::from_string(source).unwrap(), None).unwrap();
+    // Linking leaves the main file as the program's path, and functions
+    // remember the file they came from.
+    program.path = Some(PathBuf::from("main.beach"));
+    for definition in &mut program.definitions {
+        if let Definition::Function(function) = definition {
+            function.file = Some(PathBuf::from("imported.beach"));
+        }
+    }
+    let diagnostics = check(&mut program);
+    let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, vec![
+        "imported.beach:4:13: Argument 1 of `small` should be `u8`, but an integer that doesn't fit in `u8` was given.",
+        "main.beach:7:13: Argument 1 of `small` should be `u8`, but an integer that doesn't fit in `u8` was given.",
+    ]);
+}
+
+#[test]
+fn test_typecheck_evaluated() {
+    use crate::parser::beach::ast::user_token_format::Syntax;
+    use crate::utils::Bigint;
+    let source = String::from("
+        type string {}
+        var small = |value: u8| {};
+        var named = |value: string| {};
+        var make = |value: u8| -> string {};
+        main {
+            small(first);
+            small(second);
+            named(third);
+            small(fourth);
+            named(fifth);
+        }
+    ");
+    let mut program = Program::from_lst(Syntax::from_string(source).unwrap(), None).unwrap();
+    // Labels are checked as what they were declared or evaluated as.
+    let set = |label: &str, type_: Option<&str>, value: Value| Task::Set {
+        label: String::from(label),
+        type_: type_.map(String::from),
+        value,
+        location: Annotation::default()
+    };
+    let evaluate = |label: &str, task: Evaluatable| Task::Evaluate { label: String::from(label), task };
+    let mut tasks = vec![
+        set("first", None, Value::Integer(Bigint::from_i64(256))),
+        set("second", Some("string"), Value::String(String::from("beach"))),
+        evaluate("third", Evaluatable::Value { value: Value::Label(String::from("second")) }),
+        evaluate("fourth", Evaluatable::Call {
+            label: String::from("make"),
+            generics: vec![],
+            arguments: vec![Value::Integer(Bigint::from_i64(1))],
+            location: Annotation::default()
+        }),
+        evaluate("fifth", Evaluatable::Value { value: Value::Integer(Bigint::from_i64(1)) }),
+    ];
+    tasks.append(&mut program.main_tasks);
+    program.main_tasks = tasks;
+    let diagnostics = check(&mut program);
+    let messages: Vec<String> = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect();
+    assert_eq!(messages, vec![
+        "7:13: Argument 1 of `small` should be `u8`, but an integer that doesn't fit in `u8` was given.",
+        "8:13: Argument 1 of `small` should be `u8`, but `second`, which is `string`, was given.",
+        "10:13: Argument 1 of `small` should be `u8`, but `fourth`, which is `string`, was given.",
+        "11:13: Argument 1 of `named` should be `string`, but an integer was given.",
+    ]);
+}
@@ -3,6 +3,6 @@ pub mod aarch64;
 
 use crate::parser::beach::ast::user_token_format;
 
-pub fn parse_string_file(file: String) -> user_token_format::Syntax {
+pub fn parse_string_file(file: String) -> Result<user_token_format::Syntax, anyhow::Error> {
     user_token_format::Syntax::from_string(file)
 }
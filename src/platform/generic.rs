@@ -1,4 +1,6 @@
-use crate::generator::generic::AssemblyGenerator;
+use crate::generator::generic::{Architecture, AssemblyGenerator, Priority, SymbolKind};
+use crate::parser::beach::GeneratableTask;
+use crate::packager::Packager;
 
 /// Represents a platform and the things it supports.
 pub struct Platform<G: AssemblyGenerator> {
@@ -14,6 +16,9 @@ pub struct Platform<G: AssemblyGenerator> {
     pub friendly_name: &'static str,
     /// A name for this platform that makes it distinct from others.
     pub technical_name: &'static str,
+    /// A short identifier for this platform, used to find its parts of the
+    /// standard library. (`std/io/macos_aarch64.beach`)
+    pub technical_id: &'static str,
     /// Assembly code to begin the file.
     pub assembly_header: &'static str,
     /// Assembly code run on program completion.
@@ -23,24 +28,104 @@ pub struct Platform<G: AssemblyGenerator> {
     pub assembly_entry: &'static str,
     /// A list of features supported by this platform, and the data to include.
     pub features: Vec<(&'static str, &'static str)>,
+    /// How this platform makes system calls. (`!!syscall`)
+    pub syscall_convention: SyscallConvention,
+    /// The executable formats programs are packaged as for this platform.
+    pub packagers: Vec<Packager>,
+}
+
+/// Describes how a platform's kernel expects system calls to be made.
+pub struct SyscallConvention {
+    /// The register holding the number of the system call to make.
+    pub number_register: &'static str,
+    /// Registers used for passing arguments to system calls, in order.
+    pub argument_registers: &'static [&'static str],
+    /// Assembly that hands control to the kernel.
+    pub instruction: &'static str,
+    /// The number of the system call writing to a file descriptor, as
+    /// `write(descriptor, pointer, length)`. The IR interpreter provides it.
+    pub write: u64,
+    /// The number of the system call ending the program, as `exit(code)`.
+    /// The IR interpreter provides it.
+    pub exit: u64,
+}
+
+impl SyscallConvention {
+    /// XNU on AArch64 takes the system call number in x16.
+    pub const MACOS_AARCH64: SyscallConvention = SyscallConvention {
+        number_register: "x16",
+        argument_registers: &["x0", "x1", "x2", "x3", "x4", "x5"],
+        instruction: "svc #0\n",
+        write: 4,
+        exit: 1,
+    };
+    /// Linux on AArch64 takes the system call number in x8.
+    pub const LINUX_AARCH64: SyscallConvention = SyscallConvention {
+        number_register: "x8",
+        argument_registers: &["x0", "x1", "x2", "x3", "x4", "x5"],
+        instruction: "svc #0\n",
+        write: 64,
+        exit: 93,
+    };
 }
 
 impl <G: AssemblyGenerator>Platform<G> {
-    pub fn generate_assembly(&self, program: crate::parser::beach::Executable) -> String {
+    /// The processor architecture this platform runs on.
+    pub fn architecture(&self) -> Architecture {
+        G::ARCHITECTURE
+    }
+    /// Generates the assembly for `program`, choosing instructions for
+    /// `priority`. With `debug`, the assembly also marks which source line
+    /// each instruction comes from, so the assembler can make a line table.
+    /// Fails if a task can't be made into assembly for this platform.
+    pub fn generate_assembly(&self, mut program: crate::parser::beach::Executable, priority: Priority, debug: bool) -> Result<String, anyhow::Error> {
+        program.allocate_registers::<G>();
+        let extensions = priority.extensions::<G>();
         // This is the final assembly code file, in String form.
         let mut master_output = String::new();
         // Add the platform assembly header.
         master_output += self.assembly_header;
 
+        // Number every source file, starting from the one with the main block
+        // so it names the program in debug information.
+        let mut files: Vec<&std::path::Path> = vec![];
+        if debug {
+            let tasks = std::iter::once(&program.entry_point)
+                .chain(program.code_sections.iter())
+                .flat_map(|section| section.tasks.iter());
+            for task in tasks {
+                match task {
+                    GeneratableTask::SourceLine { file, .. } if !files.contains(&file.as_path()) => {
+                        files.push(file);
+                        master_output += &G::source_file(files.len(), &file.to_string_lossy());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let generate = |task: &GeneratableTask| {
+            let mut output = task.call_generator(self, extensions)?;
+            match task {
+                GeneratableTask::SourceLine { file, line, .. } if debug => {
+                    let index = files.iter().position(|known| known == file).expect("every file was numbered");
+                    output += &G::source_location(index + 1, *line);
+                }
+                _ => {}
+            }
+            Ok::<String, anyhow::Error>(output)
+        };
+
         // Add each CodeSegment's assembly.
         for section in &program.code_sections {
+            master_output += &G::symbol_kind(&section.label, SymbolKind::Function);
             master_output += &G::label(section.label.clone());
             for task in &section.tasks {
-                master_output += &task.call_generator::<G>();
+                master_output += &generate(task)?;
             }
         }
         // Add any needed data, if relevant.
         for data in &program.data {
+            master_output += &G::symbol_kind(&data.label, SymbolKind::Data);
             if let Some(def_val) = &data.default {
                 assert_eq!(data.size, def_val.len());
                 master_output += &G::data(data.label.clone(), def_val);
@@ -49,26 +134,23 @@ impl <G: AssemblyGenerator>Platform<G> {
                 master_output += &G::data(data.label.clone(), &vec![0; data.size]);
             }
         }
-        // Add any platform imports.
+        // Add any platform imports. Requirements that aren't features of this
+        // platform are implemented by the standard library instead.
         for requirement in &program.platform_requirements {
-            let mut found = false;
             for (feature, data) in &self.features {
                 if requirement == feature {
                     master_output += data;
-                    found = true;
                 }
             }
-            assert!(found);
         }
         // Add the platform's entry point code.
         master_output += self.assembly_entry;
         // Add the main function code.
         for task in &program.entry_point.tasks {
-            master_output += &task.call_generator::<G>();
+            master_output += &generate(task)?;
         }
         // Add the platform's exit code.
         master_output += self.assembly_exit;
-        println!("master pt1: \n{}", master_output);
-        todo!();
+        return Ok(master_output);
     }
 }
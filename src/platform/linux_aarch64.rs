@@ -0,0 +1,25 @@
+use crate::generator::generic::AssemblyGenerator;
+use crate::generator::aarch64::AArch64AssemblyGenerator;
+use crate::packager::Packager;
+use super::generic::{Platform, SyscallConvention};
+
+pub fn get_this() -> Platform<AArch64AssemblyGenerator> {
+    Platform {
+        assembly_generator: AArch64AssemblyGenerator::new(),
+        pointer_width: 8,
+        memory_size_minimum: 1024 * 1024 * 512,
+        memory_size_maximum: 1024 * 1024 * 1024 * 1024 * 256,
+        friendly_name: "Linux",
+        technical_name: "AArch64-based Linux",
+        technical_id: "linux_aarch64",
+        assembly_header: ".global _start\n",
+        assembly_entry: ".type _start, %function\n_start:\n",
+        assembly_exit: "\
+mov x0, #0
+mov x8, #93
+svc #0\n",
+        features: vec![],
+        packagers: vec![Packager::Elf],
+        syscall_convention: SyscallConvention::LINUX_AARCH64,
+    }
+}
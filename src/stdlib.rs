@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use crate::parser::beach::ast::user_token_format::{keywords::Keyword, Symbol, Syntax};
+
+// Generated by `build.rs`
+include!(concat!(env!("OUT_DIR"), "/stdlib.rs"));
+
+/// The folder the standard library built into beach appears to be in. Nothing
+/// is read from disk for paths inside it, so it only needs to be unlikely to
+/// be a real folder, and to read well in error messages.
+pub const EMBEDDED_FOLDER: &str = "<stdlib>";
+
+/// The contents of `path`, if it can be read. Paths inside [EMBEDDED_FOLDER]
+/// are read from the standard library built into beach, and everything else
+/// from disk.
+pub fn read_file(path: &Path) -> Option<String> {
+    match embedded_name(path) {
+        Some(name) => EMBEDDED_FILES.iter()
+            .find(|(file, _)| *file == name)
+            .map(|(_, source)| source.to_string()),
+        None => std::fs::read_to_string(path).ok()
+    }
+}
+
+/// If `path` is a file, either on disk or built into beach.
+pub fn is_file(path: &Path) -> bool {
+    match embedded_name(path) {
+        Some(name) => EMBEDDED_FILES.iter().any(|(file, _)| *file == name),
+        None => path.is_file()
+    }
+}
+
+/// The files directly inside `folder`, either on disk or built into beach, or
+/// [None] if it isn't a folder.
+pub fn files_in(folder: &Path) -> Option<Vec<PathBuf>> {
+    let Some(name) = embedded_name(folder)
+    else {
+        let entries = std::fs::read_dir(folder).ok()?;
+        return Some(entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.is_file())
+            .collect());
+    };
+    // Folders built into beach only exist if they have files in them.
+    let prefix = if name.is_empty() { name } else { format!("{name}/") };
+    let inside: Vec<&str> = EMBEDDED_FILES.iter()
+        .filter_map(|(file, _)| file.strip_prefix(prefix.as_str()))
+        .collect();
+    if inside.is_empty() {
+        return None;
+    }
+    Some(inside.into_iter()
+        .filter(|file| !file.contains('/'))
+        .map(|file| folder.join(file))
+        .collect())
+}
+
+/// The canonical form of `path`, if it's a file. Files built into beach are
+/// already canonical.
+pub fn canonicalize(path: &Path) -> Option<PathBuf> {
+    match embedded_name(path) {
+        Some(_) if is_file(path) => Some(path.to_path_buf()),
+        Some(_) => None,
+        None => path.canonicalize().ok()
+    }
+}
+
+/// The name of a file built into beach (`std/io.beach`) from its path inside
+/// [EMBEDDED_FOLDER], or [None] if it's elsewhere.
+fn embedded_name(path: &Path) -> Option<String> {
+    let name: Vec<String> = path.strip_prefix(EMBEDDED_FOLDER).ok()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some(name.join("/"))
+}
+
+/// Finds the file implementing `include <path>;` on the platform with the
+/// technical id `platform_id`. `std` is the standard library's `std` folder.
+///
+/// A platform's own implementation (`io/macos_aarch64.beach`) is preferred over
+/// a generic one (`io.beach`). When the path names an item inside a module
+/// (`io:stdout`) the file found must define that item. Returns [None] if the
+/// platform has no implementation.
+pub fn resolve_include(std: &Path, path: &[String], platform_id: &str) -> Option<PathBuf> {
+    // The whole path may be a module, (`include io;`)
+    let module: PathBuf = path.iter().collect();
+    for candidate in candidates(std, &module, platform_id) {
+        if is_file(&candidate) {
+            return Some(candidate);
+        }
+    }
+    // or an item inside one. (`include io:stdout;`)
+    let (item, module) = path.split_last()?;
+    if module.is_empty() {
+        return None;
+    }
+    let module: PathBuf = module.iter().collect();
+    for candidate in candidates(std, &module, platform_id) {
+        let implemented = read_file(&candidate)
+            .is_some_and(|source| crate::parser::parse_string_file(source).is_ok_and(|syntax| defines(&syntax, item)));
+        if implemented {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// The files a module might be implemented in, most specific first.
+fn candidates(std: &Path, module: &Path, platform_id: &str) -> [PathBuf; 2] {
+    [
+        std.join(module).join(format!("{platform_id}.beach")),
+        std.join(module).with_extension("beach"),
+    ]
+}
+
+/// If `syntax` declares something called `name`. (`var name = ...`,
+/// `type name { ... }`, etc.)
+fn defines(syntax: &Syntax, name: &str) -> bool {
+    syntax.symbols.windows(2).any(|pair| match pair {
+        [Symbol::Label(var), Symbol::Label(label)] => var == "var" && label == name,
+        [Symbol::Keyword(Keyword::Ktype | Keyword::Kenum | Keyword::Ktrait), Symbol::Label(label)] => label == name,
+        _ => false
+    })
+}
+
+#[test]
+fn test_resolve_include() {
+    let mut std = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    std.push("stdlib");
+    std.push("std");
+    let stdout = vec![String::from("io"), String::from("stdout")];
+    assert_eq!(
+        resolve_include(&std, &stdout, "macos_aarch64"),
+        Some(std.join("io").join("macos_aarch64.beach"))
+    );
+    // `io.beach` exists, but doesn't implement `stdout`.
+    assert_eq!(resolve_include(&std, &stdout, "windows_x86_64"), None);
+    // A whole module falls back to its generic file.
+    assert_eq!(
+        resolve_include(&std, &[String::from("process")], "windows_x86_64"),
+        Some(std.join("process.beach"))
+    );
+}
+
+#[test]
+fn test_embedded_files() {
+    let stdlib = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("stdlib");
+    for (name, source) in EMBEDDED_FILES {
+        let embedded = Path::new(EMBEDDED_FOLDER).join(name);
+        assert!(is_file(&embedded));
+        assert_eq!(canonicalize(&embedded), Some(embedded.clone()));
+        assert_eq!(read_file(&embedded).as_deref(), Some(*source));
+        assert_eq!(read_file(&stdlib.join(name)).as_deref(), Some(*source), "{name} changed since beach was built");
+    }
+    assert!(!is_file(&Path::new(EMBEDDED_FOLDER).join("std")));
+    // Folders list the same files inside beach as they do on disk.
+    for folder in ["", "std", "std/io"] {
+        let names = |folder: &Path| {
+            let mut names: Vec<String> = files_in(folder).unwrap().iter()
+                .filter(|file| file.extension().is_some_and(|extension| extension == "beach"))
+                .map(|file| file.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&Path::new(EMBEDDED_FOLDER).join(folder)), names(&stdlib.join(folder)), "{folder}");
+    }
+    assert_eq!(files_in(&Path::new(EMBEDDED_FOLDER).join("missing")), None);
+    assert_eq!(canonicalize(&Path::new(EMBEDDED_FOLDER).join("missing.beach")), None);
+    // Includes resolve the same way inside beach as they do on disk.
+    let std = Path::new(EMBEDDED_FOLDER).join("std");
+    let stdout = vec![String::from("io"), String::from("stdout")];
+    assert_eq!(
+        resolve_include(&std, &stdout, "linux_aarch64"),
+        Some(std.join("io").join("linux_aarch64.beach"))
+    );
+    assert_eq!(resolve_include(&std, &stdout, "windows_x86_64"), None);
+}
@@ -1,35 +1,156 @@
-use std::{io::Read, path::PathBuf, str::FromStr};
+use std::{io::Read, path::{Path, PathBuf}, str::FromStr};
 
 use crate::parser::beach::ast::user_token_format::RESERVED_LABEL_SYMBOLS;
 
-/// Returns a path to the folder in which beach is installed.
-pub fn install_directory() -> PathBuf {
-    // TODO: find and point to install path
+/// Where beach's install directory, or its standard library, was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallSource {
+    /// The `BEACH_HOME` environment variable.
+    Environment,
+    /// The `--stdlib` argument. This only moves the standard library.
+    Argument,
+    /// The folder this platform keeps application data in.
+    DataDirectory,
+    /// A folder relative to the running executable.
+    Executable,
+    /// The repository a debug build of beach was built from. Only used for
+    /// the standard library.
+    Repository,
+    /// Built into beach. Only used for the standard library.
+    Embedded,
+}
+
+impl std::fmt::Display for InstallSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallSource::Environment => write!(f, "from `BEACH_HOME`"),
+            InstallSource::Argument => write!(f, "from `--stdlib`"),
+            InstallSource::DataDirectory => write!(f, "in the data directory"),
+            InstallSource::Executable => write!(f, "beside the executable"),
+            InstallSource::Repository => write!(f, "in the repository beach was built from"),
+            InstallSource::Embedded => write!(f, "built into beach"),
+        }
+    }
+}
+
+/// Returns a path to the folder in which beach is installed, and where it was
+/// found. In order, this is `$BEACH_HOME`, the `beach` folder of the
+/// platform's data directory if it exists, or an install beside the running
+/// executable. Otherwise, it's the data directory's `beach` folder, which
+/// doesn't exist yet.
+pub fn install_directory() -> (PathBuf, InstallSource) {
+    if let Some(home) = std::env::var_os("BEACH_HOME").filter(|home| !home.is_empty()) {
+        return (PathBuf::from(home), InstallSource::Environment);
+    }
+    let data = data_directory();
+    if let Some(data) = data.as_ref().filter(|data| data.is_dir()) {
+        return (data.clone(), InstallSource::DataDirectory);
+    }
+    if let Some(install) = executable_directory() {
+        return (install, InstallSource::Executable);
+    }
+    match data {
+        Some(data) => return (data, InstallSource::DataDirectory),
+        // Without a data directory, the executable's folder is all that's
+        // left.
+        None => return (executable_folder(), InstallSource::Executable)
+    }
+}
+
+/// Returns a path to the folder holding the standard library (`core.beach`
+/// and `std/`), and where it was found. In order, this is the `stdlib` folder
+/// of `$BEACH_HOME`, `argument` (the folder given with `--stdlib`), the
+/// `stdlib` folder in the platform's data directory, the `stdlib` folder of an
+/// install beside the running executable, or, for debug builds, the `stdlib`
+/// folder of the repository beach was built from. Otherwise, the standard
+/// library built into beach is used, found in [crate::stdlib::EMBEDDED_FOLDER].
+pub fn stdlib_directory(argument: Option<&Path>) -> (PathBuf, InstallSource) {
+    let home = std::env::var_os("BEACH_HOME").filter(|home| !home.is_empty()).map(PathBuf::from);
+    return find_stdlib(home, argument, data_directory(), executable_directory());
+}
+
+/// Finds the standard library as described in [stdlib_directory], given
+/// `$BEACH_HOME`, `--stdlib`, the `beach` folder of the data directory and the
+/// install beside the executable, whichever of these there are.
+fn find_stdlib(
+    home: Option<PathBuf>,
+    argument: Option<&Path>,
+    data: Option<PathBuf>,
+    executable: Option<PathBuf>
+) -> (PathBuf, InstallSource) {
+    if let Some(home) = home {
+        return (home.join("stdlib"), InstallSource::Environment);
+    }
+    if let Some(argument) = argument {
+        return (argument.to_path_buf(), InstallSource::Argument);
+    }
+    let installed = [(data, InstallSource::DataDirectory), (executable, InstallSource::Executable)];
+    for (install, source) in installed {
+        if let Some(stdlib) = install.map(|install| install.join("stdlib"))
+            && stdlib.join("core.beach").is_file()
+        {
+            return (stdlib, source);
+        }
+    }
+    // Release builds can be moved anywhere, so only debug builds trust the
+    // path they were built at.
+    #[cfg(debug_assertions)]
+    {
+        let repository = Path::new(env!("CARGO_MANIFEST_DIR")).join("stdlib");
+        if repository.join("core.beach").is_file() {
+            return (repository, InstallSource::Repository);
+        }
+    }
+    return (PathBuf::from(crate::stdlib::EMBEDDED_FOLDER), InstallSource::Embedded);
+}
+
+/// The `beach` folder of the folder this platform keeps application data in,
+/// if that can be found.
+fn data_directory() -> Option<PathBuf> {
     #[cfg(target_os = "macos")]
     {
-        let mut path = PathBuf::from_str(
-            std::env::var_os("HOME")
-                .expect("$HOME not set on MacOS")
-                .to_str()
-                .expect("$HOME not valid UTF-8")
-            ).expect("Unable to construct $HOME path");
-        path.push("Library");
-        path.push("Application Support");
-        path.push("beach");
-        return path;
+        let home = PathBuf::from(std::env::var_os("HOME")?);
+        return Some(home.join("Library").join("Application Support").join("beach"));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        return Some(PathBuf::from(std::env::var_os("APPDATA")?).join("beach"));
     }
+    // Everything else is assumed to follow the XDG base directory spec, which
+    // ignores `$XDG_DATA_HOME` if it isn't absolute.
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|data| data.is_absolute())
+            .or(std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))?;
+        return Some(data.join("beach"));
+    }
+}
 
-    //#[cfg(target_os = "windows")]
-    // %appdata%/beach?
-    // not certain of the stability of %appdata%, or how it would be found
+/// The install beside the running executable, if there is one. Installs keep
+/// a `stdlib` folder either in the executable's folder, or in `share/beach`
+/// beside it, as with `/usr/local/bin/beach` and `/usr/local/share/beach`.
+fn executable_directory() -> Option<PathBuf> {
+    return install_beside(&executable_folder());
+}
 
-    //#[cfg(target_os = "linux")]
-    // $HOME/beach?
-    // this is not a specific os and does not always work
+/// The install for an executable in `folder`, as described in
+/// [executable_directory].
+fn install_beside(folder: &Path) -> Option<PathBuf> {
+    let shared = folder.parent().map(|prefix| prefix.join("share").join("beach"));
+    return std::iter::once(folder.to_path_buf())
+        .chain(shared)
+        .find(|install| install.join("stdlib").is_dir());
+}
 
-    // other arches
-    #[cfg(not(target_os = "macos"))]
-    todo!();
+/// The folder the running executable is in.
+fn executable_folder() -> PathBuf {
+    return std::env::current_exe()
+        .ok()
+        .and_then(|executable| executable.canonicalize().ok())
+        .and_then(|executable| executable.parent().map(Path::to_path_buf))
+        .unwrap_or(PathBuf::from("."));
 }
 
 #[test]
@@ -37,6 +158,40 @@ fn test_install_directory() {
     // verify getting the path is working
     let dir = install_directory();
     std::hint::black_box(dir);
+
+    // Installs are only found beside the executable or in `../share/beach`,
+    // never further up.
+    let prefix = std::env::temp_dir().join(format!("beach_test_install_directory_{}", std::process::id()));
+    let bin = prefix.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    std::fs::create_dir_all(prefix.join("stdlib")).unwrap();
+    assert_eq!(install_beside(&bin), None);
+    std::fs::create_dir_all(prefix.join("share").join("beach").join("stdlib")).unwrap();
+    assert_eq!(install_beside(&bin), Some(prefix.join("share").join("beach")));
+    std::fs::create_dir_all(bin.join("stdlib")).unwrap();
+    assert_eq!(install_beside(&bin), Some(bin.clone()));
+
+    // The standard library is looked for in `$BEACH_HOME`, then `--stdlib`,
+    // then the data directory, then beside the executable.
+    let home = prefix.join("home");
+    let data = prefix.join("data");
+    let elsewhere = Path::new("elsewhere");
+    let found = |home: bool, argument: bool| find_stdlib(
+        home.then(|| prefix.join("home")),
+        argument.then_some(elsewhere),
+        Some(data.clone()),
+        Some(bin.clone())
+    );
+    assert_eq!(found(true, true), (home.join("stdlib"), InstallSource::Environment));
+    assert_eq!(found(false, true), (elsewhere.to_path_buf(), InstallSource::Argument));
+    // Installs without a standard library in them are passed over.
+    assert!(!matches!(found(false, false).1, InstallSource::DataDirectory | InstallSource::Executable));
+    std::fs::write(bin.join("stdlib").join("core.beach"), "").unwrap();
+    assert_eq!(found(false, false), (bin.join("stdlib"), InstallSource::Executable));
+    std::fs::create_dir_all(data.join("stdlib")).unwrap();
+    std::fs::write(data.join("stdlib").join("core.beach"), "").unwrap();
+    assert_eq!(found(false, false), (data.join("stdlib"), InstallSource::DataDirectory));
+    std::fs::remove_dir_all(&prefix).unwrap();
 }
 
 /// Reads `num_bytes` from a [Read] source, returning the output in a new [Vec].
@@ -129,6 +284,8 @@ impl StringReader {
                     }
                 }
                 else {
+                    // end of buffer!
+                    self.location = usize::MAX;
                     break;
                 }
                 idx += 1;
@@ -173,6 +330,15 @@ impl StringReader {
         }
         output
     }
+    /// The byte offset of the next [char] to be read.
+    pub fn location(&self) -> usize {
+        self.location
+    }
+    pub fn skip_whitespace(&mut self) {
+        while self.peek_char().is_some_and(char::is_whitespace) {
+            self.read_char();
+        }
+    }
     pub fn next_non_whitespace_char(&mut self) -> Option<char> {
         loop {
             let this_char = self.read_char()?;
@@ -183,8 +349,13 @@ impl StringReader {
     }
 }
 
-/// TODO
-#[derive(Debug, PartialEq, Clone)]
+/// An integer of any size, used for integer literals and for computing values
+/// while compiling, such as those of `u512`s and `i512`s.
+///
+/// Numbers are stored as a sign and magnitude, but bitwise operations and
+/// shifts act as if negative numbers were in two's complement, with infinitely
+/// many sign bits.
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Bigint {
     /// true = number negative
     pub sign: bool,
@@ -194,11 +365,58 @@ pub struct Bigint {
 
 impl std::fmt::Display for Bigint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.sign {
-            write!(f, "-")?;
+        f.pad_integral(!self.sign, "", &magnitude_string(&self.bytes, 10))
+    }
+}
+
+// Unlike Rust's integers, negative numbers are printed with a `-` instead of
+// in two's complement, as there's no width to print them at.
+impl std::fmt::LowerHex for Bigint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad_integral(!self.sign, "0x", &magnitude_string(&self.bytes, 16))
+    }
+}
+
+impl std::fmt::UpperHex for Bigint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad_integral(!self.sign, "0x", &magnitude_string(&self.bytes, 16).to_uppercase())
+    }
+}
+
+impl std::fmt::Octal for Bigint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad_integral(!self.sign, "0o", &magnitude_string(&self.bytes, 8))
+    }
+}
+
+impl std::fmt::Binary for Bigint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad_integral(!self.sign, "0b", &magnitude_string(&self.bytes, 2))
+    }
+}
+
+impl FromStr for Bigint {
+    type Err = anyhow::Error;
+    /// Reads a decimal integer, or a hexadecimal (`0x`), octal (`0o`) or
+    /// binary (`0b`) one, with an optional `-` before it.
+    fn from_str(input: &str) -> Result<Bigint, anyhow::Error> {
+        let (sign, digits) = match input.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, input)
+        };
+        let (radix, digits) = match digits.get(..2) {
+            Some("0x") => (16, &digits[2..]),
+            Some("0o") => (8, &digits[2..]),
+            Some("0b") => (2, &digits[2..]),
+            _ => (10, digits)
+        };
+        // A second sign isn't allowed after the prefix.
+        if digits.starts_with('-') {
+            return Err(anyhow::Error::msg(format!("`{input}` isn't an integer.")));
         }
-        todo!();
-        write!(f, "")
+        let magnitude = Bigint::from_str_radix(digits, radix)
+            .map_err(|_| anyhow::Error::msg(format!("`{input}` isn't an integer.")))?;
+        return Ok(Bigint::from_parts(sign, magnitude.bytes));
     }
 }
 
@@ -210,39 +428,55 @@ impl Bigint {
         }
     }
     pub fn from_i8(input: i8) -> Bigint {
-        if input.is_negative() {
-            Bigint {
-                sign: true,
-                bytes: vec![(input * -1) as u8]
-            }
-        }
-        else {
-            Bigint {
-                sign: false,
-                bytes: vec![input as u8]
-            }
-        }
+        Bigint::from_i128(input as i128)
     }
     pub fn from_i64(input: i64) -> Bigint {
-        if input.is_negative() {
-            let mut out = Bigint {
-                sign: true,
-                bytes: (input * -1).to_le_bytes().to_vec()
-            };
-            out.trim_bytes();
-            return out;
+        Bigint::from_i128(input as i128)
+    }
+    pub fn from_i128(input: i128) -> Bigint {
+        Bigint::from_parts(input.is_negative(), input.unsigned_abs().to_le_bytes().to_vec())
+    }
+    /// Reads digits in base `radix`, from 2 to 36, with an optional `-`
+    /// before them. Digits past 9 are letters, in either case.
+    pub fn from_str_radix(input: &str, radix: u32) -> Result<Bigint, anyhow::Error> {
+        assert!((2..=36).contains(&radix), "Bigint radix must be from 2 to 36");
+        let (sign, digits) = match input.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, input)
+        };
+        if digits.is_empty() {
+            return Err(anyhow::Error::msg(format!("`{input}` has no digits.")));
         }
-        else {
-            let mut out = Bigint {
-                sign: false,
-                bytes: input.to_le_bytes().to_vec()
+        let mut bytes = vec![0];
+        for character in digits.chars() {
+            let Some(digit) = character.to_digit(radix) else {
+                return Err(anyhow::Error::msg(format!(
+                    "`{character}` isn't a base {radix} digit. (in `{input}`)"
+                )));
             };
-            out.trim_bytes();
-            return out;
+            // bytes = bytes * radix + digit
+            let mut carry = digit;
+            for byte in &mut bytes {
+                let value = *byte as u32 * radix + carry;
+                *byte = value as u8;
+                carry = value >> 8;
+            }
+            while carry != 0 {
+                bytes.push(carry as u8);
+                carry >>= 8;
+            }
         }
+        return Ok(Bigint::from_parts(sign, bytes));
     }
-    pub fn from_str(input: &str) -> Bigint {
-        todo!();
+    /// Writes this number in base `radix`, from 2 to 36, using lowercase
+    /// letters for digits past 9.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "Bigint radix must be from 2 to 36");
+        let digits = magnitude_string(&self.bytes, radix);
+        if self.sign {
+            return format!("-{digits}");
+        }
+        return digits;
     }
     pub fn to_u8(&self) -> Result<u8, anyhow::Error> {
         if self.bit_width() <= 8 && !self.sign {
@@ -260,12 +494,8 @@ impl Bigint {
         }
     }
     pub fn to_i8(&self) -> Result<i8, anyhow::Error> {
-        if self.bit_width() <= 7 {
-            let mut as_i8 = self.bytes[0] as i8;
-            if self.sign {
-                as_i8 *= -1;
-            }
-            return Ok(as_i8);
+        if self.fits(8, true) {
+            return Ok(self.wrapping_le_bytes(1)[0] as i8);
         }
         else {
             return Err(anyhow::Error::msg(
@@ -273,16 +503,93 @@ impl Bigint {
             ));
         }
     }
-    pub fn to_le_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
-        if !self.sign {
-            return Ok(self.bytes.clone());
+    pub fn to_i128(&self) -> Result<i128, anyhow::Error> {
+        if self.fits(128, true) {
+            let bytes = self.wrapping_le_bytes(16);
+            return Ok(i128::from_le_bytes(bytes.try_into().unwrap()));
+        }
+        else {
+            return Err(anyhow::Error::msg(
+                "Bigint too large to convert to i128"
+            ));
+        }
+    }
+    /// This number in `width` bytes, in LE order, as an integer type that
+    /// wide would store it. Negative numbers are stored in two's complement.
+    /// Fails if the number is too large for either a signed or unsigned
+    /// integer that wide.
+    pub fn to_le_bytes(&self, width: usize) -> Result<Vec<u8>, anyhow::Error> {
+        if !self.fits(width * 8, false) && !self.fits(width * 8, true) {
+            return Err(anyhow::Error::msg(format!(
+                "{self} is too large to store in {width} bytes"
+            )));
         }
-        todo!()
+        return Ok(self.wrapping_le_bytes(width));
+    }
+    /// Reads an unsigned number stored in LE order.
+    pub fn from_le_bytes(bytes: &[u8]) -> Bigint {
+        let mut out = Bigint {
+            sign: false,
+            bytes: bytes.to_vec()
+        };
+        if out.bytes.is_empty() {
+            out.bytes.push(0);
+        }
+        out.trim_bytes();
+        return out;
+    }
+    /// Reads a two's complement number stored in LE order, which is negative
+    /// if its highest bit is set.
+    pub fn from_signed_le_bytes(bytes: &[u8]) -> Bigint {
+        match bytes.last() {
+            Some(last) if last & 0x80 != 0 => {
+                // -x is !x + 1, so x is !(-x) + 1
+                let inverted: Vec<u8> = bytes.iter().map(|byte| !byte).collect();
+                Bigint::from_parts(true, add_magnitudes(&inverted, &[1]))
+            }
+            _ => Bigint::from_le_bytes(bytes)
+        }
+    }
+    /// The lowest `width` bytes of this number in two's complement, in LE
+    /// order, as a register `width` bytes wide would hold it.
+    pub fn wrapping_le_bytes(&self, width: usize) -> Vec<u8> {
+        let mut bytes = self.bytes.clone();
+        bytes.resize(width, 0x00);
+        if self.sign {
+            // -x is !x + 1
+            let mut carry = true;
+            for byte in &mut bytes {
+                let (sum, overflow) = (!*byte).overflowing_add(carry as u8);
+                *byte = sum;
+                carry = overflow;
+            }
+        }
+        return bytes;
     }
     fn trim_bytes(&mut self) {
-        while self.bytes[self.bytes.len() - 1] == 0x00 {
+        // zero keeps one byte so there's always a last byte to look at
+        while self.bytes.len() > 1 && self.bytes[self.bytes.len() - 1] == 0x00 {
             self.bytes.pop();
         }
+        if self.bytes.is_empty() {
+            self.bytes.push(0x00);
+        }
+        // there's no negative zero
+        if self.bytes == [0] {
+            self.sign = false;
+        }
+    }
+    /// Builds a number from its sign and LE magnitude.
+    fn from_parts(sign: bool, bytes: Vec<u8>) -> Bigint {
+        let mut out = Bigint { sign, bytes };
+        out.trim_bytes();
+        return out;
+    }
+    pub fn is_zero(&self) -> bool {
+        self.bytes == [0]
+    }
+    pub fn abs(&self) -> Bigint {
+        Bigint::from_parts(false, self.bytes.clone())
     }
     pub fn bit_width(&self) -> usize {
         // how many bits are used in the last byte of this number
@@ -292,27 +599,1030 @@ impl Bigint {
             // is the number negative
             (self.sign as usize)
     }
+    /// Whether this number can be stored in an integer type `bits` wide.
+    pub fn fits(&self, bits: usize, signed: bool) -> bool {
+        let magnitude_bits = self.bit_width() - (self.sign as usize);
+        if !signed {
+            return !self.sign && magnitude_bits <= bits;
+        }
+        if magnitude_bits < bits {
+            return true;
+        }
+        // The one value that needs every bit is the most negative one,
+        // -(2 ^ (bits - 1)), which has only its top magnitude bit set.
+        self.sign && magnitude_bits == bits &&
+            self.bytes.iter().map(|byte| byte.count_ones()).sum::<u32>() == 1
+    }
+    /// Divides this number by `other`, returning the quotient and remainder.
+    /// The quotient is rounded towards zero, so the remainder has the same
+    /// sign as this number, as with Rust's integers and most processors'
+    /// division instructions.
+    pub fn divmod(&self, other: &Bigint) -> Result<(Bigint, Bigint), anyhow::Error> {
+        if other.is_zero() {
+            return Err(anyhow::Error::msg(format!("Can't divide {self} by zero")));
+        }
+        let (quotient, remainder) = divide_magnitudes(&self.bytes, &other.bytes);
+        return Ok((
+            Bigint::from_parts(self.sign != other.sign, quotient),
+            Bigint::from_parts(self.sign, remainder)
+        ));
+    }
+    /// Raises this number to the power of `exponent`.
+    pub fn pow(&self, mut exponent: u32) -> Bigint {
+        // square and multiply
+        let mut result = Bigint::from_u8(1);
+        let mut base = self.clone();
+        while exponent != 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent != 0 {
+                base = &base * &base;
+            }
+        }
+        return result;
+    }
+    /// Shifts this number left by `amount` bits, filling the new bits with
+    /// ones instead of zeroes. (`<<+`)
+    pub fn filling_shl(&self, amount: usize) -> Bigint {
+        let ones = &(&Bigint::from_u8(1) << amount) - &Bigint::from_u8(1);
+        return &(self << amount) | &ones;
+    }
+    /// Applies `operation` to each byte of two numbers in two's complement.
+    fn bitwise(&self, other: &Bigint, operation: impl Fn(u8, u8) -> u8) -> Bigint {
+        // one extra byte always holds the sign bits
+        let width = self.bytes.len().max(other.bytes.len()) + 1;
+        let bytes: Vec<u8> = self.wrapping_le_bytes(width).into_iter()
+            .zip(other.wrapping_le_bytes(width))
+            .map(|(a, b)| operation(a, b))
+            .collect();
+        return Bigint::from_signed_le_bytes(&bytes);
+    }
 }
 
+impl Ord for Bigint {
+    fn cmp(&self, other: &Bigint) -> std::cmp::Ordering {
+        match (self.sign, other.sign) {
+            (false, true) => std::cmp::Ordering::Greater,
+            (true, false) => std::cmp::Ordering::Less,
+            (false, false) => compare_magnitudes(&self.bytes, &other.bytes),
+            // larger magnitudes are more negative
+            (true, true) => compare_magnitudes(&other.bytes, &self.bytes),
+        }
+    }
+}
+
+impl PartialOrd for Bigint {
+    fn partial_cmp(&self, other: &Bigint) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::ops::Neg for &Bigint {
+    type Output = Bigint;
+    fn neg(self) -> Bigint {
+        Bigint::from_parts(!self.sign, self.bytes.clone())
+    }
+}
+
+impl std::ops::Add for &Bigint {
+    type Output = Bigint;
+    fn add(self, other: &Bigint) -> Bigint {
+        if self.sign == other.sign {
+            return Bigint::from_parts(self.sign, add_magnitudes(&self.bytes, &other.bytes));
+        }
+        // Opposite signs subtract the smaller magnitude from the larger, which
+        // keeps its sign.
+        match compare_magnitudes(&self.bytes, &other.bytes) {
+            std::cmp::Ordering::Less => Bigint::from_parts(other.sign, subtract_magnitudes(&other.bytes, &self.bytes)),
+            _ => Bigint::from_parts(self.sign, subtract_magnitudes(&self.bytes, &other.bytes))
+        }
+    }
+}
+
+impl std::ops::Sub for &Bigint {
+    type Output = Bigint;
+    fn sub(self, other: &Bigint) -> Bigint {
+        self + &-other
+    }
+}
+
+impl std::ops::Mul for &Bigint {
+    type Output = Bigint;
+    fn mul(self, other: &Bigint) -> Bigint {
+        let mut product = vec![0u8; self.bytes.len() + other.bytes.len()];
+        for (i, a) in self.bytes.iter().enumerate() {
+            let mut carry = 0u16;
+            for (j, b) in other.bytes.iter().enumerate() {
+                let value = product[i + j] as u16 + *a as u16 * *b as u16 + carry;
+                product[i + j] = value as u8;
+                carry = value >> 8;
+            }
+            product[i + other.bytes.len()] = carry as u8;
+        }
+        Bigint::from_parts(self.sign != other.sign, product)
+    }
+}
+
+impl std::ops::Div for &Bigint {
+    type Output = Bigint;
+    /// Panics if `other` is zero. See [Bigint::divmod].
+    fn div(self, other: &Bigint) -> Bigint {
+        self.divmod(other).expect("attempt to divide by zero").0
+    }
+}
+
+impl std::ops::Rem for &Bigint {
+    type Output = Bigint;
+    /// Panics if `other` is zero. See [Bigint::divmod].
+    fn rem(self, other: &Bigint) -> Bigint {
+        self.divmod(other).expect("attempt to calculate the remainder with a divisor of zero").1
+    }
+}
+
+impl std::ops::Shl<usize> for &Bigint {
+    type Output = Bigint;
+    /// Multiplies by `2 ^ amount`, filling the new bits with zeroes. (`<<`)
+    fn shl(self, amount: usize) -> Bigint {
+        let mut bytes = vec![0x00; amount / 8];
+        let mut carry = 0u8;
+        for byte in &self.bytes {
+            let wide = (*byte as u16) << (amount % 8);
+            bytes.push(wide as u8 | carry);
+            carry = (wide >> 8) as u8;
+        }
+        bytes.push(carry);
+        Bigint::from_parts(self.sign, bytes)
+    }
+}
+
+impl std::ops::Shr<usize> for &Bigint {
+    type Output = Bigint;
+    /// Divides by `2 ^ amount`, rounding down, as shifting two's complement
+    /// numbers does. (`>>`)
+    fn shr(self, amount: usize) -> Bigint {
+        let skipped = (amount / 8).min(self.bytes.len());
+        let mut bytes = vec![];
+        for (index, byte) in self.bytes[skipped..].iter().enumerate() {
+            let next = *self.bytes.get(skipped + index + 1).unwrap_or(&0);
+            let wide = (*byte as u16 | (next as u16) << 8) >> (amount % 8);
+            bytes.push(wide as u8);
+        }
+        let shifted = Bigint::from_parts(self.sign, bytes);
+        // Negative numbers that lost any set bits round towards negative
+        // infinity, rather than zero.
+        let lost = self.bytes[..skipped].iter().any(|byte| *byte != 0) ||
+            self.bytes.get(skipped).is_some_and(|byte| byte & ((1u16 << (amount % 8)) - 1) as u8 != 0);
+        if self.sign && lost {
+            return &shifted - &Bigint::from_u8(1);
+        }
+        return shifted;
+    }
+}
+
+impl std::ops::Not for &Bigint {
+    type Output = Bigint;
+    /// Inverts every bit, which is `-x - 1` in two's complement.
+    fn not(self) -> Bigint {
+        &-self - &Bigint::from_u8(1)
+    }
+}
+
+impl std::ops::BitAnd for &Bigint {
+    type Output = Bigint;
+    fn bitand(self, other: &Bigint) -> Bigint {
+        self.bitwise(other, |a, b| a & b)
+    }
+}
+
+impl std::ops::BitOr for &Bigint {
+    type Output = Bigint;
+    fn bitor(self, other: &Bigint) -> Bigint {
+        self.bitwise(other, |a, b| a | b)
+    }
+}
+
+impl std::ops::BitXor for &Bigint {
+    type Output = Bigint;
+    fn bitxor(self, other: &Bigint) -> Bigint {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+}
+
+/// Adds two LE magnitudes.
+fn add_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut sum = vec![];
+    let mut carry = 0u16;
+    for index in 0..a.len().max(b.len()) {
+        let value = *a.get(index).unwrap_or(&0) as u16 + *b.get(index).unwrap_or(&0) as u16 + carry;
+        sum.push(value as u8);
+        carry = value >> 8;
+    }
+    sum.push(carry as u8);
+    sum
+}
+
+/// Subtracts the LE magnitude `b` from `a`, which must be at least as large.
+fn subtract_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut difference = vec![];
+    let mut borrow = 0i16;
+    for (index, byte) in a.iter().enumerate() {
+        let mut value = *byte as i16 - *b.get(index).unwrap_or(&0) as i16 - borrow;
+        borrow = (value < 0) as i16;
+        if value < 0 {
+            value += 256;
+        }
+        difference.push(value as u8);
+    }
+    difference
+}
+
+/// Compares two LE magnitudes, which may have leading zeroes.
+fn compare_magnitudes(a: &[u8], b: &[u8]) -> std::cmp::Ordering {
+    for index in (0..a.len().max(b.len())).rev() {
+        let ordering = a.get(index).unwrap_or(&0).cmp(b.get(index).unwrap_or(&0));
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+/// Divides the LE magnitude `a` by `b`, which isn't zero, returning the
+/// quotient and remainder.
+fn divide_magnitudes(a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    // Long division, one bit at a time from the top.
+    let mut quotient = vec![0x00; a.len()];
+    let mut remainder: Vec<u8> = vec![0x00; b.len() + 1];
+    for bit in (0..a.len() * 8).rev() {
+        // remainder = remainder << 1 | the next bit
+        let mut carry = (a[bit / 8] >> (bit % 8)) & 1;
+        for byte in &mut remainder {
+            let next_carry = *byte >> 7;
+            *byte = *byte << 1 | carry;
+            carry = next_carry;
+        }
+        if compare_magnitudes(&remainder, b).is_ge() {
+            remainder = subtract_magnitudes(&remainder, b);
+            quotient[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    (quotient, remainder)
+}
+
+/// Writes an LE magnitude in base `radix`.
+fn magnitude_string(bytes: &[u8], radix: u32) -> String {
+    let mut remaining = bytes.to_vec();
+    let mut digits = vec![];
+    loop {
+        // remaining = remaining / radix, keeping the remainder as a digit
+        let mut remainder = 0u32;
+        for byte in remaining.iter_mut().rev() {
+            let value = remainder << 8 | *byte as u32;
+            *byte = (value / radix) as u8;
+            remainder = value % radix;
+        }
+        digits.push(std::char::from_digit(remainder, radix).unwrap());
+        while remaining.last() == Some(&0) {
+            remaining.pop();
+        }
+        if remaining.is_empty() {
+            return digits.into_iter().rev().collect();
+        }
+    }
+}
+
+/// Returns how many bits of `input` are in use, counting up to its highest set
+/// bit.
 fn highest_bit(input: u8) -> usize {
-    let mut highest = 0;
-    for i in 0..8 {
-        if input << i & 0b00000001 == 0b00000001 {
-            highest = i;
+    (8 - input.leading_zeros()) as usize
+}
+
+/// The IEEE 754 binary interchange formats, used by `f16` to `f128`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatFormat {
+    Binary16,
+    Binary32,
+    Binary64,
+    Binary128,
+}
+
+impl FloatFormat {
+    /// The format `bits` wide, if there is one.
+    pub fn from_width(bits: usize) -> Option<FloatFormat> {
+        match bits {
+            16 => Some(Self::Binary16),
+            32 => Some(Self::Binary32),
+            64 => Some(Self::Binary64),
+            128 => Some(Self::Binary128),
+            _ => None
+        }
+    }
+    /// The width of this format, in bytes.
+    pub fn width(&self) -> usize {
+        (1 + self.exponent_bits() + self.fraction_bits()) / 8
+    }
+    fn exponent_bits(&self) -> usize {
+        match self {
+            Self::Binary16 => 5,
+            Self::Binary32 => 8,
+            Self::Binary64 => 11,
+            Self::Binary128 => 15,
+        }
+    }
+    /// The bits stored after the exponent, not counting the implicit leading
+    /// one of normal numbers.
+    fn fraction_bits(&self) -> usize {
+        match self {
+            Self::Binary16 => 10,
+            Self::Binary32 => 23,
+            Self::Binary64 => 52,
+            Self::Binary128 => 112,
         }
     }
-    return highest;
+    /// The exponent of the largest finite numbers.
+    fn max_exponent(&self) -> isize {
+        (1 << (self.exponent_bits() - 1)) - 1
+    }
 }
 
-/// TODO
-#[derive(Debug, PartialEq, Clone)]
+/// A floating point number with unlimited precision and range, used for
+/// decimal literals and computing them while compiling.
+///
+/// Finite numbers are stored exactly, as fractions, so they're only rounded
+/// when converted to a [FloatFormat]. Like IEEE floats, zero and infinity have
+/// signs, and there's a NaN.
+#[derive(Debug, Clone)]
 pub struct Bigfloat {
+    /// Kept apart from the value, so that zero and infinity can be negative.
+    negative: bool,
+    value: FloatValue,
+}
+
+#[derive(Debug, Clone)]
+enum FloatValue {
+    /// `numerator / denominator`, in lowest terms. Both are positive, except
+    /// that the numerator is zero for zero.
+    Finite { numerator: Bigint, denominator: Bigint },
+    Infinite,
+    NaN,
+}
+
+/// Literals with exponents further from zero than this are refused, as they're
+/// far outside the range of every [FloatFormat].
+const MAX_LITERAL_EXPONENT: u32 = 10_000;
+
+impl Bigfloat {
+    pub fn nan() -> Bigfloat {
+        Bigfloat { negative: false, value: FloatValue::NaN }
+    }
+    pub fn infinity(negative: bool) -> Bigfloat {
+        Bigfloat { negative, value: FloatValue::Infinite }
+    }
+    pub fn from_bigint(integer: &Bigint) -> Bigfloat {
+        Bigfloat::fraction(integer, &Bigint::from_u8(1))
+    }
+    /// `numerator / denominator`, which is infinite or NaN if `denominator`
+    /// is zero.
+    pub fn fraction(numerator: &Bigint, denominator: &Bigint) -> Bigfloat {
+        let negative = numerator.sign != denominator.sign;
+        if denominator.is_zero() {
+            if numerator.is_zero() {
+                return Bigfloat::nan();
+            }
+            return Bigfloat::infinity(negative);
+        }
+        let (mut numerator, mut denominator) = (numerator.abs(), denominator.abs());
+        let divisor = gcd(&numerator, &denominator);
+        if divisor != Bigint::from_u8(1) {
+            numerator = &numerator / &divisor;
+            denominator = &denominator / &divisor;
+        }
+        return Bigfloat { negative, value: FloatValue::Finite { numerator, denominator } };
+    }
+    pub fn is_nan(&self) -> bool {
+        matches!(self.value, FloatValue::NaN)
+    }
+    pub fn is_infinite(&self) -> bool {
+        matches!(self.value, FloatValue::Infinite)
+    }
+    pub fn is_zero(&self) -> bool {
+        matches!(&self.value, FloatValue::Finite { numerator, .. } if numerator.is_zero())
+    }
+    /// Whether the sign is negative, which includes `-0` and `-∞`.
+    pub fn is_sign_negative(&self) -> bool {
+        self.negative
+    }
+    /// The numerator with this number's sign, and the denominator.
+    fn signed_parts(&self) -> Option<(Bigint, &Bigint)> {
+        match &self.value {
+            FloatValue::Finite { numerator, denominator } if self.negative => Some((-numerator, denominator)),
+            FloatValue::Finite { numerator, denominator } => Some((numerator.clone(), denominator)),
+            _ => None
+        }
+    }
+    /// Rounds this number to the nearest value `format` can hold, ties going
+    /// to the value with an even last bit, and returns its bits in LE order.
+    /// NaN becomes a quiet NaN.
+    pub fn to_ieee_bytes(&self, format: FloatFormat) -> Vec<u8> {
+        let (exponent_bits, fraction_bits) = (format.exponent_bits(), format.fraction_bits());
+        let all_ones = (1 << exponent_bits) - 1;
+        let (biased, fraction) = match &self.value {
+            FloatValue::NaN => (all_ones, &Bigint::from_u8(1) << (fraction_bits - 1)),
+            FloatValue::Infinite => (all_ones, Bigint::from_u8(0)),
+            FloatValue::Finite { numerator, .. } if numerator.is_zero() => (0, Bigint::from_u8(0)),
+            FloatValue::Finite { numerator, denominator } => {
+                round_fraction(numerator, denominator, format).unwrap_or((all_ones, Bigint::from_u8(0)))
+            }
+        };
+        let bits = &(&Bigint::from_u8(self.negative as u8) << (exponent_bits + fraction_bits))
+            | &(&(&Bigint::from_i64(biased as i64) << fraction_bits) | &fraction);
+        return bits.wrapping_le_bytes(format.width());
+    }
+    /// Reads the bits of a number in `format`, in LE order. Every value is
+    /// kept exactly, but NaN's payload is lost.
+    pub fn from_ieee_bytes(bytes: &[u8], format: FloatFormat) -> Bigfloat {
+        let (exponent_bits, fraction_bits) = (format.exponent_bits(), format.fraction_bits());
+        let bits = Bigint::from_le_bytes(bytes);
+        let negative = !(&bits >> (exponent_bits + fraction_bits)).is_zero();
+        let mask = |width: usize| &(&Bigint::from_u8(1) << width) - &Bigint::from_u8(1);
+        let fraction = &bits & &mask(fraction_bits);
+        let biased = (&(&bits >> fraction_bits) & &mask(exponent_bits)).to_i128().unwrap() as isize;
+        if biased == (1 << exponent_bits) - 1 {
+            if fraction.is_zero() {
+                return Bigfloat::infinity(negative);
+            }
+            return Bigfloat::nan();
+        }
+        // Subnormal numbers have no implicit leading one, and the exponent
+        // of the smallest normal numbers.
+        let (significand, exponent) = match biased {
+            0 => (fraction, 1 - format.max_exponent()),
+            _ => (&fraction | &(&Bigint::from_u8(1) << fraction_bits), biased - format.max_exponent())
+        };
+        let shift = exponent - fraction_bits as isize;
+        let one = Bigint::from_u8(1);
+        let mut value = match shift {
+            0.. => Bigfloat::from_bigint(&(&significand << shift as usize)),
+            _ => Bigfloat::fraction(&significand, &(&one << (-shift) as usize))
+        };
+        value.negative = negative;
+        return value;
+    }
+}
+
+/// Rounds the positive fraction `numerator / denominator` for `format`,
+/// returning its biased exponent and stored fraction bits, or [None] if it's
+/// too large and becomes infinity.
+fn round_fraction(numerator: &Bigint, denominator: &Bigint, format: FloatFormat) -> Option<(isize, Bigint)> {
+    let precision = format.fraction_bits() + 1;
+    let min_exponent = 1 - format.max_exponent();
+    // exponent = floor(log2(numerator / denominator))
+    let mut exponent = numerator.bit_width() as isize - denominator.bit_width() as isize;
+    let scaled = |exponent: isize| match exponent {
+        0.. => (numerator.clone(), denominator << exponent as usize),
+        _ => (numerator << (-exponent) as usize, denominator.clone())
+    };
+    let (scaled_numerator, scaled_denominator) = scaled(exponent);
+    if scaled_numerator < scaled_denominator {
+        exponent -= 1;
+    }
+    // Numbers too small to be normal are stored with the smallest exponent,
+    // losing precision.
+    let exponent = exponent.max(min_exponent);
+    // significand = numerator / denominator * 2 ^ (precision - 1 - exponent)
+    let (numerator, denominator) = scaled(exponent - (precision as isize - 1));
+    let (mut significand, remainder) = numerator.divmod(&denominator).unwrap();
+    let one = Bigint::from_u8(1);
+    let halfway = (&remainder << 1).cmp(&denominator);
+    let odd = !(&significand & &one).is_zero();
+    if halfway.is_gt() || (halfway.is_eq() && odd) {
+        significand = &significand + &one;
+    }
+    let mut exponent = exponent;
+    // Rounding up can carry into another bit.
+    if significand.bit_width() > precision {
+        significand = &significand >> 1;
+        exponent += 1;
+    }
+    if exponent > format.max_exponent() {
+        return None;
+    }
+    let implicit = &one << (precision - 1);
+    if significand < implicit {
+        // subnormal
+        return Some((0, significand));
+    }
+    return Some((exponent + format.max_exponent(), &significand - &implicit));
+}
+
+/// The greatest common divisor of two positive numbers.
+fn gcd(a: &Bigint, b: &Bigint) -> Bigint {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_zero() {
+        let remainder = &a % &b;
+        a = b;
+        b = remainder;
+    }
+    return a;
+}
+
+impl FromStr for Bigfloat {
+    type Err = anyhow::Error;
+    /// Reads a decimal number such as `-58.3`, `6` or `1.5e-3`, or `NaN`, `∞`
+    /// or `-∞`.
+    fn from_str(input: &str) -> Result<Bigfloat, anyhow::Error> {
+        let invalid = || anyhow::Error::msg(format!("`{input}` isn't a decimal number."));
+        let (negative, text) = match input.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, input)
+        };
+        match text {
+            "NaN" if !negative => return Ok(Bigfloat::nan()),
+            "∞" => return Ok(Bigfloat::infinity(negative)),
+            _ => {}
+        }
+        let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => {
+                let exponent = exponent.strip_prefix('+').unwrap_or(exponent);
+                (mantissa, exponent.parse::<i64>().map_err(|_| invalid())?)
+            }
+            None => (text, 0)
+        };
+        let (whole, fractional) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{whole}{fractional}");
+        if whole.is_empty() || !digits.chars().all(|character| character.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        // 12.34e1 is 1234 * 10 ^ (1 - 2)
+        let exponent = exponent - fractional.len() as i64;
+        if exponent.unsigned_abs() > MAX_LITERAL_EXPONENT as u64 {
+            return Err(anyhow::Error::msg(format!("`{input}`'s exponent is too large.")));
+        }
+        let digits = Bigint::from_str_radix(&digits, 10)?;
+        let scale = Bigint::from_u8(10).pow(exponent.unsigned_abs() as u32);
+        let mut value = match exponent {
+            0.. => Bigfloat::from_bigint(&(&digits * &scale)),
+            _ => Bigfloat::fraction(&digits, &scale)
+        };
+        value.negative = negative;
+        return Ok(value);
+    }
+}
+
+impl std::fmt::Display for Bigfloat {
+    /// Writes the number in decimal, with up to the formatter's precision
+    /// (or 20) digits after the point, leaving off trailing zeroes when no
+    /// precision is given.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.negative { "-" } else { "" };
+        let (numerator, denominator) = match &self.value {
+            FloatValue::NaN => return write!(f, "NaN"),
+            FloatValue::Infinite => return write!(f, "{sign}∞"),
+            FloatValue::Finite { numerator, denominator } => (numerator, denominator)
+        };
+        let (whole, mut remainder) = numerator.divmod(denominator).unwrap();
+        let mut digits = String::new();
+        let ten = Bigint::from_u8(10);
+        for _ in 0..f.precision().unwrap_or(20) {
+            let (digit, rest) = (&remainder * &ten).divmod(denominator).unwrap();
+            digits += &digit.to_string();
+            remainder = rest;
+        }
+        if f.precision().is_none() {
+            digits = digits.trim_end_matches('0').to_string();
+        }
+        if digits.is_empty() {
+            return write!(f, "{sign}{whole}");
+        }
+        write!(f, "{sign}{whole}.{digits}")
+    }
+}
+
+impl PartialEq for Bigfloat {
+    /// Compares as IEEE floats do, so NaN isn't equal to anything and `-0`
+    /// equals `0`.
+    fn eq(&self, other: &Bigfloat) -> bool {
+        self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Bigfloat {
+    fn partial_cmp(&self, other: &Bigfloat) -> Option<std::cmp::Ordering> {
+        use std::cmp::Ordering;
+        match (&self.value, &other.value) {
+            (FloatValue::NaN, _) | (_, FloatValue::NaN) => None,
+            (FloatValue::Infinite, FloatValue::Infinite) => Some(other.negative.cmp(&self.negative)),
+            (FloatValue::Infinite, _) => Some(if self.negative { Ordering::Less } else { Ordering::Greater }),
+            (_, FloatValue::Infinite) => Some(if other.negative { Ordering::Greater } else { Ordering::Less }),
+            _ => {
+                let ((a, b), (c, d)) = (self.signed_parts().unwrap(), other.signed_parts().unwrap());
+                // a/b and c/d compare as a*d and c*b, as b and d are positive.
+                Some((&a * d).cmp(&(&c * b)))
+            }
+        }
+    }
+}
+
+impl std::ops::Neg for &Bigfloat {
+    type Output = Bigfloat;
+    fn neg(self) -> Bigfloat {
+        let mut negated = self.clone();
+        negated.negative = !self.negative && !self.is_nan();
+        negated
+    }
+}
 
+impl std::ops::Add for &Bigfloat {
+    type Output = Bigfloat;
+    fn add(self, other: &Bigfloat) -> Bigfloat {
+        match (&self.value, &other.value) {
+            (FloatValue::NaN, _) | (_, FloatValue::NaN) => Bigfloat::nan(),
+            (FloatValue::Infinite, FloatValue::Infinite) if self.negative != other.negative => Bigfloat::nan(),
+            (FloatValue::Infinite, _) => self.clone(),
+            (_, FloatValue::Infinite) => other.clone(),
+            _ => {
+                let ((a, b), (c, d)) = (self.signed_parts().unwrap(), other.signed_parts().unwrap());
+                let mut sum = Bigfloat::fraction(&(&(&a * d) + &(&c * b)), &(b * d));
+                // Only two negative zeroes add to a negative zero.
+                sum.negative = match sum.is_zero() {
+                    true => self.negative && other.negative,
+                    false => sum.negative
+                };
+                sum
+            }
+        }
+    }
 }
 
-/// TODO
+impl std::ops::Sub for &Bigfloat {
+    type Output = Bigfloat;
+    fn sub(self, other: &Bigfloat) -> Bigfloat {
+        self + &-other
+    }
+}
+
+impl std::ops::Mul for &Bigfloat {
+    type Output = Bigfloat;
+    fn mul(self, other: &Bigfloat) -> Bigfloat {
+        let negative = self.negative != other.negative;
+        match (&self.value, &other.value) {
+            (FloatValue::NaN, _) | (_, FloatValue::NaN) => Bigfloat::nan(),
+            // ∞ * 0 has no sensible value
+            (FloatValue::Infinite, _) | (_, FloatValue::Infinite) if self.is_zero() || other.is_zero() => Bigfloat::nan(),
+            (FloatValue::Infinite, _) | (_, FloatValue::Infinite) => Bigfloat::infinity(negative),
+            (
+                FloatValue::Finite { numerator: a, denominator: b },
+                FloatValue::Finite { numerator: c, denominator: d }
+            ) => {
+                let mut product = Bigfloat::fraction(&(a * c), &(b * d));
+                product.negative = negative;
+                product
+            }
+        }
+    }
+}
+
+impl std::ops::Div for &Bigfloat {
+    type Output = Bigfloat;
+    /// Dividing by zero gives infinity, or NaN for `0 / 0`, as with IEEE
+    /// floats.
+    fn div(self, other: &Bigfloat) -> Bigfloat {
+        let negative = self.negative != other.negative;
+        match (&self.value, &other.value) {
+            (FloatValue::NaN, _) | (_, FloatValue::NaN) => Bigfloat::nan(),
+            (FloatValue::Infinite, FloatValue::Infinite) => Bigfloat::nan(),
+            (FloatValue::Infinite, _) => Bigfloat::infinity(negative),
+            (_, FloatValue::Infinite) => {
+                let mut zero = Bigfloat::from_bigint(&Bigint::from_u8(0));
+                zero.negative = negative;
+                zero
+            }
+            (
+                FloatValue::Finite { numerator: a, denominator: b },
+                FloatValue::Finite { numerator: c, denominator: d }
+            ) => {
+                let mut quotient = Bigfloat::fraction(&(a * d), &(b * c));
+                if !quotient.is_nan() {
+                    quotient.negative = negative;
+                }
+                quotient
+            }
+        }
+    }
+}
+
+/// A complex number made of two [Bigfloat]s, used by `c32` to `c256`.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Bigcplx {
     pub real: Bigfloat,
     pub immaginary: Bigfloat,
 }
+
+impl Bigcplx {
+    pub fn from_real(real: Bigfloat) -> Bigcplx {
+        Bigcplx { real, immaginary: Bigfloat::from_bigint(&Bigint::from_u8(0)) }
+    }
+    /// The bits of the real part then the imaginary part, each in `format`,
+    /// which is half the width of the complex type.
+    pub fn to_ieee_bytes(&self, format: FloatFormat) -> Vec<u8> {
+        let mut bytes = self.real.to_ieee_bytes(format);
+        bytes.append(&mut self.immaginary.to_ieee_bytes(format));
+        return bytes;
+    }
+}
+
+impl FromStr for Bigcplx {
+    type Err = anyhow::Error;
+    /// Reads a complex number such as `23.5+14.0i`, `-2i` or `6`.
+    fn from_str(input: &str) -> Result<Bigcplx, anyhow::Error> {
+        let Some(without_i) = input.strip_suffix('i') else {
+            return Ok(Bigcplx::from_real(input.parse()?));
+        };
+        // The imaginary part starts at the last sign that isn't the first
+        // character or part of an exponent.
+        let split = without_i.char_indices()
+            .filter(|(index, character)| {
+                *index > 0 && matches!(character, '+' | '-') && !without_i[..*index].ends_with(['e', 'E'])
+            })
+            .map(|(index, _)| index)
+            .next_back();
+        let (real, immaginary) = match split {
+            Some(index) => (without_i[..index].parse()?, without_i[index..].trim_start_matches('+')),
+            None => (Bigfloat::from_bigint(&Bigint::from_u8(0)), without_i)
+        };
+        let immaginary = immaginary.parse()
+            .map_err(|_| anyhow::Error::msg(format!("`{input}` isn't a complex number.")))?;
+        return Ok(Bigcplx { real, immaginary });
+    }
+}
+
+impl std::fmt::Display for Bigcplx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.immaginary.is_sign_negative() {
+            return write!(f, "{} - {}i", self.real, -&self.immaginary);
+        }
+        write!(f, "{} + {}i", self.real, self.immaginary)
+    }
+}
+
+impl std::ops::Neg for &Bigcplx {
+    type Output = Bigcplx;
+    fn neg(self) -> Bigcplx {
+        Bigcplx { real: -&self.real, immaginary: -&self.immaginary }
+    }
+}
+
+impl std::ops::Add for &Bigcplx {
+    type Output = Bigcplx;
+    fn add(self, other: &Bigcplx) -> Bigcplx {
+        Bigcplx { real: &self.real + &other.real, immaginary: &self.immaginary + &other.immaginary }
+    }
+}
+
+impl std::ops::Sub for &Bigcplx {
+    type Output = Bigcplx;
+    fn sub(self, other: &Bigcplx) -> Bigcplx {
+        Bigcplx { real: &self.real - &other.real, immaginary: &self.immaginary - &other.immaginary }
+    }
+}
+
+impl std::ops::Mul for &Bigcplx {
+    type Output = Bigcplx;
+    /// (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+    fn mul(self, other: &Bigcplx) -> Bigcplx {
+        let (a, b, c, d) = (&self.real, &self.immaginary, &other.real, &other.immaginary);
+        Bigcplx {
+            real: &(a * c) - &(b * d),
+            immaginary: &(a * d) + &(b * c)
+        }
+    }
+}
+
+impl std::ops::Div for &Bigcplx {
+    type Output = Bigcplx;
+    /// (a + bi) / (c + di) = ((ac + bd) + (bc - ad)i) / (c² + d²)
+    fn div(self, other: &Bigcplx) -> Bigcplx {
+        let (a, b, c, d) = (&self.real, &self.immaginary, &other.real, &other.immaginary);
+        let divisor = &(c * c) + &(d * d);
+        Bigcplx {
+            real: &(&(a * c) + &(b * d)) / &divisor,
+            immaginary: &(&(b * c) - &(a * d)) / &divisor
+        }
+    }
+}
+
+#[test]
+fn test_bigint_arithmetic() {
+    let (a, b) = (Bigint::from_i64(300), Bigint::from_i64(-45));
+    assert_eq!(&a + &b, Bigint::from_i64(255));
+    assert_eq!(&b - &a, Bigint::from_i64(-345));
+    assert_eq!(&a * &b, Bigint::from_i64(-13500));
+    // Zero has one sign.
+    assert_eq!(&a - &a, Bigint::from_i64(0));
+    assert_eq!(Bigint::from_le_bytes(&[0x2c, 0x01, 0x00]), a);
+    // Registers wrap around.
+    assert_eq!(b.wrapping_le_bytes(2), (-45i16).to_le_bytes());
+    assert_eq!((&a * &a).wrapping_le_bytes(1), [(300u32 * 300) as u8]);
+
+    // Every operation matches Rust's integers, for values around the edges of
+    // bytes and signs.
+    let mut values: Vec<i128> = vec![0, 1, -1, 2, -2, 7, -7, 127, 128, -128, -129, 255, 256, -256, 65535, -65536];
+    values.extend([i64::MAX as i128, i64::MIN as i128, 0x1234_5678_9abc_def0, -0x0fed_cba9_8765_4321]);
+    let big = Bigint::from_i128;
+    for x in &values {
+        for y in &values {
+            let (a, b) = (big(*x), big(*y));
+            assert_eq!(&a + &b, big(x + y), "{x} + {y}");
+            assert_eq!(&a - &b, big(x - y), "{x} - {y}");
+            assert_eq!(&a * &b, big(x * y), "{x} * {y}");
+            if *y == 0 {
+                assert!(a.divmod(&b).is_err());
+            }
+            else {
+                assert_eq!(a.divmod(&b).unwrap(), (big(x / y), big(x % y)), "{x} divmod {y}");
+            }
+            assert_eq!(&a & &b, big(x & y), "{x} & {y}");
+            assert_eq!(&a | &b, big(x | y), "{x} | {y}");
+            assert_eq!(&a ^ &b, big(x ^ y), "{x} ^ {y}");
+            assert_eq!(a.cmp(&b), x.cmp(y), "{x} cmp {y}");
+        }
+        let a = big(*x);
+        assert_eq!(!&a, big(!x));
+        assert_eq!(-&a, big(-x));
+        assert_eq!(a.to_i128().unwrap(), *x);
+        for amount in [0, 1, 7, 8, 9, 16, 33] {
+            assert_eq!(&a << amount, big(x << amount), "{x} << {amount}");
+            assert_eq!(&a >> amount, big(x >> amount), "{x} >> {amount}");
+            assert_eq!(a.filling_shl(amount), big(x << amount | ((1 << amount) - 1)), "{x} <<+ {amount}");
+        }
+        assert_eq!(&a >> 200, big(if *x < 0 { -1 } else { 0 }));
+    }
+
+    // Numbers larger than any of Rust's.
+    let power = Bigint::from_u8(2).pow(512);
+    assert_eq!(&power, &(&Bigint::from_u8(1) << 512));
+    assert_eq!(power.bit_width(), 513);
+    assert!(!power.fits(512, false));
+    assert!((&power - &Bigint::from_u8(1)).fits(512, false));
+    assert!((-&power).fits(513, true) && !(-&power).fits(512, true));
+    assert_eq!(Bigint::from_u8(3).pow(100).to_string(), "515377520732011331036461129765621272702107522001");
+    assert_eq!(Bigint::from_i64(-7).pow(0), Bigint::from_u8(1));
+    let (quotient, remainder) = (&power + &Bigint::from_u8(5)).divmod(&Bigint::from_u8(2).pow(256)).unwrap();
+    assert_eq!((quotient, remainder), (Bigint::from_u8(2).pow(256), Bigint::from_u8(5)));
+    assert!(power.to_i128().is_err());
+    assert_eq!(power.cmp(&Bigint::from_i128(i128::MAX)), std::cmp::Ordering::Greater);
+}
+
+#[test]
+fn test_bigint_text() {
+    let parse = |text: &str| text.parse::<Bigint>();
+    assert_eq!(parse("0").unwrap(), Bigint::from_u8(0));
+    assert_eq!(parse("-0").unwrap(), Bigint::from_u8(0));
+    assert_eq!(parse("-583815").unwrap(), Bigint::from_i64(-583815));
+    assert_eq!(parse("0xDEADBEEF").unwrap(), Bigint::from_i64(0xDEADBEEF));
+    assert_eq!(parse("-0b1011").unwrap(), Bigint::from_i64(-11));
+    assert_eq!(parse("0o777").unwrap(), Bigint::from_i64(0o777));
+    for invalid in ["", "-", "0x", "12a", "0b102", "--1", "0x-1", "1.5", "ten"] {
+        assert!(parse(invalid).is_err(), "`{invalid}`");
+    }
+    let huge = "13407807929942597099574024998205846127479365820592393377723561443721764030073546976801874298166903427690031858186486050853753882811946569946433649006084096";
+    assert_eq!(parse(huge).unwrap(), Bigint::from_u8(2).pow(512));
+    assert_eq!(parse(huge).unwrap().to_string(), huge);
+
+    assert_eq!(Bigint::from_str_radix("zz", 36).unwrap(), Bigint::from_i64(36 * 36 - 1));
+    assert_eq!(Bigint::from_str_radix("-FfFf", 16).unwrap(), Bigint::from_i64(-0xffff));
+    assert!(Bigint::from_str_radix("8", 8).is_err());
+    assert_eq!(Bigint::from_i64(-255).to_string_radix(16), "-ff");
+    assert_eq!(Bigint::from_i64(0).to_string_radix(2), "0");
+    assert_eq!(Bigint::from_i64(35).to_string_radix(36), "z");
+
+    let number = Bigint::from_i64(-3054);
+    assert_eq!(number.to_string(), "-3054");
+    assert_eq!(format!("{number:x}"), "-bee");
+    assert_eq!(format!("{number:#X}"), "-0xBEE");
+    assert_eq!(format!("{:#b}", Bigint::from_u8(5)), "0b101");
+    assert_eq!(format!("{:o}", Bigint::from_u8(8)), "10");
+    assert_eq!(format!("{:>6}", Bigint::from_u8(42)), "    42");
+    assert_eq!(format!("{:+}", Bigint::from_u8(42)), "+42");
+}
+
+#[test]
+fn test_bigint_bytes() {
+    assert_eq!(Bigint::from_i64(-2).to_le_bytes(2).unwrap(), [0xfe, 0xff]);
+    assert_eq!(Bigint::from_i64(255).to_le_bytes(1).unwrap(), [0xff]);
+    assert_eq!(Bigint::from_i64(-128).to_le_bytes(1).unwrap(), [0x80]);
+    assert_eq!(Bigint::from_i64(6).to_le_bytes(8).unwrap(), [6, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(Bigint::from_i64(256).to_le_bytes(1).is_err());
+    assert!(Bigint::from_i64(-129).to_le_bytes(1).is_err());
+    assert_eq!(Bigint::from_signed_le_bytes(&[0xfe, 0xff]), Bigint::from_i64(-2));
+    assert_eq!(Bigint::from_signed_le_bytes(&[0xfe, 0x7f]), Bigint::from_i64(0x7ffe));
+    assert_eq!(Bigint::from_signed_le_bytes(&[]), Bigint::from_u8(0));
+    assert_eq!(Bigint::from_i8(i8::MIN).to_i8().unwrap(), i8::MIN);
+    assert_eq!(Bigint::from_i64(i64::MIN).to_le_bytes(8).unwrap(), i64::MIN.to_le_bytes());
+}
+
+#[test]
+fn test_bigfloat_conversion() {
+    let parse = |text: &str| text.parse::<Bigfloat>().unwrap();
+    // Rust's own parsing rounds correctly, so it checks binary32 and
+    // binary64.
+    let texts = [
+        "0", "1", "0.1", "0.2", "0.3", "58.3", "3.14159265358979323846264338327950288",
+        "1e10", "1.5e-3", "123456789012345678901234567890", "9007199254740993",
+        "16777217", "0.000000000000000000000000000000000000000000001401298464324817",
+        "4.9406564584124654e-324", "2.4703282292062327e-324", "2.2250738585072011e-308",
+        "1.7976931348623157e308", "1.7976931348623159e308", "3.4028235e38", "3.4028236e38",
+        "1e-400", "1e400", "0.5", "2.5", "340282356779733661637539395458142568448",
+    ];
+    for text in texts {
+        let value = parse(text);
+        assert_eq!(value.to_ieee_bytes(FloatFormat::Binary32), text.parse::<f32>().unwrap().to_le_bytes(), "{text}");
+        assert_eq!(value.to_ieee_bytes(FloatFormat::Binary64), text.parse::<f64>().unwrap().to_le_bytes(), "{text}");
+        let negative = parse(&format!("-{text}"));
+        assert_eq!(negative.to_ieee_bytes(FloatFormat::Binary64), (-text.parse::<f64>().unwrap()).to_le_bytes(), "-{text}");
+    }
+    // Bit patterns from the IEEE 754 tables for binary16 and binary128.
+    let half = |text: &str| u16::from_le_bytes(parse(text).to_ieee_bytes(FloatFormat::Binary16).try_into().unwrap());
+    assert_eq!(half("1"), 0x3c00);
+    assert_eq!(half("0.1"), 0x2e66);
+    assert_eq!(half("-2"), 0xc000);
+    assert_eq!(half("65504"), 0x7bff);
+    // halfway between the largest number and the next, which rounds to even
+    assert_eq!(half("65520"), 0x7c00);
+    assert_eq!(half("65519.99"), 0x7bff);
+    assert_eq!(half("0.000000059604644775390625"), 0x0001);
+    assert_eq!(half("0.00006103515625"), 0x0400);
+    assert_eq!(half("0.0000000298023223876953125"), 0x0000);
+    assert_eq!(half("0.00000002980232238769532"), 0x0001);
+    let quad = |text: &str| u128::from_le_bytes(parse(text).to_ieee_bytes(FloatFormat::Binary128).try_into().unwrap());
+    assert_eq!(quad("1"), 0x3fff_0000_0000_0000_0000_0000_0000_0000);
+    assert_eq!(quad("0.1"), 0x3ffb_9999_9999_9999_9999_9999_9999_999a);
+    assert_eq!(quad("-3"), 0xc000_8000_0000_0000_0000_0000_0000_0000);
+    assert_eq!(quad("1e5000"), 0x7fff_0000_0000_0000_0000_0000_0000_0000);
+
+    // Special values.
+    assert_eq!(Bigfloat::nan().to_ieee_bytes(FloatFormat::Binary32), f32::NAN.to_le_bytes());
+    assert_eq!(parse("∞").to_ieee_bytes(FloatFormat::Binary64), f64::INFINITY.to_le_bytes());
+    assert_eq!(parse("-∞").to_ieee_bytes(FloatFormat::Binary16), [0x00, 0xfc]);
+    assert_eq!(parse("-0").to_ieee_bytes(FloatFormat::Binary64), (-0.0f64).to_le_bytes());
+    for invalid in ["", ".5", "1.2.3", "1e", "e5", "-NaN", "1e99999", "five"] {
+        assert!(invalid.parse::<Bigfloat>().is_err(), "`{invalid}`");
+    }
+
+    // Every binary64 value reads back exactly.
+    for value in [0.1f64, -1.5, f64::MAX, f64::MIN_POSITIVE, 5e-324, -0.0, 1.0 / 3.0] {
+        let read = Bigfloat::from_ieee_bytes(&value.to_le_bytes(), FloatFormat::Binary64);
+        assert_eq!(read.to_ieee_bytes(FloatFormat::Binary64), value.to_le_bytes(), "{value}");
+    }
+    assert!(Bigfloat::from_ieee_bytes(&f32::NAN.to_le_bytes(), FloatFormat::Binary32).is_nan());
+    assert_eq!(Bigfloat::from_ieee_bytes(&0.375f32.to_le_bytes(), FloatFormat::Binary32), parse("0.375"));
+}
+
+#[test]
+fn test_bigfloat_arithmetic() {
+    let parse = |text: &str| text.parse::<Bigfloat>().unwrap();
+    // Nothing is rounded until the end.
+    let sum = &parse("0.1") + &parse("0.2");
+    assert_eq!(sum, parse("0.3"));
+    assert_eq!(sum.to_ieee_bytes(FloatFormat::Binary64), 0.3f64.to_le_bytes());
+    assert_eq!(&parse("1") / &parse("3"), Bigfloat::fraction(&Bigint::from_u8(1), &Bigint::from_u8(3)));
+    assert_eq!(&(&parse("1") / &parse("3")) * &parse("3"), parse("1"));
+    assert_eq!(&parse("2.5") - &parse("4"), parse("-1.5"));
+    assert!(parse("-1.5") < parse("-1.25") && parse("1e3") > parse("999.9"));
+    assert_eq!(parse("-1.5").to_string(), "-1.5");
+    assert_eq!((&parse("2") / &parse("3")).to_string(), "0.66666666666666666666");
+    assert_eq!(format!("{:.3}", parse("0.5")), "0.500");
+
+    // Special values behave as IEEE floats do.
+    let (zero, infinity) = (parse("0"), parse("∞"));
+    assert!((&zero / &zero).is_nan());
+    assert!((&infinity - &infinity).is_nan());
+    assert!((&infinity * &zero).is_nan());
+    assert_eq!(&parse("-1") / &zero, parse("-∞"));
+    assert!((&parse("1") / &parse("-∞")).is_sign_negative());
+    assert!((&parse("-0") + &parse("-0")).is_sign_negative());
+    assert!(!(&parse("1.5") - &parse("1.5")).is_sign_negative());
+    assert!(Bigfloat::nan() != Bigfloat::nan());
+    assert_eq!(parse("-0"), zero);
+    assert!(parse("-∞") < parse("-1e400"));
+
+    // Complex numbers.
+    let complex = |text: &str| text.parse::<Bigcplx>().unwrap();
+    let (a, b) = (complex("23.5+14i"), complex("-2i"));
+    assert_eq!(a.real, parse("23.5"));
+    assert_eq!(a.immaginary, parse("14"));
+    assert_eq!(b, Bigcplx { real: parse("0"), immaginary: parse("-2") });
+    assert_eq!(complex("1e-3-1e+2i").immaginary, parse("-100"));
+    assert_eq!(complex("6"), Bigcplx::from_real(parse("6")));
+    assert_eq!(&a * &b, complex("28-47i"));
+    assert_eq!(&(&a * &b) / &b, a);
+    assert_eq!(&a + &b, complex("23.5+12i"));
+    assert_eq!((&a - &b).to_string(), "23.5 + 16i");
+    assert_eq!((-&a).to_string(), "-23.5 - 14i");
+    assert!("1+i".parse::<Bigcplx>().is_err());
+    let mut bytes = 1.5f32.to_le_bytes().to_vec();
+    bytes.extend((-2.0f32).to_le_bytes());
+    assert_eq!(complex("1.5-2i").to_ieee_bytes(FloatFormat::Binary32), bytes);
+}